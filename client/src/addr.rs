@@ -14,6 +14,10 @@ pub enum ConnectionArgs {
         hostname: String,
         prefer_ipv6: bool,
     },
+    /// url: (ws|wss)://hostname[:<port>][/path]
+    WebSocket {
+        url: String,
+    },
     Mpsc(u64),
 }
 
@@ -48,10 +52,15 @@ pub(crate) async fn resolve(
     }
 }
 
+/// Connects to the first resolved address that works, see
+/// [`Client::new_pinned`] for `pinned_identity`.
+///
+/// [`Client::new_pinned`]: crate::Client::new_pinned
 pub(crate) async fn try_connect<F>(
     network: &network::Network,
     address: &str,
     prefer_ipv6: bool,
+    pinned_identity: Option<network::PublicIdentity>,
     f: F,
 ) -> Result<network::Participant, crate::error::Error>
 where
@@ -63,7 +72,11 @@ where
         .await
         .map_err(Error::HostnameLookupFailed)?
     {
-        match network.connect(f(addr)).await {
+        let connected = match pinned_identity {
+            Some(identity) => network.connect_pinned(f(addr), identity).await,
+            None => network.connect(f(addr)).await,
+        };
+        match connected {
             Ok(p) => {
                 participant = Some(Ok(p));
                 break;
@@ -0,0 +1,189 @@
+//! Replays the server side of a network recording against a [`Client`].
+//!
+//! Record the traffic of a client by setting the `VELOREN_NETWORK_RECORDING`
+//! env variable to a file path, the recording can then be replayed with
+//! `cargo run --bin replay --features bin_replay -- <path>`.
+//! The replay doesn't react to the client, it only sends the received
+//! messages at their recorded time, or message by message with `--step`.
+
+use clap::{App, Arg};
+use common::{clock::Clock, comp};
+use common_net::msg::{PingMsg, ServerGeneral};
+use network::{Direction, ListenAddr, Network, Pid, RecordedMessage, Recording, Stream};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::runtime::Runtime;
+use tracing::{error, info, warn};
+use veloren_client::{addr::ConnectionArgs, Client};
+
+/// order in which the server opens its streams, see `ConnectionHandler`
+const PING_STREAM: usize = 1;
+const REGISTER_STREAM: usize = 2;
+
+fn main() {
+    common_frontend::init_stdout(None);
+
+    let matches = App::new("Veloren network replay")
+        .version(common::util::DISPLAY_VERSION_LONG.as_str())
+        .author("The veloren devs <https://gitlab.com/veloren/veloren>")
+        .about("Replays a network recording of a client against a new client")
+        .arg(
+            Arg::with_name("recording")
+                .required(true)
+                .help("path of the recording"),
+        )
+        .arg(
+            Arg::with_name("step")
+                .long("step")
+                .help("wait for enter before every message instead of keeping the timing"),
+        )
+        .get_matches();
+
+    let path = matches.value_of("recording").unwrap();
+    let step = matches.is_present("step");
+    let recording = match Recording::load(path) {
+        Ok(recording) => recording,
+        Err(e) => {
+            error!(?e, ?path, "Failed to load network recording");
+            return;
+        },
+    };
+    info!(
+        streams = recording.streams.len(),
+        messages = recording.messages.len(),
+        "Loaded network recording"
+    );
+    if step {
+        warn!("The client might time out while waiting for the next message");
+    }
+
+    let runtime = Arc::new(Runtime::new().unwrap());
+    let network = Network::new(Pid::new(), &runtime);
+    let id = rand_id();
+    runtime
+        .block_on(network.listen(ListenAddr::Mpsc(id)))
+        .expect("Failed to listen for the client");
+    runtime.spawn(serve(network, recording, step));
+
+    let mut client = match runtime.block_on(Client::new(
+        ConnectionArgs::Mpsc(id),
+        Arc::clone(&runtime),
+        &mut None,
+    )) {
+        Ok(client) => client,
+        Err(e) => {
+            error!(?e, "Client failed to connect to the replay");
+            return;
+        },
+    };
+    info!("Client connected to the replay");
+
+    let mut clock = Clock::new(Duration::from_secs_f64(1.0 / 60.0));
+    loop {
+        match client.tick(comp::ControllerInputs::default(), clock.dt(), |_| {}) {
+            Ok(events) => {
+                for event in events {
+                    info!(?event, "Client event");
+                }
+            },
+            Err(e) => {
+                error!(?e, "Client stopped");
+                break;
+            },
+        }
+        client.cleanup();
+        clock.tick();
+    }
+}
+
+fn rand_id() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+/// Plays the server side of the `recording` to the first connected client
+async fn serve(network: Network, recording: Recording, step: bool) {
+    let participant = match network.connected().await {
+        Ok(participant) => participant,
+        Err(e) => return error!(?e, "No client connected to the replay"),
+    };
+    let mut streams = Vec::with_capacity(recording.streams.len());
+    for recorded in &recording.streams {
+        match participant
+            .open(
+                recorded.prio,
+                recorded.promises,
+                recorded.guaranteed_bandwidth,
+            )
+            .await
+        {
+            Ok(stream) => streams.push((recorded.sid, stream)),
+            Err(e) => return error!(?e, "Failed to open a recorded stream"),
+        }
+    }
+
+    let start = Instant::now();
+    let first = recording
+        .messages
+        .iter()
+        .find(|m| m.direction == Direction::Received)
+        .map_or(Duration::ZERO, |m| m.time);
+    for message in recording
+        .messages
+        .iter()
+        .filter(|m| m.direction == Direction::Received)
+    {
+        let index = match streams.iter().position(|(sid, _)| *sid == message.sid) {
+            Some(index) => index,
+            None => {
+                warn!(sid = message.sid, "Message for an unknown stream, skipping");
+                continue;
+            },
+        };
+        let stream = &mut streams[index].1;
+        if step {
+            println!("[{:?}] {}", message.time, describe(index, stream, message));
+            println!("press enter to send");
+            let mut line = String::new();
+            if tokio::task::spawn_blocking(move || std::io::stdin().read_line(&mut line))
+                .await
+                .map_or(true, |r| r.is_err())
+            {
+                return;
+            }
+        } else {
+            let due = message.time.saturating_sub(first);
+            tokio::time::sleep(due.saturating_sub(start.elapsed())).await;
+        }
+        if let Err(e) = stream.send_raw(&message.to_message(stream.params())) {
+            return error!(?e, "Client closed the connection");
+        }
+    }
+    info!("Replay finished");
+    // keep the participant alive, the client disconnects on its own
+    let _ = participant.opened().await;
+}
+
+fn describe(index: usize, stream: &Stream, message: &RecordedMessage) -> String {
+    const MAX_LEN: usize = 200;
+    let msg = message.to_message(stream.params());
+    let mut description = match index {
+        PING_STREAM => format!("{:?}", msg.deserialize::<PingMsg>()),
+        // the register stream carries different types during the handshake
+        REGISTER_STREAM => format!("register message, {} bytes", message.data.len()),
+        _ => format!("{:?}", msg.deserialize::<ServerGeneral>()),
+    };
+    if description.len() > MAX_LEN {
+        let mut end = MAX_LEN;
+        while !description.is_char_boundary(end) {
+            end -= 1;
+        }
+        description.truncate(end);
+        description.push_str("...");
+    }
+    format!("stream {}: {}", index, description)
+}
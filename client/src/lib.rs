@@ -10,6 +10,7 @@ pub mod error;
 pub use crate::error::Error;
 pub use authc::AuthClientError;
 pub use common_net::msg::ServerInfo;
+pub use network::PublicIdentity;
 pub use specs::{
     join::Join,
     saveload::{Marker, MarkerAllocator},
@@ -20,6 +21,7 @@ use crate::addr::ConnectionArgs;
 use byteorder::{ByteOrder, LittleEndian};
 use common::{
     character::{CharacterId, CharacterItem},
+    cmd::ServerChatCommand,
     comp::{
         self,
         chat::{KillSource, KillType},
@@ -50,10 +52,10 @@ use common_net::{
     msg::{
         self, validate_chat_msg,
         world_msg::{EconomyInfo, PoiInfo, SiteId, SiteInfo},
-        ChatMsgValidationError, ClientGeneral, ClientMsg, ClientRegister, ClientType,
-        DisconnectReason, InviteAnswer, Notification, PingMsg, PlayerInfo, PlayerListUpdate,
-        PresenceKind, RegisterError, ServerGeneral, ServerInit, ServerRegisterAnswer,
-        MAX_BYTES_CHAT_MSG,
+        ChatMsgValidationError, ClientGeneral, ClientMsg, ClientPluginInfo, ClientRegister,
+        ClientType, DisconnectReason, InviteAnswer, Notification, PingMsg, PlayerInfo,
+        PlayerListUpdate, PresenceKind, RegisterError, ServerGeneral, ServerInit,
+        ServerRegisterAnswer, MAX_BYTES_CHAT_MSG,
     },
     sync::WorldSyncExt,
 };
@@ -62,7 +64,7 @@ use common_systems::add_local_systems;
 use comp::BuffKind;
 use hashbrown::{HashMap, HashSet};
 use image::DynamicImage;
-use network::{ConnectAddr, Network, Participant, Pid, Stream};
+use network::{ConnectAddr, Network, Participant, Pid, Recorder, Stream};
 use num::traits::FloatConst;
 use rayon::prelude::*;
 use specs::Component;
@@ -73,9 +75,17 @@ use std::{
     time::{Duration, Instant},
 };
 use tokio::runtime::Runtime;
-use tracing::{debug, error, trace, warn};
+use tracing::{debug, error, info, trace, warn};
 use vek::*;
 
+#[cfg(feature = "plugins")]
+use {
+    common_net::msg::{PluginHash, PluginMsg},
+    common_state::plugin::{hash_tarball, PluginMgr},
+    std::path::{Path, PathBuf},
+    tracing::info,
+};
+
 #[cfg(feature = "tracy")]
 mod tracy_plots {
     use common_base::tracy_client::{create_plot, Plot};
@@ -88,6 +98,9 @@ mod tracy_plots {
 
 const PING_ROLLING_AVERAGE_SECS: usize = 10;
 
+/// Set to a file path to record the network traffic of the [`Client`]
+pub const RECORDING_ENV: &str = "VELOREN_NETWORK_RECORDING";
+
 #[derive(Debug)]
 pub enum Event {
     Chat(comp::ChatMsg),
@@ -166,6 +179,10 @@ pub struct Client {
     sites: HashMap<SiteId, SiteInfoRich>,
     pois: Vec<PoiInfo>,
     pub chat_mode: ChatMode,
+    /// Chat commands provided by the server in addition to the built-in ones
+    server_commands: Vec<ServerChatCommand>,
+    /// Plugins the server asks clients to run
+    server_plugins: Vec<ClientPluginInfo>,
     recipe_book: RecipeBook,
     available_recipes: HashMap<String, Option<SpriteKind>>,
 
@@ -188,6 +205,8 @@ pub struct Client {
     character_screen_stream: Stream,
     in_game_stream: Stream,
     terrain_stream: Stream,
+    plugin_stream: Stream,
+    physics_stream: Stream,
 
     client_timeout: Duration,
     last_server_ping: f64,
@@ -197,6 +216,9 @@ pub struct Client {
 
     tick: u64,
     state: State,
+    /// Server tick of the last forced physics update, older physics updates
+    /// arriving after it are outdated
+    last_forced_physics_tick: Option<u64>,
 
     view_distance: Option<u32>,
     // TODO: move into voxygen
@@ -220,6 +242,21 @@ impl Client {
         runtime: Arc<Runtime>,
         // TODO: refactor to avoid needing to use this out parameter
         mismatched_server_info: &mut Option<ServerInfo>,
+    ) -> Result<Self, Error> {
+        Self::new_pinned(addr, None, runtime, mismatched_server_info).await
+    }
+
+    /// Like [`Client::new`], but if `pinned_identity` is set TCP and WebSocket
+    /// connections fail unless the server proves it. Pass the
+    /// [`Client::server_identity`] of the first connection to notice when
+    /// someone gets in the middle later on. QUIC servers are verified with
+    /// their certificate instead.
+    pub async fn new_pinned(
+        addr: ConnectionArgs,
+        pinned_identity: Option<PublicIdentity>,
+        runtime: Arc<Runtime>,
+        // TODO: refactor to avoid needing to use this out parameter
+        mismatched_server_info: &mut Option<ServerInfo>,
     ) -> Result<Self, Error> {
         let network = Network::new(Pid::new(), &runtime);
 
@@ -227,7 +264,16 @@ impl Client {
             ConnectionArgs::Tcp {
                 hostname,
                 prefer_ipv6,
-            } => addr::try_connect(&network, &hostname, prefer_ipv6, ConnectAddr::Tcp).await?,
+            } => {
+                addr::try_connect(
+                    &network,
+                    &hostname,
+                    prefer_ipv6,
+                    pinned_identity,
+                    ConnectAddr::Tcp,
+                )
+                .await?
+            },
             ConnectionArgs::Quic {
                 hostname,
                 prefer_ipv6,
@@ -237,20 +283,43 @@ impl Client {
                      TCP servers unless deactivated"
                 );
                 let config = quinn::ClientConfig::with_native_roots();
-                addr::try_connect(&network, &hostname, prefer_ipv6, |a| {
+                addr::try_connect(&network, &hostname, prefer_ipv6, None, |a| {
                     ConnectAddr::Quic(a, config.clone(), hostname.clone())
                 })
                 .await?
             },
+            ConnectionArgs::WebSocket { url } => match pinned_identity {
+                Some(identity) => {
+                    network
+                        .connect_pinned(ConnectAddr::WebSocket(url), identity)
+                        .await?
+                },
+                None => network.connect(ConnectAddr::WebSocket(url)).await?,
+            },
             ConnectionArgs::Mpsc(id) => network.connect(ConnectAddr::Mpsc(id)).await?,
         };
 
+        // Record all traffic to debug desyncs, replay it with the `replay` bin
+        if let Some(path) = std::env::var_os(RECORDING_ENV) {
+            match Recorder::create(&path) {
+                Ok(recorder) => {
+                    info!(?path, "Recording network traffic");
+                    participant.record(recorder).await;
+                },
+                Err(e) => warn!(?e, ?path, "Failed to create network recording"),
+            }
+        }
+
         let stream = participant.opened().await?;
         let mut ping_stream = participant.opened().await?;
         let mut register_stream = participant.opened().await?;
+        // the auth token is send on it, it must not end up in a recording
+        register_stream.skip_recording_sent();
         let character_screen_stream = participant.opened().await?;
         let in_game_stream = participant.opened().await?;
         let terrain_stream = participant.opened().await?;
+        let plugin_stream = participant.opened().await?;
+        let physics_stream = participant.opened().await?;
 
         register_stream.send(ClientType::Game)?;
         let server_info: ServerInfo = register_stream.recv().await?;
@@ -284,6 +353,7 @@ impl Client {
             recipe_book,
             max_group_size,
             client_timeout,
+            server_plugins,
         ) = match loop {
             tokio::select! {
                 res = register_stream.recv() => break res?,
@@ -299,6 +369,7 @@ impl Client {
                 recipe_book,
                 material_stats,
                 ability_map,
+                client_plugins,
             } => {
                 // Initialize `State`
                 let mut state = State::client();
@@ -586,6 +657,7 @@ impl Client {
                     recipe_book,
                     max_group_size,
                     client_timeout,
+                    client_plugins,
                 ))
             },
             ServerInit::TooManyPlayers => Err(Error::TooManyPlayers),
@@ -620,6 +692,8 @@ impl Client {
             recipe_book,
             available_recipes: HashMap::default(),
             chat_mode: ChatMode::default(),
+            server_commands: Vec::new(),
+            server_plugins,
 
             max_group_size,
             invite: None,
@@ -636,6 +710,8 @@ impl Client {
             character_screen_stream,
             in_game_stream,
             terrain_stream,
+            plugin_stream,
+            physics_stream,
 
             client_timeout,
 
@@ -646,6 +722,7 @@ impl Client {
 
             tick: 0,
             state,
+            last_forced_physics_tick: None,
             view_distance: None,
             loaded_distance: 0.0,
 
@@ -707,6 +784,92 @@ impl Client {
         }
     }
 
+    /// Loads the plugins the server asks clients to run, downloading the ones
+    /// missing from `cache_dir`. Since this runs code provided by the server,
+    /// `consent` is first asked with the names of every plugin that isn't
+    /// loaded yet, even the cached ones which another server may have sent. If
+    /// it refuses, no plugin of the server is loaded.
+    #[cfg(feature = "plugins")]
+    pub async fn load_server_plugins(
+        &mut self,
+        cache_dir: &Path,
+        consent: impl FnOnce(&[String]) -> bool,
+    ) -> Result<(), Error> {
+        let cached_path = |hash: &PluginHash| -> PathBuf { cache_dir.join(plugin_file_name(hash)) };
+        // Plugins shared with the client, like in singleplayer, are already loaded
+        let plugins = {
+            let plugin_mgr = self.state.ecs().read_resource::<PluginMgr>();
+            self.server_plugins
+                .iter()
+                .filter(|plugin| !plugin_mgr.is_loaded(&plugin.hash))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        if plugins.is_empty() {
+            return Ok(());
+        }
+        let names = plugins
+            .iter()
+            .map(|plugin| plugin.name.clone())
+            .collect::<Vec<_>>();
+        if !consent(&names) {
+            info!("Plugins of the server were refused, not loading them");
+            return Ok(());
+        }
+
+        let missing = plugins
+            .iter()
+            .filter(|plugin| !cached_path(&plugin.hash).exists())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            std::fs::create_dir_all(cache_dir).map_err(|e| {
+                Error::Other(format!(
+                    "Failed to create the plugin cache directory: {}",
+                    e
+                ))
+            })?;
+
+            let hashes = missing.iter().map(|plugin| plugin.hash).collect::<Vec<_>>();
+            let mut remaining = hashes.len();
+            self.send_msg_err(PluginMsg::Request(hashes))?;
+
+            // Keep the connection alive while downloading
+            let mut ping_interval = tokio::time::interval(Duration::from_secs(1));
+            while remaining > 0 {
+                let msg = tokio::select! {
+                    msg = self.plugin_stream.recv() => msg?,
+                    _ = ping_interval.tick() => {
+                        self.ping_stream.send(PingMsg::Ping)?;
+                        continue;
+                    },
+                };
+                if let PluginMsg::Tarball(hash, data) = msg {
+                    remaining -= 1;
+                    match data {
+                        Some(data) if hash_tarball(&data) == hash => {
+                            if let Err(e) = std::fs::write(cached_path(&hash), &data) {
+                                warn!(?e, "Failed to write plugin to the cache");
+                            }
+                        },
+                        Some(_) => warn!("Server sent a plugin which doesn't match its hash"),
+                        None => warn!("Server doesn't have one of its plugins anymore"),
+                    }
+                }
+            }
+        }
+
+        for plugin in &plugins {
+            let path = cached_path(&plugin.hash);
+            if !path.exists() {
+                continue;
+            }
+            if let Err(e) = self.state.load_plugin(&path) {
+                warn!(?e, "Failed to load plugin '{}' of the server", plugin.name);
+            }
+        }
+        Ok(())
+    }
+
     fn send_msg_err<S>(&mut self, msg: S) -> Result<(), network::StreamError>
     where
         S: Into<ClientMsg>,
@@ -785,6 +948,7 @@ impl Client {
                 stream.send(msg)
             },
             ClientMsg::Ping(msg) => self.ping_stream.send(msg),
+            ClientMsg::Plugin(msg) => self.plugin_stream.send(msg),
         }
     }
 
@@ -975,10 +1139,22 @@ impl Client {
 
     pub fn player_list(&self) -> &HashMap<Uid, PlayerInfo> { &self.player_list }
 
+    pub fn server_commands(&self) -> &[ServerChatCommand] { &self.server_commands }
+
+    pub fn server_plugins(&self) -> &[ClientPluginInfo] { &self.server_plugins }
+
     pub fn character_list(&self) -> &CharacterList { &self.character_list }
 
     pub fn server_info(&self) -> &ServerInfo { &self.server_info }
 
+    /// The network identity the server proved when connecting over TCP or
+    /// WebSocket, see [`Client::new_pinned`]
+    pub fn server_identity(&self) -> Option<PublicIdentity> {
+        self.participant
+            .as_ref()
+            .and_then(|participant| participant.remote_identity())
+    }
+
     pub fn world_data(&self) -> &WorldData { &self.world_data }
 
     pub fn recipe_book(&self) -> &RecipeBook { &self.recipe_book }
@@ -1783,6 +1959,9 @@ impl Client {
             ServerGeneral::ChatMode(m) => {
                 self.chat_mode = m;
             },
+            ServerGeneral::ChatCommands(commands) => {
+                self.server_commands = commands;
+            },
             ServerGeneral::SetPlayerEntity(uid) => {
                 if let Some(entity) = self.state.ecs().entity_from_uid(uid.0) {
                     *self.state.ecs_mut().write_resource() = PlayerEntity(Some(entity));
@@ -1804,6 +1983,10 @@ impl Client {
                     .ecs_mut()
                     .apply_comp_sync_package(comp_sync_package);
             },
+            ServerGeneral::ForcedPhysicsSync { tick, package } => {
+                self.last_forced_physics_tick = Some(tick);
+                self.state.ecs_mut().apply_comp_sync_package(package);
+            },
             ServerGeneral::CreateEntity(entity_package) => {
                 self.state.ecs_mut().apply_entity_package(entity_package);
             },
@@ -1980,6 +2163,14 @@ impl Client {
                         impulse,
                     });
             },
+            ServerGeneral::PhysicsSync { tick, package } => {
+                if self
+                    .last_forced_physics_tick
+                    .map_or(true, |forced| tick > forced)
+                {
+                    self.state.ecs_mut().apply_comp_sync_package(package);
+                }
+            },
             ServerGeneral::UpdatePendingTrade(id, trade, pricing) => {
                 tracing::trace!("UpdatePendingTrade {:?} {:?}", id, trade);
                 self.pending_trade = Some((id, trade, pricing));
@@ -2108,6 +2299,10 @@ impl Client {
                 }
                 self.handle_server_in_game_msg(frontend_events, msg)?;
             }
+            while let Some(msg) = self.physics_stream.try_recv()? {
+                cnt += 1;
+                self.handle_server_in_game_msg(frontend_events, msg)?;
+            }
             while let Some(msg) = self.terrain_stream.try_recv()? {
                 cnt += 1;
                 #[cfg(feature = "tracy")]
@@ -2471,6 +2666,16 @@ impl Client {
     }
 }
 
+/// Name of the file a plugin with the given hash is cached in
+#[cfg(feature = "plugins")]
+fn plugin_file_name(hash: &PluginHash) -> String {
+    let hex = hash
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    format!("{}.plugin.tar", hex)
+}
+
 impl Drop for Client {
     fn drop(&mut self) {
         trace!("Dropping client");
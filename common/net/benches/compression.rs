@@ -0,0 +1,112 @@
+use common::{
+    comp::{Ori, Pos, Vel},
+    terrain::{
+        block::{Block, BlockKind},
+        SpriteKind, TerrainChunk, TerrainChunkMeta, TerrainChunkSize,
+    },
+    uid::Uid,
+    vol::*,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use network::Compression;
+use vek::*;
+use veloren_common_net::{
+    msg::{EcsCompPacket, SerializedTerrainChunk, ServerGeneral},
+    sync::CompSyncPackage,
+};
+
+const MIN_Z: i32 = 140;
+
+/// hilly terrain with grass on top of earth and rock, and a lake
+fn terrain_chunk_update() -> ServerGeneral {
+    let mut chunk = TerrainChunk::new(
+        MIN_Z,
+        Block::new(BlockKind::Rock, Rgb::new(100, 100, 100)),
+        Block::air(SpriteKind::Empty),
+        TerrainChunkMeta::void(),
+    );
+    for x in 0..TerrainChunkSize::RECT_SIZE.x as i32 {
+        for y in 0..TerrainChunkSize::RECT_SIZE.y as i32 {
+            let height =
+                MIN_Z + 40 + ((x as f32 * 0.2).sin() * 6.0 + (y as f32 * 0.3).cos() * 4.0) as i32;
+            for z in MIN_Z..MIN_Z + 60 {
+                let block = if z < height - 4 {
+                    continue;
+                } else if z < height {
+                    Block::new(BlockKind::Earth, Rgb::new(90, 60, 30))
+                } else if z == height {
+                    Block::new(BlockKind::Grass, Rgb::new(40, 140 + (x % 8) as u8, 30))
+                } else if z < MIN_Z + 42 {
+                    Block::new(BlockKind::Water, Rgb::zero())
+                } else {
+                    continue;
+                };
+                chunk.set(Vec3::new(x, y, z), block).unwrap();
+            }
+        }
+    }
+    ServerGeneral::TerrainChunkUpdate {
+        key: Vec2::new(42, 1337),
+        chunk: Ok(SerializedTerrainChunk::via_heuristic(&chunk, false)),
+    }
+}
+
+/// position, velocity and orientation updates of 200 moving entities
+fn comp_sync() -> ServerGeneral {
+    let mut package = CompSyncPackage::<EcsCompPacket>::new();
+    for i in 0..200u64 {
+        let uid = Uid(1000 + i * 7);
+        let f = i as f32;
+        package.comp_modified(
+            uid,
+            Pos(Vec3::new(1000.0 + f * 3.1, 2000.0 - f * 1.7, 150.0)),
+        );
+        package.comp_modified(uid, Vel(Vec3::new((f * 0.3).sin(), (f * 0.3).cos(), 0.0)));
+        package.comp_modified(uid, Ori::default());
+    }
+    ServerGeneral::CompSync(package)
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let codecs = [
+        ("lz4", Compression::Lz4),
+        ("zstd_1", Compression::Zstd { level: 1 }),
+        ("zstd_3", Compression::Zstd { level: 3 }),
+        ("zstd_9", Compression::Zstd { level: 9 }),
+    ];
+    let payloads = [
+        ("terrain_chunk_update", terrain_chunk_update()),
+        ("comp_sync", comp_sync()),
+    ];
+
+    for (payload_name, payload) in payloads.iter() {
+        let data = bincode::serialize(payload).unwrap();
+        let mut c = c.benchmark_group(format!("compression_{}", payload_name));
+        c.throughput(Throughput::Bytes(data.len() as u64));
+        for (codec_name, codec) in codecs.iter() {
+            let compressed = codec.compress(&data);
+            println!(
+                "{} with {}: {} -> {} bytes ({:.1}%)",
+                payload_name,
+                codec_name,
+                data.len(),
+                compressed.len(),
+                compressed.len() as f64 * 100.0 / data.len() as f64
+            );
+            c.bench_with_input(
+                BenchmarkId::new("compress", codec_name),
+                &data,
+                |b, data| b.iter(|| codec.compress(black_box(data))),
+            );
+            c.bench_with_input(
+                BenchmarkId::new("decompress", codec_name),
+                &compressed,
+                |b, compressed| b.iter(|| codec.decompress(black_box(compressed)).unwrap()),
+            );
+        }
+        c.finish();
+    }
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -1,4 +1,4 @@
-use super::{world_msg::SiteId, PingMsg};
+use super::{world_msg::SiteId, PingMsg, PluginMsg};
 use common::{
     character::CharacterId,
     comp,
@@ -21,6 +21,8 @@ pub enum ClientMsg {
     ///Msg that can be send ALWAYS as soon as we are registered, e.g. `Chat`
     General(ClientGeneral),
     Ping(PingMsg),
+    /// Requests for the client-side plugins of the server
+    Plugin(PluginMsg),
 }
 
 /*
@@ -142,6 +144,7 @@ impl ClientMsg {
                     }
             },
             ClientMsg::Ping(_) => true,
+            ClientMsg::Plugin(_) => c_type == ClientType::Game,
         }
     }
 }
@@ -165,3 +168,7 @@ impl From<ClientGeneral> for ClientMsg {
 impl From<PingMsg> for ClientMsg {
     fn from(other: PingMsg) -> ClientMsg { ClientMsg::Ping(other) }
 }
+
+impl From<PluginMsg> for ClientMsg {
+    fn from(other: PluginMsg) -> ClientMsg { ClientMsg::Plugin(other) }
+}
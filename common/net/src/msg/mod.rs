@@ -13,9 +13,9 @@ pub use self::{
     },
     ecs_packet::EcsCompPacket,
     server::{
-        CharacterInfo, DisconnectReason, InviteAnswer, Notification, PlayerInfo, PlayerListUpdate,
-        RegisterError, SerializedTerrainChunk, ServerGeneral, ServerInfo, ServerInit, ServerMsg,
-        ServerRegisterAnswer,
+        CharacterInfo, ClientPluginInfo, DisconnectReason, InviteAnswer, Notification, PlayerInfo,
+        PlayerListUpdate, RegisterError, SerializedTerrainChunk, ServerGeneral, ServerInfo,
+        ServerInit, ServerMsg, ServerRegisterAnswer,
     },
     world_msg::WorldMapMsg,
 };
@@ -34,6 +34,21 @@ pub enum PingMsg {
     Pong,
 }
 
+/// BLAKE2b hash of a plugin tarball, used to identify the exact version of a
+/// plugin
+pub type PluginHash = [u8; 32];
+
+/// Messages of the plugin stream, used by clients to download the client-side
+/// plugins of the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PluginMsg {
+    /// Sent by the client to ask for the tarballs with the given hashes
+    Request(Vec<PluginHash>),
+    /// Sent by the server for every requested hash, without data if it has no
+    /// such plugin (anymore)
+    Tarball(PluginHash, Option<Vec<u8>>),
+}
+
 pub const MAX_BYTES_CHAT_MSG: usize = 256;
 
 pub enum ChatMsgValidationError {
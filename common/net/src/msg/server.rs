@@ -1,11 +1,12 @@
 use super::{
-    world_msg::EconomyInfo, ClientType, CompressedData, EcsCompPacket, PingMsg, QuadPngEncoding,
-    TriPngEncoding, WidePacking, WireChonk,
+    world_msg::EconomyInfo, ClientType, CompressedData, EcsCompPacket, PingMsg, PluginHash,
+    PluginMsg, QuadPngEncoding, TriPngEncoding, WidePacking, WireChonk,
 };
 use crate::sync;
 use common::{
     calendar::Calendar,
     character::{self, CharacterItem},
+    cmd::ServerChatCommand,
     comp::{self, invite::InviteKind, item::MaterialStatManifest},
     outcome::Outcome,
     recipe::RecipeBook,
@@ -33,6 +34,8 @@ pub enum ServerMsg {
     ///Msg that can be send ALWAYS as soon as client is registered, e.g. `Chat`
     General(ServerGeneral),
     Ping(PingMsg),
+    /// Tarballs of client-side plugins requested by the client
+    Plugin(PluginMsg),
 }
 
 /*
@@ -62,9 +65,19 @@ pub enum ServerInit {
         recipe_book: RecipeBook,
         material_stats: MaterialStatManifest,
         ability_map: comp::item::tool::AbilityMap,
+        /// The plugins the client should load, the missing ones can be
+        /// downloaded with `PluginMsg::Request`
+        client_plugins: Vec<ClientPluginInfo>,
     },
 }
 
+/// A plugin the server sends to clients so they run it on their side
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientPluginInfo {
+    pub name: String,
+    pub hash: PluginHash,
+}
+
 pub type ServerRegisterAnswer = Result<(), RegisterError>;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -164,6 +177,13 @@ pub enum ServerGeneral {
     SetViewDistance(u32),
     Outcomes(Vec<Outcome>),
     Knockback(Vec3<f32>),
+    // Ingame related AND physics stream
+    /// Modified positions, velocities and orientations, only sent when the
+    /// server uses unreliable physics sync, so they might get dropped
+    PhysicsSync {
+        tick: u64,
+        package: sync::CompSyncPackage<EcsCompPacket>,
+    },
     // Ingame related AND terrain stream
     TerrainChunkUpdate {
         key: Vec2<i32>,
@@ -176,10 +196,19 @@ pub enum ServerGeneral {
     /// formatting the message and turning it into a speech bubble.
     ChatMsg(comp::ChatMsg),
     ChatMode(comp::ChatMode),
+    /// The chat commands provided by the server in addition to the built-in
+    /// ones, replacing the previously sent list
+    ChatCommands(Vec<ServerChatCommand>),
     SetPlayerEntity(Uid),
     TimeOfDay(TimeOfDay, Calendar),
     EntitySync(sync::EntitySyncPackage),
     CompSync(sync::CompSyncPackage<EcsCompPacket>),
+    /// Forced physics updates when the server uses unreliable physics sync.
+    /// `PhysicsSync` of an older tick arriving later is outdated by them.
+    ForcedPhysicsSync {
+        tick: u64,
+        package: sync::CompSyncPackage<EcsCompPacket>,
+    },
     CreateEntity(sync::EntityPackage<EcsCompPacket>),
     DeleteEntity(Uid),
     Disconnect(DisconnectReason),
@@ -293,6 +322,7 @@ impl ServerMsg {
                         | ServerGeneral::InventoryUpdate(_, _)
                         | ServerGeneral::TerrainChunkUpdate { .. }
                         | ServerGeneral::TerrainBlockUpdates(_)
+                        | ServerGeneral::PhysicsSync { .. }
                         | ServerGeneral::SetViewDistance(_)
                         | ServerGeneral::Outcomes(_)
                         | ServerGeneral::Knockback(_)
@@ -305,10 +335,12 @@ impl ServerMsg {
                         ServerGeneral::PlayerListUpdate(_)
                         | ServerGeneral::ChatMsg(_)
                         | ServerGeneral::ChatMode(_)
+                        | ServerGeneral::ChatCommands(_)
                         | ServerGeneral::SetPlayerEntity(_)
                         | ServerGeneral::TimeOfDay(_, _)
                         | ServerGeneral::EntitySync(_)
                         | ServerGeneral::CompSync(_)
+                        | ServerGeneral::ForcedPhysicsSync { .. }
                         | ServerGeneral::CreateEntity(_)
                         | ServerGeneral::DeleteEntity(_)
                         | ServerGeneral::Disconnect(_)
@@ -316,6 +348,7 @@ impl ServerMsg {
                     }
             },
             ServerMsg::Ping(_) => true,
+            ServerMsg::Plugin(_) => c_type == ClientType::Game,
         }
     }
 }
@@ -343,3 +376,7 @@ impl From<ServerGeneral> for ServerMsg {
 impl From<PingMsg> for ServerMsg {
     fn from(o: PingMsg) -> ServerMsg { ServerMsg::Ping(o) }
 }
+
+impl From<PluginMsg> for ServerMsg {
+    fn from(o: PluginMsg) -> ServerMsg { ServerMsg::Plugin(o) }
+}
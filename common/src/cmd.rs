@@ -46,7 +46,9 @@ pub enum ChatCommand {
     Airship,
     Alias,
     ApplyBuff,
+    Audit,
     Ban,
+    BanIp,
     BattleMode,
     BattleModeForce,
     Build,
@@ -82,9 +84,11 @@ pub enum ChatCommand {
     MakeNpc,
     MakeSprite,
     Motd,
+    Mute,
     Object,
     PermitBuild,
     Players,
+    Plugin,
     Region,
     RemoveLights,
     RevokeBuild,
@@ -102,6 +106,8 @@ pub enum ChatCommand {
     Time,
     Tp,
     Unban,
+    UnbanIp,
+    Unmute,
     Version,
     Waypoint,
     Whitelist,
@@ -315,6 +321,18 @@ impl ChatCommand {
                 "Cast a buff on player",
                 Some(Admin),
             ),
+            ChatCommand::Audit => cmd(
+                vec![
+                    Any("player", Optional),
+                    Command(Optional),
+                    Any("since", Optional),
+                    Any("until", Optional),
+                ],
+                "Show the most recent privileged commands, optionally only those by or on a \
+                 player, of a command, or in a time range given as durations like 2h ago.  Use * \
+                 to match any player or command.",
+                Some(Admin),
+            ),
             ChatCommand::Ban => cmd(
                 vec![
                     Any("username", Required),
@@ -326,6 +344,19 @@ impl ChatCommand {
                  true for overwrite to alter an existing ban..",
                 Some(Moderator),
             ),
+            ChatCommand::BanIp => cmd(
+                vec![
+                    Any("address or username", Required),
+                    Boolean("overwrite", "true".to_string(), Optional),
+                    Any("ban duration", Optional),
+                    Message(Optional),
+                ],
+                "Ban an address, a range of addresses like 10.0.0.0/24, or the address an online \
+                 player is connected from, for a given duration (if provided).  Pass true for \
+                 overwrite to alter an existing ban.  Ranges wider than /24 (/48 for IPv6) need \
+                 the admin role.",
+                Some(Moderator),
+            ),
             #[rustfmt::skip]
             ChatCommand::BattleMode => cmd(
                 vec![Enum(
@@ -513,6 +544,16 @@ impl ChatCommand {
                 Some(Admin),
             ),
             ChatCommand::Motd => cmd(vec![Message(Optional)], "View the server description", None),
+            ChatCommand::Mute => cmd(
+                vec![
+                    Any("username", Required),
+                    Any("mute duration", Optional),
+                    Message(Optional),
+                ],
+                "Prevent a player from chatting, for a given duration (if provided).  Muting an \
+                 already muted player replaces their mute.",
+                Some(Moderator),
+            ),
             ChatCommand::Object => cmd(
                 vec![Enum("object", OBJECTS.clone(), Required)],
                 "Spawn an object",
@@ -524,6 +565,18 @@ impl ChatCommand {
                 Some(Admin),
             ),
             ChatCommand::Players => cmd(vec![], "Lists players currently online", None),
+            ChatCommand::Plugin => cmd(
+                vec![
+                    Enum(
+                        "action",
+                        vec!["list".to_owned(), "reload".to_owned(), "unload".to_owned()],
+                        Required,
+                    ),
+                    Any("plugin", Optional),
+                ],
+                "Lists the loaded plugins, or reloads/unloads one of them",
+                Some(Admin),
+            ),
             ChatCommand::RemoveLights => cmd(
                 vec![Float("radius", 20.0, Optional)],
                 "Removes all lights spawned by players",
@@ -622,6 +675,16 @@ impl ChatCommand {
                 "Remove the ban for the given username",
                 Some(Moderator),
             ),
+            ChatCommand::UnbanIp => cmd(
+                vec![Any("address", Required)],
+                "Remove the ban for the given address or range of addresses",
+                Some(Moderator),
+            ),
+            ChatCommand::Unmute => cmd(
+                vec![Any("username", Required)],
+                "Allow a muted player to chat again",
+                Some(Moderator),
+            ),
             ChatCommand::Version => cmd(vec![], "Prints server version", None),
             ChatCommand::Waypoint => cmd(
                 vec![],
@@ -650,7 +713,9 @@ impl ChatCommand {
             ChatCommand::Airship => "airship",
             ChatCommand::Alias => "alias",
             ChatCommand::ApplyBuff => "buff",
+            ChatCommand::Audit => "audit",
             ChatCommand::Ban => "ban",
+            ChatCommand::BanIp => "ban_ip",
             ChatCommand::BattleMode => "battlemode",
             ChatCommand::BattleModeForce => "battlemode_force",
             ChatCommand::Build => "build",
@@ -686,9 +751,11 @@ impl ChatCommand {
             ChatCommand::MakeNpc => "make_npc",
             ChatCommand::MakeSprite => "make_sprite",
             ChatCommand::Motd => "motd",
+            ChatCommand::Mute => "mute",
             ChatCommand::Object => "object",
             ChatCommand::PermitBuild => "permit_build",
             ChatCommand::Players => "players",
+            ChatCommand::Plugin => "plugin",
             ChatCommand::Region => "region",
             ChatCommand::RemoveLights => "remove_lights",
             ChatCommand::RevokeBuild => "revoke_build",
@@ -706,6 +773,8 @@ impl ChatCommand {
             ChatCommand::Time => "time",
             ChatCommand::Tp => "tp",
             ChatCommand::Unban => "unban",
+            ChatCommand::UnbanIp => "unban_ip",
+            ChatCommand::Unmute => "unmute",
             ChatCommand::Version => "version",
             ChatCommand::Waypoint => "waypoint",
             ChatCommand::Wiring => "wiring",
@@ -797,28 +866,56 @@ impl FromStr for ChatCommand {
     }
 }
 
-#[derive(Eq, PartialEq, Debug)]
+/// A chat command that isn't built into the game, such as the ones provided by
+/// server plugins. The server sends these to clients so they can be tab
+/// completed like the built-in ones.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerChatCommand {
+    /// The keyword used to run the command, without the leading `/`
+    pub keyword: String,
+    /// A list of arguments useful for tab completion
+    pub args: Vec<ArgumentSpec<String>>,
+    /// A one-line message that explains what the command does
+    pub description: String,
+    /// Whether the command requires administrator permissions.
+    pub needs_role: Option<Role>,
+}
+
+impl ServerChatCommand {
+    /// A message that explains what the command does
+    pub fn help_string(&self) -> String {
+        let usage = std::iter::once(format!("/{}", self.keyword))
+            .chain(self.args.iter().map(|arg| arg.usage_string()))
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!("{}: {}", usage, self.description)
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Requirement {
     Required,
     Optional,
 }
 
-/// Representation for chat command arguments
-pub enum ArgumentSpec {
+/// Representation for chat command arguments. Labels are static strings for
+/// the built-in commands, and owned strings for the ones sent by the server.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ArgumentSpec<S = &'static str> {
     /// The argument refers to a player by alias
     PlayerName(Requirement),
     /// The argument is a float. The associated values are
     /// * label
     /// * suggested tab-completion
     /// * whether it's optional
-    Float(&'static str, f32, Requirement),
+    Float(S, f32, Requirement),
     /// The argument is an integer. The associated values are
     /// * label
     /// * suggested tab-completion
     /// * whether it's optional
-    Integer(&'static str, i32, Requirement),
+    Integer(S, i32, Requirement),
     /// The argument is any string that doesn't contain spaces
-    Any(&'static str, Requirement),
+    Any(S, Requirement),
     /// The argument is a command name (such as in /help)
     Command(Requirement),
     /// This is the final argument, consuming all characters until the end of
@@ -830,15 +927,15 @@ pub enum ArgumentSpec {
     /// * label
     /// * Predefined string completions
     /// * whether it's optional
-    Enum(&'static str, Vec<String>, Requirement),
+    Enum(S, Vec<String>, Requirement),
     /// The argument is likely a boolean. The associated values are
     /// * label
     /// * suggested tab-completion
     /// * whether it's optional
-    Boolean(&'static str, String, Requirement),
+    Boolean(S, String, Requirement),
 }
 
-impl ArgumentSpec {
+impl<S: Display> ArgumentSpec<S> {
     pub fn usage_string(&self) -> String {
         match self {
             ArgumentSpec::PlayerName(req) => {
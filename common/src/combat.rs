@@ -17,7 +17,7 @@ use crate::{
     event::ServerEvent,
     outcome::Outcome,
     states::utils::StageSection,
-    uid::{Uid, UidAllocator},
+    uid::UidAllocator,
     util::Dir,
 };
 
@@ -26,7 +26,7 @@ use rand::{thread_rng, Rng};
 
 use serde::{Deserialize, Serialize};
 
-use crate::{comp::Group, resources::Time};
+use crate::{comp::Group, resources::Time, uid::Uid};
 #[cfg(not(target_arch = "wasm32"))]
 use specs::{saveload::MarkerAllocator, Entity as EcsEntity, ReadStorage};
 #[cfg(not(target_arch = "wasm32"))]
@@ -72,6 +72,28 @@ pub struct TargetInfo<'a> {
     pub char_state: Option<&'a CharacterState>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum KillType {
+    Buff(BuffKind),
+    Melee,
+    Projectile,
+    Explosion,
+    Energy,
+    Other,
+    // Projectile(String), TODO: add projectile name when available
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum KillSource {
+    Player(Uid, KillType),
+    NonPlayer(String, KillType),
+    NonExistent(KillType),
+    Environment(String),
+    FallDamage,
+    Suicide,
+    Other,
+}
+
 #[derive(Clone, Copy)]
 pub struct AttackOptions {
     pub target_dodging: bool,
@@ -1,9 +1,10 @@
 use clap::arg_enum;
+use serde::{Deserialize, Serialize};
 use specs::Component;
 use specs_idvs::IdvStorage;
 
 arg_enum! {
-    #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+    #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
     pub enum AdminRole {
         Moderator = 0,
         Admin = 1,
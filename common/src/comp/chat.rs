@@ -1,7 +1,6 @@
-use crate::{
-    comp::{group::Group, BuffKind},
-    uid::Uid,
-};
+// Kept in `combat` so that plugins can use them
+pub use crate::combat::{KillSource, KillType};
+use crate::{comp::group::Group, uid::Uid};
 use serde::{Deserialize, Serialize};
 use specs::Component;
 use specs_idvs::IdvStorage;
@@ -48,28 +47,6 @@ impl ChatMode {
     pub const fn default() -> Self { Self::World }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum KillType {
-    Buff(BuffKind),
-    Melee,
-    Projectile,
-    Explosion,
-    Energy,
-    Other,
-    // Projectile(String), TODO: add projectile name when available
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum KillSource {
-    Player(Uid, KillType),
-    NonPlayer(String, KillType),
-    NonExistent(KillType),
-    Environment(String),
-    FallDamage,
-    Suicide,
-    Other,
-}
-
 /// List of chat types. Each one is colored differently and has its own icon.
 ///
 /// This is a superset of `SpeechBubbleType`, which is a superset of `ChatMode`
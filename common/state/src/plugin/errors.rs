@@ -7,6 +7,14 @@ pub enum PluginError {
     Toml(toml::de::Error),
     NoConfig,
     NoSuchModule,
+    /// No plugin with this name is loaded
+    NoSuchPlugin(String),
+    /// A plugin (first) depends on a plugin that isn't loaded (second)
+    MissingDependency(String, String),
+    /// These plugins depend on each other
+    DependencyCycle(Vec<String>),
+    /// The plugin can't be unloaded while these plugins depend on it
+    RequiredBy(String, Vec<String>),
     Encoding(Box<ErrorKind>),
     PluginModuleError(String, String, PluginModuleError),
 }
@@ -20,6 +28,11 @@ pub enum PluginModuleError {
     RunFunction(RuntimeError),
     InvalidArgumentType(),
     Encoding(Box<ErrorKind>),
+    /// The module ran more instructions than its budget for a single event
+    OutOfFuel,
+    /// The module trapped after failing to grow its linear memory past the
+    /// limit
+    MemoryLimitReached(RuntimeError),
 }
 
 #[derive(Debug)]
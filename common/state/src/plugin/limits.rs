@@ -0,0 +1,477 @@
+use std::{
+    convert::TryInto,
+    ptr::NonNull,
+    sync::{Arc, Mutex},
+};
+
+use loupe::MemoryUsage;
+use serde::{Deserialize, Serialize};
+use wasmer::{
+    vm::{self, MemoryError, MemoryStyle, TableStyle, VMMemoryDefinition, VMTableDefinition},
+    wasmparser::{Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType},
+    ExportIndex, FunctionMiddleware, GlobalInit, GlobalType, Instance, LocalFunctionIndex,
+    MemoryType, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Pages,
+    TableType, Tunables, Type, WASM_PAGE_SIZE,
+};
+use wasmer_types::GlobalIndex;
+use wasmer_vm::ModuleInfo;
+
+const REMAINING_POINTS_EXPORT: &str = "veloren_metering_remaining_points";
+const POINTS_EXHAUSTED_EXPORT: &str = "veloren_metering_points_exhausted";
+const MEMORY_GROW_FAILED_EXPORT: &str = "veloren_memory_grow_failed";
+
+/// The resources a plugin module is allowed to use
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginLimits {
+    /// Number of WASM instructions a module may run while handling a single
+    /// event
+    pub fuel_per_event: u64,
+    /// Maximum size of the linear memory of a module, in bytes
+    pub max_memory_bytes: u64,
+    /// Number of times a module may go over its limits before being disabled
+    pub max_offences: u32,
+}
+
+impl Default for PluginLimits {
+    fn default() -> Self {
+        Self {
+            fuel_per_event: 50_000_000,
+            max_memory_bytes: 64 * 1024 * 1024,
+            max_offences: 3,
+        }
+    }
+}
+
+impl PluginLimits {
+    /// The maximum linear memory size, rounded down to whole WASM pages
+    pub fn max_memory_pages(&self) -> Pages {
+        Pages((self.max_memory_bytes / WASM_PAGE_SIZE as u64).min(u64::from(u32::MAX)) as u32)
+    }
+}
+
+/// This structure wraps the default tunables to cap the linear memory of
+/// modules to a maximum amount of pages. Growing the memory past this limit
+/// fails like it would on a host out of memory.
+pub struct LimitingTunables<T: Tunables> {
+    limit: Pages,
+    base: T,
+}
+
+impl<T: Tunables> LimitingTunables<T> {
+    pub fn new(base: T, limit: Pages) -> Self { Self { limit, base } }
+
+    /// Clamps the maximum of the requested memory to the limit
+    fn adjust_memory(&self, requested: &MemoryType) -> MemoryType {
+        let mut adjusted = *requested;
+        adjusted.maximum = Some(
+            requested
+                .maximum
+                .map_or(self.limit, |maximum| maximum.min(self.limit)),
+        );
+        adjusted
+    }
+
+    /// Refuses memories whose initial size is already over the limit
+    fn validate_memory(&self, ty: &MemoryType) -> Result<(), MemoryError> {
+        if ty.minimum > self.limit {
+            return Err(MemoryError::Generic(format!(
+                "Module requires {:?} of memory but plugins are limited to {:?}",
+                ty.minimum, self.limit
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl<T: Tunables> Tunables for LimitingTunables<T> {
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        self.base.memory_style(&self.adjust_memory(memory))
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle { self.base.table_style(table) }
+
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<Arc<dyn vm::Memory>, MemoryError> {
+        let adjusted = self.adjust_memory(ty);
+        self.validate_memory(&adjusted)?;
+        self.base.create_host_memory(&adjusted, style)
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<Arc<dyn vm::Memory>, MemoryError> {
+        let adjusted = self.adjust_memory(ty);
+        self.validate_memory(&adjusted)?;
+        self.base
+            .create_vm_memory(&adjusted, style, vm_definition_location)
+    }
+
+    fn create_host_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+    ) -> Result<Arc<dyn vm::Table>, String> {
+        self.base.create_host_table(ty, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<Arc<dyn vm::Table>, String> {
+        self.base.create_vm_table(ty, style, vm_definition_location)
+    }
+}
+
+/// Fuel left in an instance, see [`get_remaining_points`]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MeteringPoints {
+    Remaining(u64),
+    Exhausted,
+}
+
+#[derive(Copy, Clone, Debug, MemoryUsage)]
+struct MeteringGlobals {
+    remaining_points: GlobalIndex,
+    points_exhausted: GlobalIndex,
+    /// Result of the last `memory.grow`, only needed to inspect it
+    grow_result: GlobalIndex,
+    memory_grow_failed: GlobalIndex,
+}
+
+/// This middleware charges one point of fuel for every instruction and traps
+/// once the remaining points of an instance don't cover the next block of
+/// instructions. The points are kept in two globals which are exported so
+/// that they can be refilled from the host with [`set_remaining_points`].
+///
+/// It also flags every `memory.grow` that fails, e.g. because of the
+/// [`LimitingTunables`], see [`memory_grow_failed`].
+///
+/// A `Metering` can only be used to compile a single module.
+#[derive(Debug, MemoryUsage)]
+pub struct Metering {
+    initial_limit: u64,
+    globals: Mutex<Option<MeteringGlobals>>,
+}
+
+#[derive(Debug)]
+struct FunctionMetering {
+    globals: MeteringGlobals,
+    /// Cost of the instructions since the last check
+    accumulated_cost: u64,
+}
+
+impl Metering {
+    pub fn new(initial_limit: u64) -> Self {
+        Self {
+            initial_limit,
+            globals: Mutex::new(None),
+        }
+    }
+}
+
+impl ModuleMiddleware for Metering {
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionMetering {
+            globals: self
+                .globals
+                .lock()
+                .unwrap()
+                .expect("Module info wasn't transformed before its functions"),
+            accumulated_cost: 0,
+        })
+    }
+
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut globals = self.globals.lock().unwrap();
+        assert!(
+            globals.is_none(),
+            "A Metering middleware can't be used for multiple modules"
+        );
+
+        let remaining_points = module_info
+            .globals
+            .push(GlobalType::new(Type::I64, Mutability::Var));
+        module_info
+            .global_initializers
+            .push(GlobalInit::I64Const(self.initial_limit as i64));
+        module_info.exports.insert(
+            REMAINING_POINTS_EXPORT.to_string(),
+            ExportIndex::Global(remaining_points),
+        );
+
+        let points_exhausted = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+        module_info.exports.insert(
+            POINTS_EXHAUSTED_EXPORT.to_string(),
+            ExportIndex::Global(points_exhausted),
+        );
+
+        let grow_result = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+
+        let memory_grow_failed = module_info
+            .globals
+            .push(GlobalType::new(Type::I32, Mutability::Var));
+        module_info
+            .global_initializers
+            .push(GlobalInit::I32Const(0));
+        module_info.exports.insert(
+            MEMORY_GROW_FAILED_EXPORT.to_string(),
+            ExportIndex::Global(memory_grow_failed),
+        );
+
+        *globals = Some(MeteringGlobals {
+            remaining_points,
+            points_exhausted,
+            grow_result,
+            memory_grow_failed,
+        });
+    }
+}
+
+impl FunctionMiddleware for FunctionMetering {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        self.accumulated_cost += 1;
+
+        // Check the fuel before every instruction that may leave the current block,
+        // so that no loop or call runs without being paid for
+        match operator {
+            Operator::Loop { .. }
+            | Operator::End
+            | Operator::Else
+            | Operator::Br { .. }
+            | Operator::BrIf { .. }
+            | Operator::BrTable { .. }
+            | Operator::Call { .. }
+            | Operator::CallIndirect { .. }
+            | Operator::Return => {
+                let remaining_points = self.globals.remaining_points.as_u32();
+                let cost = self.accumulated_cost as i64;
+                state.extend(&[
+                    Operator::GlobalGet {
+                        global_index: remaining_points,
+                    },
+                    Operator::I64Const { value: cost },
+                    Operator::I64LtU,
+                    Operator::If {
+                        ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+                    },
+                    Operator::I32Const { value: 1 },
+                    Operator::GlobalSet {
+                        global_index: self.globals.points_exhausted.as_u32(),
+                    },
+                    Operator::Unreachable,
+                    Operator::End,
+                    Operator::GlobalGet {
+                        global_index: remaining_points,
+                    },
+                    Operator::I64Const { value: cost },
+                    Operator::I64Sub,
+                    Operator::GlobalSet {
+                        global_index: remaining_points,
+                    },
+                ]);
+                self.accumulated_cost = 0;
+            },
+            _ => {},
+        }
+
+        let grows_memory = matches!(operator, Operator::MemoryGrow { .. });
+        state.push_operator(operator);
+
+        // `memory.grow` returns -1 instead of trapping when it fails, remember that so
+        // a later trap can be attributed to the memory limit
+        if grows_memory {
+            let grow_result = self.globals.grow_result.as_u32();
+            state.extend(&[
+                Operator::GlobalSet {
+                    global_index: grow_result,
+                },
+                Operator::GlobalGet {
+                    global_index: grow_result,
+                },
+                Operator::I32Const { value: -1 },
+                Operator::I32Eq,
+                Operator::If {
+                    ty: WpTypeOrFuncType::Type(WpType::EmptyBlockType),
+                },
+                Operator::I32Const { value: 1 },
+                Operator::GlobalSet {
+                    global_index: self.globals.memory_grow_failed.as_u32(),
+                },
+                Operator::End,
+                Operator::GlobalGet {
+                    global_index: grow_result,
+                },
+            ]);
+        }
+        Ok(())
+    }
+}
+
+/// Returns the fuel left in an instance compiled with [`Metering`]
+pub fn get_remaining_points(instance: &Instance) -> MeteringPoints {
+    let exhausted: i32 = instance
+        .exports
+        .get_global(POINTS_EXHAUSTED_EXPORT)
+        .expect("Module wasn't compiled with the Metering middleware")
+        .get()
+        .try_into()
+        .expect("Points exhausted global has the wrong type");
+    if exhausted > 0 {
+        return MeteringPoints::Exhausted;
+    }
+
+    let points: i64 = instance
+        .exports
+        .get_global(REMAINING_POINTS_EXPORT)
+        .expect("Module wasn't compiled with the Metering middleware")
+        .get()
+        .try_into()
+        .expect("Remaining points global has the wrong type");
+    MeteringPoints::Remaining(points as u64)
+}
+
+/// Whether a `memory.grow` failed since the last [`set_remaining_points`] in
+/// an instance compiled with [`Metering`]
+pub fn memory_grow_failed(instance: &Instance) -> bool {
+    let failed: i32 = instance
+        .exports
+        .get_global(MEMORY_GROW_FAILED_EXPORT)
+        .expect("Module wasn't compiled with the Metering middleware")
+        .get()
+        .try_into()
+        .expect("Memory grow failed global has the wrong type");
+    failed > 0
+}
+
+/// Refills the fuel of an instance compiled with [`Metering`] and resets
+/// [`memory_grow_failed`]
+pub fn set_remaining_points(instance: &Instance, points: u64) {
+    instance
+        .exports
+        .get_global(REMAINING_POINTS_EXPORT)
+        .expect("Module wasn't compiled with the Metering middleware")
+        .set((points as i64).into())
+        .expect("Remaining points global can't be set");
+    instance
+        .exports
+        .get_global(POINTS_EXHAUSTED_EXPORT)
+        .expect("Module wasn't compiled with the Metering middleware")
+        .set(0i32.into())
+        .expect("Points exhausted global can't be set");
+    instance
+        .exports
+        .get_global(MEMORY_GROW_FAILED_EXPORT)
+        .expect("Module wasn't compiled with the Metering middleware")
+        .set(0i32.into())
+        .expect("Memory grow failed global can't be set");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmer::{
+        imports, BaseTunables, CompilerConfig, Cranelift, InstantiationError, Module, Store,
+        Target, Universal, Value,
+    };
+
+    const WAT: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "nop"))
+            (func (export "spin") (loop (br 0)))
+            (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0))))
+    "#;
+
+    fn instance(fuel: u64, memory_pages: u32) -> Result<Instance, InstantiationError> {
+        let mut compiler = Cranelift::default();
+        compiler.push_middleware(Arc::new(Metering::new(fuel)));
+        let engine = Universal::new(compiler).engine();
+        let tunables = LimitingTunables::new(
+            BaseTunables::for_target(&Target::default()),
+            Pages(memory_pages),
+        );
+        let store = Store::new_with_tunables(&engine, tunables);
+        let module = Module::new(&store, WAT).unwrap();
+        Instance::new(&module, &imports! {})
+    }
+
+    #[test]
+    fn charges_every_instruction() {
+        let instance = instance(100, 2).unwrap();
+        let nop = instance.exports.get_function("nop").unwrap();
+        nop.call(&[]).unwrap();
+        // the only instruction of `nop` is its `end`
+        assert_eq!(
+            get_remaining_points(&instance),
+            MeteringPoints::Remaining(99)
+        );
+        nop.call(&[]).unwrap();
+        assert_eq!(
+            get_remaining_points(&instance),
+            MeteringPoints::Remaining(98)
+        );
+    }
+
+    #[test]
+    fn traps_when_exhausted() {
+        let instance = instance(10_000, 2).unwrap();
+        let spin = instance.exports.get_function("spin").unwrap();
+        assert!(spin.call(&[]).is_err());
+        assert_eq!(get_remaining_points(&instance), MeteringPoints::Exhausted);
+
+        set_remaining_points(&instance, 10);
+        assert_eq!(
+            get_remaining_points(&instance),
+            MeteringPoints::Remaining(10)
+        );
+        assert!(spin.call(&[]).is_err());
+        assert_eq!(get_remaining_points(&instance), MeteringPoints::Exhausted);
+    }
+
+    #[test]
+    fn flags_failed_memory_grow() {
+        let instance = instance(10_000, 2).unwrap();
+        let grow = instance.exports.get_function("grow").unwrap();
+        assert_eq!(grow.call(&[Value::I32(1)]).unwrap()[0].i32(), Some(1));
+        assert!(!memory_grow_failed(&instance));
+        assert_eq!(grow.call(&[Value::I32(1)]).unwrap()[0].i32(), Some(-1));
+        assert!(memory_grow_failed(&instance));
+
+        set_remaining_points(&instance, 10_000);
+        assert!(!memory_grow_failed(&instance));
+    }
+
+    #[test]
+    fn refuses_memory_over_the_limit() {
+        let limits = PluginLimits {
+            max_memory_bytes: 3 * WASM_PAGE_SIZE as u64 + 1,
+            ..PluginLimits::default()
+        };
+        assert_eq!(limits.max_memory_pages(), Pages(3));
+        // the module starts with one page
+        assert!(instance(100, 0).is_err());
+    }
+}
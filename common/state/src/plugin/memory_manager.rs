@@ -2,13 +2,16 @@ use std::sync::atomic::{AtomicPtr, AtomicU32, AtomicU64, Ordering};
 
 use serde::{de::DeserializeOwned, Serialize};
 use specs::{
-    storage::GenericReadStorage, Component, Entities, Entity, Read, ReadStorage, WriteStorage,
+    storage::GenericReadStorage, Component, Entities, Entity, Read, ReadExpect, ReadStorage,
+    WriteStorage,
 };
 use wasmer::{Function, Memory, Value};
 
 use common::{
-    comp::{Health, Player},
+    comp::{Group, Health, Inventory, Player, Pos, SkillSet, Stats, Vel},
+    terrain::TerrainGrid,
     uid::{Uid, UidAllocator},
+    CachedSpatialGrid,
 };
 
 use super::errors::{MemoryAllocationError, PluginModuleError};
@@ -18,7 +21,15 @@ pub struct EcsWorld<'a, 'b> {
     pub health: EcsComponentAccess<'a, 'b, Health>,
     pub uid: EcsComponentAccess<'a, 'b, Uid>,
     pub player: EcsComponentAccess<'a, 'b, Player>,
+    pub pos: EcsComponentAccess<'a, 'b, Pos>,
+    pub vel: EcsComponentAccess<'a, 'b, Vel>,
+    pub inventory: EcsComponentAccess<'a, 'b, Inventory>,
+    pub stats: EcsComponentAccess<'a, 'b, Stats>,
+    pub skill_set: EcsComponentAccess<'a, 'b, SkillSet>,
+    pub group: EcsComponentAccess<'a, 'b, Group>,
     pub uid_allocator: &'b Read<'a, UidAllocator>,
+    pub spatial_grid: &'b Read<'a, CachedSpatialGrid>,
+    pub terrain: &'b ReadExpect<'a, TerrainGrid>,
 }
 
 pub enum EcsComponentAccess<'a, 'b, T: Component> {
@@ -1,24 +1,35 @@
 pub mod errors;
+pub mod limits;
 pub mod memory_manager;
 pub mod module;
+pub mod storage;
+pub mod timer;
 pub mod wasm_env;
 
-use common::assets::ASSETS_PATH;
+use common::{
+    assets::ASSETS_PATH,
+    cmd::{ChatCommand, ServerChatCommand},
+};
+use common_net::msg::{ClientPluginInfo, PluginHash};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
     fs,
     io::Read,
     path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
 };
 use tracing::{error, info};
 
-use plugin_api::Event;
+use plugin_api::{Action, CommandSpec, Event};
 
 use self::{
     errors::PluginError,
+    limits::PluginLimits,
     memory_manager::EcsWorld,
     module::{PluginModule, PreparedEventQuery},
+    storage::PluginStorage,
 };
 
 use rayon::prelude::*;
@@ -28,6 +39,13 @@ pub struct PluginData {
     name: String,
     modules: HashSet<PathBuf>,
     dependencies: HashSet<String>,
+    /// The chat commands handled by the plugin
+    #[serde(default)]
+    commands: Vec<CommandSpec>,
+    /// Whether the server sends the plugin to clients, so it runs on their
+    /// side too
+    #[serde(default)]
+    client: bool,
 }
 
 #[derive(Clone)]
@@ -36,10 +54,34 @@ pub struct Plugin {
     modules: Vec<PluginModule>,
     #[allow(dead_code)]
     files: HashMap<PathBuf, Vec<u8>>,
+    /// The tarball the plugin was loaded from, if any
+    path: Option<PathBuf>,
+    /// Modification time of the tarball when it was loaded
+    modified: Option<SystemTime>,
+    hash: PluginHash,
+    /// The whole tarball, only kept for plugins sent to clients
+    tarball: Option<Arc<[u8]>>,
 }
 
 impl Plugin {
-    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, PluginError> {
+    pub fn from_file(
+        path: &Path,
+        limits: PluginLimits,
+        storage: Option<Arc<dyn PluginStorage>>,
+    ) -> Result<Self, PluginError> {
+        let file = fs::File::open(path).map_err(PluginError::Io)?;
+        let modified = file.metadata().and_then(|m| m.modified()).ok();
+        let mut plugin = Self::from_reader(file, limits, storage)?;
+        plugin.path = Some(path.to_owned());
+        plugin.modified = modified;
+        Ok(plugin)
+    }
+
+    pub fn from_reader<R: Read>(
+        mut reader: R,
+        limits: PluginLimits,
+        storage: Option<Arc<dyn PluginStorage>>,
+    ) -> Result<Self, PluginError> {
         let mut buf = Vec::new();
         reader.read_to_end(&mut buf).map_err(PluginError::Io)?;
 
@@ -69,19 +111,64 @@ impl Plugin {
             .iter()
             .map(|path| {
                 let wasm_data = files.remove(path).ok_or(PluginError::NoSuchModule)?;
-                PluginModule::new(data.name.to_owned(), &wasm_data).map_err(|e| {
-                    PluginError::PluginModuleError(data.name.to_owned(), "<init>".to_owned(), e)
-                })
+                PluginModule::new(data.name.to_owned(), &wasm_data, limits, storage.clone())
+                    .map_err(|e| {
+                        PluginError::PluginModuleError(data.name.to_owned(), "<init>".to_owned(), e)
+                    })
             })
             .collect::<Result<_, _>>()?;
 
+        let hash = hash_tarball(&buf);
+        let tarball = data.client.then(|| Arc::from(buf));
+
         Ok(Plugin {
             data,
             modules,
             files,
+            path: None,
+            modified: None,
+            hash,
+            tarball,
         })
     }
 
+    pub fn name(&self) -> &str { &self.data.name }
+
+    /// The paths of the WASM modules inside the plugin tarball
+    pub fn modules(&self) -> impl Iterator<Item = &Path> {
+        self.data.modules.iter().map(PathBuf::as_path)
+    }
+
+    pub fn path(&self) -> Option<&Path> { self.path.as_deref() }
+
+    /// The hash of the tarball the plugin was loaded from
+    pub fn hash(&self) -> &PluginHash { &self.hash }
+
+    /// Whether the plugin is sent to clients
+    pub fn is_client_plugin(&self) -> bool { self.data.client }
+
+    /// The chat commands declared in `plugin.toml`, followed by the ones
+    /// registered by the modules which take precedence over them
+    pub fn commands(&self) -> Vec<CommandSpec> {
+        let mut commands = self.data.commands.clone();
+        for command in self.modules.iter().flat_map(PluginModule::commands) {
+            commands.retain(|c| c.name != command.name);
+            commands.push(command);
+        }
+        commands
+    }
+
+    pub fn execute_event<T>(
+        &self,
+        ecs: &EcsWorld,
+        event: &T,
+    ) -> Result<Vec<T::Response>, PluginError>
+    where
+        T: Event,
+    {
+        self.execute_prepared(ecs, &PreparedEventQuery::new(event)?)
+    }
+
     pub fn execute_prepared<T>(
         &self,
         ecs: &EcsWorld,
@@ -105,19 +192,60 @@ impl Plugin {
             })
             .collect::<Result<Vec<_>, _>>()
     }
+
+    /// Takes the actions emitted by every module of the plugin since the last
+    /// call
+    pub fn take_actions(&self) -> Vec<Action> {
+        self.modules
+            .iter()
+            .flat_map(|module| module.take_actions())
+            .collect()
+    }
+
+    /// Sends `on_timer` to the modules for each of their timers due at `now`.
+    /// A failing handler doesn't prevent the other timers from firing.
+    pub fn fire_timers(&self, ecs: &EcsWorld, now: f64) {
+        for module in &self.modules {
+            for event in module.take_due_timers(now) {
+                let result = PreparedEventQuery::new(&event).and_then(|query| {
+                    module.try_execute(ecs, &query).transpose().map_err(|e| {
+                        PluginError::PluginModuleError(
+                            self.data.name.to_owned(),
+                            query.get_function_name().to_owned(),
+                            e,
+                        )
+                    })
+                });
+                if let Err(e) = result {
+                    error!(?e, "Failed to fire timer {:?}", event.id);
+                }
+            }
+        }
+    }
 }
 
 #[derive(Clone, Default)]
 pub struct PluginMgr {
     plugins: Vec<Plugin>,
+    /// The directory plugins were loaded from
+    dir: Option<PathBuf>,
+    limits: PluginLimits,
+    /// The persistent storage given to the plugins
+    storage: Option<Arc<dyn PluginStorage>>,
+    /// Tarballs of the plugins unloaded at runtime, with their modification
+    /// time at that moment, so they are only picked up again once they change
+    unloaded: HashMap<PathBuf, Option<SystemTime>>,
 }
 
 impl PluginMgr {
-    pub fn from_assets() -> Result<Self, PluginError> {
+    pub fn from_assets(
+        limits: PluginLimits,
+        storage: Option<Arc<dyn PluginStorage>>,
+    ) -> Result<Self, PluginError> {
         let mut assets_path = (&*ASSETS_PATH).clone();
         assets_path.push("plugins");
         info!("Searching {:?} for plugins...", assets_path);
-        Self::from_dir(assets_path)
+        Self::from_dir(assets_path, limits, storage)
     }
 
     pub fn execute_prepared<T>(
@@ -128,14 +256,19 @@ impl PluginMgr {
     where
         T: Event,
     {
-        Ok(self
-            .plugins
-            .par_iter()
-            .map(|plugin| plugin.execute_prepared(ecs, event))
-            .collect::<Result<Vec<_>, _>>()?
-            .into_iter()
-            .flatten()
-            .collect())
+        let responses = if T::ORDERED {
+            // Dependencies handle the event before the plugins using them
+            self.plugins
+                .iter()
+                .map(|plugin| plugin.execute_prepared(ecs, event))
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            self.plugins
+                .par_iter()
+                .map(|plugin| plugin.execute_prepared(ecs, event))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        Ok(responses.into_iter().flatten().collect())
     }
 
     pub fn execute_event<T>(
@@ -149,31 +282,42 @@ impl PluginMgr {
         self.execute_prepared(ecs, &PreparedEventQuery::new(event)?)
     }
 
-    pub fn from_dir<P: AsRef<Path>>(path: P) -> Result<Self, PluginError> {
-        let plugins = fs::read_dir(path)
-            .map_err(PluginError::Io)?
-            .filter_map(|e| e.ok())
-            .map(|entry| {
-                if entry.file_type().map(|ft| ft.is_file()).unwrap_or(false)
-                    && entry
-                        .path()
-                        .file_name()
-                        .and_then(|n| n.to_str())
-                        .map(|s| s.ends_with(".plugin.tar"))
-                        .unwrap_or(false)
-                {
-                    info!("Loading plugin at {:?}", entry.path());
-                    Plugin::from_reader(fs::File::open(entry.path()).map_err(PluginError::Io)?)
-                        .map(Some)
-                } else {
-                    Ok(None)
-                }
+    /// Fires the timers of all the plugins due at `now`, the current value of
+    /// the `Time` resource
+    pub fn fire_timers(&self, ecs: &EcsWorld, now: f64) {
+        self.plugins
+            .par_iter()
+            .for_each(|plugin| plugin.fire_timers(ecs, now));
+    }
+
+    /// Takes the actions emitted by all the plugins since the last call. The
+    /// caller is responsible for applying them to the game.
+    pub fn take_actions(&self) -> Vec<Action> {
+        self.plugins
+            .iter()
+            .flat_map(|plugin| plugin.take_actions())
+            .collect()
+    }
+
+    pub fn from_dir<P: AsRef<Path>>(
+        path: P,
+        limits: PluginLimits,
+        storage: Option<Arc<dyn PluginStorage>>,
+    ) -> Result<Self, PluginError> {
+        let plugins = Self::plugin_files(path.as_ref())?
+            .into_iter()
+            .map(|path| {
+                info!("Loading plugin at {:?}", path);
+                Plugin::from_file(&path, limits, storage.clone())
             })
-            .filter_map(Result::transpose)
             .inspect(|p| {
                 let _ = p.as_ref().map_err(|e| error!(?e, "Failed to load plugin"));
             })
             .collect::<Result<Vec<_>, _>>()?;
+        let plugins = sort_by_dependencies(plugins).map_err(|e| {
+            error!(?e, "Failed to resolve plugin dependencies");
+            e
+        })?;
 
         for plugin in &plugins {
             info!(
@@ -183,6 +327,379 @@ impl PluginMgr {
             );
         }
 
-        Ok(Self { plugins })
+        Ok(Self {
+            plugins,
+            dir: Some(path.as_ref().to_owned()),
+            limits,
+            storage,
+            unloaded: HashMap::new(),
+        })
+    }
+
+    /// Lists the plugin tarballs in a directory
+    fn plugin_files(dir: &Path) -> Result<Vec<PathBuf>, PluginError> {
+        Ok(fs::read_dir(dir)
+            .map_err(PluginError::Io)?
+            .filter_map(|e| e.ok())
+            .filter(|entry| {
+                entry.file_type().map(|ft| ft.is_file()).unwrap_or(false)
+                    && entry
+                        .path()
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .map(|s| s.ends_with(".plugin.tar"))
+                        .unwrap_or(false)
+            })
+            .map(|entry| entry.path())
+            .collect())
+    }
+
+    pub fn plugins(&self) -> &[Plugin] { &self.plugins }
+
+    /// The plugins sent to clients when they connect
+    pub fn client_plugins(&self) -> Vec<ClientPluginInfo> {
+        self.plugins
+            .iter()
+            .filter(|plugin| plugin.is_client_plugin())
+            .map(|plugin| ClientPluginInfo {
+                name: plugin.name().to_owned(),
+                hash: plugin.hash,
+            })
+            .collect()
+    }
+
+    /// Whether a plugin built from the tarball with the given hash is loaded
+    pub fn is_loaded(&self, hash: &PluginHash) -> bool {
+        self.plugins.iter().any(|plugin| plugin.hash == *hash)
+    }
+
+    /// The tarball of the client plugin with the given hash, if it is loaded
+    pub fn client_tarball(&self, hash: &PluginHash) -> Option<&[u8]> {
+        self.plugins
+            .iter()
+            .find(|plugin| plugin.hash == *hash)
+            .and_then(|plugin| plugin.tarball.as_deref())
+    }
+
+    /// Loads a plugin from a tarball outside of the plugin directory, such as
+    /// one downloaded from a server. A loaded plugin with the same name is
+    /// replaced. Returns the name of the plugin, which still needs to receive
+    /// `on_load`.
+    pub fn load_file(&mut self, path: &Path) -> Result<String, PluginError> {
+        let plugin = Plugin::from_file(path, self.limits, self.storage.clone())?;
+        let name = plugin.name().to_owned();
+        let index = self.plugins.iter().position(|p| p.name() == name);
+        self.insert(plugin, index)?;
+        info!("Loaded plugin '{}' from {}", name, path.display());
+        Ok(name)
+    }
+
+    /// Finds the plugin handling a chat command along with the declaration of
+    /// the command. Plugins loaded first take precedence.
+    pub fn find_command(&self, name: &str) -> Option<(&Plugin, CommandSpec)> {
+        self.plugins.iter().find_map(|plugin| {
+            plugin
+                .commands()
+                .into_iter()
+                .find(|command| command.name == name)
+                .map(|command| (plugin, command))
+        })
+    }
+
+    /// The chat commands handled by the plugins, as described to clients.
+    /// Commands hidden by a built-in command or by a plugin loaded earlier are
+    /// skipped.
+    pub fn commands(&self) -> Vec<ServerChatCommand> {
+        let mut seen = HashSet::new();
+        self.plugins
+            .iter()
+            .flat_map(Plugin::commands)
+            .filter(|command| {
+                command.name.parse::<ChatCommand>().is_err() && seen.insert(command.name.clone())
+            })
+            .map(|command| ServerChatCommand::from(&command))
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Plugin> {
+        self.plugins.iter().find(|plugin| plugin.name() == name)
+    }
+
+    /// Loads the plugin with the given name again from its tarball, replacing
+    /// the currently loaded version. The caller is responsible for sending
+    /// `on_load` to the new version.
+    pub fn reload(&mut self, name: &str) -> Result<&Plugin, PluginError> {
+        let index = self
+            .plugins
+            .iter()
+            .position(|plugin| plugin.name() == name)
+            .ok_or_else(|| PluginError::NoSuchPlugin(name.to_owned()))?;
+        let path = self.plugins[index]
+            .path
+            .clone()
+            .ok_or_else(|| PluginError::NoSuchPlugin(name.to_owned()))?;
+        info!("Reloading plugin '{}' from {:?}", name, path);
+        let plugin = Plugin::from_file(&path, self.limits, self.storage.clone())?;
+        let name = plugin.name().to_owned();
+        self.insert(plugin, Some(index))?;
+        self.get(&name).ok_or(PluginError::NoSuchPlugin(name))
+    }
+
+    /// Removes the plugin with the given name, it won't receive any event
+    /// anymore. Fails if other plugins depend on it.
+    pub fn unload(&mut self, name: &str) -> Result<Plugin, PluginError> {
+        let index = self
+            .plugins
+            .iter()
+            .position(|plugin| plugin.name() == name)
+            .ok_or_else(|| PluginError::NoSuchPlugin(name.to_owned()))?;
+        let dependents = self
+            .plugins
+            .iter()
+            .filter(|plugin| plugin.data.dependencies.contains(name))
+            .map(|plugin| plugin.name().to_owned())
+            .collect::<Vec<_>>();
+        if !dependents.is_empty() {
+            return Err(PluginError::RequiredBy(name.to_owned(), dependents));
+        }
+        let plugin = self.plugins.remove(index);
+        if let Some(path) = &plugin.path {
+            self.unloaded.insert(path.clone(), plugin.modified);
+        }
+        info!("Unloaded plugin '{}'", name);
+        Ok(plugin)
+    }
+
+    /// Replaces the plugin at `index`, or adds it if there's none, keeping the
+    /// plugins sorted by dependencies. Nothing changes if the dependencies of
+    /// the new set of plugins can't be resolved.
+    fn insert(&mut self, plugin: Plugin, index: Option<usize>) -> Result<(), PluginError> {
+        let mut plugins = self.plugins.clone();
+        match index {
+            Some(index) => plugins[index] = plugin,
+            None => plugins.push(plugin),
+        }
+        self.plugins = sort_by_dependencies(plugins)?;
+        Ok(())
+    }
+
+    /// Reloads the plugins whose tarball changed on disk since they were
+    /// loaded, and loads new tarballs added to the plugin directory. Returns
+    /// the names of the plugins that were (re)loaded, which still need to
+    /// receive `on_load`.
+    pub fn reload_changed(&mut self) -> Vec<String> {
+        let dir = match &self.dir {
+            Some(dir) => dir.clone(),
+            None => return Vec::new(),
+        };
+        let files = match Self::plugin_files(&dir) {
+            Ok(files) => files,
+            Err(e) => {
+                error!(?e, "Failed to read plugin directory {:?}", dir);
+                return Vec::new();
+            },
+        };
+
+        let mut reloaded = Vec::new();
+        for path in files {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+            let loaded = self
+                .plugins
+                .iter()
+                .position(|plugin| plugin.path.as_ref() == Some(&path));
+            let changed = match loaded {
+                Some(index) => self.plugins[index].modified != modified,
+                None => self
+                    .unloaded
+                    .get(&path)
+                    .map_or(true, |unloaded| *unloaded != modified),
+            };
+            if !changed {
+                continue;
+            }
+
+            info!("Plugin tarball {:?} changed, reloading it", path);
+            match Plugin::from_file(&path, self.limits, self.storage.clone()).and_then(|plugin| {
+                let name = plugin.name().to_owned();
+                self.insert(plugin, loaded).map(|_| name)
+            }) {
+                Ok(name) => {
+                    self.unloaded.remove(&path);
+                    reloaded.push(name);
+                },
+                Err(e) => {
+                    error!(?e, "Failed to reload plugin at {:?}", path);
+                    // Don't try again until the tarball changes another time
+                    match loaded {
+                        Some(index) => self.plugins[index].modified = modified,
+                        None => {
+                            self.unloaded.insert(path, modified);
+                        },
+                    }
+                },
+            }
+        }
+        // `on_load` is sent in dependency order
+        self.plugins
+            .iter()
+            .map(|plugin| plugin.name().to_owned())
+            .filter(|name| reloaded.contains(name))
+            .collect()
+    }
+}
+
+/// Hashes a plugin tarball, to tell whether two tarballs contain the same
+/// version of a plugin
+pub fn hash_tarball(data: &[u8]) -> PluginHash {
+    let mut hash = PluginHash::default();
+    hash.copy_from_slice(
+        blake2b_simd::Params::new()
+            .hash_length(hash.len())
+            .hash(data)
+            .as_bytes(),
+    );
+    hash
+}
+
+/// Orders the plugins so that every plugin comes after its dependencies
+fn sort_by_dependencies(mut plugins: Vec<Plugin>) -> Result<Vec<Plugin>, PluginError> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Unvisited,
+        Visiting,
+        Done,
+    }
+
+    fn visit(
+        index: usize,
+        plugins: &[Plugin],
+        indices: &HashMap<&str, usize>,
+        marks: &mut [Mark],
+        stack: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), PluginError> {
+        match marks[index] {
+            Mark::Done => return Ok(()),
+            Mark::Visiting => {
+                let start = stack.iter().position(|i| *i == index).unwrap_or(0);
+                return Err(PluginError::DependencyCycle(
+                    stack[start..]
+                        .iter()
+                        .map(|i| plugins[*i].name().to_owned())
+                        .collect(),
+                ));
+            },
+            Mark::Unvisited => {},
+        }
+        marks[index] = Mark::Visiting;
+        stack.push(index);
+        let mut dependencies = plugins[index].data.dependencies.iter().collect::<Vec<_>>();
+        dependencies.sort();
+        for dependency in dependencies {
+            let dependency_index = *indices.get(dependency.as_str()).ok_or_else(|| {
+                PluginError::MissingDependency(
+                    plugins[index].name().to_owned(),
+                    dependency.to_owned(),
+                )
+            })?;
+            visit(dependency_index, plugins, indices, marks, stack, order)?;
+        }
+        stack.pop();
+        marks[index] = Mark::Done;
+        order.push(index);
+        Ok(())
+    }
+
+    // Sorting by name first keeps the order the same between runs
+    plugins.sort_by(|a, b| a.name().cmp(b.name()));
+    let indices = plugins
+        .iter()
+        .enumerate()
+        .map(|(i, plugin)| (plugin.name(), i))
+        .collect::<HashMap<_, _>>();
+    let mut marks = vec![Mark::Unvisited; plugins.len()];
+    let mut order = Vec::with_capacity(plugins.len());
+    for index in 0..plugins.len() {
+        visit(
+            index,
+            &plugins,
+            &indices,
+            &mut marks,
+            &mut Vec::new(),
+            &mut order,
+        )?;
+    }
+
+    let mut plugins = plugins.into_iter().map(Some).collect::<Vec<_>>();
+    Ok(order
+        .into_iter()
+        .filter_map(|index| plugins[index].take())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin(name: &str, dependencies: &[&str]) -> Plugin {
+        Plugin {
+            data: PluginData {
+                name: name.to_owned(),
+                modules: HashSet::new(),
+                dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+                commands: Vec::new(),
+                client: false,
+            },
+            modules: Vec::new(),
+            files: HashMap::new(),
+            path: None,
+            modified: None,
+            hash: PluginHash::default(),
+            tarball: None,
+        }
+    }
+
+    fn names(plugins: &[Plugin]) -> Vec<&str> { plugins.iter().map(|p| p.name()).collect() }
+
+    #[test]
+    fn dependencies_come_first() {
+        let plugins = sort_by_dependencies(vec![
+            plugin("zeta", &["base"]),
+            plugin("alpha", &["zeta", "base"]),
+            plugin("base", &[]),
+            plugin("other", &[]),
+        ])
+        .unwrap();
+        assert_eq!(names(&plugins), ["base", "zeta", "alpha", "other"]);
+    }
+
+    #[test]
+    fn missing_dependency() {
+        let result = sort_by_dependencies(vec![plugin("base", &[]), plugin("alpha", &["gone"])]);
+        assert!(matches!(
+            result,
+            Err(PluginError::MissingDependency(plugin, dependency))
+                if plugin == "alpha" && dependency == "gone"
+        ));
+    }
+
+    #[test]
+    fn dependency_cycle() {
+        let result = sort_by_dependencies(vec![
+            plugin("c", &["a"]),
+            plugin("b", &["c"]),
+            plugin("a", &["b"]),
+            plugin("base", &[]),
+        ]);
+        assert!(matches!(
+            result,
+            Err(PluginError::DependencyCycle(cycle)) if cycle == ["a", "b", "c"]
+        ));
+
+        let result = sort_by_dependencies(vec![plugin("self", &["self"])]);
+        assert!(matches!(
+            result,
+            Err(PluginError::DependencyCycle(cycle)) if cycle == ["self"]
+        ));
     }
 }
@@ -2,19 +2,42 @@ use std::{
     collections::HashSet,
     convert::TryInto,
     marker::PhantomData,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex,
+    },
 };
 
 use specs::saveload::MarkerAllocator;
-use wasmer::{imports, Cranelift, Function, Instance, Memory, Module, Store, Universal, Value};
+use wasmer::{
+    imports, BaseTunables, CompilerConfig, Cranelift, Function, Instance, Memory, Module, Store,
+    Target, Universal, Value,
+};
 
 use super::{
     errors::{PluginError, PluginModuleError},
-    memory_manager::{self, EcsAccessManager, EcsWorld, MemoryManager},
+    limits::{
+        get_remaining_points, memory_grow_failed, set_remaining_points, LimitingTunables, Metering,
+        MeteringPoints, PluginLimits,
+    },
+    memory_manager::{self, EcsAccessManager, EcsComponentAccess, EcsWorld, MemoryManager},
+    storage::PluginStorage,
+    timer::TimerQueue,
     wasm_env::HostFunctionEnvironement,
 };
 
-use plugin_api::{Action, EcsAccessError, Event, Retrieve, RetrieveError, RetrieveResult};
+use common::{comp::group, uid::Uid, vol::ReadVol};
+use plugin_api::{
+    event::TimerEvent, Action, BlockInfo, CommandSpec, EcsAccessError, Event, Retrieve,
+    RetrieveError, RetrieveResult, StatsInfo, StorageAction, StorageError, StorageResult,
+    TimerAction, TimerError, TimerResult,
+};
+use specs::{Component, Entity, Join};
+use vek::*;
+
+/// Upper bound of the radius of [`Retrieve::GetEntitiesInRadius`], in blocks.
+/// This is about the largest view distance of a server.
+pub const MAX_ENTITIES_RADIUS: f32 = 2048.0;
 
 #[derive(Clone)]
 /// This structure represent the WASM State of the plugin.
@@ -23,31 +46,60 @@ pub struct PluginModule {
     wasm_state: Arc<Mutex<Instance>>,
     memory_manager: Arc<MemoryManager>,
     events: HashSet<String>,
+    actions: Arc<Mutex<Vec<Action>>>,
+    commands: Arc<Mutex<Vec<CommandSpec>>>,
+    timers: Arc<Mutex<TimerQueue>>,
     allocator: Function,
     memory: Memory,
-    #[allow(dead_code)]
+    limits: PluginLimits,
+    offences: Arc<AtomicU32>,
+    disabled: Arc<AtomicBool>,
     name: String,
 }
 
 impl PluginModule {
     /// This function takes bytes from a WASM File and compile them
-    pub fn new(name: String, wasm_data: &[u8]) -> Result<Self, PluginModuleError> {
+    pub fn new(
+        name: String,
+        wasm_data: &[u8],
+        limits: PluginLimits,
+        storage: Option<Arc<dyn PluginStorage>>,
+    ) -> Result<Self, PluginModuleError> {
+        // Every instruction costs one point of fuel, the budget is refilled before
+        // each event
+        let metering = Arc::new(Metering::new(limits.fuel_per_event));
+        let mut compiler = Cranelift::default();
+        compiler.push_middleware(metering);
         // This is creating the engine is this case a JIT based on Cranelift
-        let engine = Universal::new(Cranelift::default()).engine();
+        let engine = Universal::new(compiler).engine();
+        let tunables = LimitingTunables::new(
+            BaseTunables::for_target(&Target::default()),
+            limits.max_memory_pages(),
+        );
         // We are creating an enironnement
-        let store = Store::new(&engine);
+        let store = Store::new_with_tunables(&engine, tunables);
         // We are compiling the WASM file in the previously generated environement
         let module = Module::new(&store, &wasm_data).expect("Can't compile");
 
         // This is the function imported into the wasm environement
         fn raw_emit_actions(env: &HostFunctionEnvironement, ptr: i64, len: i64) {
-            handle_actions(match env.read_data(from_i64(ptr), from_i64(len)) {
-                Ok(e) => e,
-                Err(e) => {
-                    tracing::error!(?e, "Can't decode action");
-                    return;
+            match env.read_data::<Vec<Action>>(from_i64(ptr), from_i64(len)) {
+                Ok(e) => {
+                    for action in e {
+                        match action {
+                            // Commands are registered right away so they are known as soon as
+                            // `on_load` returns
+                            Action::RegisterCommand(spec) => {
+                                let mut commands = env.commands.lock().unwrap();
+                                commands.retain(|command| command.name != spec.name);
+                                commands.push(spec);
+                            },
+                            action => env.actions.lock().unwrap().push(action),
+                        }
+                    }
                 },
-            });
+                Err(e) => tracing::error!(?e, "Can't decode action"),
+            }
         }
 
         fn raw_retrieve_action(env: &HostFunctionEnvironement, ptr: i64, len: i64) -> i64 {
@@ -61,18 +113,41 @@ impl PluginModule {
             to_i64(env.write_data_as_pointer(&out).unwrap())
         }
 
+        fn raw_storage_action(env: &HostFunctionEnvironement, ptr: i64, len: i64) -> i64 {
+            let out = match env.read_data(from_i64(ptr), from_i64(len)) {
+                Ok(data) => storage_action(env, data),
+                Err(e) => Err(StorageError::BincodeError(e.to_string())),
+            };
+
+            to_i64(env.write_data_as_pointer(&out).unwrap())
+        }
+
+        fn raw_timer_action(env: &HostFunctionEnvironement, ptr: i64, len: i64) -> i64 {
+            let out = match env.read_data(from_i64(ptr), from_i64(len)) {
+                Ok(data) => timer_action(env, data),
+                Err(e) => Err(TimerError::BincodeError(e.to_string())),
+            };
+
+            to_i64(env.write_data_as_pointer(&out).unwrap())
+        }
+
         fn dbg(a: i32) {
             println!("WASM DEBUG: {}", a);
         }
 
         let ecs = Arc::new(EcsAccessManager::default());
         let memory_manager = Arc::new(MemoryManager::default());
+        let actions = Arc::new(Mutex::new(Vec::new()));
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let timers = Arc::new(Mutex::new(TimerQueue::default()));
 
         // Create an import object.
         let import_object = imports! {
             "env" => {
-                "raw_emit_actions" => Function::new_native_with_env(&store, HostFunctionEnvironement::new(name.clone(), ecs.clone(),memory_manager.clone()), raw_emit_actions),
-                "raw_retrieve_action" => Function::new_native_with_env(&store, HostFunctionEnvironement::new(name.clone(), ecs.clone(),memory_manager.clone()), raw_retrieve_action),
+                "raw_emit_actions" => Function::new_native_with_env(&store, HostFunctionEnvironement::new(name.clone(), ecs.clone(),memory_manager.clone(), actions.clone(), commands.clone(), storage.clone(), timers.clone()), raw_emit_actions),
+                "raw_retrieve_action" => Function::new_native_with_env(&store, HostFunctionEnvironement::new(name.clone(), ecs.clone(),memory_manager.clone(), actions.clone(), commands.clone(), storage.clone(), timers.clone()), raw_retrieve_action),
+                "raw_storage_action" => Function::new_native_with_env(&store, HostFunctionEnvironement::new(name.clone(), ecs.clone(),memory_manager.clone(), actions.clone(), commands.clone(), storage.clone(), timers.clone()), raw_storage_action),
+                "raw_timer_action" => Function::new_native_with_env(&store, HostFunctionEnvironement::new(name.clone(), ecs.clone(),memory_manager.clone(), actions.clone(), commands.clone(), storage.clone(), timers.clone()), raw_timer_action),
                 "dbg" => Function::new_native(&store, dbg),
             }
         };
@@ -83,6 +158,9 @@ impl PluginModule {
         Ok(Self {
             memory_manager,
             ecs,
+            actions,
+            commands,
+            timers,
             memory: instance
                 .exports
                 .get_memory("memory")
@@ -99,6 +177,9 @@ impl PluginModule {
                 .map(|(name, _)| name.to_string())
                 .collect(),
             wasm_state: Arc::new(Mutex::new(instance)),
+            limits,
+            offences: Arc::new(AtomicU32::new(0)),
+            disabled: Arc::new(AtomicBool::new(false)),
             name,
         })
     }
@@ -113,19 +194,77 @@ impl PluginModule {
     where
         T: Event,
     {
-        if !self.events.contains(&request.function_name) {
+        if !self.events.contains(&request.function_name) || self.is_disabled() {
             return None;
         }
         // Store the ECS Pointer for later use in `retreives`
         let bytes = match self.ecs.execute_with(ecs, || {
-            let mut state = self.wasm_state.lock().unwrap();
-            execute_raw(self, &mut state, &request.function_name, &request.bytes)
+            self.execute_limited(&request.function_name, &request.bytes)
         }) {
             Ok(e) => e,
             Err(e) => return Some(Err(e)),
         };
         Some(bincode::deserialize(&bytes).map_err(PluginModuleError::Encoding))
     }
+
+    /// Runs an exported function with a full fuel budget, recording an offence
+    /// if it went over its limits
+    fn execute_limited(
+        &self,
+        function_name: &str,
+        bytes: &[u8],
+    ) -> Result<Vec<u8>, PluginModuleError> {
+        let mut state = self.wasm_state.lock().unwrap();
+        set_remaining_points(&state, self.limits.fuel_per_event);
+        let result = execute_raw(self, &mut state, function_name, bytes);
+        drop(state);
+        if let Err(e) = &result {
+            if matches!(
+                e,
+                PluginModuleError::OutOfFuel | PluginModuleError::MemoryLimitReached(_)
+            ) {
+                self.record_offence(e);
+            }
+        }
+        result
+    }
+
+    /// Whether the module was disabled for going over its limits too often
+    pub fn is_disabled(&self) -> bool { self.disabled.load(Ordering::Relaxed) }
+
+    /// Counts a violation of the limits, disabling the module once it reached
+    /// the maximum number of offences
+    fn record_offence(&self, error: &PluginModuleError) {
+        let offences = self.offences.fetch_add(1, Ordering::Relaxed) + 1;
+        tracing::warn!(
+            ?error,
+            ?offences,
+            "Plugin module {} went over its limits",
+            self.name
+        );
+        if offences >= self.limits.max_offences && !self.disabled.swap(true, Ordering::Relaxed) {
+            tracing::error!(
+                ?error,
+                "Plugin module {} disabled after going over its limits {} times",
+                self.name,
+                offences
+            );
+        }
+    }
+
+    /// This function takes all the actions emitted by the module since the
+    /// last call, in the order they were emitted
+    pub fn take_actions(&self) -> Vec<Action> { std::mem::take(&mut *self.actions.lock().unwrap()) }
+
+    /// The chat commands registered by the module with
+    /// `Action::RegisterCommand`
+    pub fn commands(&self) -> Vec<CommandSpec> { self.commands.lock().unwrap().clone() }
+
+    /// Takes the events of the timers of the module due at `now`, the current
+    /// value of the `Time` resource
+    pub fn take_due_timers(&self, now: f64) -> Vec<TimerEvent> {
+        self.timers.lock().unwrap().take_due(now)
+    }
 }
 
 /// This structure represent a Pre-encoded event object (Useful to avoid
@@ -203,7 +342,17 @@ fn execute_raw(
 
     let function_result = func
         .call(&[Value::I64(to_i64(mem_position)), Value::I64(to_i64(len))])
-        .map_err(PluginModuleError::RunFunction)?;
+        .map_err(|e| {
+            // A trap is attributed to the limits if the module ran out of fuel or
+            // couldn't get more memory, other traps are bugs of the module
+            if let MeteringPoints::Exhausted = get_remaining_points(instance) {
+                PluginModuleError::OutOfFuel
+            } else if memory_grow_failed(instance) {
+                PluginModuleError::MemoryLimitReached(e)
+            } else {
+                PluginModuleError::RunFunction(e)
+            }
+        })?;
 
     // Waiting for `multi-value` to be added to LLVM. So we encode a pointer to a
     // u128 that represent [u64; 2]
@@ -231,74 +380,221 @@ fn retrieve_action(
     ecs: &EcsAccessManager,
     action: Retrieve,
 ) -> Result<RetrieveResult, RetrieveError> {
+    // Safety: No reference is leaked out the function so it is safe.
+    let world = unsafe {
+        ecs.get().ok_or(RetrieveError::EcsAccessError(
+            EcsAccessError::EcsPointerNotAvailable,
+        ))?
+    };
     match action {
         Retrieve::GetPlayerName(e) => {
-            // Safety: No reference is leaked out the function so it is safe.
-            let world = unsafe {
-                ecs.get().ok_or(RetrieveError::EcsAccessError(
-                    EcsAccessError::EcsPointerNotAvailable,
-                ))?
-            };
-            let player = world.uid_allocator.retrieve_entity_internal(e.0).ok_or(
-                RetrieveError::EcsAccessError(EcsAccessError::EcsEntityNotFound(e)),
-            )?;
-
+            let player = find_entity(world, e)?;
             Ok(RetrieveResult::GetPlayerName(
-                world
-                    .player
-                    .get(player)
-                    .ok_or_else(|| {
-                        RetrieveError::EcsAccessError(EcsAccessError::EcsComponentNotFound(
-                            e,
-                            "Player".to_owned(),
-                        ))
-                    })?
+                find_component(&world.player, player, e, "Player")?
                     .alias
                     .to_owned(),
             ))
         },
         Retrieve::GetEntityHealth(e) => {
-            // Safety: No reference is leaked out the function so it is safe.
-            let world = unsafe {
-                ecs.get().ok_or(RetrieveError::EcsAccessError(
-                    EcsAccessError::EcsPointerNotAvailable,
-                ))?
-            };
-            let player = world.uid_allocator.retrieve_entity_internal(e.0).ok_or(
-                RetrieveError::EcsAccessError(EcsAccessError::EcsEntityNotFound(e)),
-            )?;
+            let entity = find_entity(world, e)?;
             Ok(RetrieveResult::GetEntityHealth(
+                find_component(&world.health, entity, e, "Health")?.clone(),
+            ))
+        },
+        Retrieve::GetEntityPosition(e) => {
+            let entity = find_entity(world, e)?;
+            Ok(RetrieveResult::GetEntityPosition(
+                find_component(&world.pos, entity, e, "Pos")?.0.into_array(),
+            ))
+        },
+        Retrieve::GetEntityVelocity(e) => {
+            let entity = find_entity(world, e)?;
+            Ok(RetrieveResult::GetEntityVelocity(
+                find_component(&world.vel, entity, e, "Vel")?.0.into_array(),
+            ))
+        },
+        Retrieve::GetEntityInventory(e) => {
+            let entity = find_entity(world, e)?;
+            Ok(RetrieveResult::GetEntityInventory(
+                find_component(&world.inventory, entity, e, "Inventory")?.into(),
+            ))
+        },
+        Retrieve::GetEntityStats(e) => {
+            let entity = find_entity(world, e)?;
+            Ok(RetrieveResult::GetEntityStats(StatsInfo::new(
+                find_component(&world.stats, entity, e, "Stats")?,
+                world.skill_set.get(entity),
+            )))
+        },
+        Retrieve::GetEntityGroup(e) => {
+            let entity = find_entity(world, e)?;
+            let members = world
+                .group
+                .get(entity)
+                // NPCs and enemies share a group per alignment, it isn't a real group
+                .filter(|g| **g != group::ENEMY && **g != group::NPC)
+                .map(|group| {
+                    world
+                        .entities
+                        .join()
+                        .filter(|member| world.group.get(*member) == Some(group))
+                        .filter_map(|member| world.uid.get(member).copied())
+                        .collect()
+                });
+            Ok(RetrieveResult::GetEntityGroup(members))
+        },
+        Retrieve::GetEntitiesInRadius { center, radius } => {
+            if !radius.is_finite() || radius < 0.0 {
+                return Err(RetrieveError::OtherError(format!(
+                    "Invalid radius {}",
+                    radius
+                )));
+            }
+            // The search runs on the host without being charged fuel
+            let radius = radius.min(MAX_ENTITIES_RADIUS);
+            let center = Vec3::from(center);
+            Ok(RetrieveResult::GetEntitiesInRadius(
                 world
-                    .health
-                    .get(player)
-                    .ok_or_else(|| {
-                        RetrieveError::EcsAccessError(EcsAccessError::EcsComponentNotFound(
-                            e,
-                            "Health".to_owned(),
-                        ))
-                    })?
-                    .clone(),
+                    .spatial_grid
+                    .0
+                    .in_circle_aabr(center.xy(), radius)
+                    .filter(|entity| {
+                        world
+                            .pos
+                            .get(*entity)
+                            .map_or(false, |pos| pos.0.distance_squared(center) < radius.powi(2))
+                    })
+                    .filter_map(|entity| world.uid.get(entity).copied())
+                    .collect(),
             ))
         },
+        Retrieve::GetBlock(pos) => Ok(RetrieveResult::GetBlock(
+            world
+                .terrain
+                .get(Vec3::from(pos))
+                .ok()
+                .map(|block| BlockInfo::from(*block)),
+        )),
     }
 }
 
-fn handle_actions(actions: Vec<Action>) {
-    for action in actions {
-        match action {
-            Action::ServerClose => {
-                tracing::info!("Server closed by plugin");
-                std::process::exit(-1);
-            },
-            Action::Print(e) => {
-                tracing::info!("{}", e);
-            },
-            Action::PlayerSendMessage(a, b) => {
-                tracing::info!("SendMessage {} -> {}", a, b);
-            },
-            Action::KillEntity(e) => {
-                tracing::info!("Kill Entity {}", e);
-            },
-        }
+fn find_entity(world: &EcsWorld, uid: Uid) -> Result<Entity, RetrieveError> {
+    world
+        .uid_allocator
+        .retrieve_entity_internal(uid.0)
+        .ok_or(RetrieveError::EcsAccessError(
+            EcsAccessError::EcsEntityNotFound(uid),
+        ))
+}
+
+fn find_component<'c, T: Component>(
+    storage: &'c EcsComponentAccess<T>,
+    entity: Entity,
+    uid: Uid,
+    name: &str,
+) -> Result<&'c T, RetrieveError> {
+    storage.get(entity).ok_or_else(|| {
+        RetrieveError::EcsAccessError(EcsAccessError::EcsComponentNotFound(uid, name.to_owned()))
+    })
+}
+
+fn storage_action(
+    env: &HostFunctionEnvironement,
+    action: StorageAction,
+) -> Result<StorageResult, StorageError> {
+    let storage = env.storage.as_ref().ok_or(StorageError::Unavailable)?;
+    match action {
+        StorageAction::Get(key) => storage.get(&env.name, &key).map(StorageResult::Get),
+        StorageAction::Set(key, value) => storage
+            .set(&env.name, &key, &value)
+            .map(|_| StorageResult::Set),
+        StorageAction::Delete(key) => storage
+            .delete(&env.name, &key)
+            .map(|_| StorageResult::Delete),
+        StorageAction::ListKeys => storage.keys(&env.name).map(StorageResult::ListKeys),
+    }
+    .map_err(StorageError::BackendError)
+}
+
+fn timer_action(
+    env: &HostFunctionEnvironement,
+    action: TimerAction,
+) -> Result<TimerResult, TimerError> {
+    let mut timers = env.timers.lock().unwrap();
+    match action {
+        TimerAction::Schedule {
+            delay,
+            interval,
+            payload,
+        } => timers
+            .schedule(delay, interval, payload)
+            .map(TimerResult::Schedule),
+        TimerAction::Cancel(id) => Ok(TimerResult::Cancel(timers.cancel(id))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmer::WASM_PAGE_SIZE;
+
+    const WAT: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "wasm_prepare_buffer") (param i32) (result i64) (i64.const 1024))
+            (func (export "spin") (param i64 i64) (result i64) (loop (br 0)) (i64.const 0))
+            (func (export "trap") (param i64 i64) (result i64) (unreachable))
+            (func (export "grow") (param i64 i64) (result i64)
+                (drop (memory.grow (i32.const 1)))
+                (unreachable)))
+    "#;
+
+    fn module(max_offences: u32) -> PluginModule {
+        let limits = PluginLimits {
+            fuel_per_event: 100_000,
+            max_memory_bytes: WASM_PAGE_SIZE as u64,
+            max_offences,
+        };
+        PluginModule::new("test".to_owned(), WAT.as_bytes(), limits, None).unwrap()
+    }
+
+    #[test]
+    fn out_of_fuel() {
+        let module = module(3);
+        assert!(matches!(
+            module.execute_limited("spin", &[]),
+            Err(PluginModuleError::OutOfFuel)
+        ));
+    }
+
+    #[test]
+    fn memory_limit_only_after_failed_grow() {
+        let module = module(3);
+        // the memory is already at its limit, but nothing tried to grow it
+        assert!(matches!(
+            module.execute_limited("trap", &[]),
+            Err(PluginModuleError::RunFunction(_))
+        ));
+        assert!(matches!(
+            module.execute_limited("grow", &[]),
+            Err(PluginModuleError::MemoryLimitReached(_))
+        ));
+        // the failed grow of the last event isn't held against the next one
+        assert!(matches!(
+            module.execute_limited("trap", &[]),
+            Err(PluginModuleError::RunFunction(_))
+        ));
+    }
+
+    #[test]
+    fn disabled_after_offences() {
+        let module = module(2);
+        let _ = module.execute_limited("trap", &[]);
+        let _ = module.execute_limited("trap", &[]);
+        assert!(!module.is_disabled());
+        let _ = module.execute_limited("spin", &[]);
+        assert!(!module.is_disabled());
+        let _ = module.execute_limited("grow", &[]);
+        assert!(module.is_disabled());
     }
 }
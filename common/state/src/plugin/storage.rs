@@ -0,0 +1,12 @@
+/// A persistent key/value store given to plugins by the host. Every method
+/// takes the name of the plugin, which namespaces the keys so that plugins
+/// can't read or overwrite each other's values.
+pub trait PluginStorage: Send + Sync {
+    fn get(&self, plugin: &str, key: &str) -> Result<Option<Vec<u8>>, String>;
+
+    fn set(&self, plugin: &str, key: &str, value: &[u8]) -> Result<(), String>;
+
+    fn delete(&self, plugin: &str, key: &str) -> Result<(), String>;
+
+    fn keys(&self, plugin: &str) -> Result<Vec<String>, String>;
+}
@@ -0,0 +1,224 @@
+use std::cmp::Ordering;
+
+use plugin_api::{event::TimerEvent, TimerError, TimerId};
+
+/// How many timers a single plugin module can have pending at once
+pub const MAX_TIMERS: usize = 1024;
+
+/// A timer scheduled by a plugin module, keyed by the `Time` resource
+struct Timer {
+    id: TimerId,
+    /// `Time` at which the timer fires next
+    due: f64,
+    interval: Option<f64>,
+    payload: Vec<u8>,
+}
+
+/// The timers of a single plugin module. New timers only know their delay,
+/// they are given a due time the next time the queue is polled, as the host
+/// function scheduling them has no access to the `Time` resource.
+#[derive(Default)]
+pub struct TimerQueue {
+    next_id: u64,
+    pending: Vec<(TimerId, f64, Option<f64>, Vec<u8>)>,
+    timers: Vec<Timer>,
+}
+
+impl TimerQueue {
+    /// Schedules a timer firing after `delay` seconds, then every `interval`
+    /// seconds if it is set. Fails if the module already has [`MAX_TIMERS`]
+    /// timers.
+    pub fn schedule(
+        &mut self,
+        delay: f64,
+        interval: Option<f64>,
+        payload: Vec<u8>,
+    ) -> Result<TimerId, TimerError> {
+        if !delay.is_finite() || delay < 0.0 {
+            return Err(TimerError::InvalidDuration(delay));
+        }
+        if let Some(interval) = interval.filter(|i| !i.is_finite() || *i <= 0.0) {
+            return Err(TimerError::InvalidDuration(interval));
+        }
+        if self.pending.len() + self.timers.len() >= MAX_TIMERS {
+            return Err(TimerError::TooManyTimers(MAX_TIMERS));
+        }
+        let id = TimerId(self.next_id);
+        self.next_id += 1;
+        self.pending.push((id, delay, interval, payload));
+        Ok(id)
+    }
+
+    /// Removes a timer, returns `false` if there was no such timer
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        let count = self.pending.len() + self.timers.len();
+        self.pending.retain(|(pending, ..)| *pending != id);
+        self.timers.retain(|timer| timer.id != id);
+        count != self.pending.len() + self.timers.len()
+    }
+
+    /// Takes the events of the timers due at `now`, in the order they were
+    /// due. One-shot timers are removed while repeating ones are scheduled
+    /// again, firing at most once per poll even if several intervals elapsed.
+    pub fn take_due(&mut self, now: f64) -> Vec<TimerEvent> {
+        self.timers.extend(
+            self.pending
+                .drain(..)
+                .map(|(id, delay, interval, payload)| Timer {
+                    id,
+                    due: now + delay,
+                    interval,
+                    payload,
+                }),
+        );
+
+        let mut due = self
+            .timers
+            .iter()
+            .filter(|timer| timer.due <= now)
+            .map(|timer| (timer.due, timer.id))
+            .collect::<Vec<_>>();
+        due.sort_by(|a, b| {
+            a.0.partial_cmp(&b.0)
+                .unwrap_or(Ordering::Equal)
+                .then(a.1.cmp(&b.1))
+        });
+
+        let events = due
+            .iter()
+            .filter_map(|(_, id)| self.timers.iter().find(|timer| timer.id == *id))
+            .map(|timer| TimerEvent {
+                id: timer.id,
+                payload: timer.payload.clone(),
+            })
+            .collect();
+
+        self.timers
+            .retain(|timer| timer.due > now || timer.interval.is_some());
+        for timer in self.timers.iter_mut().filter(|timer| timer.due <= now) {
+            if let Some(interval) = timer.interval {
+                timer.due += interval;
+                if timer.due <= now {
+                    timer.due = now + interval;
+                }
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fired(queue: &mut TimerQueue, now: f64) -> Vec<TimerId> {
+        queue
+            .take_due(now)
+            .into_iter()
+            .map(|event| event.id)
+            .collect()
+    }
+
+    #[test]
+    fn one_shot_fires_once() {
+        let mut queue = TimerQueue::default();
+        queue.take_due(10.0);
+        let id = queue.schedule(1.0, None, vec![42]).unwrap();
+        // The delay starts from the first poll after scheduling
+        assert!(queue.take_due(10.5).is_empty());
+        let events = queue.take_due(11.5);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].id, id);
+        assert_eq!(events[0].payload, vec![42]);
+        assert!(fired(&mut queue, 20.0).is_empty());
+        assert!(!queue.cancel(id));
+    }
+
+    #[test]
+    fn repeating_fires_every_interval() {
+        let mut queue = TimerQueue::default();
+        let id = queue.schedule(1.0, Some(2.0), Vec::new()).unwrap();
+        assert!(fired(&mut queue, 0.0).is_empty());
+        assert_eq!(fired(&mut queue, 1.0), vec![id]);
+        assert!(fired(&mut queue, 2.0).is_empty());
+        assert_eq!(fired(&mut queue, 3.0), vec![id]);
+        assert_eq!(fired(&mut queue, 5.5), vec![id]);
+        // Stays aligned to its interval when polled late
+        assert_eq!(fired(&mut queue, 7.0), vec![id]);
+    }
+
+    #[test]
+    fn missed_intervals_fire_once() {
+        let mut queue = TimerQueue::default();
+        let id = queue.schedule(1.0, Some(1.0), Vec::new()).unwrap();
+        queue.take_due(0.0);
+        assert_eq!(fired(&mut queue, 10.0), vec![id]);
+        // Scheduled again an interval after the poll instead of catching up
+        assert!(fired(&mut queue, 10.5).is_empty());
+        assert_eq!(fired(&mut queue, 11.0), vec![id]);
+    }
+
+    #[test]
+    fn fires_in_due_order() {
+        let mut queue = TimerQueue::default();
+        let late = queue.schedule(2.0, None, Vec::new()).unwrap();
+        let early = queue.schedule(1.0, None, Vec::new()).unwrap();
+        let same = queue.schedule(2.0, None, Vec::new()).unwrap();
+        queue.take_due(0.0);
+        assert_eq!(fired(&mut queue, 3.0), vec![early, late, same]);
+    }
+
+    #[test]
+    fn cancel() {
+        let mut queue = TimerQueue::default();
+        let pending = queue.schedule(1.0, None, Vec::new()).unwrap();
+        assert!(queue.cancel(pending));
+        assert!(!queue.cancel(pending));
+        assert!(fired(&mut queue, 0.0).is_empty());
+        assert!(fired(&mut queue, 5.0).is_empty());
+
+        let repeating = queue.schedule(1.0, Some(1.0), Vec::new()).unwrap();
+        queue.take_due(5.0);
+        assert_eq!(fired(&mut queue, 6.0), vec![repeating]);
+        assert!(queue.cancel(repeating));
+        assert!(fired(&mut queue, 10.0).is_empty());
+    }
+
+    #[test]
+    fn invalid_durations() {
+        let mut queue = TimerQueue::default();
+        assert!(matches!(
+            queue.schedule(-1.0, None, Vec::new()),
+            Err(TimerError::InvalidDuration(_))
+        ));
+        assert!(matches!(
+            queue.schedule(f64::NAN, None, Vec::new()),
+            Err(TimerError::InvalidDuration(_))
+        ));
+        assert!(matches!(
+            queue.schedule(0.0, Some(0.0), Vec::new()),
+            Err(TimerError::InvalidDuration(_))
+        ));
+    }
+
+    #[test]
+    fn timer_limit() {
+        let mut queue = TimerQueue::default();
+        for _ in 0..MAX_TIMERS / 2 {
+            queue.schedule(1.0, None, Vec::new()).unwrap();
+        }
+        queue.take_due(0.0);
+        let ids = (MAX_TIMERS / 2..MAX_TIMERS)
+            .map(|_| queue.schedule(1.0, None, Vec::new()).unwrap())
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            queue.schedule(1.0, None, Vec::new()),
+            Err(TimerError::TooManyTimers(MAX_TIMERS))
+        ));
+        assert!(queue.cancel(ids[0]));
+        assert!(queue.schedule(1.0, None, Vec::new()).is_ok());
+        // Fired one-shot timers free their slot
+        assert_eq!(queue.take_due(1.0).len(), MAX_TIMERS / 2);
+        assert!(queue.schedule(1.0, None, Vec::new()).is_ok());
+    }
+}
@@ -1,11 +1,15 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use serde::{de::DeserializeOwned, Serialize};
 use wasmer::{Function, HostEnvInitError, Instance, LazyInit, Memory, WasmerEnv};
 
+use plugin_api::{Action, CommandSpec};
+
 use super::{
     errors::PluginModuleError,
     memory_manager::{self, EcsAccessManager, MemoryManager},
+    storage::PluginStorage,
+    timer::TimerQueue,
 };
 
 #[derive(Clone)]
@@ -17,7 +21,11 @@ pub struct HostFunctionEnvironement {
     pub allocator: LazyInit<Function>, // Linked to: wasm_prepare_buffer
     pub memory_manager: Arc<MemoryManager>, /* This object represent the current buffer size and
                                    * pointer */
-    pub name: String, // This represent the plugin name
+    pub name: String,                            // This represent the plugin name
+    pub actions: Arc<Mutex<Vec<Action>>>,        // The actions waiting for the next tick
+    pub commands: Arc<Mutex<Vec<CommandSpec>>>,  // The chat commands registered by the plugin
+    pub storage: Option<Arc<dyn PluginStorage>>, // The persistent storage given by the host
+    pub timers: Arc<Mutex<TimerQueue>>,          // The timers scheduled by the plugin
 }
 
 impl HostFunctionEnvironement {
@@ -25,6 +33,10 @@ impl HostFunctionEnvironement {
         name: String,
         ecs: Arc<EcsAccessManager>,
         memory_manager: Arc<MemoryManager>,
+        actions: Arc<Mutex<Vec<Action>>>,
+        commands: Arc<Mutex<Vec<CommandSpec>>>,
+        storage: Option<Arc<dyn PluginStorage>>,
+        timers: Arc<Mutex<TimerQueue>>,
     ) -> Self {
         Self {
             memory_manager,
@@ -32,6 +44,10 @@ impl HostFunctionEnvironement {
             allocator: LazyInit::new(),
             memory: LazyInit::new(),
             name,
+            actions,
+            commands,
+            storage,
+            timers,
         }
     }
 
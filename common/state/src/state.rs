@@ -1,9 +1,7 @@
 #[cfg(feature = "plugins")]
 use crate::plugin::memory_manager::EcsWorld;
 #[cfg(feature = "plugins")]
-use crate::plugin::PluginMgr;
-#[cfg(feature = "plugins")]
-use common::uid::UidAllocator;
+use crate::plugin::{errors::PluginError, limits::PluginLimits, storage::PluginStorage, PluginMgr};
 use common::{
     calendar::Calendar,
     comp,
@@ -20,6 +18,8 @@ use common::{
     trade::Trades,
     vol::{ReadVol, WriteVol},
 };
+#[cfg(feature = "plugins")]
+use common::{uid::UidAllocator, CachedSpatialGrid};
 use common_base::span;
 use common_ecs::{PhysicsMetrics, SysMetrics};
 use common_net::sync::{interpolation as sync_interp, WorldSyncExt};
@@ -98,7 +98,24 @@ impl State {
     /// Create a new `State` in server mode.
     pub fn server() -> Self { Self::new(GameMode::Server) }
 
+    /// Create a new `State` in server mode, loading plugins with the given
+    /// resource limits and persistent storage.
+    #[cfg(feature = "plugins")]
+    pub fn server_with_plugins(
+        limits: PluginLimits,
+        storage: Option<Arc<dyn PluginStorage>>,
+    ) -> Self {
+        Self::build(GameMode::Server).with_plugins(GameMode::Server, limits, storage)
+    }
+
     pub fn new(game_mode: GameMode) -> Self {
+        let state = Self::build(game_mode);
+        #[cfg(feature = "plugins")]
+        let state = state.with_plugins(game_mode, PluginLimits::default(), None);
+        state
+    }
+
+    fn build(game_mode: GameMode) -> Self {
         let thread_name_infix = match game_mode {
             GameMode::Server => "s",
             GameMode::Client => "c",
@@ -234,16 +251,34 @@ impl State {
         ecs.insert(Trades::default());
         ecs.insert(PlayerPhysicsSettings::default());
 
+        ecs
+    }
+
+    /// Loads the plugins from the asset directory and runs their init event
+    #[cfg(feature = "plugins")]
+    fn with_plugins(
+        mut self,
+        game_mode: GameMode,
+        limits: PluginLimits,
+        storage: Option<Arc<dyn PluginStorage>>,
+    ) -> Self {
         // Load plugins from asset directory
-        #[cfg(feature = "plugins")]
-        ecs.insert(match PluginMgr::from_assets() {
+        let plugin_mgr = match PluginMgr::from_assets(limits, storage) {
             Ok(plugin_mgr) => {
                 let ecs_world = EcsWorld {
-                    entities: &ecs.entities(),
-                    health: ecs.read_component().into(),
-                    uid: ecs.read_component().into(),
-                    uid_allocator: &ecs.read_resource::<UidAllocator>().into(),
-                    player: ecs.read_component().into(),
+                    entities: &self.ecs.entities(),
+                    health: self.ecs.read_component().into(),
+                    uid: self.ecs.read_component().into(),
+                    uid_allocator: &self.ecs.read_resource::<UidAllocator>().into(),
+                    player: self.ecs.read_component().into(),
+                    pos: self.ecs.read_component().into(),
+                    vel: self.ecs.read_component().into(),
+                    inventory: self.ecs.read_component().into(),
+                    stats: self.ecs.read_component().into(),
+                    skill_set: self.ecs.read_component().into(),
+                    group: self.ecs.read_component().into(),
+                    spatial_grid: &self.ecs.read_resource::<CachedSpatialGrid>().into(),
+                    terrain: &self.ecs.read_resource::<TerrainGrid>().into(),
                 };
                 if let Err(e) = plugin_mgr
                     .execute_event(&ecs_world, &plugin_api::event::PluginLoadEvent {
@@ -262,9 +297,9 @@ impl State {
                 tracing::info!("Plugins disabled, enable debug logging for more information.");
                 PluginMgr::default()
             },
-        });
-
-        ecs
+        };
+        self.ecs.insert(plugin_mgr);
+        self
     }
 
     /// Register a component with the state's ECS.
@@ -327,6 +362,107 @@ impl State {
         self.ecs.read_storage::<C>()
     }
 
+    /// Execute an event on every loaded plugin, giving them read access to the
+    /// ECS while they run.
+    ///
+    /// Note: This reads the components exposed to plugins, so it must not be
+    /// called while a write borrow of one of them is held.
+    #[cfg(feature = "plugins")]
+    pub fn execute_plugin_event<T: plugin_api::Event>(
+        &self,
+        event: &T,
+    ) -> Result<Vec<T::Response>, PluginError> {
+        let ecs_world = EcsWorld {
+            entities: &self.ecs.entities(),
+            health: self.ecs.read_component().into(),
+            uid: self.ecs.read_component().into(),
+            uid_allocator: &self.ecs.read_resource::<UidAllocator>().into(),
+            player: self.ecs.read_component().into(),
+            pos: self.ecs.read_component().into(),
+            vel: self.ecs.read_component().into(),
+            inventory: self.ecs.read_component().into(),
+            stats: self.ecs.read_component().into(),
+            skill_set: self.ecs.read_component().into(),
+            group: self.ecs.read_component().into(),
+            spatial_grid: &self.ecs.read_resource::<CachedSpatialGrid>().into(),
+            terrain: &self.ecs.read_resource::<TerrainGrid>().into(),
+        };
+        self.ecs
+            .read_resource::<PluginMgr>()
+            .execute_event(&ecs_world, event)
+    }
+
+    /// Loads a plugin again from its tarball and sends `on_load` to the new
+    /// version.
+    #[cfg(feature = "plugins")]
+    pub fn reload_plugin(&self, name: &str) -> Result<(), PluginError> {
+        self.ecs.write_resource::<PluginMgr>().reload(name)?;
+        self.init_plugin(name)
+    }
+
+    /// Loads a plugin from a tarball outside of the plugin directory and sends
+    /// it `on_load`.
+    #[cfg(feature = "plugins")]
+    pub fn load_plugin(&self, path: &std::path::Path) -> Result<(), PluginError> {
+        let name = self.ecs.write_resource::<PluginMgr>().load_file(path)?;
+        self.init_plugin(&name)
+    }
+
+    /// Unloads a plugin, it won't receive any event anymore.
+    #[cfg(feature = "plugins")]
+    pub fn unload_plugin(&self, name: &str) -> Result<(), PluginError> {
+        self.ecs
+            .write_resource::<PluginMgr>()
+            .unload(name)
+            .map(|_| ())
+    }
+
+    /// Reloads the plugins whose tarball changed on disk, and loads the ones
+    /// added to the plugin directory. Returns whether any plugin changed.
+    #[cfg(feature = "plugins")]
+    pub fn reload_changed_plugins(&self) -> bool {
+        let reloaded = self.ecs.write_resource::<PluginMgr>().reload_changed();
+        for name in &reloaded {
+            if let Err(e) = self.init_plugin(name) {
+                tracing::error!(?e, "Failed to initialize plugin '{}'", name);
+            }
+        }
+        !reloaded.is_empty()
+    }
+
+    /// Sends `on_load` to a single plugin, unloading it if it fails.
+    #[cfg(feature = "plugins")]
+    fn init_plugin(&self, name: &str) -> Result<(), PluginError> {
+        let ecs_world = EcsWorld {
+            entities: &self.ecs.entities(),
+            health: self.ecs.read_component().into(),
+            uid: self.ecs.read_component().into(),
+            uid_allocator: &self.ecs.read_resource::<UidAllocator>().into(),
+            player: self.ecs.read_component().into(),
+            pos: self.ecs.read_component().into(),
+            vel: self.ecs.read_component().into(),
+            inventory: self.ecs.read_component().into(),
+            stats: self.ecs.read_component().into(),
+            skill_set: self.ecs.read_component().into(),
+            group: self.ecs.read_component().into(),
+            spatial_grid: &self.ecs.read_resource::<CachedSpatialGrid>().into(),
+            terrain: &self.ecs.read_resource::<TerrainGrid>().into(),
+        };
+        let game_mode = *self.ecs.read_resource::<GameMode>();
+        let mut plugin_mgr = self.ecs.write_resource::<PluginMgr>();
+        let result = plugin_mgr
+            .get(name)
+            .ok_or_else(|| PluginError::NoSuchPlugin(name.to_owned()))?
+            .execute_event(&ecs_world, &plugin_api::event::PluginLoadEvent {
+                game_mode,
+            })
+            .map(|_| ());
+        if result.is_err() {
+            plugin_mgr.unload(name)?;
+        }
+        result
+    }
+
     /// Get a reference to the internal ECS world.
     pub fn ecs(&self) -> &specs::World { &self.ecs }
 
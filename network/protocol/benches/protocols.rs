@@ -4,11 +4,13 @@ use bytes::{Bytes, BytesMut};
 use criterion::{criterion_group, criterion_main, Criterion, Throughput};
 use std::{sync::Arc, time::Duration};
 use tokio::runtime::Runtime;
+#[cfg(feature = "sim")]
+use veloren_network_protocol::sim::{SimConfig, SimDrain, SimSink};
 use veloren_network_protocol::{
-    InitProtocol, MpscMsg, MpscRecvProtocol, MpscSendProtocol, Pid, Promises, ProtocolError,
-    ProtocolEvent, ProtocolMetricCache, ProtocolMetrics, QuicDataFormat, QuicRecvProtocol,
-    QuicSendProtocol, RecvProtocol, SendProtocol, Sid, TcpRecvProtocol, TcpSendProtocol,
-    UnreliableDrain, UnreliableSink, _internal::OTFrame,
+    Capabilities, InitProtocol, MpscMsg, MpscRecvProtocol, MpscSendProtocol, Pid, Promises,
+    ProtocolError, ProtocolEvent, ProtocolMetricCache, ProtocolMetrics, QuicDataFormat,
+    QuicRecvProtocol, QuicSendProtocol, RecvProtocol, SendProtocol, Sid, TcpRecvProtocol,
+    TcpSendProtocol, UnreliableDrain, UnreliableSink, _internal::OTFrame,
 };
 
 fn frame_serialize(frame: OTFrame, buffer: &mut BytesMut) { frame.write_bytes(buffer); }
@@ -22,11 +24,15 @@ where
     let [mut p1, mut p2] = p;
     tokio::join!(
         async {
-            p1.initialize(true, Pid::fake(2), 1337).await.unwrap();
+            p1.initialize(true, Pid::fake(2), 1337, Capabilities::all())
+                .await
+                .unwrap();
             p1
         },
         async {
-            p2.initialize(false, Pid::fake(3), 42).await.unwrap();
+            p2.initialize(false, Pid::fake(3), 42, Capabilities::all())
+                .await
+                .unwrap();
             p2
         }
     );
@@ -146,6 +152,30 @@ fn criterion_tcp(c: &mut Criterion) {
     c.finish();
 }
 
+/// TCP over a link with latency and limited bandwidth
+#[cfg(feature = "sim")]
+fn criterion_tcp_sim(c: &mut Criterion) {
+    let rt = || {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+    };
+    let mut c = c.benchmark_group("tcp_sim");
+    c.significance_level(0.1).sample_size(10);
+    c.throughput(Throughput::Bytes(10000000))
+        .bench_function("10MB_in_100_msg", |b| {
+            let buf = Bytes::from(&[155u8; 100_000][..]);
+            // the simulation spawns tasks, so it is created within the runtime
+            b.to_async(rt())
+                .iter(|| send_and_recv_msg(utils::tcp_sim_bound(10000, None), buf.clone(), 100))
+        });
+    c.finish();
+}
+
+#[cfg(not(feature = "sim"))]
+fn criterion_tcp_sim(_: &mut Criterion) {}
+
 fn criterion_quic(c: &mut Criterion) {
     let mut c = c.benchmark_group("quic");
     c.significance_level(0.1).sample_size(10);
@@ -173,6 +203,7 @@ criterion_group!(
     criterion_util,
     criterion_mpsc,
     criterion_tcp,
+    criterion_tcp_sim,
     criterion_quic
 );
 criterion_main!(benches);
@@ -239,6 +270,39 @@ mod utils {
         ]
     }
 
+    /// like `tcp_bound`, but every direction has 20ms latency and 100MB/s
+    #[cfg(feature = "sim")]
+    pub fn tcp_sim_bound(
+        cap: usize,
+        metrics: Option<ProtocolMetricCache>,
+    ) -> [(
+        TcpSendProtocol<SimDrain<TcpDrain>>,
+        TcpRecvProtocol<SimSink<TcpSink>>,
+    ); 2] {
+        let (s1, r1) = async_channel::bounded(cap);
+        let (s2, r2) = async_channel::bounded(cap);
+        let m = metrics.unwrap_or_else(|| {
+            ProtocolMetricCache::new("tcp_sim", Arc::new(ProtocolMetrics::new().unwrap()))
+        });
+        let config = SimConfig {
+            latency: Duration::from_millis(20),
+            bandwidth: 100_000_000,
+            ..SimConfig::default()
+        };
+        let drain = |sender| SimDrain::new(TcpDrain { sender }, config.clone(), true);
+        let sink = |receiver| SimSink::new(TcpSink { receiver }, config.clone(), true);
+        [
+            (
+                TcpSendProtocol::new(drain(s1), m.clone()),
+                TcpRecvProtocol::new(sink(r2), m.clone()),
+            ),
+            (
+                TcpSendProtocol::new(drain(s2), m.clone()),
+                TcpRecvProtocol::new(sink(r1), m),
+            ),
+        ]
+    }
+
     pub struct QuicDrain {
         pub sender: Sender<QuicDataFormat>,
     }
@@ -0,0 +1,192 @@
+use crate::types::Bandwidth;
+use std::time::{Duration, Instant};
+
+/// Estimates the available bandwidth of a channel from the round trip time
+/// and the rate in which the remote acknowledges messages.
+///
+/// As long as the round trip time stays close to the lowest one seen, the
+/// link isn't saturated and the limit grows. Once it rises, data is queueing
+/// up somewhere on the way and the limit is reduced below the rate in which
+/// the remote actually received data, so that the queue can drain.
+///
+/// While the application sends less than the link could carry, the delivery
+/// rate only shows what was sent. Such samples are only taken when they
+/// exceed the current estimate.
+#[derive(Debug)]
+pub(crate) struct CongestionControl {
+    bandwidth: Bandwidth,
+    delivery_rate: Option<f64>,
+    srtt: Option<Duration>,
+    min_rtt: Option<(Duration, Instant)>,
+    /// start of the current delivery rate sample
+    sample_start: Option<Instant>,
+    sample_bytes: u64,
+    /// some of the acked messages of the current sample were app-limited
+    sample_app_limited: bool,
+    last_decrease: Option<Instant>,
+}
+
+impl CongestionControl {
+    /// The limit is never reduced below this, in bytes per second
+    pub(crate) const MIN_BANDWIDTH: Bandwidth = 16 * 1024;
+    /// the lowest round trip time is forgotten after this, the route might
+    /// have changed
+    const MIN_RTT_WINDOW: Duration = Duration::from_secs(30);
+    /// queueing delay that is tolerated before the link counts as saturated
+    const QUEUE_DELAY: Duration = Duration::from_millis(50);
+    /// acks of different streams arrive in bursts, so the delivery rate is
+    /// measured over at least this time
+    const SAMPLE_TIME: Duration = Duration::from_millis(250);
+
+    pub(crate) fn new() -> Self {
+        Self {
+            bandwidth: Bandwidth::MAX,
+            delivery_rate: None,
+            srtt: None,
+            min_rtt: None,
+            sample_start: None,
+            sample_bytes: 0,
+            sample_app_limited: false,
+            last_decrease: None,
+        }
+    }
+
+    /// current limit in bytes per second
+    pub(crate) fn bandwidth(&self) -> Bandwidth { self.bandwidth }
+
+    /// smoothed round trip time, `None` till the first ack
+    pub(crate) fn rtt(&self) -> Option<Duration> { self.srtt }
+
+    /// `bytes` got acknowledged at `now`, `rtt` is the time since the newest
+    /// of them was sent without the ack delay of the remote. `app_limited` if
+    /// the sender had nothing more to send when it was sent
+    pub(crate) fn on_ack(&mut self, bytes: u64, rtt: Duration, app_limited: bool, now: Instant) {
+        let srtt = match self.srtt {
+            Some(srtt) => (srtt * 7 + rtt) / 8,
+            None => rtt,
+        };
+        self.srtt = Some(srtt);
+        let min_rtt = match self.min_rtt {
+            Some((min_rtt, at))
+                if min_rtt <= rtt && now.duration_since(at) < Self::MIN_RTT_WINDOW =>
+            {
+                min_rtt
+            },
+            _ => {
+                self.min_rtt = Some((rtt, now));
+                rtt
+            },
+        };
+
+        match self.sample_start {
+            Some(start) if now.duration_since(start) >= Self::SAMPLE_TIME => {
+                let sample =
+                    (self.sample_bytes + bytes) as f64 / now.duration_since(start).as_secs_f64();
+                let app_limited = self.sample_app_limited || app_limited;
+                self.delivery_rate = match self.delivery_rate {
+                    Some(rate) if app_limited && sample < rate => Some(rate),
+                    Some(rate) => Some(0.75 * rate + 0.25 * sample),
+                    None => Some(sample),
+                };
+                self.sample_start = Some(now);
+                self.sample_bytes = 0;
+                self.sample_app_limited = false;
+            },
+            Some(_) => {
+                self.sample_bytes += bytes;
+                self.sample_app_limited |= app_limited;
+            },
+            // the first acked bytes were sent before the sample started
+            None => self.sample_start = Some(now),
+        }
+
+        let saturated = srtt > min_rtt + Self::QUEUE_DELAY.max(min_rtt / 2);
+        if !saturated {
+            self.bandwidth = self.bandwidth.saturating_add(self.bandwidth / 4);
+            return;
+        }
+        // give the previous decrease a round trip to take effect
+        if self
+            .last_decrease
+            .map_or(false, |last| now.duration_since(last) < srtt)
+        {
+            return;
+        }
+        if let Some(rate) = self.delivery_rate {
+            let bandwidth = (self.bandwidth as f64).min(rate) * 0.85;
+            self.bandwidth = (bandwidth as Bandwidth).max(Self::MIN_BANDWIDTH);
+            self.last_decrease = Some(now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn unlimited_without_saturation() {
+        let mut cc = CongestionControl::new();
+        let start = Instant::now();
+        for i in 0..20 {
+            cc.on_ack(100_000, MS * 30, false, start + MS * 500 * i);
+        }
+        assert_eq!(cc.bandwidth(), Bandwidth::MAX);
+        assert_eq!(cc.rtt(), Some(MS * 30));
+    }
+
+    #[test]
+    fn reduce_on_rising_rtt() {
+        let mut cc = CongestionControl::new();
+        let start = Instant::now();
+        for i in 0..4 {
+            cc.on_ack(100_000, MS * 30, false, start + MS * 500 * i);
+        }
+        // the queue fills up, 100_000 bytes every 500ms
+        for i in 4..40 {
+            cc.on_ack(100_000, MS * 400, false, start + MS * 500 * i);
+        }
+        assert!(cc.bandwidth() < 200_000, "{}", cc.bandwidth());
+        assert!(cc.bandwidth() >= CongestionControl::MIN_BANDWIDTH);
+
+        // the queue drained, probe for more again
+        let limited = cc.bandwidth();
+        for i in 40..80 {
+            cc.on_ack(100_000, MS * 30, false, start + MS * 500 * i);
+        }
+        assert!(cc.bandwidth() > limited);
+    }
+
+    #[test]
+    fn ignore_app_limited_samples() {
+        let mut cc = CongestionControl::new();
+        let start = Instant::now();
+        // 2MB/s got through
+        for i in 0..4 {
+            cc.on_ack(1_000_000, MS * 30, false, start + MS * 500 * i);
+        }
+        // the application has little to send for a while
+        for i in 4..14 {
+            cc.on_ack(1_000, MS * 30, true, start + MS * 500 * i);
+        }
+        for i in 14..16 {
+            cc.on_ack(1_000, MS * 400, true, start + MS * 500 * i);
+        }
+        // reduced below what got through, not below what was sent lately
+        assert!(cc.bandwidth() > 1_000_000, "{}", cc.bandwidth());
+        assert!(cc.bandwidth() < 2_000_000, "{}", cc.bandwidth());
+    }
+
+    #[test]
+    fn never_below_min() {
+        let mut cc = CongestionControl::new();
+        let start = Instant::now();
+        cc.on_ack(1, MS * 10, false, start);
+        for i in 1..100 {
+            cc.on_ack(1, MS * 1000, false, start + MS * 1000 * i);
+        }
+        assert_eq!(cc.bandwidth(), CongestionControl::MIN_BANDWIDTH);
+    }
+}
@@ -0,0 +1,337 @@
+use crate::{error::ProtocolError, types::Capabilities};
+use bytes::BytesMut;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    agreement::{self, EphemeralPrivateKey, UnparsedPublicKey, X25519},
+    hkdf::{Salt, HKDF_SHA256},
+    rand::SystemRandom,
+    signature::{self, Ed25519KeyPair, KeyPair},
+};
+
+pub(crate) const PUBLIC_KEY_LEN: usize = 32;
+/// bytes added to every sealed frame
+pub(crate) const TAG_LEN: usize = 16;
+pub(crate) const IDENTITY_LEN: usize = 32;
+pub(crate) const SIGNATURE_LEN: usize = 64;
+
+const INITIATOR_INFO: &[u8] = b"veloren initiator";
+const RESPONDER_INFO: &[u8] = b"veloren responder";
+/// signed together with the transcript, so the signature can't be reused
+/// for anything else
+const IDENTITY_CONTEXT: &[u8] = b"veloren identity";
+
+/// One direction of an encrypted channel.
+/// The nonce is a counter, so both sides MUST seal and open frames in the
+/// same order, which is the case for TCP.
+#[derive(Debug)]
+pub struct Cipher {
+    key: LessSafeKey,
+    counter: u64,
+}
+
+/// Long-term Ed25519 key of the side that initializes the handshake, i.e. the
+/// listening one. It signs every key exchange so that a remote which pinned
+/// the [`PublicIdentity`] can tell that no one is in the middle.
+pub struct Identity {
+    key_pair: Ed25519KeyPair,
+    pkcs8: Vec<u8>,
+}
+
+/// Public part of an [`Identity`], printed as hex
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PublicIdentity([u8; IDENTITY_LEN]);
+
+/// Ephemeral X25519 key exchange done during the handshake.
+/// On its own it only protects against eavesdropping. An active man in the
+/// middle can run one exchange with each side, which is only detected if the
+/// connecting side pinned the [`PublicIdentity`] of the listener, see
+/// [`ReliableDrain::pinned_identity`].
+///
+/// [`ReliableDrain::pinned_identity`]: crate::ReliableDrain::pinned_identity
+#[derive(Debug)]
+pub(crate) struct KeyExchange {
+    private_key: EphemeralPrivateKey,
+    public_key: [u8; PUBLIC_KEY_LEN],
+}
+
+impl Cipher {
+    fn new(key: UnboundKey) -> Self {
+        Self {
+            key: LessSafeKey::new(key),
+            counter: 0,
+        }
+    }
+
+    fn next_nonce(&mut self) -> Nonce {
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce[NONCE_LEN - 8..].copy_from_slice(&self.counter.to_le_bytes());
+        self.counter += 1;
+        Nonce::assume_unique_for_key(nonce)
+    }
+
+    /// encrypts `data` in place and appends the tag
+    pub(crate) fn seal(&mut self, data: &mut BytesMut) -> Result<(), ProtocolError> {
+        let nonce = self.next_nonce();
+        self.key
+            .seal_in_place_append_tag(nonce, Aad::empty(), data)
+            .map_err(|_| ProtocolError::Violated)
+    }
+
+    /// decrypts `data` in place and removes the tag
+    pub(crate) fn open(&mut self, data: &mut BytesMut) -> Result<(), ProtocolError> {
+        let nonce = self.next_nonce();
+        let len = self
+            .key
+            .open_in_place(nonce, Aad::empty(), data)
+            .map_err(|_| ProtocolError::Violated)?
+            .len();
+        data.truncate(len);
+        Ok(())
+    }
+}
+
+impl Identity {
+    pub fn generate() -> Self {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .expect("system random number generator failed");
+        Self::from_pkcs8(pkcs8.as_ref()).expect("freshly generated key was rejected")
+    }
+
+    /// returns `None` if `pkcs8` is not a valid Ed25519 key
+    pub fn from_pkcs8(pkcs8: &[u8]) -> Option<Self> {
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8).ok()?;
+        Some(Self {
+            key_pair,
+            pkcs8: pkcs8.to_vec(),
+        })
+    }
+
+    /// the private key, store it to keep the same identity across restarts
+    pub fn to_pkcs8(&self) -> &[u8] { &self.pkcs8 }
+
+    pub fn public_identity(&self) -> PublicIdentity {
+        let mut identity = [0u8; IDENTITY_LEN];
+        identity.copy_from_slice(self.key_pair.public_key().as_ref());
+        PublicIdentity(identity)
+    }
+
+    pub(crate) fn sign(&self, transcript: &[u8]) -> [u8; SIGNATURE_LEN] {
+        let mut signature = [0u8; SIGNATURE_LEN];
+        signature.copy_from_slice(
+            self.key_pair
+                .sign(&[IDENTITY_CONTEXT, transcript].concat())
+                .as_ref(),
+        );
+        signature
+    }
+}
+
+impl std::fmt::Debug for Identity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // never log the private key
+        f.debug_tuple("Identity")
+            .field(&self.public_identity())
+            .finish()
+    }
+}
+
+impl PublicIdentity {
+    pub fn from_bytes(bytes: [u8; IDENTITY_LEN]) -> Self { Self(bytes) }
+
+    pub fn to_bytes(self) -> [u8; IDENTITY_LEN] { self.0 }
+
+    /// parses the hex representation it's printed with, `None` if invalid
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        if hex.len() != 2 * IDENTITY_LEN {
+            return None;
+        }
+        let digit = |c: u8| (c as char).to_digit(16);
+        let mut identity = [0u8; IDENTITY_LEN];
+        for (byte, hex) in identity.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            *byte = (digit(hex[0])? << 4 | digit(hex[1])?) as u8;
+        }
+        Some(Self(identity))
+    }
+
+    pub(crate) fn verify(&self, transcript: &[u8], signature: &[u8; SIGNATURE_LEN]) -> bool {
+        signature::UnparsedPublicKey::new(&signature::ED25519, &self.0)
+            .verify(&[IDENTITY_CONTEXT, transcript].concat(), signature)
+            .is_ok()
+    }
+}
+
+impl std::fmt::Display for PublicIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+impl KeyExchange {
+    pub(crate) fn new() -> Result<Self, ProtocolError> {
+        let private_key = EphemeralPrivateKey::generate(&X25519, &SystemRandom::new())
+            .map_err(|_| ProtocolError::Violated)?;
+        let mut public_key = [0u8; PUBLIC_KEY_LEN];
+        public_key.copy_from_slice(
+            private_key
+                .compute_public_key()
+                .map_err(|_| ProtocolError::Violated)?
+                .as_ref(),
+        );
+        Ok(Self {
+            private_key,
+            public_key,
+        })
+    }
+
+    pub(crate) fn public_key(&self) -> [u8; PUBLIC_KEY_LEN] { self.public_key }
+
+    /// both public keys and the [`Capabilities`] both sides sent, in the same
+    /// order on both sides. It's signed by the [`Identity`] and used as salt
+    /// of the key derivation, so if anything was tampered with the keys
+    /// differ and the first sealed frame fails to open.
+    pub(crate) fn transcript(
+        &self,
+        initializer: bool,
+        remote_public_key: [u8; PUBLIC_KEY_LEN],
+        local_capabilities: Capabilities,
+        remote_capabilities: Capabilities,
+    ) -> Vec<u8> {
+        let (initiator, responder) = if initializer {
+            (
+                (self.public_key, local_capabilities),
+                (remote_public_key, remote_capabilities),
+            )
+        } else {
+            (
+                (remote_public_key, remote_capabilities),
+                (self.public_key, local_capabilities),
+            )
+        };
+        let mut transcript = Vec::with_capacity(2 * (PUBLIC_KEY_LEN + 8));
+        for (public_key, capabilities) in [initiator, responder] {
+            transcript.extend_from_slice(&public_key);
+            transcript.extend_from_slice(&capabilities.bits().to_le_bytes());
+        }
+        transcript
+    }
+
+    /// returns the `Cipher` for sending and the one for receiving
+    pub(crate) fn agree(
+        self,
+        initializer: bool,
+        remote_public_key: [u8; PUBLIC_KEY_LEN],
+        transcript: &[u8],
+    ) -> Result<(Cipher, Cipher), ProtocolError> {
+        let (initiator, responder) = agreement::agree_ephemeral(
+            self.private_key,
+            &UnparsedPublicKey::new(&X25519, remote_public_key),
+            ProtocolError::Violated,
+            |material| {
+                let prk = Salt::new(HKDF_SHA256, transcript).extract(material);
+                let derive = |info: &[u8]| {
+                    prk.expand(&[info], &CHACHA20_POLY1305)
+                        .map(UnboundKey::from)
+                        .map_err(|_| ProtocolError::Violated)
+                };
+                Ok((derive(INITIATOR_INFO)?, derive(RESPONDER_INFO)?))
+            },
+        )?;
+        let (initiator, responder) = (Cipher::new(initiator), Cipher::new(responder));
+        Ok(if initializer {
+            (initiator, responder)
+        } else {
+            (responder, initiator)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// returns the `Cipher`s of both sides, which saw the given `Capabilities`
+    fn agree(
+        a_saw: (Capabilities, Capabilities),
+        b_saw: (Capabilities, Capabilities),
+    ) -> ((Cipher, Cipher), (Cipher, Cipher)) {
+        let a = KeyExchange::new().unwrap();
+        let b = KeyExchange::new().unwrap();
+        let (a_pub, b_pub) = (a.public_key(), b.public_key());
+        let a_transcript = a.transcript(true, b_pub, a_saw.0, a_saw.1);
+        let b_transcript = b.transcript(false, a_pub, b_saw.0, b_saw.1);
+        (
+            a.agree(true, b_pub, &a_transcript).unwrap(),
+            b.agree(false, a_pub, &b_transcript).unwrap(),
+        )
+    }
+
+    #[test]
+    fn public_identity_hex() {
+        let identity = Identity::generate().public_identity();
+        let hex = identity.to_string();
+        assert_eq!(hex.len(), 2 * IDENTITY_LEN);
+        assert_eq!(PublicIdentity::from_hex(&hex), Some(identity));
+        assert_eq!(
+            PublicIdentity::from_hex(&hex.to_uppercase()),
+            Some(identity)
+        );
+        assert_eq!(PublicIdentity::from_hex(&hex[1..]), None);
+        assert_eq!(
+            PublicIdentity::from_hex(&hex.replacen(&hex[..2], "+f", 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn seal_open() {
+        let all = (Capabilities::all(), Capabilities::all());
+        let ((mut a_send, mut a_recv), (mut b_send, mut b_recv)) = agree(all, all);
+
+        for i in 0..3u8 {
+            let mut data = BytesMut::from(&[i; 100][..]);
+            a_send.seal(&mut data).unwrap();
+            assert_eq!(data.len(), 100 + TAG_LEN);
+            assert_ne!(&data[..100], &[i; 100][..]);
+            b_recv.open(&mut data).unwrap();
+            assert_eq!(&data[..], &[i; 100][..]);
+        }
+
+        let mut data = BytesMut::from(&b"Hello World"[..]);
+        b_send.seal(&mut data).unwrap();
+        a_recv.open(&mut data).unwrap();
+        assert_eq!(&data[..], b"Hello World");
+    }
+
+    #[test]
+    fn tampered_frame() {
+        let all = (Capabilities::all(), Capabilities::all());
+        let ((mut a_send, _), (_, mut b_recv)) = agree(all, all);
+
+        let mut data = BytesMut::from(&b"Hello World"[..]);
+        a_send.seal(&mut data).unwrap();
+        data[0] ^= 1;
+        assert_eq!(b_recv.open(&mut data), Err(ProtocolError::Violated));
+    }
+
+    #[test]
+    fn tampered_capabilities() {
+        let all = (Capabilities::all(), Capabilities::all());
+        let stripped = (Capabilities::all(), Capabilities::ENCRYPTION);
+        let ((mut a_send, _), (_, mut b_recv)) = agree(all, stripped);
+
+        let mut data = BytesMut::from(&b"Hello World"[..]);
+        a_send.seal(&mut data).unwrap();
+        assert_eq!(b_recv.open(&mut data), Err(ProtocolError::Violated));
+    }
+
+    #[test]
+    fn identity_signature() {
+        let identity = Identity::from_pkcs8(Identity::generate().to_pkcs8()).unwrap();
+        let public = identity.public_identity();
+        let signature = identity.sign(b"transcript");
+        assert!(public.verify(b"transcript", &signature));
+        assert!(!public.verify(b"other transcript", &signature));
+        let other = Identity::generate().public_identity();
+        assert!(!other.verify(b"transcript", &signature));
+    }
+}
@@ -1,3 +1,5 @@
+use crate::crypto::PublicIdentity;
+
 /// All possible Errors that can happen during Handshake [`InitProtocol`]
 ///
 /// [`InitProtocol`]: crate::InitProtocol
@@ -6,6 +8,9 @@ pub enum InitProtocolError {
     Closed,
     WrongMagicNumber([u8; 7]),
     WrongVersion([u32; 3]),
+    /// the remote didn't prove the pinned identity, contains the one it
+    /// proved instead, if any
+    WrongIdentity(Option<PublicIdentity>),
 }
 
 /// When you return closed you must stay closed!
@@ -46,6 +51,14 @@ impl core::fmt::Display for InitProtocolError {
                 &r,
                 &crate::types::VELOREN_NETWORK_VERSION
             ),
+            InitProtocolError::WrongIdentity(Some(r)) => write!(
+                f,
+                "Identity doesn't match the pinned one, remote side proved '{}'",
+                r
+            ),
+            InitProtocolError::WrongIdentity(None) => {
+                write!(f, "Remote side couldn't prove the pinned identity")
+            },
         }
     }
 }
@@ -3,6 +3,7 @@ use crate::{
     types::{Bandwidth, Prio, Promises, Sid},
 };
 use bytes::Bytes;
+use std::time::Duration;
 
 /// used for communication with [`SendProtocol`] and [`RecvProtocol`]
 ///
@@ -25,6 +26,15 @@ pub enum ProtocolEvent {
         data: Bytes,
         sid: Sid,
     },
+    /// Number of messages received on a stream so far, lets the remote side
+    /// forget about them or replay the missing ones after a reconnect.
+    /// `delay` is the time since the last of them arrived, so that it can be
+    /// taken out of the round trip time
+    Ack {
+        sid: Sid,
+        count: u64,
+        delay: Duration,
+    },
 }
 
 impl ProtocolEvent {
@@ -43,6 +53,11 @@ impl ProtocolEvent {
                 guaranteed_bandwidth: *guaranteed_bandwidth,
             },
             ProtocolEvent::CloseStream { sid } => OTFrame::CloseStream { sid: *sid },
+            ProtocolEvent::Ack { sid, count, delay } => OTFrame::Ack {
+                sid: *sid,
+                count: *count,
+                delay: *delay,
+            },
             ProtocolEvent::Message { .. } => {
                 unimplemented!("Event::Message to OTFrame IS NOT supported")
             },
@@ -61,6 +76,19 @@ mod tests {
             ProtocolEvent::CloseStream { sid: Sid::new(42) }.to_frame(),
             OTFrame::CloseStream { sid: Sid::new(42) }
         );
+        assert_eq!(
+            ProtocolEvent::Ack {
+                sid: Sid::new(42),
+                count: 7,
+                delay: Duration::from_millis(3),
+            }
+            .to_frame(),
+            OTFrame::Ack {
+                sid: Sid::new(42),
+                count: 7,
+                delay: Duration::from_millis(3),
+            }
+        );
     }
 
     #[test]
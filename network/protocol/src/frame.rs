@@ -1,5 +1,9 @@
-use crate::types::{Bandwidth, Mid, Pid, Prio, Promises, Sid};
+use crate::{
+    crypto::{IDENTITY_LEN, PUBLIC_KEY_LEN, SIGNATURE_LEN},
+    types::{Bandwidth, Capabilities, Mid, Pid, Prio, Promises, Sid},
+};
 use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::time::Duration;
 
 // const FRAME_RESERVED_1: u8 = 0;
 const FRAME_HANDSHAKE: u8 = 1;
@@ -10,8 +14,14 @@ const FRAME_CLOSE_STREAM: u8 = 5;
 const FRAME_DATA_HEADER: u8 = 6;
 const FRAME_DATA: u8 = 7;
 const FRAME_RAW: u8 = 8;
+const FRAME_ACK: u8 = 9;
 //const FRAME_RESERVED_2: u8 = 10;
+const FRAME_KEY_EXCHANGE: u8 = 11;
+const FRAME_SEALED: u8 = 12;
 //const FRAME_RESERVED_3: u8 = 13;
+const FRAME_SEALED_DATA: u8 = 14;
+const FRAME_CAPABILITIES: u8 = 15;
+const FRAME_IDENTITY: u8 = 16;
 
 /// Used for Communication between Channel <----(TCP/UDP)----> Channel
 #[derive(Debug, PartialEq, Clone)]
@@ -24,6 +34,20 @@ pub enum InitFrame {
         pid: Pid,
         secret: u128,
     },
+    Capabilities {
+        capabilities: Capabilities,
+    },
+    KeyExchange {
+        public_key: [u8; PUBLIC_KEY_LEN],
+    },
+    /// long-term identity of the initializer and its signature of the
+    /// `KeyExchange`
+    Identity {
+        identity: [u8; IDENTITY_LEN],
+        signature: [u8; SIGNATURE_LEN],
+    },
+    /// another `InitFrame`, encrypted with the keys of the `KeyExchange`
+    Sealed(Vec<u8>),
     /// WARNING: sending RAW is only for debug purposes and will drop the
     /// connection
     Raw(Vec<u8>),
@@ -52,6 +76,16 @@ pub enum OTFrame {
         mid: Mid,
         data: Bytes,
     },
+    /// `Data` of a [`Promises::ENCRYPTED`] stream
+    SealedData {
+        mid: Mid,
+        data: Bytes,
+    },
+    Ack {
+        sid: Sid,
+        count: u64,
+        delay: Duration,
+    },
 }
 
 /// Used for IN TCP Communication between Channel <--(TCP)-- Channel
@@ -77,14 +111,28 @@ pub enum ITFrame {
         mid: Mid,
         data: BytesMut,
     },
+    SealedData {
+        mid: Mid,
+        data: BytesMut,
+    },
+    Ack {
+        sid: Sid,
+        count: u64,
+        delay: Duration,
+    },
 }
 
 impl InitFrame {
+    pub(crate) const CAPABILITIES_CNS: usize = 8;
     // Size WITHOUT the 1rst indicating byte
     pub(crate) const HANDSHAKE_CNS: usize = 19;
+    pub(crate) const IDENTITY_CNS: usize = IDENTITY_LEN + SIGNATURE_LEN;
     pub(crate) const INIT_CNS: usize = 32;
+    pub(crate) const KEY_EXCHANGE_CNS: usize = PUBLIC_KEY_LEN;
     /// const part of the RAW frame, actual size is variable
     pub(crate) const RAW_CNS: usize = 2;
+    /// const part of the SEALED frame, actual size is variable
+    pub(crate) const SEALED_CNS: usize = 2;
 
     //provide an appropriate buffer size. > 1500
     pub(crate) fn write_bytes(self, bytes: &mut BytesMut) {
@@ -104,6 +152,27 @@ impl InitFrame {
                 pid.to_bytes(bytes);
                 bytes.put_u128_le(secret);
             },
+            InitFrame::Capabilities { capabilities } => {
+                bytes.put_u8(FRAME_CAPABILITIES);
+                bytes.put_u64_le(capabilities.bits());
+            },
+            InitFrame::KeyExchange { public_key } => {
+                bytes.put_u8(FRAME_KEY_EXCHANGE);
+                bytes.put_slice(&public_key);
+            },
+            InitFrame::Identity {
+                identity,
+                signature,
+            } => {
+                bytes.put_u8(FRAME_IDENTITY);
+                bytes.put_slice(&identity);
+                bytes.put_slice(&signature);
+            },
+            InitFrame::Sealed(data) => {
+                bytes.put_u8(FRAME_SEALED);
+                bytes.put_u16_le(data.len() as u16);
+                bytes.put_slice(&data);
+            },
             InitFrame::Raw(data) => {
                 bytes.put_u8(FRAME_RAW);
                 bytes.put_u16_le(data.len() as u16);
@@ -141,6 +210,49 @@ impl InitFrame {
                     secret: bytes.get_u128_le(),
                 }
             },
+            FRAME_CAPABILITIES => {
+                if bytes.len() < Self::CAPABILITIES_CNS + 1 {
+                    return None;
+                }
+                bytes.advance(1);
+                InitFrame::Capabilities {
+                    capabilities: Capabilities::from_bits_truncate(bytes.get_u64_le()),
+                }
+            },
+            FRAME_KEY_EXCHANGE => {
+                if bytes.len() < Self::KEY_EXCHANGE_CNS + 1 {
+                    return None;
+                }
+                bytes.advance(1);
+                let mut public_key = [0u8; PUBLIC_KEY_LEN];
+                bytes.copy_to_slice(&mut public_key);
+                InitFrame::KeyExchange { public_key }
+            },
+            FRAME_IDENTITY => {
+                if bytes.len() < Self::IDENTITY_CNS + 1 {
+                    return None;
+                }
+                bytes.advance(1);
+                let mut identity = [0u8; IDENTITY_LEN];
+                bytes.copy_to_slice(&mut identity);
+                let mut signature = [0u8; SIGNATURE_LEN];
+                bytes.copy_to_slice(&mut signature);
+                InitFrame::Identity {
+                    identity,
+                    signature,
+                }
+            },
+            FRAME_SEALED => {
+                if bytes.len() < Self::SEALED_CNS + 1 {
+                    return None;
+                }
+                let length = u16::from_le_bytes([bytes[1], bytes[2]]) as usize;
+                if bytes.len() < Self::SEALED_CNS + 1 + length {
+                    return None;
+                }
+                bytes.advance(1 + Self::SEALED_CNS);
+                InitFrame::Sealed(bytes.split_to(length).to_vec())
+            },
             FRAME_RAW => {
                 if bytes.len() < Self::RAW_CNS + 1 {
                     return None;
@@ -159,6 +271,7 @@ impl InitFrame {
     }
 }
 
+pub(crate) const TCP_ACK_CNS: usize = 20;
 pub(crate) const TCP_CLOSE_STREAM_CNS: usize = 8;
 /// const part of the DATA frame, actual size is variable
 pub(crate) const TCP_DATA_CNS: usize = 10;
@@ -201,6 +314,19 @@ impl OTFrame {
                 bytes.put_u16_le(data.len() as u16);
                 bytes.put_slice(&data);
             },
+            Self::SealedData { mid, data } => {
+                bytes.put_u8(FRAME_SEALED_DATA);
+                bytes.put_u64_le(mid);
+                bytes.put_u16_le(data.len() as u16);
+                bytes.put_slice(&data);
+            },
+            Self::Ack { sid, count, delay } => {
+                bytes.put_u8(FRAME_ACK);
+                sid.to_bytes(bytes);
+                bytes.put_u64_le(count);
+                // in microseconds, saturating at ~71 minutes
+                bytes.put_u32_le(delay.as_micros().min(u32::MAX as u128) as u32);
+            },
         }
     }
 }
@@ -218,12 +344,13 @@ impl ITFrame {
             FRAME_OPEN_STREAM => TCP_OPEN_STREAM_CNS,
             FRAME_CLOSE_STREAM => TCP_CLOSE_STREAM_CNS,
             FRAME_DATA_HEADER => TCP_DATA_HEADER_CNS,
-            FRAME_DATA => {
+            FRAME_DATA | FRAME_SEALED_DATA => {
                 if bytes.len() < 9 + 1 + 1 {
                     return Ok(None);
                 }
                 u16::from_le_bytes([bytes[8 + 1], bytes[9 + 1]]) as usize + TCP_DATA_CNS
             },
+            FRAME_ACK => TCP_ACK_CNS,
             _ => return Err(()),
         };
 
@@ -270,6 +397,23 @@ impl ITFrame {
                 let data = bytes.split_to(length as usize);
                 Self::Data { mid, data }
             },
+            FRAME_SEALED_DATA => {
+                bytes.advance(1);
+                let mid = bytes.get_u64_le();
+                let length = bytes.get_u16_le();
+                debug_assert_eq!(length as usize, size - TCP_DATA_CNS);
+                let data = bytes.split_to(length as usize);
+                Self::SealedData { mid, data }
+            },
+            FRAME_ACK => {
+                let mut bytes = bytes.split_to(size + 1);
+                bytes.advance(1);
+                Self::Ack {
+                    sid: Sid::from_bytes(&mut bytes),
+                    count: bytes.get_u64_le(),
+                    delay: Duration::from_micros(bytes.get_u32_le() as u64),
+                }
+            },
             _ => unreachable!("Frame::to_frame should be handled before!"),
         };
         Ok(Some(frame))
@@ -297,6 +441,12 @@ impl PartialEq<ITFrame> for OTFrame {
                 matches!(other, ITFrame::DataHeader { mid, sid, length })
             },
             Self::Data { mid, data } => matches!(other, ITFrame::Data { mid, data }),
+            Self::SealedData { mid, data } => {
+                matches!(other, ITFrame::SealedData { mid, data })
+            },
+            Self::Ack { sid, count, delay } => {
+                matches!(other, ITFrame::Ack { sid, count, delay })
+            },
         }
     }
 }
@@ -316,6 +466,17 @@ mod tests {
                 pid: Pid::fake(0),
                 secret: 0u128,
             },
+            InitFrame::Capabilities {
+                capabilities: Capabilities::all(),
+            },
+            InitFrame::KeyExchange {
+                public_key: [42u8; PUBLIC_KEY_LEN],
+            },
+            InitFrame::Identity {
+                identity: [13u8; IDENTITY_LEN],
+                signature: [37u8; SIGNATURE_LEN],
+            },
+            InitFrame::Sealed(vec![7u8; 50]),
             InitFrame::Raw(vec![1, 2, 3]),
         ]
     }
@@ -341,6 +502,15 @@ mod tests {
                 mid: 0,
                 data: Bytes::from(&[42u8; 16][..]),
             },
+            OTFrame::SealedData {
+                mid: 1,
+                data: Bytes::from(&[13u8; 40][..]),
+            },
+            OTFrame::Ack {
+                sid: Sid::new(1337),
+                count: 1,
+                delay: Duration::from_micros(1500),
+            },
             OTFrame::CloseStream {
                 sid: Sid::new(1337),
             },
@@ -460,6 +630,19 @@ mod tests {
         assert_eq!(frame1d, None);
     }
 
+    #[test]
+    fn initframe_unknown_capabilities() {
+        let mut buffer = BytesMut::with_capacity(20);
+
+        let frame1 = InitFrame::Capabilities {
+            capabilities: Capabilities::ENCRYPTION,
+        };
+        InitFrame::write_bytes(frame1.clone(), &mut buffer);
+        // a newer remote might support capabilities we don't know yet
+        buffer[8] = 0x80;
+        assert_eq!(InitFrame::read_frame(&mut buffer), Some(frame1));
+    }
+
     #[test]
     fn initframe_rubish() {
         let mut buffer = BytesMut::from(&b"dtrgwcser"[..]);
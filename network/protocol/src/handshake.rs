@@ -1,9 +1,10 @@
 use crate::{
+    crypto::{Cipher, Identity, KeyExchange, PublicIdentity},
     error::{InitProtocolError, ProtocolError},
     frame::InitFrame,
     types::{
-        Pid, Sid, STREAM_ID_OFFSET1, STREAM_ID_OFFSET2, VELOREN_MAGIC_NUMBER,
-        VELOREN_NETWORK_VERSION,
+        Capabilities, Pid, Sid, CAPABILITIES_VERSION, STREAM_ID_OFFSET1, STREAM_ID_OFFSET2,
+        VELOREN_MAGIC_NUMBER, VELOREN_NETWORK_VERSION,
     },
     InitProtocol,
 };
@@ -22,6 +23,29 @@ use tracing::{debug, error, info, trace};
 #[async_trait]
 pub trait ReliableDrain {
     async fn send(&mut self, frame: InitFrame) -> Result<(), ProtocolError>;
+
+    /// Return `true` to do a key exchange during the Handshake, for protocols
+    /// that encrypt [`Promises::ENCRYPTED`] streams themselves.
+    ///
+    /// [`Promises::ENCRYPTED`]: crate::Promises::ENCRYPTED
+    fn key_exchange(&self) -> bool { false }
+
+    /// Signs the key exchange when initializing the handshake, a random one is
+    /// used if this returns `None`.
+    fn identity(&self) -> Option<&Identity> { None }
+
+    /// The identity the initializer MUST sign the key exchange with, checked
+    /// when not initializing the handshake. Fails the handshake if no key is
+    /// exchanged.
+    fn pinned_identity(&self) -> Option<PublicIdentity> { None }
+
+    /// Called with the identity the initializer proved when not initializing
+    /// the handshake, also if none was pinned.
+    fn set_remote_identity(&mut self, _identity: PublicIdentity) {}
+
+    /// Called with the result of the key exchange, everything send afterwards
+    /// SHOULD be sealed with it.
+    fn set_cipher(&mut self, _cipher: Cipher) {}
 }
 
 /// Implement this for auto Handshake with [`ReliableDrain`]. See
@@ -31,6 +55,9 @@ pub trait ReliableDrain {
 #[async_trait]
 pub trait ReliableSink {
     async fn recv(&mut self) -> Result<InitFrame, ProtocolError>;
+
+    /// See [`ReliableDrain::set_cipher`]
+    fn set_cipher(&mut self, _cipher: Cipher) {}
 }
 
 #[async_trait]
@@ -44,7 +71,8 @@ where
         initializer: bool,
         local_pid: Pid,
         local_secret: u128,
-    ) -> Result<(Pid, Sid, u128), InitProtocolError> {
+        local_capabilities: Capabilities,
+    ) -> Result<(Pid, Sid, u128, Capabilities), InitProtocolError> {
         #[cfg(debug_assertions)]
         const WRONG_NUMBER: &str = "Handshake does not contain the magic number required by \
                                     veloren server.\nWe are not sure if you are a valid veloren \
@@ -81,8 +109,7 @@ where
                         .send(InitFrame::Raw(WRONG_NUMBER.as_bytes().to_vec()))
                         .await?;
                     Err(InitProtocolError::WrongMagicNumber(magic_number))
-                } else if version[0] != VELOREN_NETWORK_VERSION[0]
-                    || version[1] != VELOREN_NETWORK_VERSION[1]
+                } else if version[0] != VELOREN_NETWORK_VERSION[0] || version < CAPABILITIES_VERSION
                 {
                     error!(?version, "Connection with wrong network version");
                     #[cfg(debug_assertions)]
@@ -99,14 +126,7 @@ where
                     Err(InitProtocolError::WrongVersion(version))
                 } else {
                     trace!("Handshake Frame completed");
-                    if initializer {
-                        drain
-                            .send(InitFrame::Init {
-                                pid: local_pid,
-                                secret: local_secret,
-                            })
-                            .await?;
-                    } else {
+                    if !initializer {
                         drain
                             .send(InitFrame::Handshake {
                                 magic_number: VELOREN_MAGIC_NUMBER,
@@ -130,6 +150,115 @@ where
             },
         }?;
 
+        // both sides send their capabilities right after the Handshake
+        drain
+            .send(InitFrame::Capabilities {
+                capabilities: local_capabilities,
+            })
+            .await?;
+        let remote_capabilities = match sink.recv().await? {
+            InitFrame::Capabilities { capabilities } => Ok(capabilities),
+            _ => {
+                info!("Capability negotiation failed");
+                Err(InitProtocolError::Closed)
+            },
+        }?;
+        let capabilities = local_capabilities & remote_capabilities;
+        debug!(?capabilities, "Negotiated capabilities");
+
+        // the Capabilities are send in plain, so a man in the middle could strip the
+        // encryption. Both are part of the transcript below, but that only helps
+        // if there is a key exchange at all
+        if drain.key_exchange()
+            && local_capabilities.contains(Capabilities::ENCRYPTION)
+            && !remote_capabilities.contains(Capabilities::ENCRYPTION)
+        {
+            info!("Remote doesn't offer encryption");
+            return Err(InitProtocolError::Closed);
+        }
+
+        if drain.key_exchange() && capabilities.contains(Capabilities::ENCRYPTION) {
+            // the Init frame containing our secret is already encrypted
+            let key_exchange = KeyExchange::new()?;
+            drain
+                .send(InitFrame::KeyExchange {
+                    public_key: key_exchange.public_key(),
+                })
+                .await?;
+            let remote_public_key = match sink.recv().await? {
+                InitFrame::KeyExchange { public_key } => Ok(public_key),
+                _ => {
+                    info!("Key exchange failed");
+                    Err(InitProtocolError::Closed)
+                },
+            }?;
+
+            // the initializer proves that it did the key exchange with us
+            let transcript = key_exchange.transcript(
+                initializer,
+                remote_public_key,
+                local_capabilities,
+                remote_capabilities,
+            );
+            if initializer {
+                let random_identity;
+                let identity = match drain.identity() {
+                    Some(identity) => identity,
+                    None => {
+                        random_identity = Identity::generate();
+                        &random_identity
+                    },
+                };
+                let frame = InitFrame::Identity {
+                    identity: identity.public_identity().to_bytes(),
+                    signature: identity.sign(&transcript),
+                };
+                drain.send(frame).await?;
+            } else {
+                match sink.recv().await? {
+                    InitFrame::Identity {
+                        identity,
+                        signature,
+                    } => {
+                        let identity = PublicIdentity::from_bytes(identity);
+                        if !identity.verify(&transcript, &signature) {
+                            info!(%identity, "Identity signature is invalid");
+                            Err(InitProtocolError::Closed)
+                        } else if drain.pinned_identity().map_or(false, |p| p != identity) {
+                            error!(%identity, "Remote doesn't have the pinned identity");
+                            Err(InitProtocolError::WrongIdentity(Some(identity)))
+                        } else {
+                            debug!(%identity, "Remote proved its identity");
+                            drain.set_remote_identity(identity);
+                            Ok(())
+                        }
+                    },
+                    _ => {
+                        info!("Key exchange failed");
+                        Err(InitProtocolError::Closed)
+                    },
+                }?;
+            }
+
+            trace!("Key exchange completed");
+            let (send_cipher, recv_cipher) =
+                key_exchange.agree(initializer, remote_public_key, &transcript)?;
+            drain.set_cipher(send_cipher);
+            sink.set_cipher(recv_cipher);
+        } else if !initializer && drain.pinned_identity().is_some() {
+            error!("No key exchange, can't verify the pinned identity");
+            return Err(InitProtocolError::WrongIdentity(None));
+        }
+
+        if initializer {
+            drain
+                .send(InitFrame::Init {
+                    pid: local_pid,
+                    secret: local_secret,
+                })
+                .await?;
+        }
+
         match sink.recv().await? {
             InitFrame::Init { pid, secret } => {
                 debug!(?pid, "Participant send their ID");
@@ -145,7 +274,7 @@ where
                     STREAM_ID_OFFSET2
                 };
                 info!(?pid, "This Handshake is now configured!");
-                Ok((pid, stream_id_offset, secret))
+                Ok((pid, stream_id_offset, secret, capabilities))
             },
             InitFrame::Raw(bytes) => {
                 match std::str::from_utf8(bytes.as_slice()) {
@@ -170,7 +299,10 @@ mod tests {
     #[tokio::test]
     async fn handshake_drop_start() {
         let [mut p1, p2] = ac_bound(10, None);
-        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await });
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, Capabilities::all())
+                .await
+        });
         let r2 = tokio::spawn(async move {
             let _ = p2;
         });
@@ -181,7 +313,10 @@ mod tests {
     #[tokio::test]
     async fn handshake_wrong_magic_number() {
         let [mut p1, mut p2] = ac_bound(10, None);
-        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await });
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, Capabilities::all())
+                .await
+        });
         let r2 = tokio::spawn(async move {
             let _ = p2.1.recv().await?;
             p2.0.send(InitFrame::Handshake {
@@ -203,7 +338,10 @@ mod tests {
     #[tokio::test]
     async fn handshake_wrong_version() {
         let [mut p1, mut p2] = ac_bound(10, None);
-        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await });
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, Capabilities::all())
+                .await
+        });
         let r2 = tokio::spawn(async move {
             let _ = p2.1.recv().await?;
             p2.0.send(InitFrame::Handshake {
@@ -220,10 +358,57 @@ mod tests {
         assert_eq!(r2.unwrap(), Err(InitProtocolError::Closed));
     }
 
+    #[tokio::test]
+    async fn handshake_newer_minor_version() {
+        let [mut p1, mut p2] = ac_bound(10, None);
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, Capabilities::all())
+                .await
+        });
+        let r2 = tokio::spawn(async move {
+            let _ = p2.1.recv().await?;
+            p2.0.send(InitFrame::Handshake {
+                magic_number: VELOREN_MAGIC_NUMBER,
+                version: [
+                    VELOREN_NETWORK_VERSION[0],
+                    VELOREN_NETWORK_VERSION[1] + 1,
+                    0,
+                ],
+            })
+            .await?;
+            let _ = p2.1.recv().await?;
+            p2.0.send(InitFrame::Capabilities {
+                capabilities: Capabilities::ENCRYPTION,
+            })
+            .await?;
+            let _ = p2.1.recv().await?;
+            p2.0.send(InitFrame::Init {
+                pid: Pid::fake(3),
+                secret: 42,
+            })
+            .await?;
+            Result::<(), InitProtocolError>::Ok(())
+        });
+        let (r1, r2) = tokio::join!(r1, r2);
+        assert_eq!(
+            r1.unwrap(),
+            Ok((
+                Pid::fake(3),
+                STREAM_ID_OFFSET1,
+                42,
+                Capabilities::ENCRYPTION
+            ))
+        );
+        assert_eq!(r2.unwrap(), Ok(()));
+    }
+
     #[tokio::test]
     async fn handshake_unexpected_raw() {
         let [mut p1, mut p2] = ac_bound(10, None);
-        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await });
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, Capabilities::all())
+                .await
+        });
         let r2 = tokio::spawn(async move {
             let _ = p2.1.recv().await?;
             p2.0.send(InitFrame::Handshake {
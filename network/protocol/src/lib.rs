@@ -15,8 +15,7 @@
 //!  - TCP
 //!  - MPSC
 //!  - QUIC
-//!
-//! eventually a pure UDP implementation will follow
+//!  - UDP
 //!
 //! warning: don't mix protocol, using the TCP variant for actual UDP socket
 //! will result in dropped data  using UDP with a TCP socket will be a waste of
@@ -42,6 +41,9 @@
 //!
 //! For an *example* see `TcpDrain` and `TcpSink` in the [tcp.rs](tcp.rs)
 //!
+//! With the `sim` feature, the `sim` module wraps Drains and Sinks to
+//! simulate latency, loss and limited bandwidth.
+//!
 //! [`UnreliableDrain`]: crate::UnreliableDrain
 //! [`UnreliableSink`]: crate::UnreliableSink
 //! [`Vec<u8>`]: std::vec::Vec
@@ -50,6 +52,8 @@
 //! [`RecvProtocol`]: crate::RecvProtocol
 //! [`InitProtocol`]: crate::InitProtocol
 
+mod congestion;
+mod crypto;
 mod error;
 mod event;
 mod frame;
@@ -59,10 +63,13 @@ mod metrics;
 mod mpsc;
 mod prio;
 mod quic;
+#[cfg(feature = "sim")] pub mod sim;
 mod tcp;
 mod types;
+mod udp;
 mod util;
 
+pub use crypto::{Identity, PublicIdentity};
 pub use error::{InitProtocolError, ProtocolError};
 pub use event::ProtocolEvent;
 pub use metrics::ProtocolMetricCache;
@@ -71,7 +78,10 @@ pub use metrics::ProtocolMetrics;
 pub use mpsc::{MpscMsg, MpscRecvProtocol, MpscSendProtocol};
 pub use quic::{QuicDataFormat, QuicDataFormatStream, QuicRecvProtocol, QuicSendProtocol};
 pub use tcp::{TcpRecvProtocol, TcpSendProtocol};
-pub use types::{Bandwidth, Cid, Pid, Prio, Promises, Sid, HIGHEST_PRIO, VELOREN_NETWORK_VERSION};
+pub use types::{
+    Bandwidth, Capabilities, Cid, Pid, Prio, Promises, Sid, HIGHEST_PRIO, VELOREN_NETWORK_VERSION,
+};
+pub use udp::{UdpDataFormat, UdpRecvProtocol, UdpSendProtocol, UDP_SINK_TIMEOUT};
 
 ///use at own risk, might change any time, for internal benchmarks
 pub mod _internal {
@@ -91,7 +101,8 @@ pub trait InitProtocol {
         initializer: bool,
         local_pid: Pid,
         secret: u128,
-    ) -> Result<(Pid, Sid, u128), InitProtocolError>;
+        capabilities: Capabilities,
+    ) -> Result<(Pid, Sid, u128, Capabilities), InitProtocolError>;
 }
 
 /// Generic Network Send Protocol.
@@ -109,6 +120,8 @@ pub trait InitProtocol {
 pub trait SendProtocol {
     /// YOU MUST inform the `SendProtocol` by any Stream Open BEFORE using it in
     /// `send` and Stream Close AFTER using it in `send` via this fn.
+    /// Acks of the remote SHOULD be passed here too, they are used to
+    /// estimate the available bandwidth.
     fn notify_from_recv(&mut self, event: ProtocolEvent);
     /// Send a Event via this Protocol. The `SendProtocol` MAY require `flush`
     /// to be called before actual data is send to the respective `Sink`.
@@ -116,7 +129,9 @@ pub trait SendProtocol {
     /// Flush all buffered messages according to their [`Prio`] and
     /// [`Bandwidth`]. provide the current bandwidth budget (per second) as
     /// well as the `dt` since last call. According to the budget the
-    /// respective messages will be flushed.
+    /// respective messages will be flushed. The budget is limited to the
+    /// estimated available bandwidth, a budget of `Bandwidth::MAX` flushes
+    /// everything.
     ///
     /// [`Prio`]: crate::Prio
     /// [`Bandwidth`]: crate::Bandwidth
@@ -1,4 +1,6 @@
-use crate::types::Sid;
+#[cfg(feature = "metrics")]
+use crate::types::HIGHEST_PRIO;
+use crate::types::{Bandwidth, Prio, Sid};
 #[cfg(feature = "metrics")]
 use prometheus::{
     core::{AtomicI64, AtomicU64, GenericCounter, GenericGauge},
@@ -6,7 +8,7 @@ use prometheus::{
 };
 #[cfg(feature = "metrics")]
 use std::collections::HashMap;
-use std::{error::Error, sync::Arc};
+use std::{error::Error, sync::Arc, time::Duration};
 
 #[allow(dead_code)]
 pub enum RemoveReason {
@@ -40,6 +42,10 @@ pub struct ProtocolMetrics {
     sdata_frames_t: IntCounterVec,
     /// data frames bytes send by prio by CHANNEL,
     sdata_frames_b: IntCounterVec,
+    /// estimated available bandwidth by CHANNEL,
+    bandwidth_estimate: IntGaugeVec,
+    /// flushes in which a stream kept messages back by CHANNEL AND PRIO,
+    sthrottled_t: IntCounterVec,
 
     // based on CHANNEL/STREAM
     /// messages added to be received total, by STREAM,
@@ -56,7 +62,7 @@ pub struct ProtocolMetrics {
     rdata_frames_t: IntCounterVec,
     /// data frames bytes send by prio by CHANNEL,
     rdata_frames_b: IntCounterVec,
-    /// ping per CHANNEL //TODO: implement
+    /// smoothed round trip time in ms per CHANNEL
     ping: IntGaugeVec,
 }
 
@@ -74,7 +80,8 @@ pub struct ProtocolMetricCache {
     sdata_frames_b: GenericCounter<AtomicU64>,
     rdata_frames_t: GenericCounter<AtomicU64>,
     rdata_frames_b: GenericCounter<AtomicU64>,
-    #[allow(dead_code)]
+    bandwidth_estimate: GenericGauge<AtomicI64>,
+    sthrottled_t: Vec<GenericCounter<AtomicU64>>,
     ping: GenericGauge<AtomicI64>,
 }
 
@@ -129,6 +136,21 @@ impl ProtocolMetrics {
             ),
             &["channel"],
         )?;
+        let bandwidth_estimate = IntGaugeVec::new(
+            Opts::new(
+                "bandwidth_estimate",
+                "Estimated available bandwidth in bytes per second per channel",
+            ),
+            &["channel"],
+        )?;
+        let sthrottled_t = IntCounterVec::new(
+            Opts::new(
+                "send_throttled_total",
+                "Number of flushes in which a stream kept messages back, because the bandwidth \
+                 was used up, per channel and prio",
+            ),
+            &["channel", "prio"],
+        )?;
 
         let rmsg_it = IntCounterVec::new(
             Opts::new(
@@ -174,7 +196,10 @@ impl ProtocolMetrics {
             ),
             &["channel"],
         )?;
-        let ping = IntGaugeVec::new(Opts::new("ping", "Ping per channel"), &["channel"])?;
+        let ping = IntGaugeVec::new(
+            Opts::new("ping", "Smoothed round trip time in ms per channel"),
+            &["channel"],
+        )?;
 
         Ok(Self {
             smsg_it,
@@ -183,6 +208,8 @@ impl ProtocolMetrics {
             smsg_ob,
             sdata_frames_t,
             sdata_frames_b,
+            bandwidth_estimate,
+            sthrottled_t,
             rmsg_it,
             rmsg_ib,
             rmsg_ot,
@@ -200,6 +227,8 @@ impl ProtocolMetrics {
         registry.register(Box::new(self.smsg_ob.clone()))?;
         registry.register(Box::new(self.sdata_frames_t.clone()))?;
         registry.register(Box::new(self.sdata_frames_b.clone()))?;
+        registry.register(Box::new(self.bandwidth_estimate.clone()))?;
+        registry.register(Box::new(self.sthrottled_t.clone()))?;
         registry.register(Box::new(self.rmsg_it.clone()))?;
         registry.register(Box::new(self.rmsg_ib.clone()))?;
         registry.register(Box::new(self.rmsg_ot.clone()))?;
@@ -235,6 +264,14 @@ impl ProtocolMetricCache {
         let sdata_frames_b = metrics.sdata_frames_b.with_label_values(&[&cid]);
         let rdata_frames_t = metrics.rdata_frames_t.with_label_values(&[&cid]);
         let rdata_frames_b = metrics.rdata_frames_b.with_label_values(&[&cid]);
+        let bandwidth_estimate = metrics.bandwidth_estimate.with_label_values(&[&cid]);
+        let sthrottled_t = (0..=HIGHEST_PRIO)
+            .map(|prio| {
+                metrics
+                    .sthrottled_t
+                    .with_label_values(&[&cid, &prio.to_string()])
+            })
+            .collect();
         let ping = metrics.ping.with_label_values(&[&cid]);
         Self {
             cid,
@@ -244,6 +281,8 @@ impl ProtocolMetricCache {
             sdata_frames_b,
             rdata_frames_t,
             rdata_frames_b,
+            bandwidth_estimate,
+            sthrottled_t,
             ping,
         }
     }
@@ -297,6 +336,14 @@ impl ProtocolMetricCache {
         self.sdata_frames_b.inc_by(bytes);
     }
 
+    pub(crate) fn throttled(&mut self, prio: Prio) { self.sthrottled_t[prio as usize].inc(); }
+
+    pub(crate) fn congestion(&mut self, bandwidth: Bandwidth, rtt: Duration) {
+        self.bandwidth_estimate
+            .set(bandwidth.min(i64::MAX as u64) as i64);
+        self.ping.set(rtt.as_millis() as i64);
+    }
+
     pub(crate) fn rmsg_ib(&mut self, sid: Sid, bytes: u64) {
         let line = self.init_sid(sid);
         line.rmsg_it.inc();
@@ -368,6 +415,12 @@ impl Drop for ProtocolMetricCache {
             let _ = m.rmsg_ob.remove_label_values(&[cid, &s, dropped]);
         }
         let _ = m.ping.remove_label_values(&[cid]);
+        let _ = m.bandwidth_estimate.remove_label_values(&[cid]);
+        for prio in 0..=HIGHEST_PRIO {
+            let _ = m
+                .sthrottled_t
+                .remove_label_values(&[cid, &prio.to_string()]);
+        }
         let _ = m.sdata_frames_t.remove_label_values(&[cid]);
         let _ = m.sdata_frames_b.remove_label_values(&[cid]);
         let _ = m.rdata_frames_t.remove_label_values(&[cid]);
@@ -393,6 +446,10 @@ impl ProtocolMetricCache {
 
     pub(crate) fn sdata_frames_b(&mut self, _cnt: u64, _b: u64) {}
 
+    pub(crate) fn throttled(&mut self, _prio: Prio) {}
+
+    pub(crate) fn congestion(&mut self, _bandwidth: Bandwidth, _rtt: Duration) {}
+
     pub(crate) fn rmsg_ib(&mut self, _sid: Sid, _b: u64) {}
 
     pub(crate) fn rmsg_ob(&mut self, _sid: Sid, _reason: RemoveReason, _b: u64) {}
@@ -66,6 +66,7 @@ where
             | Promises::CONSISTENCY
             | Promises::GUARANTEED_DELIVERY
             | Promises::COMPRESSED
+            | Promises::COMPRESSED_ZSTD
             | Promises::ENCRYPTED /*assume a direct mpsc connection is secure*/
     }
 }
@@ -236,17 +237,29 @@ pub mod test_utils {
 mod tests {
     use crate::{
         mpsc::test_utils::*,
-        types::{Pid, STREAM_ID_OFFSET1, STREAM_ID_OFFSET2},
+        types::{Capabilities, Pid, STREAM_ID_OFFSET1, STREAM_ID_OFFSET2},
         InitProtocol,
     };
 
     #[tokio::test]
     async fn handshake_all_good() {
         let [mut p1, mut p2] = ac_bound(10, None);
-        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await });
-        let r2 = tokio::spawn(async move { p2.initialize(false, Pid::fake(3), 42).await });
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, Capabilities::all())
+                .await
+        });
+        let r2 = tokio::spawn(async move {
+            p2.initialize(false, Pid::fake(3), 42, Capabilities::all())
+                .await
+        });
         let (r1, r2) = tokio::join!(r1, r2);
-        assert_eq!(r1.unwrap(), Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42)));
-        assert_eq!(r2.unwrap(), Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337)));
+        assert_eq!(
+            r1.unwrap(),
+            Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42, Capabilities::all()))
+        );
+        assert_eq!(
+            r2.unwrap(),
+            Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337, Capabilities::all()))
+        );
     }
 }
@@ -1,4 +1,5 @@
 use crate::{
+    congestion::CongestionControl,
     frame::OTFrame,
     message::OTMessage,
     metrics::{ProtocolMetricCache, RemoveReason},
@@ -7,34 +8,53 @@ use crate::{
 use bytes::Bytes;
 use std::{
     collections::{HashMap, VecDeque},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 #[derive(Debug)]
 struct StreamInfo {
     pub(crate) guaranteed_bandwidth: Bandwidth,
     pub(crate) prio: Prio,
-    #[allow(dead_code)]
     pub(crate) promises: Promises,
     pub(crate) messages: VecDeque<OTMessage>,
+    /// finish time and size of messages the remote didn't acknowledge yet,
+    /// and whether all queues ran empty while sending them, only tracked for
+    /// [`Promises::GUARANTEED_DELIVERY`]
+    pub(crate) in_flight: VecDeque<(Instant, u64, bool)>,
+    pub(crate) acked: u64,
+    /// negative if more guaranteed bytes were send than budgeted, as frames
+    /// are never split
+    pub(crate) guaranteed_credit: i64,
 }
 
 /// Responsible for queueing messages.
 /// every stream has a guaranteed bandwidth and a prio 0-7.
 /// when `n` Bytes are available in the buffer, first the guaranteed bandwidth
 /// is used. Then remaining bandwidth is used to fill up the prios.
+/// The bandwidth is limited to what [`CongestionControl`] estimates to be
+/// available, so once the link saturates, the lowest prios are held back
+/// first.
 #[derive(Debug)]
 pub(crate) struct PrioManager {
     streams: HashMap<Sid, StreamInfo>,
+    congestion: CongestionControl,
+    /// bytes send over the budget of the last `grab`
+    overshoot: u64,
     metrics: ProtocolMetricCache,
 }
 
+/// messages of a stream tracked for bandwidth estimation, when the remote
+/// doesn't ack them
+const MAX_IN_FLIGHT: usize = 1024;
+
 // Send everything ONCE, then keep it till it's confirmed
 
 impl PrioManager {
     pub fn new(metrics: ProtocolMetricCache) -> Self {
         Self {
             streams: HashMap::new(),
+            congestion: CongestionControl::new(),
+            overshoot: 0,
             metrics,
         }
     }
@@ -51,6 +71,9 @@ impl PrioManager {
             prio,
             promises,
             messages: VecDeque::new(),
+            in_flight: VecDeque::new(),
+            acked: 0,
+            guaranteed_credit: 0,
         });
     }
 
@@ -66,6 +89,10 @@ impl PrioManager {
 
     pub fn is_empty(&self) -> bool { self.streams.is_empty() }
 
+    pub fn promises(&self, sid: Sid) -> Option<Promises> {
+        self.streams.get(&sid).map(|si| si.promises)
+    }
+
     pub fn add(&mut self, buffer: Bytes, mid: Mid, sid: Sid) {
         self.streams
             .get_mut(&sid)
@@ -74,12 +101,52 @@ impl PrioManager {
             .push_back(OTMessage::new(buffer, mid, sid));
     }
 
+    /// The remote received `count` messages of the stream so far, used to
+    /// estimate the available bandwidth. It held back the ack for `delay`
+    pub fn ack(&mut self, sid: Sid, count: u64, delay: Duration) {
+        let stream = match self.streams.get_mut(&sid) {
+            Some(stream) => stream,
+            None => return,
+        };
+        let n = (count.saturating_sub(stream.acked) as usize).min(stream.in_flight.len());
+        if n == 0 {
+            return;
+        }
+        stream.acked += n as u64;
+        let (bytes, newest) = stream
+            .in_flight
+            .drain(..n)
+            .fold((0, None), |(bytes, _), (sent, b, app_limited)| {
+                (bytes + b, Some((sent, app_limited)))
+            });
+        if let Some((sent, app_limited)) = newest {
+            let now = Instant::now();
+            let rtt = now.duration_since(sent).saturating_sub(delay);
+            self.congestion.on_ack(bytes, rtt, app_limited, now);
+            self.metrics.congestion(
+                self.congestion.bandwidth(),
+                self.congestion.rtt().unwrap_or_default(),
+            );
+        }
+    }
+
     /// bandwidth might be extended, as for technical reasons
     /// guaranteed_bandwidth is used and frames are always 1400 bytes.
+    /// The bandwidth is limited to the estimated one, except for
+    /// `Bandwidth::MAX` which flushes everything.
     pub fn grab(&mut self, bandwidth: Bandwidth, dt: Duration) -> (Vec<(Sid, OTFrame)>, Bandwidth) {
-        let total_bytes = (bandwidth as f64 * dt.as_secs_f64()) as u64;
+        let bandwidth = match bandwidth {
+            Bandwidth::MAX => Bandwidth::MAX,
+            bandwidth => bandwidth.min(self.congestion.bandwidth()),
+        };
+        let budget = (bandwidth as f64 * dt.as_secs_f64()) as u64;
+        // frames are never split, so the bytes send too much last time are
+        // taken from this budget
+        let total_bytes = budget.saturating_sub(self.overshoot);
+        self.overshoot = self.overshoot.saturating_sub(budget);
         let mut cur_bytes = 0u64;
         let mut frames = vec![];
+        let now = Instant::now();
 
         let mut prios = [0u64; (HIGHEST_PRIO + 1) as usize];
         let metrics = &mut self.metrics;
@@ -103,19 +170,33 @@ impl PrioManager {
                     }
                     let (sid, bytes) = msg.get_sid_len();
                     metrics.smsg_ob(sid, RemoveReason::Finished, bytes);
+                    if stream.promises.contains(Promises::GUARANTEED_DELIVERY) {
+                        if stream.in_flight.len() >= MAX_IN_FLIGHT {
+                            // the remote doesn't ack, stop tracking the oldest
+                            stream.in_flight.pop_front();
+                            stream.acked += 1;
+                        }
+                        stream.in_flight.push_back((now, bytes, true));
+                    }
                     finished = Some(i);
                 }
                 if let Some(i) = finished {
                     //cleanup
                     stream.messages.drain(..=i);
                 }
+                bandwidth
             };
 
         // Add guaranteed bandwidth
         for (sid, stream) in self.streams.iter_mut() {
             prios[stream.prio as usize] += 1;
-            let stream_byte_cnt = (stream.guaranteed_bandwidth as f64 * dt.as_secs_f64()) as u64;
-            process_stream(sid, stream, stream_byte_cnt as i64, &mut cur_bytes);
+            let stream_byte_cnt = (stream.guaranteed_bandwidth as f64 * dt.as_secs_f64()) as i64;
+            let credit = stream.guaranteed_credit.min(0) + stream_byte_cnt;
+            stream.guaranteed_credit = if credit > 0 {
+                process_stream(sid, stream, credit, &mut cur_bytes)
+            } else {
+                credit
+            };
         }
 
         if cur_bytes < total_bytes {
@@ -124,7 +205,11 @@ impl PrioManager {
                 if prios[prio as usize] == 0 {
                     continue;
                 }
-                let per_stream_bytes = ((total_bytes - cur_bytes) / prios[prio as usize]) as i64;
+                if cur_bytes >= total_bytes {
+                    break;
+                }
+                let per_stream_bytes =
+                    ((total_bytes - cur_bytes) / prios[prio as usize]).min(i64::MAX as u64) as i64;
                 for (sid, stream) in self.streams.iter_mut() {
                     if stream.prio != prio {
                         continue;
@@ -133,6 +218,105 @@ impl PrioManager {
                 }
             }
         }
+        self.overshoot += cur_bytes.saturating_sub(total_bytes);
+
+        // streams with messages left got throttled
+        let mut throttled = false;
+        for stream in self.streams.values() {
+            if !stream.messages.is_empty() {
+                self.metrics.throttled(stream.prio);
+                throttled = true;
+            }
+        }
+        // the messages finished now were limited by the bandwidth, not by
+        // the application
+        if throttled {
+            for stream in self.streams.values_mut() {
+                for (sent, _, app_limited) in stream.in_flight.iter_mut().rev() {
+                    if *sent != now {
+                        break;
+                    }
+                    *app_limited = false;
+                }
+            }
+        }
         (frames, cur_bytes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::ProtocolMetrics;
+    use std::sync::Arc;
+
+    fn prio_manager() -> PrioManager {
+        PrioManager::new(ProtocolMetricCache::new(
+            "prio",
+            Arc::new(ProtocolMetrics::new().unwrap()),
+        ))
+    }
+
+    fn sids(frames: &[(Sid, OTFrame)]) -> Vec<Sid> {
+        let mut sids = frames.iter().map(|(sid, _)| *sid).collect::<Vec<_>>();
+        sids.dedup();
+        sids
+    }
+
+    #[test]
+    fn lower_prio_throttled_first() {
+        let mut pm = prio_manager();
+        let (high, low) = (Sid::new(1), Sid::new(2));
+        pm.open_stream(high, 0, Promises::ORDERED, 0);
+        pm.open_stream(low, 7, Promises::ORDERED, 0);
+        pm.add(Bytes::from(vec![1u8; 10_000]), 1, low);
+        pm.add(Bytes::from(vec![0u8; 10_000]), 2, high);
+
+        let (frames, _) = pm.grab(5_000, Duration::from_secs(1));
+        assert_eq!(sids(&frames), vec![high]);
+
+        let (frames, _) = pm.grab(1_000_000, Duration::from_secs(1));
+        assert_eq!(sids(&frames), vec![high, low]);
+        assert!(pm.try_close_stream(high));
+        assert!(pm.try_close_stream(low));
+    }
+
+    #[test]
+    fn overshoot_is_paid_back() {
+        let mut pm = prio_manager();
+        let sid = Sid::new(1);
+        pm.open_stream(sid, 0, Promises::ORDERED, 0);
+        pm.add(Bytes::from(vec![0u8; 10_000]), 1, sid);
+
+        // a full frame is send, even if the budget is smaller
+        let (frames, bytes) = pm.grab(100, Duration::from_secs(1));
+        assert!(!frames.is_empty());
+        // so the next budgets are used to pay it back
+        let (frames, _) = pm.grab(100, Duration::from_secs(1));
+        assert!(frames.is_empty());
+        let (_, bytes2) = pm.grab(bytes, Duration::from_secs(10));
+        assert!(bytes2 > 0);
+    }
+
+    #[test]
+    fn ack_in_flight() {
+        let mut pm = prio_manager();
+        let sid = Sid::new(1);
+        pm.open_stream(sid, 0, Promises::GUARANTEED_DELIVERY, 0);
+        for mid in 0..3 {
+            pm.add(Bytes::from_static(b"Hello World"), mid, sid);
+        }
+        pm.grab(Bandwidth::MAX, Duration::from_secs(1));
+        assert_eq!(pm.streams[&sid].in_flight.len(), 3);
+        // everything got send at once
+        assert!(pm.streams[&sid].in_flight.iter().all(|(_, _, a)| *a));
+
+        pm.ack(sid, 2, Duration::ZERO);
+        assert_eq!(pm.streams[&sid].in_flight.len(), 1);
+        assert!(pm.congestion.rtt().is_some());
+        // acks for more messages than were sent are ignored
+        pm.ack(sid, 10, Duration::ZERO);
+        assert_eq!(pm.streams[&sid].acked, 3);
+        assert!(pm.streams[&sid].in_flight.is_empty());
+    }
+}
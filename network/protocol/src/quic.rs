@@ -11,13 +11,30 @@ use crate::{
     RecvProtocol, SendProtocol, UnreliableDrain, UnreliableSink,
 };
 use async_trait::async_trait;
-use bytes::BytesMut;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use hashbrown::HashMap;
-use std::time::{Duration, Instant};
+use std::{
+    collections::{btree_map, BTreeMap, VecDeque},
+    time::{Duration, Instant},
+};
 use tracing::info;
 #[cfg(feature = "trace_pedantic")]
 use tracing::trace;
 
+/// Upper bound of a datagram carrying a message of an unreliable stream. QUIC
+/// paths support at least 1200 bytes, this leaves room for the packet headers.
+const MAX_DATAGRAM_SIZE: usize = 1100;
+/// sid, mid, length and offset of a [`Fragment`]
+const FRAGMENT_HEADER_SIZE: usize = 32;
+/// Number of incomplete messages of unreliable streams kept while waiting for
+/// their missing fragments, the oldest ones are dropped first.
+const MAX_INCOMPLETE_MESSAGES: usize = 64;
+/// Bytes of incomplete messages of unreliable streams kept, the oldest ones
+/// are dropped first.
+const MAX_INCOMPLETE_BYTES: u64 = 2 * ALLOC_BLOCK as u64;
+/// Longest message accepted on an unreliable stream (anti-ddos)
+const MAX_UNRELIABLE_LENGTH: u64 = ALLOC_BLOCK as u64;
+
 #[derive(PartialEq)]
 pub enum QuicDataFormatStream {
     Main,
@@ -45,9 +62,9 @@ impl QuicDataFormat {
         }
     }
 
-    fn with_unreliable(frame: OTFrame) -> Self {
+    fn with_unreliable(fragment: Fragment) -> Self {
         let mut buffer = BytesMut::new();
-        frame.write_bytes(&mut buffer);
+        fragment.write_bytes(&mut buffer);
         Self {
             stream: QuicDataFormatStream::Unreliable,
             data: buffer,
@@ -55,6 +72,107 @@ impl QuicDataFormat {
     }
 }
 
+/// Part of a message of an unreliable stream, which is sent as a single
+/// datagram. As datagrams get lost or reordered, every fragment repeats the
+/// message header and its offset in the message.
+#[derive(Debug, PartialEq)]
+struct Fragment {
+    sid: Sid,
+    mid: Mid,
+    length: u64,
+    offset: u64,
+    data: Bytes,
+}
+
+impl Fragment {
+    fn write_bytes(self, bytes: &mut BytesMut) {
+        bytes.reserve(FRAGMENT_HEADER_SIZE + self.data.len());
+        self.sid.to_bytes(bytes);
+        bytes.put_u64_le(self.mid);
+        bytes.put_u64_le(self.length);
+        bytes.put_u64_le(self.offset);
+        bytes.put_slice(&self.data);
+    }
+
+    fn read_bytes(mut bytes: BytesMut) -> Option<Self> {
+        if bytes.len() < FRAGMENT_HEADER_SIZE {
+            return None;
+        }
+        let sid = Sid::from_bytes(&mut bytes);
+        let mid = bytes.get_u64_le();
+        let length = bytes.get_u64_le();
+        let offset = bytes.get_u64_le();
+        let data = bytes.freeze();
+        if length > MAX_UNRELIABLE_LENGTH || offset.checked_add(data.len() as u64)? > length {
+            return None;
+        }
+        Some(Self {
+            sid,
+            mid,
+            length,
+            offset,
+            data,
+        })
+    }
+}
+
+/// A message of an unreliable stream, which is assembled from its fragments
+#[derive(Debug)]
+struct IncomingFragments {
+    sid: Sid,
+    length: u64,
+    /// bytes in `fragments`
+    received: u64,
+    /// fragments received so far by their offset, they never overlap
+    fragments: BTreeMap<u64, Bytes>,
+}
+
+impl IncomingFragments {
+    /// ignores duplicates, Err if the fragment overlaps with another one
+    fn insert(&mut self, offset: u64, data: Bytes) -> Result<(), ()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let end = offset + data.len() as u64;
+        if let Some((&prev, prev_data)) = self.fragments.range(..=offset).next_back() {
+            if prev + prev_data.len() as u64 > offset {
+                return if prev == offset && prev_data.len() == data.len() {
+                    Ok(())
+                } else {
+                    Err(())
+                };
+            }
+        }
+        if let Some((&next, _)) = self.fragments.range(offset + 1..).next() {
+            if next < end {
+                return Err(());
+            }
+        }
+        self.received += data.len() as u64;
+        self.fragments.insert(offset, data);
+        Ok(())
+    }
+
+    /// as fragments don't overlap, all of the message was received once
+    /// their bytes add up to its length
+    fn is_complete(&self) -> bool { self.received == self.length }
+
+    fn into_data(self) -> BytesMut {
+        let mut data = BytesMut::with_capacity(self.length as usize);
+        for fragment in self.fragments.values() {
+            data.extend_from_slice(fragment);
+        }
+        data
+    }
+}
+
+#[derive(Debug)]
+struct UnreliableStream {
+    ordered: bool,
+    /// newest message delivered, older ones are stale on an ordered stream
+    last_mid: Option<Mid>,
+}
+
 /// QUIC implementation of [`SendProtocol`]
 ///
 /// [`SendProtocol`]: crate::SendProtocol
@@ -70,6 +188,8 @@ where
     closing_streams: Vec<Sid>,
     notify_closing_streams: Vec<Sid>,
     pending_shutdown: bool,
+    /// sid, length and bytes sent of the messages on unreliable streams
+    unreliable_messages: HashMap<Mid, (Sid, u64, u64)>,
     drain: D,
     #[allow(dead_code)]
     last: Instant,
@@ -85,20 +205,20 @@ where
     S: UnreliableSink<DataFormat = QuicDataFormat>,
 {
     main_buffer: BytesMut,
-    unreliable_buffer: BytesMut,
+    unreliable_datagrams: VecDeque<BytesMut>,
     reliable_buffers: SortedVec<Sid, BytesMut>,
     pending_reliable_buffers: Vec<(Sid, BytesMut)>,
     itmsg_allocator: BytesMut,
     incoming: HashMap<Mid, ITMessage>,
+    unreliable_streams: HashMap<Sid, UnreliableStream>,
+    unreliable_incoming: BTreeMap<Mid, IncomingFragments>,
     sink: S,
     metrics: ProtocolMetricCache,
 }
 
-fn is_reliable(p: &Promises) -> bool {
-    p.contains(Promises::ORDERED)
-        || p.contains(Promises::CONSISTENCY)
-        || p.contains(Promises::GUARANTEED_DELIVERY)
-}
+/// Streams without [`Promises::GUARANTEED_DELIVERY`] are sent as datagrams, so
+/// lost messages never hold back newer ones
+fn is_reliable(p: &Promises) -> bool { p.contains(Promises::GUARANTEED_DELIVERY) }
 
 impl<D> QuicSendProtocol<D>
 where
@@ -113,6 +233,7 @@ where
             closing_streams: vec![],
             notify_closing_streams: vec![],
             pending_shutdown: false,
+            unreliable_messages: HashMap::new(),
             drain,
             last: Instant::now(),
             metrics,
@@ -126,8 +247,57 @@ where
             | Promises::CONSISTENCY
             | Promises::GUARANTEED_DELIVERY
             | Promises::COMPRESSED
+            | Promises::COMPRESSED_ZSTD
             | Promises::ENCRYPTED
     }
+
+    /// splits the frames of unreliable streams into datagrams
+    async fn send_unreliable(&mut self, frame: OTFrame) -> Result<(), ProtocolError> {
+        match frame {
+            OTFrame::DataHeader { mid, sid, length } => {
+                if length == 0 {
+                    let fragment = Fragment {
+                        sid,
+                        mid,
+                        length,
+                        offset: 0,
+                        data: Bytes::new(),
+                    };
+                    self.drain
+                        .send(QuicDataFormat::with_unreliable(fragment))
+                        .await?;
+                } else {
+                    self.unreliable_messages.insert(mid, (sid, length, 0));
+                }
+            },
+            OTFrame::Data { mid, mut data } => {
+                let (sid, length, offset) = match self.unreliable_messages.get_mut(&mid) {
+                    Some(message) => message,
+                    None => return Err(ProtocolError::Violated),
+                };
+                while !data.is_empty() {
+                    let chunk =
+                        data.split_to(data.len().min(MAX_DATAGRAM_SIZE - FRAGMENT_HEADER_SIZE));
+                    let fragment = Fragment {
+                        sid: *sid,
+                        mid,
+                        length: *length,
+                        offset: *offset,
+                        data: chunk,
+                    };
+                    *offset += fragment.data.len() as u64;
+                    self.drain
+                        .send(QuicDataFormat::with_unreliable(fragment))
+                        .await?;
+                }
+                if offset >= length {
+                    self.unreliable_messages.remove(&mid);
+                }
+            },
+            _ => return Err(ProtocolError::Violated),
+        }
+        Ok(())
+    }
 }
 
 impl<S> QuicRecvProtocol<S>
@@ -137,11 +307,13 @@ where
     pub fn new(sink: S, metrics: ProtocolMetricCache) -> Self {
         Self {
             main_buffer: BytesMut::new(),
-            unreliable_buffer: BytesMut::new(),
+            unreliable_datagrams: VecDeque::new(),
             reliable_buffers: SortedVec::default(),
             pending_reliable_buffers: vec![],
             itmsg_allocator: BytesMut::with_capacity(ALLOC_BLOCK),
             incoming: HashMap::new(),
+            unreliable_streams: HashMap::new(),
+            unreliable_incoming: BTreeMap::new(),
             sink,
             metrics,
         }
@@ -151,7 +323,10 @@ where
         let chunk = self.sink.recv().await?;
         let buffer = match chunk.stream {
             QuicDataFormatStream::Main => &mut self.main_buffer,
-            QuicDataFormatStream::Unreliable => &mut self.unreliable_buffer,
+            QuicDataFormatStream::Unreliable => {
+                self.unreliable_datagrams.push_back(chunk.data);
+                return Ok(chunk.stream);
+            },
             QuicDataFormatStream::Reliable(id) => {
                 match self.reliable_buffers.get_mut(&id) {
                     Some(buffer) => buffer,
@@ -174,6 +349,87 @@ where
         }
         Ok(chunk.stream)
     }
+
+    /// adds a datagram to its message, returns the message once it's complete
+    fn recv_fragment(
+        &mut self,
+        datagram: BytesMut,
+    ) -> Result<Option<ProtocolEvent>, ProtocolError> {
+        let fragment = Fragment::read_bytes(datagram).ok_or(ProtocolError::Violated)?;
+        let stream = match self.unreliable_streams.get_mut(&fragment.sid) {
+            Some(stream) => stream,
+            // the stream is not opened yet or already closed
+            None => return Ok(None),
+        };
+        if stream.ordered && stream.last_mid.map_or(false, |last| fragment.mid <= last) {
+            // a newer message was delivered already
+            return Ok(None);
+        }
+
+        self.metrics.rdata_frames_b(fragment.data.len() as u64);
+        let m = match self.unreliable_incoming.entry(fragment.mid) {
+            btree_map::Entry::Occupied(occupied) => occupied.into_mut(),
+            btree_map::Entry::Vacant(vacant) => {
+                self.metrics.rmsg_ib(fragment.sid, fragment.length);
+                vacant.insert(IncomingFragments {
+                    sid: fragment.sid,
+                    length: fragment.length,
+                    received: 0,
+                    fragments: BTreeMap::new(),
+                })
+            },
+        };
+        if m.sid != fragment.sid || m.length != fragment.length {
+            return Err(ProtocolError::Violated);
+        }
+        if m.insert(fragment.offset, fragment.data).is_err() {
+            info!("protocol violation by remote side: overlapping fragments");
+            return Err(ProtocolError::Violated);
+        }
+
+        if !m.is_complete() {
+            while self.unreliable_incoming.len() > MAX_INCOMPLETE_MESSAGES
+                || self
+                    .unreliable_incoming
+                    .values()
+                    .map(|m| m.received)
+                    .sum::<u64>()
+                    > MAX_INCOMPLETE_BYTES
+            {
+                if let Some(&mid) = self.unreliable_incoming.keys().next() {
+                    if let Some(m) = self.unreliable_incoming.remove(&mid) {
+                        self.metrics
+                            .rmsg_ob(m.sid, RemoveReason::Dropped, m.received);
+                    }
+                }
+            }
+            return Ok(None);
+        }
+
+        let mid = fragment.mid;
+        let m = self
+            .unreliable_incoming
+            .remove(&mid)
+            .ok_or(ProtocolError::Violated)?;
+        if stream.ordered {
+            stream.last_mid = Some(mid);
+            // older incomplete messages of this stream are stale now
+            let metrics = &mut self.metrics;
+            self.unreliable_incoming.retain(|&old, old_m| {
+                let stale = old_m.sid == m.sid && old < mid;
+                if stale {
+                    metrics.rmsg_ob(old_m.sid, RemoveReason::Dropped, old_m.received);
+                }
+                !stale
+            });
+        }
+        self.metrics
+            .rmsg_ob(m.sid, RemoveReason::Finished, m.length);
+        Ok(Some(ProtocolEvent::Message {
+            sid: m.sid,
+            data: m.into_data().freeze(),
+        }))
+    }
 }
 
 #[async_trait]
@@ -202,6 +458,7 @@ where
                     self.notify_closing_streams.push(sid);
                 }
             },
+            ProtocolEvent::Ack { sid, count, delay } => self.store.ack(sid, count, delay),
             _ => {},
         }
     }
@@ -260,6 +517,12 @@ where
                 self.store.add(data, self.next_mid, sid);
                 self.next_mid += 1;
             },
+            ProtocolEvent::Ack { .. } => {
+                event.to_frame().write_bytes(&mut self.main_buffer);
+                self.drain
+                    .send(QuicDataFormat::with_main(&mut self.main_buffer))
+                    .await?;
+            },
         }
         Ok(())
     }
@@ -280,11 +543,7 @@ where
             }
             match self.reliable_buffers.get_mut(&sid) {
                 Some(buffer) => frame.write_bytes(buffer),
-                None => {
-                    self.drain
-                        .send(QuicDataFormat::with_unreliable(frame))
-                        .await?
-                },
+                None => self.send_unreliable(frame).await?,
             }
         }
         for (sid, buffer) in self.reliable_buffers.data.iter_mut() {
@@ -359,6 +618,11 @@ where
                         } => {
                             if is_reliable(&promises) {
                                 self.reliable_buffers.insert(sid, BytesMut::new());
+                            } else {
+                                self.unreliable_streams.insert(sid, UnreliableStream {
+                                    ordered: promises.contains(Promises::ORDERED),
+                                    last_mid: None,
+                                });
                             }
                             break 'outer Ok(ProtocolEvent::OpenStream {
                                 sid,
@@ -370,8 +634,14 @@ where
                         ITFrame::CloseStream { sid } => {
                             //FIXME: defer close!
                             //let _ = self.reliable_buffers.delete(sid); // if it was reliable
+                            if self.unreliable_streams.remove(&sid).is_some() {
+                                self.unreliable_incoming.retain(|_, m| m.sid != sid);
+                            }
                             break 'outer Ok(ProtocolEvent::CloseStream { sid });
                         },
+                        ITFrame::Ack { sid, count, delay } => {
+                            break 'outer Ok(ProtocolEvent::Ack { sid, count, delay });
+                        },
                         _ => break 'outer Err(ProtocolError::Violated),
                     };
                 },
@@ -409,15 +679,7 @@ where
                 self.reliable_buffers.insert(sid, buffer)
             }
 
-            let mut iter = self
-                .reliable_buffers
-                .data
-                .iter_mut()
-                .map(|(_, b)| (b, true))
-                .collect::<Vec<_>>();
-            iter.push((&mut self.unreliable_buffer, false));
-
-            for (buffer, reliable) in iter {
+            for (_, buffer) in self.reliable_buffers.data.iter_mut() {
                 loop {
                     match ITFrame::read_frame(buffer) {
                         Ok(Some(frame)) => {
@@ -434,17 +696,12 @@ where
                                     let m = match self.incoming.get_mut(&mid) {
                                         Some(m) => m,
                                         None => {
-                                            if reliable {
-                                                info!(
-                                                    ?mid,
-                                                    "protocol violation by remote side: send Data \
-                                                     before Header"
-                                                );
-                                                break 'outer Err(ProtocolError::Violated);
-                                            } else {
-                                                //TODO: cleanup old messages from time to time
-                                                continue;
-                                            }
+                                            info!(
+                                                ?mid,
+                                                "protocol violation by remote side: send Data \
+                                                 before Header"
+                                            );
+                                            break 'outer Err(ProtocolError::Violated);
                                         },
                                     };
                                     m.data.extend_from_slice(&data);
@@ -474,6 +731,12 @@ where
                 }
             }
 
+            while let Some(datagram) = self.unreliable_datagrams.pop_front() {
+                if let Some(event) = self.recv_fragment(datagram)? {
+                    break 'outer Ok(event);
+                }
+            }
+
             self.recv_into_stream().await?;
         }
     }
@@ -599,8 +862,8 @@ mod tests {
         error::ProtocolError,
         frame::OTFrame,
         metrics::{ProtocolMetricCache, ProtocolMetrics, RemoveReason},
-        quic::{test_utils::*, QuicDataFormat},
-        types::{Pid, Promises, Sid, STREAM_ID_OFFSET1, STREAM_ID_OFFSET2},
+        quic::{test_utils::*, Fragment, QuicDataFormat},
+        types::{Capabilities, Pid, Promises, Sid, STREAM_ID_OFFSET1, STREAM_ID_OFFSET2},
         InitProtocol, ProtocolEvent, RecvProtocol, SendProtocol,
     };
     use bytes::{Bytes, BytesMut};
@@ -609,11 +872,23 @@ mod tests {
     #[tokio::test]
     async fn handshake_all_good() {
         let [mut p1, mut p2] = quic_bound(10, 0.5, None);
-        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await });
-        let r2 = tokio::spawn(async move { p2.initialize(false, Pid::fake(3), 42).await });
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, Capabilities::all())
+                .await
+        });
+        let r2 = tokio::spawn(async move {
+            p2.initialize(false, Pid::fake(3), 42, Capabilities::all())
+                .await
+        });
         let (r1, r2) = tokio::join!(r1, r2);
-        assert_eq!(r1.unwrap(), Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42)));
-        assert_eq!(r2.unwrap(), Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337)));
+        assert_eq!(
+            r1.unwrap(),
+            Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42, Capabilities::all()))
+        );
+        assert_eq!(
+            r2.unwrap(),
+            Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337, Capabilities::all()))
+        );
     }
 
     #[tokio::test]
@@ -623,7 +898,7 @@ mod tests {
         let event = ProtocolEvent::OpenStream {
             sid: Sid::new(10),
             prio: 0u8,
-            promises: Promises::ORDERED,
+            promises: Promises::ORDERED | Promises::GUARANTEED_DELIVERY,
             guaranteed_bandwidth: 1_000_000,
         };
         s.send(event.clone()).await.unwrap();
@@ -638,7 +913,7 @@ mod tests {
         let event = ProtocolEvent::OpenStream {
             sid: Sid::new(10),
             prio: 3u8,
-            promises: Promises::ORDERED,
+            promises: Promises::ORDERED | Promises::GUARANTEED_DELIVERY,
             guaranteed_bandwidth: 1_000_000,
         };
         s.send(event).await.unwrap();
@@ -672,7 +947,7 @@ mod tests {
         let event = ProtocolEvent::OpenStream {
             sid,
             prio: 5u8,
-            promises: Promises::COMPRESSED | Promises::ORDERED,
+            promises: Promises::COMPRESSED | Promises::ORDERED | Promises::GUARANTEED_DELIVERY,
             guaranteed_bandwidth: 1_000_000,
         };
         s.send(event).await.unwrap();
@@ -699,7 +974,7 @@ mod tests {
         let event = ProtocolEvent::OpenStream {
             sid,
             prio: 5u8,
-            promises: Promises::COMPRESSED | Promises::ORDERED,
+            promises: Promises::COMPRESSED | Promises::ORDERED | Promises::GUARANTEED_DELIVERY,
             guaranteed_bandwidth: 0,
         };
         s.send(event).await.unwrap();
@@ -727,7 +1002,7 @@ mod tests {
         let event = ProtocolEvent::OpenStream {
             sid,
             prio: 5u8,
-            promises: Promises::COMPRESSED | Promises::ORDERED,
+            promises: Promises::COMPRESSED | Promises::ORDERED | Promises::GUARANTEED_DELIVERY,
             guaranteed_bandwidth: 0,
         };
         s.send(event).await.unwrap();
@@ -759,7 +1034,7 @@ mod tests {
         let event = ProtocolEvent::OpenStream {
             sid,
             prio: 5u8,
-            promises: Promises::COMPRESSED | Promises::ORDERED,
+            promises: Promises::COMPRESSED | Promises::ORDERED | Promises::GUARANTEED_DELIVERY,
             guaranteed_bandwidth: 0,
         };
         s.send(event).await.unwrap();
@@ -799,7 +1074,7 @@ mod tests {
         OTFrame::OpenStream {
             sid,
             prio: 5u8,
-            promises: Promises::COMPRESSED | Promises::ORDERED,
+            promises: Promises::COMPRESSED | Promises::ORDERED | Promises::GUARANTEED_DELIVERY,
             guaranteed_bandwidth: 1_000_000,
         }
         .write_bytes(&mut bytes);
@@ -877,7 +1152,7 @@ mod tests {
         let event = ProtocolEvent::OpenStream {
             sid: Sid::new(10),
             prio: 3u8,
-            promises: Promises::ORDERED,
+            promises: Promises::ORDERED | Promises::GUARANTEED_DELIVERY,
             guaranteed_bandwidth: 1_000_000,
         };
         p1.0.send(event).await.unwrap();
@@ -900,7 +1175,7 @@ mod tests {
         let event = ProtocolEvent::OpenStream {
             sid: Sid::new(10),
             prio: 3u8,
-            promises: Promises::ORDERED,
+            promises: Promises::ORDERED | Promises::GUARANTEED_DELIVERY,
             guaranteed_bandwidth: 1_000_000,
         };
         p1.0.send(event).await.unwrap();
@@ -956,4 +1231,137 @@ mod tests {
             assert_eq!(event, e);
         }
     }
+
+    #[tokio::test]
+    async fn unreliable_long_msg() {
+        let sid = Sid::new(1);
+        let [p1, p2] = quic_bound(1000, 0.0, None);
+        let (mut s, mut r) = (p1.0, p2.1);
+        let event = ProtocolEvent::OpenStream {
+            sid,
+            prio: 5u8,
+            promises: Promises::ORDERED,
+            guaranteed_bandwidth: 1_000_000,
+        };
+        s.send(event).await.unwrap();
+        let _ = r.recv().await.unwrap();
+        let event = ProtocolEvent::Message {
+            sid,
+            data: Bytes::from((0..50_000u32).map(|i| i as u8).collect::<Vec<_>>()),
+        };
+        s.send(event.clone()).await.unwrap();
+        s.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        let e = r.recv().await.unwrap();
+        assert_eq!(event, e);
+    }
+
+    fn fragment(sid: Sid, mid: u64, length: u64, offset: u64, data: &[u8]) -> QuicDataFormat {
+        QuicDataFormat::with_unreliable(Fragment {
+            sid,
+            mid,
+            length,
+            offset,
+            data: Bytes::copy_from_slice(data),
+        })
+    }
+
+    /// a recv side with the unreliable stream `sid` opened, fed by the sender
+    async fn unreliable_stream(
+        sid: Sid,
+        promises: Promises,
+    ) -> (
+        async_channel::Sender<QuicDataFormat>,
+        super::QuicRecvProtocol<QuicSink>,
+    ) {
+        let (s, r) = async_channel::bounded(10);
+        let m = ProtocolMetricCache::new("quic", Arc::new(ProtocolMetrics::new().unwrap()));
+        let mut r = super::QuicRecvProtocol::new(QuicSink { receiver: r }, m);
+
+        let mut bytes = BytesMut::new();
+        OTFrame::OpenStream {
+            sid,
+            prio: 5u8,
+            promises,
+            guaranteed_bandwidth: 0,
+        }
+        .write_bytes(&mut bytes);
+        s.send(QuicDataFormat::with_main(&mut bytes)).await.unwrap();
+        let e = r.recv().await.unwrap();
+        assert!(matches!(e, ProtocolEvent::OpenStream { .. }));
+        (s, r)
+    }
+
+    #[tokio::test]
+    async fn unreliable_reordered_fragments() {
+        let sid = Sid::new(1);
+        let (s, mut r) = unreliable_stream(sid, Promises::empty()).await;
+
+        s.send(fragment(sid, 7, 6, 3, b"def")).await.unwrap();
+        s.send(fragment(sid, 8, 2, 0, b"gh")).await.unwrap();
+        s.send(fragment(sid, 7, 6, 0, b"abc")).await.unwrap();
+        assert_eq!(r.recv().await.unwrap(), ProtocolEvent::Message {
+            sid,
+            data: Bytes::from_static(b"gh"),
+        });
+        // without `ORDERED` older messages are still delivered
+        assert_eq!(r.recv().await.unwrap(), ProtocolEvent::Message {
+            sid,
+            data: Bytes::from_static(b"abcdef"),
+        });
+    }
+
+    #[tokio::test]
+    async fn unreliable_ordered_drops_stale_msgs() {
+        let sid = Sid::new(1);
+        let (s, mut r) = unreliable_stream(sid, Promises::ORDERED).await;
+
+        // the 2nd half of mid 1 got lost
+        s.send(fragment(sid, 1, 4, 0, b"ab")).await.unwrap();
+        s.send(fragment(sid, 3, 2, 0, b"cd")).await.unwrap();
+        s.send(fragment(sid, 2, 2, 0, b"ef")).await.unwrap();
+        s.send(fragment(sid, 4, 2, 0, b"gh")).await.unwrap();
+        assert_eq!(r.recv().await.unwrap(), ProtocolEvent::Message {
+            sid,
+            data: Bytes::from_static(b"cd"),
+        });
+        assert_eq!(r.recv().await.unwrap(), ProtocolEvent::Message {
+            sid,
+            data: Bytes::from_static(b"gh"),
+        });
+    }
+
+    #[tokio::test]
+    async fn unreliable_duplicated_fragments() {
+        let sid = Sid::new(1);
+        let (s, mut r) = unreliable_stream(sid, Promises::empty()).await;
+
+        s.send(fragment(sid, 1, 6, 0, b"abc")).await.unwrap();
+        s.send(fragment(sid, 1, 6, 0, b"abc")).await.unwrap();
+        s.send(fragment(sid, 1, 6, 3, b"def")).await.unwrap();
+        assert_eq!(r.recv().await.unwrap(), ProtocolEvent::Message {
+            sid,
+            data: Bytes::from_static(b"abcdef"),
+        });
+    }
+
+    #[tokio::test]
+    async fn unreliable_overlapping_fragments() {
+        let sid = Sid::new(1);
+        let (s, mut r) = unreliable_stream(sid, Promises::empty()).await;
+
+        // would add up to the length while leaving a gap
+        s.send(fragment(sid, 1, 6, 0, b"abc")).await.unwrap();
+        s.send(fragment(sid, 1, 6, 1, b"bcd")).await.unwrap();
+        assert_eq!(r.recv().await, Err(ProtocolError::Violated));
+    }
+
+    #[tokio::test]
+    async fn unreliable_too_long_msg() {
+        let sid = Sid::new(1);
+        let (s, mut r) = unreliable_stream(sid, Promises::empty()).await;
+
+        let length = super::MAX_UNRELIABLE_LENGTH + 1;
+        s.send(fragment(sid, 1, length, 0, b"abc")).await.unwrap();
+        assert_eq!(r.recv().await, Err(ProtocolError::Violated));
+    }
 }
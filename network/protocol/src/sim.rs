@@ -0,0 +1,515 @@
+//! Simulates a bad network between the protocols and the actual I/O.
+//!
+//! [`SimDrain`] and [`SimSink`] wrap any [`UnreliableDrain`] and
+//! [`UnreliableSink`] and add latency, jitter, loss, duplicates, reordering and
+//! a bandwidth cap as described by a [`SimConfig`]. A tokio task per wrapper
+//! holds the data back till it is due, so a tokio runtime is required.
+//!
+//! [`UnreliableDrain`]: crate::UnreliableDrain
+//! [`UnreliableSink`]: crate::UnreliableSink
+use crate::{error::ProtocolError, UdpDataFormat, UnreliableDrain, UnreliableSink};
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{cmp::Ordering, collections::BinaryHeap, time::Duration};
+use tokio::{
+    select,
+    sync::mpsc,
+    time::{sleep_until, Instant},
+};
+
+/// Describes the simulated link, the default is a perfect one.
+/// Rates are probabilities in `0.0..=1.0` per chunk of data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimConfig {
+    pub latency: Duration,
+    /// additional random delay in `0..=jitter`
+    pub jitter: Duration,
+    pub drop_rate: f64,
+    pub duplicate_rate: f64,
+    /// data is held back for another `latency + jitter`, so later data
+    /// overtakes it
+    pub reorder_rate: f64,
+    /// in bytes per second, `0` means unlimited
+    pub bandwidth: u64,
+    /// the same seed results in the same decisions for the same traffic
+    pub seed: u64,
+}
+
+/// Data that can travel over a simulated link
+pub trait SimData: Clone + Send + 'static {
+    /// bytes counted against [`SimConfig::bandwidth`]. `None` for data that
+    /// never traveled over the network, it's passed on immediately.
+    fn sim_len(&self) -> Option<usize>;
+}
+
+/// Wraps a drain, see the [module docs](self)
+#[derive(Debug)]
+pub enum SimDrain<D: UnreliableDrain> {
+    Direct(D),
+    Simulated(mpsc::UnboundedSender<D::DataFormat>),
+}
+
+/// Wraps a sink, see the [module docs](self)
+#[derive(Debug)]
+pub enum SimSink<S: UnreliableSink> {
+    Direct(S),
+    Simulated(mpsc::UnboundedReceiver<Result<S::DataFormat, ProtocolError>>),
+}
+
+struct Link {
+    config: SimConfig,
+    /// stream based transports like TCP must not lose or reorder data
+    reliable: bool,
+    rng: StdRng,
+    /// previous data is still being transferred till then
+    busy_until: Instant,
+    last_arrival: Instant,
+}
+
+struct Pending<T> {
+    arrival: Instant,
+    seq: u64,
+    data: T,
+}
+
+/// Data in the order of their arrival
+struct Queue<T> {
+    pending: BinaryHeap<Pending<T>>,
+    next_seq: u64,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            drop_rate: 0.0,
+            duplicate_rate: 0.0,
+            reorder_rate: 0.0,
+            bandwidth: 0,
+            seed: 0,
+        }
+    }
+}
+
+impl SimData for BytesMut {
+    fn sim_len(&self) -> Option<usize> { Some(self.len()) }
+}
+
+impl SimData for Bytes {
+    fn sim_len(&self) -> Option<usize> { Some(self.len()) }
+}
+
+impl SimData for UdpDataFormat {
+    fn sim_len(&self) -> Option<usize> {
+        match self {
+            UdpDataFormat::Datagram(data) => Some(data.len()),
+            UdpDataFormat::Idle => None,
+        }
+    }
+}
+
+impl Link {
+    fn new(config: SimConfig, reliable: bool) -> Self {
+        let now = Instant::now();
+        Self {
+            rng: StdRng::seed_from_u64(config.seed),
+            config,
+            reliable,
+            busy_until: now,
+            last_arrival: now,
+        }
+    }
+
+    fn chance(&mut self, rate: f64) -> bool { rate > 0.0 && self.rng.gen_bool(rate.min(1.0)) }
+
+    /// returns the arrival of every copy of the data, none if it got lost
+    fn arrivals(&mut self, len: usize) -> Vec<Instant> {
+        let now = Instant::now();
+        self.busy_until = self.busy_until.max(now);
+        if self.config.bandwidth > 0 {
+            self.busy_until += Duration::from_secs_f64(len as f64 / self.config.bandwidth as f64);
+        }
+        let lossy = !self.reliable;
+        if lossy && self.chance(self.config.drop_rate) {
+            return vec![];
+        }
+        let copies = if lossy && self.chance(self.config.duplicate_rate) {
+            2
+        } else {
+            1
+        };
+        (0..copies)
+            .map(|_| {
+                let mut delay = self.config.latency + self.config.jitter.mul_f64(self.rng.gen());
+                if lossy && self.chance(self.config.reorder_rate) {
+                    delay += self.config.latency + self.config.jitter;
+                }
+                let mut arrival = self.busy_until + delay;
+                if self.reliable {
+                    arrival = arrival.max(self.last_arrival);
+                    self.last_arrival = arrival;
+                }
+                arrival
+            })
+            .collect()
+    }
+}
+
+impl<T> PartialEq for Pending<T> {
+    fn eq(&self, other: &Self) -> bool { self.cmp(other) == Ordering::Equal }
+}
+
+impl<T> Eq for Pending<T> {}
+
+impl<T> PartialOrd for Pending<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl<T> Ord for Pending<T> {
+    /// reversed, so the `BinaryHeap` returns the earliest arrival first
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.arrival, other.seq).cmp(&(self.arrival, self.seq))
+    }
+}
+
+impl<T: SimData> Queue<T> {
+    fn new() -> Self {
+        Self {
+            pending: BinaryHeap::new(),
+            next_seq: 0,
+        }
+    }
+
+    fn push(&mut self, link: &mut Link, data: T) {
+        let arrivals = match data.sim_len() {
+            Some(len) => link.arrivals(len),
+            None => vec![Instant::now()],
+        };
+        for arrival in arrivals {
+            self.pending.push(Pending {
+                arrival,
+                seq: self.next_seq,
+                data: data.clone(),
+            });
+            self.next_seq += 1;
+        }
+    }
+
+    fn is_empty(&self) -> bool { self.pending.is_empty() }
+
+    /// when the next data arrives, far in the future if there is none
+    fn next_arrival(&self) -> Instant {
+        self.pending
+            .peek()
+            .map(|p| p.arrival)
+            .unwrap_or_else(|| Instant::now() + Duration::from_secs(3600))
+    }
+
+    fn pop_arrived(&mut self) -> Vec<T> {
+        let now = Instant::now();
+        let mut arrived = vec![];
+        while self.pending.peek().map_or(false, |p| p.arrival <= now) {
+            arrived.push(self.pending.pop().unwrap().data);
+        }
+        arrived
+    }
+}
+
+impl<D> SimDrain<D>
+where
+    D: UnreliableDrain + 'static,
+    D::DataFormat: SimData,
+{
+    /// `reliable` keeps the order and never loses data, as stream based
+    /// transports like TCP require. Errors of `drain` are returned by the
+    /// next `send` after them.
+    pub fn new(drain: D, config: SimConfig, reliable: bool) -> Self {
+        let (data_s, data_r) = mpsc::unbounded_channel();
+        tokio::spawn(Self::run(drain, Link::new(config, reliable), data_r));
+        SimDrain::Simulated(data_s)
+    }
+
+    async fn run(mut drain: D, mut link: Link, mut data_r: mpsc::UnboundedReceiver<D::DataFormat>) {
+        let mut queue = Queue::new();
+        let mut open = true;
+        while open || !queue.is_empty() {
+            select! {
+                data = data_r.recv(), if open => match data {
+                    Some(data) => queue.push(&mut link, data),
+                    None => open = false,
+                },
+                _ = sleep_until(queue.next_arrival()), if !queue.is_empty() => {
+                    for data in queue.pop_arrived() {
+                        if drain.send(data).await.is_err() {
+                            return;
+                        }
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl<S> SimSink<S>
+where
+    S: UnreliableSink + 'static,
+    S::DataFormat: SimData,
+{
+    /// see [`SimDrain::new`], the `recv` of `sink` MUST be cancel safe
+    pub fn new(sink: S, config: SimConfig, reliable: bool) -> Self {
+        let (data_s, data_r) = mpsc::unbounded_channel();
+        tokio::spawn(Self::run(sink, Link::new(config, reliable), data_s));
+        SimSink::Simulated(data_r)
+    }
+
+    async fn run(
+        mut sink: S,
+        mut link: Link,
+        data_s: mpsc::UnboundedSender<Result<S::DataFormat, ProtocolError>>,
+    ) {
+        let mut queue = Queue::new();
+        let mut error = None;
+        while error.is_none() || !queue.is_empty() {
+            select! {
+                data = sink.recv(), if error.is_none() => match data {
+                    Ok(data) => queue.push(&mut link, data),
+                    Err(e) => error = Some(e),
+                },
+                _ = sleep_until(queue.next_arrival()), if !queue.is_empty() => {
+                    for data in queue.pop_arrived() {
+                        if data_s.send(Ok(data)).is_err() {
+                            return;
+                        }
+                    }
+                },
+                _ = data_s.closed() => return,
+            }
+        }
+        if let Some(e) = error {
+            let _ = data_s.send(Err(e));
+        }
+    }
+}
+
+impl<D: UnreliableDrain + Clone> Clone for SimDrain<D> {
+    fn clone(&self) -> Self {
+        match self {
+            SimDrain::Direct(drain) => SimDrain::Direct(drain.clone()),
+            SimDrain::Simulated(data_s) => SimDrain::Simulated(data_s.clone()),
+        }
+    }
+}
+
+#[async_trait]
+impl<D> UnreliableDrain for SimDrain<D>
+where
+    D: UnreliableDrain,
+    D::DataFormat: Send,
+{
+    type DataFormat = D::DataFormat;
+
+    async fn send(&mut self, data: Self::DataFormat) -> Result<(), ProtocolError> {
+        match self {
+            SimDrain::Direct(drain) => drain.send(data).await,
+            SimDrain::Simulated(data_s) => data_s.send(data).map_err(|_| ProtocolError::Closed),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> UnreliableSink for SimSink<S>
+where
+    S: UnreliableSink,
+    S::DataFormat: Send,
+{
+    type DataFormat = S::DataFormat;
+
+    async fn recv(&mut self) -> Result<Self::DataFormat, ProtocolError> {
+        match self {
+            SimSink::Direct(sink) => sink.recv().await,
+            SimSink::Simulated(data_r) => data_r.recv().await.unwrap_or(Err(ProtocolError::Closed)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        metrics::{ProtocolMetricCache, ProtocolMetrics},
+        tcp::test_utils::*,
+        types::{Promises, Sid},
+        udp::test_utils::*,
+        ProtocolEvent, RecvProtocol, SendProtocol, TcpRecvProtocol, TcpSendProtocol,
+        UdpRecvProtocol, UdpSendProtocol,
+    };
+    use std::sync::{atomic::AtomicUsize, Arc};
+
+    fn lossy() -> SimConfig {
+        SimConfig {
+            latency: Duration::from_millis(20),
+            jitter: Duration::from_millis(10),
+            drop_rate: 0.1,
+            duplicate_rate: 0.05,
+            reorder_rate: 0.05,
+            bandwidth: 10_000_000,
+            seed: 42,
+        }
+    }
+
+    fn metrics(name: &str) -> ProtocolMetricCache {
+        ProtocolMetricCache::new(name, Arc::new(ProtocolMetrics::new().unwrap()))
+    }
+
+    /// send side of p1 and recv side of p2 over a simulated link
+    fn tcp_sim(
+        config: SimConfig,
+    ) -> (
+        TcpSendProtocol<SimDrain<TcpDrain>>,
+        TcpRecvProtocol<SimSink<TcpSink>>,
+    ) {
+        let (sender, receiver) = async_channel::bounded(10);
+        let m = metrics("tcp");
+        (
+            TcpSendProtocol::new(
+                SimDrain::new(TcpDrain { sender }, config.clone(), true),
+                m.clone(),
+            ),
+            TcpRecvProtocol::new(SimSink::new(TcpSink { receiver }, config, true), m),
+        )
+    }
+
+    /// both sides of a udp channel, lossy in both directions
+    fn udp_sim(
+        config: SimConfig,
+    ) -> [(
+        UdpSendProtocol<SimDrain<UdpDrain>>,
+        UdpRecvProtocol<SimDrain<UdpDrain>, SimSink<UdpSink>>,
+    ); 2] {
+        let (s1, r1) = async_channel::unbounded();
+        let (s2, r2) = async_channel::unbounded();
+        let m = metrics("udp");
+        let protocols = |sender, receiver, seed| {
+            let config = SimConfig {
+                seed,
+                ..config.clone()
+            };
+            let drain = UdpDrain {
+                sender,
+                lose_every: 0,
+                count: Arc::new(AtomicUsize::new(0)),
+            };
+            let drain = SimDrain::new(drain, config.clone(), false);
+            let sink = SimSink::new(UdpSink { receiver }, config, false);
+            let sp = UdpSendProtocol::new(drain.clone(), m.clone());
+            let rp = UdpRecvProtocol::new(drain, sink, &sp, m.clone());
+            (sp, rp)
+        };
+        [protocols(s1, r2, 1), protocols(s2, r1, 2)]
+    }
+
+    fn messages(sid: Sid) -> Vec<ProtocolEvent> {
+        (0..10u8)
+            .map(|i| ProtocolEvent::Message {
+                sid,
+                data: Bytes::from(vec![i; 5_000]),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_same_decisions() {
+        let mut a = Link::new(lossy(), false);
+        let mut b = Link::new(lossy(), false);
+        let copies = |l: &mut Link| (0..100).map(|_| l.arrivals(100).len()).collect::<Vec<_>>();
+        assert_eq!(copies(&mut a), copies(&mut b));
+    }
+
+    #[test]
+    fn reliable_link_keeps_order() {
+        let mut link = Link::new(lossy(), true);
+        let arrivals = (0..100).map(|_| link.arrivals(100)).collect::<Vec<_>>();
+        assert!(arrivals.iter().all(|a| a.len() == 1));
+        assert!(arrivals.windows(2).all(|w| w[0][0] <= w[1][0]));
+    }
+
+    #[tokio::test]
+    async fn bandwidth_delays_data() {
+        let config = SimConfig {
+            bandwidth: 1000,
+            ..SimConfig::default()
+        };
+        let mut link = Link::new(config, true);
+        let start = Instant::now();
+        link.arrivals(500);
+        let second = link.arrivals(500)[0];
+        assert!(second >= start + Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn tcp_over_lagged_link() {
+        let (mut s, mut r) = tcp_sim(lossy());
+        let sid = Sid::new(1);
+        let start = Instant::now();
+        s.send(ProtocolEvent::OpenStream {
+            sid,
+            prio: 5u8,
+            promises: Promises::ORDERED,
+            guaranteed_bandwidth: 1_000_000,
+        })
+        .await
+        .unwrap();
+        let events = messages(sid);
+        for event in events.iter() {
+            s.send(event.clone()).await.unwrap();
+        }
+        s.flush(1_000_000_000, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(matches!(
+            r.recv().await.unwrap(),
+            ProtocolEvent::OpenStream { .. }
+        ));
+        assert!(start.elapsed() >= lossy().latency);
+        for event in events {
+            assert_eq!(r.recv().await.unwrap(), event);
+        }
+    }
+
+    #[tokio::test]
+    async fn udp_over_lossy_link() {
+        let [p1, p2] = udp_sim(lossy());
+        let (mut s, mut r) = (p1.0, p2.1);
+        spawn_recv_sim(p1.1);
+        let sid = Sid::new(1);
+        s.send(ProtocolEvent::OpenStream {
+            sid,
+            prio: 5u8,
+            promises: Promises::ORDERED | Promises::GUARANTEED_DELIVERY,
+            guaranteed_bandwidth: 1_000_000,
+        })
+        .await
+        .unwrap();
+        let events = messages(sid);
+        for event in events.iter() {
+            s.send(event.clone()).await.unwrap();
+        }
+        s.flush(1_000_000_000, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(matches!(
+            r.recv().await.unwrap(),
+            ProtocolEvent::OpenStream { .. }
+        ));
+        for event in events {
+            assert_eq!(r.recv().await.unwrap(), event);
+        }
+    }
+
+    /// keeps a recv side running, so it answers the requests of the remote
+    fn spawn_recv_sim(mut r: UdpRecvProtocol<SimDrain<UdpDrain>, SimSink<UdpSink>>) {
+        tokio::spawn(async move { while RecvProtocol::recv(&mut r).await.is_ok() {} });
+    }
+}
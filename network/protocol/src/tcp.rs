@@ -1,4 +1,5 @@
 use crate::{
+    crypto::{Cipher, Identity, PublicIdentity},
     error::ProtocolError,
     event::ProtocolEvent,
     frame::{ITFrame, InitFrame, OTFrame},
@@ -12,7 +13,10 @@ use crate::{
 use async_trait::async_trait;
 use bytes::BytesMut;
 use hashbrown::HashMap;
-use std::time::{Duration, Instant};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tracing::info;
 #[cfg(feature = "trace_pedantic")]
 use tracing::trace;
@@ -32,6 +36,10 @@ where
     notify_closing_streams: Vec<Sid>,
     pending_shutdown: bool,
     drain: D,
+    cipher: Option<Cipher>,
+    identity: Option<Arc<Identity>>,
+    pinned_identity: Option<PublicIdentity>,
+    remote_identity: Option<PublicIdentity>,
     #[allow(dead_code)]
     last: Instant,
     metrics: ProtocolMetricCache,
@@ -49,6 +57,7 @@ where
     itmsg_allocator: BytesMut,
     incoming: HashMap<Mid, ITMessage>,
     sink: S,
+    cipher: Option<Cipher>,
     metrics: ProtocolMetricCache,
 }
 
@@ -65,11 +74,27 @@ where
             notify_closing_streams: vec![],
             pending_shutdown: false,
             drain,
+            cipher: None,
+            identity: None,
+            pinned_identity: None,
+            remote_identity: None,
             last: Instant::now(),
             metrics,
         }
     }
 
+    /// see [`ReliableDrain::identity`]
+    pub fn set_identity(&mut self, identity: Option<Arc<Identity>>) { self.identity = identity; }
+
+    /// see [`ReliableDrain::pinned_identity`]
+    pub fn pin_identity(&mut self, identity: Option<PublicIdentity>) {
+        self.pinned_identity = identity;
+    }
+
+    /// the identity the listener proved in the handshake, `None` when
+    /// listening, see [`ReliableDrain::set_remote_identity`]
+    pub fn remote_identity(&self) -> Option<PublicIdentity> { self.remote_identity }
+
     /// returns all promises that this Protocol can take care of
     /// If you open a Stream anyway, unsupported promises are ignored.
     pub fn supported_promises() -> Promises {
@@ -77,6 +102,8 @@ where
             | Promises::CONSISTENCY
             | Promises::GUARANTEED_DELIVERY
             | Promises::COMPRESSED
+            | Promises::COMPRESSED_ZSTD
+            | Promises::ENCRYPTED /*with the keys exchanged in the handshake*/
     }
 }
 
@@ -90,6 +117,7 @@ where
             itmsg_allocator: BytesMut::with_capacity(ALLOC_BLOCK),
             incoming: HashMap::new(),
             sink,
+            cipher: None,
             metrics,
         }
     }
@@ -118,6 +146,7 @@ where
                     self.notify_closing_streams.push(sid);
                 }
             },
+            ProtocolEvent::Ack { sid, count, delay } => self.store.ack(sid, count, delay),
             _ => {},
         }
     }
@@ -132,6 +161,10 @@ where
                 promises,
                 guaranteed_bandwidth,
             } => {
+                if promises.contains(Promises::ENCRYPTED) && self.cipher.is_none() {
+                    info!(?sid, "no key was exchanged, can't open an encrypted stream");
+                    return Err(ProtocolError::Violated);
+                }
                 self.store
                     .open_stream(sid, prio, promises, guaranteed_bandwidth);
                 event.to_frame().write_bytes(&mut self.buffer);
@@ -162,6 +195,10 @@ where
                 self.store.add(data, self.next_mid, sid);
                 self.next_mid += 1;
             },
+            ProtocolEvent::Ack { .. } => {
+                event.to_frame().write_bytes(&mut self.buffer);
+                self.drain.send(self.buffer.split()).await?;
+            },
         }
         Ok(())
     }
@@ -175,12 +212,29 @@ where
         self.buffer.reserve(total_bytes as usize);
         let mut data_frames = 0;
         let mut data_bandwidth = 0;
-        for (_, frame) in frames {
+        for (sid, frame) in frames {
             if let OTFrame::Data { mid: _, data } = &frame {
                 data_bandwidth += data.len();
                 data_frames += 1;
             }
-            frame.write_bytes(&mut self.buffer);
+            match frame {
+                OTFrame::Data { mid, data }
+                    if self
+                        .store
+                        .promises(sid)
+                        .map_or(false, |p| p.contains(Promises::ENCRYPTED)) =>
+                {
+                    let cipher = self.cipher.as_mut().ok_or(ProtocolError::Violated)?;
+                    let mut data = BytesMut::from(&data[..]);
+                    cipher.seal(&mut data)?;
+                    OTFrame::SealedData {
+                        mid,
+                        data: data.freeze(),
+                    }
+                    .write_bytes(&mut self.buffer);
+                },
+                frame => frame.write_bytes(&mut self.buffer),
+            }
         }
         self.drain.send(self.buffer.split()).await?;
         self.metrics
@@ -235,8 +289,28 @@ where
                     Ok(Some(frame)) => {
                         #[cfg(feature = "trace_pedantic")]
                         trace!(?frame, "recv");
+                        let frame = match (frame, &mut self.cipher) {
+                            (ITFrame::SealedData { mid, mut data }, Some(cipher)) => {
+                                cipher.open(&mut data)?;
+                                ITFrame::Data { mid, data }
+                            },
+                            (ITFrame::SealedData { .. }, None) => {
+                                break 'outer Err(ProtocolError::Violated);
+                            },
+                            (frame, _) => frame,
+                        };
                         match frame {
                             ITFrame::Shutdown => break 'outer Ok(ProtocolEvent::Shutdown),
+                            ITFrame::OpenStream { sid, promises, .. }
+                                if promises.contains(Promises::ENCRYPTED)
+                                    && self.cipher.is_none() =>
+                            {
+                                info!(
+                                    ?sid,
+                                    "remote opened an encrypted stream without a key exchange"
+                                );
+                                break 'outer Err(ProtocolError::Violated);
+                            },
                             ITFrame::OpenStream {
                                 sid,
                                 prio,
@@ -253,6 +327,9 @@ where
                             ITFrame::CloseStream { sid } => {
                                 break 'outer Ok(ProtocolEvent::CloseStream { sid });
                             },
+                            ITFrame::Ack { sid, count, delay } => {
+                                break 'outer Ok(ProtocolEvent::Ack { sid, count, delay });
+                            },
                             ITFrame::DataHeader { sid, mid, length } => {
                                 let m = ITMessage::new(sid, length, &mut self.itmsg_allocator);
                                 self.metrics.rmsg_ib(sid, length);
@@ -286,6 +363,7 @@ where
                                     });
                                 }
                             },
+                            ITFrame::SealedData { .. } => unreachable!("opened above"),
                         };
                     },
                     Ok(None) => break, //inner => read more data
@@ -310,8 +388,25 @@ where
     async fn send(&mut self, frame: InitFrame) -> Result<(), ProtocolError> {
         let mut buffer = BytesMut::with_capacity(500);
         frame.write_bytes(&mut buffer);
+        if let Some(cipher) = &mut self.cipher {
+            cipher.seal(&mut buffer)?;
+            let sealed = buffer.split().to_vec();
+            InitFrame::Sealed(sealed).write_bytes(&mut buffer);
+        }
         self.drain.send(buffer).await
     }
+
+    fn key_exchange(&self) -> bool { true }
+
+    fn identity(&self) -> Option<&Identity> { self.identity.as_deref() }
+
+    fn pinned_identity(&self) -> Option<PublicIdentity> { self.pinned_identity }
+
+    fn set_remote_identity(&mut self, identity: PublicIdentity) {
+        self.remote_identity = Some(identity);
+    }
+
+    fn set_cipher(&mut self, cipher: Cipher) { self.cipher = Some(cipher); }
 }
 
 #[async_trait]
@@ -320,19 +415,33 @@ where
     S: UnreliableSink<DataFormat = BytesMut>,
 {
     async fn recv(&mut self) -> Result<InitFrame, ProtocolError> {
-        while self.buffer.len() < 100 {
+        // the remote might have send multiple frames at once
+        let mut frame = InitFrame::read_frame(&mut self.buffer);
+        while frame.is_none() && self.buffer.len() < 100 {
             let chunk = self.sink.recv().await?;
             self.buffer.extend_from_slice(&chunk);
-            if let Some(frame) = InitFrame::read_frame(&mut self.buffer) {
-                return Ok(frame);
-            }
+            frame = InitFrame::read_frame(&mut self.buffer);
+        }
+        match (frame, &mut self.cipher) {
+            (Some(InitFrame::Sealed(data)), Some(cipher)) => {
+                let mut data = BytesMut::from(&data[..]);
+                cipher.open(&mut data)?;
+                InitFrame::read_frame(&mut data).ok_or(ProtocolError::Violated)
+            },
+            // don't allow the remote to skip the encryption after the key exchange
+            (Some(InitFrame::Init { .. }), Some(_)) | (Some(InitFrame::Sealed(_)), None) => {
+                Err(ProtocolError::Violated)
+            },
+            (Some(frame), _) => Ok(frame),
+            (None, _) => Err(ProtocolError::Violated),
         }
-        Err(ProtocolError::Violated)
     }
+
+    fn set_cipher(&mut self, cipher: Cipher) { self.cipher = Some(cipher); }
 }
 
 #[cfg(test)]
-mod test_utils {
+pub(crate) mod test_utils {
     //TCP protocol based on Channel
     use super::*;
     use crate::metrics::{ProtocolMetricCache, ProtocolMetrics};
@@ -397,11 +506,12 @@ mod test_utils {
 #[cfg(test)]
 mod tests {
     use crate::{
-        error::ProtocolError,
+        crypto::Identity,
+        error::{InitProtocolError, ProtocolError},
         frame::OTFrame,
         metrics::{ProtocolMetricCache, ProtocolMetrics, RemoveReason},
         tcp::test_utils::*,
-        types::{Pid, Promises, Sid, STREAM_ID_OFFSET1, STREAM_ID_OFFSET2},
+        types::{Capabilities, Pid, Promises, Sid, STREAM_ID_OFFSET1, STREAM_ID_OFFSET2},
         InitProtocol, ProtocolEvent, RecvProtocol, SendProtocol,
     };
     use bytes::{Bytes, BytesMut};
@@ -410,11 +520,190 @@ mod tests {
     #[tokio::test]
     async fn handshake_all_good() {
         let [mut p1, mut p2] = tcp_bound(10, None);
-        let r1 = tokio::spawn(async move { p1.initialize(true, Pid::fake(2), 1337).await });
-        let r2 = tokio::spawn(async move { p2.initialize(false, Pid::fake(3), 42).await });
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, Capabilities::all())
+                .await
+        });
+        let r2 = tokio::spawn(async move {
+            p2.initialize(false, Pid::fake(3), 42, Capabilities::all())
+                .await
+        });
+        let (r1, r2) = tokio::join!(r1, r2);
+        assert_eq!(
+            r1.unwrap(),
+            Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42, Capabilities::all()))
+        );
+        assert_eq!(
+            r2.unwrap(),
+            Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337, Capabilities::all()))
+        );
+    }
+
+    #[tokio::test]
+    async fn handshake_encryption_stripped() {
+        let [mut p1, mut p2] = tcp_bound(10, None);
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, Capabilities::all())
+                .await
+        });
+        let r2 = tokio::spawn(async move {
+            // what the first side receives if the ENCRYPTION is removed on the way
+            let stripped = Capabilities::all() - Capabilities::ENCRYPTION;
+            p2.initialize(false, Pid::fake(3), 42, stripped).await
+        });
         let (r1, r2) = tokio::join!(r1, r2);
-        assert_eq!(r1.unwrap(), Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42)));
-        assert_eq!(r2.unwrap(), Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337)));
+        assert_eq!(r1.unwrap(), Err(InitProtocolError::Closed));
+        assert_eq!(r2.unwrap(), Err(InitProtocolError::Closed));
+    }
+
+    #[tokio::test]
+    async fn handshake_pinned_identity() {
+        let [mut p1, mut p2] = tcp_bound(10, None);
+        let identity = Arc::new(Identity::generate());
+        p1.0.set_identity(Some(Arc::clone(&identity)));
+        p2.0.pin_identity(Some(identity.public_identity()));
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, Capabilities::all())
+                .await
+        });
+        let r2 = tokio::spawn(async move {
+            p2.initialize(false, Pid::fake(3), 42, Capabilities::all())
+                .await
+        });
+        let (r1, r2) = tokio::join!(r1, r2);
+        assert_eq!(
+            r1.unwrap(),
+            Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42, Capabilities::all()))
+        );
+        assert_eq!(
+            r2.unwrap(),
+            Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337, Capabilities::all()))
+        );
+    }
+
+    #[tokio::test]
+    async fn handshake_remote_identity() {
+        let [mut p1, mut p2] = tcp_bound(10, None);
+        let identity = Arc::new(Identity::generate());
+        p1.0.set_identity(Some(Arc::clone(&identity)));
+        let r1 = tokio::spawn(async move {
+            let r = p1
+                .initialize(true, Pid::fake(2), 1337, Capabilities::all())
+                .await;
+            (r, p1.0.remote_identity())
+        });
+        let r2 = tokio::spawn(async move {
+            let r = p2
+                .initialize(false, Pid::fake(3), 42, Capabilities::all())
+                .await;
+            (r, p2.0.remote_identity())
+        });
+        let (r1, r2) = tokio::join!(r1, r2);
+        let (r1, r2) = (r1.unwrap(), r2.unwrap());
+        assert!(r1.0.is_ok());
+        assert!(r2.0.is_ok());
+        // only the connecting side learns the identity of the listener
+        assert_eq!(r1.1, None);
+        assert_eq!(r2.1, Some(identity.public_identity()));
+    }
+
+    #[tokio::test]
+    async fn handshake_wrong_identity() {
+        let [mut p1, mut p2] = tcp_bound(10, None);
+        let identity = Arc::new(Identity::generate());
+        p1.0.set_identity(Some(Arc::clone(&identity)));
+        p2.0.pin_identity(Some(Identity::generate().public_identity()));
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, Capabilities::all())
+                .await
+        });
+        let r2 = tokio::spawn(async move {
+            p2.initialize(false, Pid::fake(3), 42, Capabilities::all())
+                .await
+        });
+        let (r1, r2) = tokio::join!(r1, r2);
+        assert_eq!(r1.unwrap(), Err(InitProtocolError::Closed));
+        assert_eq!(
+            r2.unwrap(),
+            Err(InitProtocolError::WrongIdentity(Some(
+                identity.public_identity()
+            )))
+        );
+    }
+
+    #[tokio::test]
+    async fn handshake_pinned_identity_without_encryption() {
+        let [mut p1, mut p2] = tcp_bound(10, None);
+        p2.0.pin_identity(Some(Identity::generate().public_identity()));
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, Capabilities::UNRELIABLE)
+                .await
+        });
+        let r2 = tokio::spawn(async move {
+            p2.initialize(false, Pid::fake(3), 42, Capabilities::UNRELIABLE)
+                .await
+        });
+        let (r1, r2) = tokio::join!(r1, r2);
+        assert_eq!(r1.unwrap(), Err(InitProtocolError::Closed));
+        assert_eq!(r2.unwrap(), Err(InitProtocolError::WrongIdentity(None)));
+    }
+
+    #[tokio::test]
+    async fn encrypted_stream() {
+        let [mut p1, mut p2] = tcp_bound(10, None);
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, Capabilities::all())
+                .await
+                .unwrap();
+            p1
+        });
+        let r2 = tokio::spawn(async move {
+            p2.initialize(false, Pid::fake(3), 42, Capabilities::all())
+                .await
+                .unwrap();
+            p2
+        });
+        let (r1, r2) = tokio::join!(r1, r2);
+        let (mut s, mut r) = (r1.unwrap().0, r2.unwrap().1);
+        let event = ProtocolEvent::OpenStream {
+            sid: Sid::new(10),
+            prio: 3u8,
+            promises: Promises::ORDERED | Promises::ENCRYPTED,
+            guaranteed_bandwidth: 1_000_000,
+        };
+        s.send(event).await.unwrap();
+        let _ = r.recv().await.unwrap();
+        let event = ProtocolEvent::Message {
+            sid: Sid::new(10),
+            data: Bytes::from(&[188u8; 2000][..]),
+        };
+        s.send(event.clone()).await.unwrap();
+        s.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        let e = r.recv().await.unwrap();
+        assert_eq!(event, e);
+    }
+
+    #[tokio::test]
+    async fn encrypted_stream_without_key_exchange() {
+        let [p1, p2] = tcp_bound(10, None);
+        let (mut s, mut r) = (p1.0, p2.1);
+        let event = ProtocolEvent::OpenStream {
+            sid: Sid::new(10),
+            prio: 3u8,
+            promises: Promises::ENCRYPTED,
+            guaranteed_bandwidth: 1_000_000,
+        };
+        assert_eq!(s.send(event).await, Err(ProtocolError::Violated));
+        let mut bytes = BytesMut::with_capacity(1500);
+        OTFrame::OpenStream {
+            sid: Sid::new(10),
+            prio: 3u8,
+            promises: Promises::ENCRYPTED,
+            guaranteed_bandwidth: 1_000_000,
+        }
+        .write_bytes(&mut bytes);
+        s.drain.sender.send(bytes).await.unwrap();
+        assert_eq!(r.recv().await, Err(ProtocolError::Violated));
     }
 
     #[tokio::test]
@@ -432,6 +721,20 @@ mod tests {
         assert_eq!(event, e);
     }
 
+    #[tokio::test]
+    async fn ack() {
+        let [p1, p2] = tcp_bound(10, None);
+        let (mut s, mut r) = (p1.0, p2.1);
+        let event = ProtocolEvent::Ack {
+            sid: Sid::new(10),
+            count: 42,
+            delay: Duration::from_millis(120),
+        };
+        s.send(event.clone()).await.unwrap();
+        let e = r.recv().await.unwrap();
+        assert_eq!(event, e);
+    }
+
     #[tokio::test]
     async fn send_short_msg() {
         let [p1, p2] = tcp_bound(10, None);
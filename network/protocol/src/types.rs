@@ -38,8 +38,39 @@ bitflags! {
         /// [`Stream`](crate::api::Stream)
         const COMPRESSED = 0b00001000;
         /// this will enable the internal encryption on this
-        /// [`Stream`](crate::api::Stream)
+        /// [`Stream`](crate::api::Stream), only supported on TCP and WebSocket.
+        /// Keys are exchanged ephemerally during the handshake, this protects
+        /// against eavesdropping. Only connections to a pinned identity are
+        /// protected against an active man in the middle, see
+        /// `Network::connect_pinned`
         const ENCRYPTED = 0b00010000;
+        /// compress a [`Promises::COMPRESSED`] [`Stream`](crate::api::Stream)
+        /// with zstd instead of lz4, see `Participant::open_compressed`
+        const COMPRESSED_ZSTD = 0b00100000;
+    }
+}
+
+bitflags! {
+    /// Optional features of the network protocol. Both sides send theirs
+    /// during the Handshake and only use the ones supported by both, so adding
+    /// a capability doesn't require a new [`VELOREN_NETWORK_VERSION`].
+    /// Unknown capabilities of a newer remote are ignored.
+    ///
+    /// [`VELOREN_NETWORK_VERSION`]: crate::VELOREN_NETWORK_VERSION
+    pub struct Capabilities: u64 {
+        /// lz4 compression of [`Promises::COMPRESSED`] streams, without it
+        /// streams are send uncompressed
+        const COMPRESSION_LZ4 = 0b00000001;
+        /// key exchange during the Handshake, required for
+        /// [`Promises::ENCRYPTED`] streams. On TCP and WebSocket the remote
+        /// MUST offer it too, so it can't be stripped on the way
+        const ENCRYPTION = 0b00000010;
+        /// streams without [`Promises::GUARANTEED_DELIVERY`] may lose messages,
+        /// without it all streams guarantee delivery
+        const UNRELIABLE = 0b00000100;
+        /// zstd compression of [`Promises::COMPRESSED_ZSTD`] streams, without
+        /// it they fall back to lz4
+        const COMPRESSION_ZSTD = 0b00001000;
     }
 }
 
@@ -48,8 +79,12 @@ impl Promises {
 }
 
 pub(crate) const VELOREN_MAGIC_NUMBER: [u8; 7] = *b"VELOREN";
-/// When this semver differs, 2 Networks can't communicate.
-pub const VELOREN_NETWORK_VERSION: [u32; 3] = [0, 6, 0];
+/// When the major version differs, 2 Networks can't communicate. Optional
+/// features are negotiated via [`Capabilities`] instead of a version bump.
+pub const VELOREN_NETWORK_VERSION: [u32; 3] = [0, 8, 0];
+/// First version that sends its [`Capabilities`] during the Handshake, older
+/// ones are refused.
+pub(crate) const CAPABILITIES_VERSION: [u32; 3] = [0, 8, 0];
 pub(crate) const STREAM_ID_OFFSET1: Sid = Sid::new(0);
 pub(crate) const STREAM_ID_OFFSET2: Sid = Sid::new(u64::MAX / 2);
 /// Maximal possible Prio to choose (for performance reasons)
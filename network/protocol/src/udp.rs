@@ -1,7 +1,10 @@
-// TODO: quick and dirty which activly waits for an ack!
 /*
 UDP protocol
 
+Every item (handshake frame, stream event or message) gets its own `Mid`. The
+receiver acknowledges each item once it is complete, the sender keeps it
+until then and resends whatever the receiver reports as missing.
+
 All Good Case:
 S --HEADER--> R
 S --DATA--> R
@@ -35,3 +38,1333 @@ S <--MISSING_DATA -- R
 S --DATA--> R
 S <--FINISHED-- R
 */
+use crate::{
+    error::ProtocolError,
+    event::ProtocolEvent,
+    frame::{ITFrame, InitFrame, OTFrame},
+    handshake::{ReliableDrain, ReliableSink},
+    metrics::{ProtocolMetricCache, RemoveReason},
+    prio::PrioManager,
+    types::{Bandwidth, Mid, Promises, Sid},
+    RecvProtocol, SendProtocol, UnreliableDrain, UnreliableSink,
+};
+use async_trait::async_trait;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::info;
+#[cfg(feature = "trace_pedantic")]
+use tracing::trace;
+
+const UDP_FRAME_PING: u8 = 1;
+const UDP_FRAME_INIT: u8 = 2;
+const UDP_FRAME_EVENT: u8 = 3;
+const UDP_FRAME_HEADER: u8 = 4;
+const UDP_FRAME_DATA: u8 = 5;
+const UDP_FRAME_STATUS: u8 = 6;
+const UDP_FRAME_FINISHED: u8 = 7;
+const UDP_FRAME_MISSING_HEADER: u8 = 8;
+const UDP_FRAME_MISSING_DATA: u8 = 9;
+
+/// A [`UnreliableSink`] of the [`UdpRecvProtocol`] MUST return
+/// [`UdpDataFormat::Idle`] when no datagram arrived within this time, as the
+/// protocol relies on it to resend lost data.
+///
+/// [`UnreliableSink`]: crate::UnreliableSink
+pub const UDP_SINK_TIMEOUT: Duration = Duration::from_millis(10);
+/// Frames are packed into a datagram as long as it stays below this size,
+/// a single frame is never split
+const MAX_DATAGRAM_SIZE: usize = 1450;
+/// Time an incomplete message waits before the missing parts are requested
+const MISSING_DELAY: Duration = Duration::from_millis(10);
+/// Time without FINISHED after which an item is resent or its STATUS is
+/// requested, also used to repeat requests for missing parts
+const RESEND_TIMEOUT: Duration = Duration::from_millis(200);
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(1);
+/// Without any datagram from the remote for this long the channel is closed
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_MISSING_RANGES: usize = 64;
+/// Items this far past the first undelivered one are dropped without being
+/// FINISHED, the remote keeps resending them till they fit in the window
+const RECV_WINDOW_ITEMS: u64 = 4096;
+/// Bytes of incomplete or undeliverable messages buffered per channel. DATA
+/// exceeding it is dropped and requested again later, except when it continues
+/// the first undelivered item, so the channel can always make progress.
+const RECV_WINDOW_BYTES: usize = 32 * 1024 * 1024;
+/// Counted on top of the data for each fragment received behind a gap, so
+/// tiny fragments can't be used to exceed the window
+const FRAGMENT_OVERHEAD: usize = 64;
+
+/// What a UDP sink hands over to the [`UdpRecvProtocol`]
+#[derive(Debug, Clone)]
+pub enum UdpDataFormat {
+    Datagram(BytesMut),
+    /// No datagram arrived within [`UDP_SINK_TIMEOUT`]
+    Idle,
+}
+
+/// Used for Communication between Channel <----(UDP)----> Channel
+#[derive(Debug, PartialEq, Clone)]
+enum UdpFrame {
+    /// Keeps the channel alive, the connecting side sends it first to get
+    /// known by the listener
+    Ping,
+    Init {
+        mid: Mid,
+        frame: Bytes,
+    },
+    /// A [`OTFrame`] not belonging to a message, e.g. `OpenStream`
+    Event {
+        mid: Mid,
+        frame: Bytes,
+    },
+    Header {
+        mid: Mid,
+        sid: Sid,
+        length: u64,
+    },
+    Data {
+        mid: Mid,
+        offset: u64,
+        data: Bytes,
+    },
+    Status {
+        mid: Mid,
+    },
+    Finished {
+        mid: Mid,
+    },
+    MissingHeader {
+        mid: Mid,
+    },
+    MissingData {
+        mid: Mid,
+        ranges: Vec<(u64, u64)>,
+    },
+}
+
+impl UdpFrame {
+    fn size(&self) -> usize {
+        1 + match self {
+            Self::Ping => 0,
+            Self::Init { frame, .. } | Self::Event { frame, .. } => 10 + frame.len(),
+            Self::Header { .. } => 24,
+            Self::Data { data, .. } => 18 + data.len(),
+            Self::Status { .. } | Self::Finished { .. } | Self::MissingHeader { .. } => 8,
+            Self::MissingData { ranges, .. } => 10 + 16 * ranges.len(),
+        }
+    }
+
+    fn write_bytes(self, bytes: &mut BytesMut) {
+        match self {
+            Self::Ping => bytes.put_u8(UDP_FRAME_PING),
+            Self::Init { mid, frame } => {
+                bytes.put_u8(UDP_FRAME_INIT);
+                bytes.put_u64_le(mid);
+                bytes.put_u16_le(frame.len() as u16);
+                bytes.put_slice(&frame);
+            },
+            Self::Event { mid, frame } => {
+                bytes.put_u8(UDP_FRAME_EVENT);
+                bytes.put_u64_le(mid);
+                bytes.put_u16_le(frame.len() as u16);
+                bytes.put_slice(&frame);
+            },
+            Self::Header { mid, sid, length } => {
+                bytes.put_u8(UDP_FRAME_HEADER);
+                bytes.put_u64_le(mid);
+                sid.to_bytes(bytes);
+                bytes.put_u64_le(length);
+            },
+            Self::Data { mid, offset, data } => {
+                bytes.put_u8(UDP_FRAME_DATA);
+                bytes.put_u64_le(mid);
+                bytes.put_u64_le(offset);
+                bytes.put_u16_le(data.len() as u16);
+                bytes.put_slice(&data);
+            },
+            Self::Status { mid } => {
+                bytes.put_u8(UDP_FRAME_STATUS);
+                bytes.put_u64_le(mid);
+            },
+            Self::Finished { mid } => {
+                bytes.put_u8(UDP_FRAME_FINISHED);
+                bytes.put_u64_le(mid);
+            },
+            Self::MissingHeader { mid } => {
+                bytes.put_u8(UDP_FRAME_MISSING_HEADER);
+                bytes.put_u64_le(mid);
+            },
+            Self::MissingData { mid, ranges } => {
+                bytes.put_u8(UDP_FRAME_MISSING_DATA);
+                bytes.put_u64_le(mid);
+                bytes.put_u16_le(ranges.len() as u16);
+                for (start, end) in ranges {
+                    bytes.put_u64_le(start);
+                    bytes.put_u64_le(end);
+                }
+            },
+        }
+    }
+
+    /// A datagram always contains complete frames, Err => malformed datagram
+    fn read_frame(bytes: &mut BytesMut) -> Result<Self, ()> {
+        fn require(bytes: &BytesMut, size: usize) -> Result<(), ()> {
+            if bytes.len() < size { Err(()) } else { Ok(()) }
+        }
+        require(bytes, 1)?;
+        let frame_no = bytes.get_u8();
+        let frame = match frame_no {
+            UDP_FRAME_PING => Self::Ping,
+            UDP_FRAME_INIT | UDP_FRAME_EVENT => {
+                require(bytes, 10)?;
+                let mid = bytes.get_u64_le();
+                let length = bytes.get_u16_le() as usize;
+                require(bytes, length)?;
+                let frame = bytes.split_to(length).freeze();
+                if frame_no == UDP_FRAME_INIT {
+                    Self::Init { mid, frame }
+                } else {
+                    Self::Event { mid, frame }
+                }
+            },
+            UDP_FRAME_HEADER => {
+                require(bytes, 24)?;
+                Self::Header {
+                    mid: bytes.get_u64_le(),
+                    sid: Sid::from_bytes(bytes),
+                    length: bytes.get_u64_le(),
+                }
+            },
+            UDP_FRAME_DATA => {
+                require(bytes, 18)?;
+                let mid = bytes.get_u64_le();
+                let offset = bytes.get_u64_le();
+                let length = bytes.get_u16_le() as usize;
+                require(bytes, length)?;
+                Self::Data {
+                    mid,
+                    offset,
+                    data: bytes.split_to(length).freeze(),
+                }
+            },
+            UDP_FRAME_STATUS | UDP_FRAME_FINISHED | UDP_FRAME_MISSING_HEADER => {
+                require(bytes, 8)?;
+                let mid = bytes.get_u64_le();
+                match frame_no {
+                    UDP_FRAME_STATUS => Self::Status { mid },
+                    UDP_FRAME_FINISHED => Self::Finished { mid },
+                    _ => Self::MissingHeader { mid },
+                }
+            },
+            UDP_FRAME_MISSING_DATA => {
+                require(bytes, 10)?;
+                let mid = bytes.get_u64_le();
+                let count = bytes.get_u16_le() as usize;
+                require(bytes, count * 16)?;
+                let ranges = (0..count)
+                    .map(|_| (bytes.get_u64_le(), bytes.get_u64_le()))
+                    .collect();
+                Self::MissingData { mid, ranges }
+            },
+            _ => return Err(()),
+        };
+        Ok(frame)
+    }
+}
+
+/// Packs the frames into as few datagrams as possible and sends them
+async fn send_frames<D>(drain: &mut D, frames: Vec<UdpFrame>) -> Result<(), ProtocolError>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+{
+    let mut datagram = BytesMut::new();
+    for frame in frames {
+        if !datagram.is_empty() && datagram.len() + frame.size() > MAX_DATAGRAM_SIZE {
+            drain.send(datagram.split()).await?;
+        }
+        frame.write_bytes(&mut datagram);
+    }
+    if !datagram.is_empty() {
+        drain.send(datagram).await?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+enum UnackedItem {
+    /// Init and Event frames are small and just resent as a whole
+    Frame(UdpFrame),
+    Message {
+        sid: Sid,
+        length: u64,
+        /// all DATA send so far
+        chunks: Vec<(u64, Bytes)>,
+    },
+}
+
+#[derive(Debug)]
+struct Unacked {
+    item: UnackedItem,
+    last_sent: Instant,
+}
+
+/// Items not FINISHED by the remote yet. Shared between both sides, as
+/// FINISHED and MISSING_* frames arrive at the [`UdpRecvProtocol`], which also
+/// keeps resending after the [`UdpSendProtocol`] got dropped on shutdown.
+type SharedUnacked = Arc<Mutex<BTreeMap<Mid, Unacked>>>;
+
+/// UDP implementation of [`SendProtocol`]
+///
+/// [`SendProtocol`]: crate::SendProtocol
+#[derive(Debug)]
+pub struct UdpSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+{
+    store: PrioManager,
+    next_mid: Mid,
+    closing_streams: Vec<Sid>,
+    notify_closing_streams: Vec<Sid>,
+    pending_shutdown: bool,
+    unacked: SharedUnacked,
+    drain: D,
+    metrics: ProtocolMetricCache,
+}
+
+#[derive(Debug)]
+struct IncomingMessage {
+    /// `Sid` and length, unknown till the HEADER arrived
+    header: Option<(Sid, u64)>,
+    /// data received without a gap
+    data: BytesMut,
+    /// DATA received behind a gap
+    pending: BTreeMap<u64, BytesMut>,
+    /// bytes in `pending`
+    pending_len: usize,
+    /// reference point for reporting missing parts
+    since: Instant,
+    reported: bool,
+}
+
+#[derive(Debug)]
+enum Incoming {
+    Init(InitFrame),
+    Event(ProtocolEvent),
+    Message(IncomingMessage),
+}
+
+/// UDP implementation of [`RecvProtocol`]. It acknowledges and resends data on
+/// its own, so it requires a drain as well.
+///
+/// [`RecvProtocol`]: crate::RecvProtocol
+#[derive(Debug)]
+pub struct UdpRecvProtocol<D, S>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+    S: UnreliableSink<DataFormat = UdpDataFormat>,
+{
+    incoming: BTreeMap<Mid, Incoming>,
+    /// every item below was delivered
+    delivered_below: Mid,
+    /// items above `delivered_below` that were delivered out of order
+    delivered: BTreeSet<Mid>,
+    /// bytes of all messages in `incoming`, see [`RECV_WINDOW_BYTES`]
+    buffered: usize,
+    unacked: SharedUnacked,
+    drain: D,
+    sink: S,
+    created: Instant,
+    last_recv: Option<Instant>,
+    last_ping: Option<Instant>,
+    last_tick: Option<Instant>,
+    metrics: ProtocolMetricCache,
+}
+
+impl<D> UdpSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+{
+    pub fn new(drain: D, metrics: ProtocolMetricCache) -> Self {
+        Self {
+            store: PrioManager::new(metrics.clone()),
+            next_mid: 0u64,
+            closing_streams: vec![],
+            notify_closing_streams: vec![],
+            pending_shutdown: false,
+            unacked: Arc::new(Mutex::new(BTreeMap::new())),
+            drain,
+            metrics,
+        }
+    }
+
+    /// returns all promises that this Protocol can take care of
+    /// If you open a Stream anyway, unsupported promises are ignored.
+    pub fn supported_promises() -> Promises {
+        Promises::ORDERED
+            | Promises::CONSISTENCY
+            | Promises::GUARANTEED_DELIVERY
+            | Promises::COMPRESSED
+            | Promises::COMPRESSED_ZSTD
+    }
+
+    /// creates the frame for an item which isn't a message and keeps it till
+    /// it's FINISHED
+    fn item_frame(&mut self, create: impl FnOnce(Mid) -> UdpFrame) -> UdpFrame {
+        let mid = self.next_mid;
+        self.next_mid += 1;
+        let frame = create(mid);
+        self.unacked.lock().unwrap().insert(mid, Unacked {
+            item: UnackedItem::Frame(frame.clone()),
+            last_sent: Instant::now(),
+        });
+        frame
+    }
+
+    fn event_frame(&mut self, frame: OTFrame) -> UdpFrame {
+        let mut bytes = BytesMut::new();
+        frame.write_bytes(&mut bytes);
+        self.item_frame(|mid| UdpFrame::Event {
+            mid,
+            frame: bytes.freeze(),
+        })
+    }
+}
+
+impl IncomingMessage {
+    fn new(now: Instant) -> Self {
+        Self {
+            header: None,
+            data: BytesMut::new(),
+            pending: BTreeMap::new(),
+            pending_len: 0,
+            since: now,
+            reported: false,
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        matches!(self.header, Some((_, length)) if self.data.len() as u64 == length)
+    }
+
+    fn set_header(&mut self, sid: Sid, length: u64) -> Result<(), ()> {
+        match self.header {
+            Some(header) if header == (sid, length) => return Ok(()),
+            Some(_) => return Err(()),
+            None => {},
+        }
+        let end = match self.pending.iter().next_back() {
+            Some((offset, data)) => offset + data.len() as u64,
+            None => self.data.len() as u64,
+        };
+        if end > length {
+            return Err(());
+        }
+        self.header = Some((sid, length));
+        Ok(())
+    }
+
+    /// memory taken by the message, as counted for the receive window
+    fn buffered(&self) -> usize {
+        self.data.len() + self.pending_len + self.pending.len() * FRAGMENT_OVERHEAD
+    }
+
+    /// ignores duplicates, Err if the data overlaps with other data
+    fn insert(&mut self, offset: u64, data: BytesMut) -> Result<(), ()> {
+        let end = offset.checked_add(data.len() as u64).ok_or(())?;
+        if let Some((_, length)) = self.header {
+            if end > length {
+                return Err(());
+            }
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+        let received = self.data.len() as u64;
+        if offset < received {
+            return if end <= received { Ok(()) } else { Err(()) };
+        }
+        if let Some((&prev, prev_data)) = self.pending.range(..=offset).next_back() {
+            if prev + prev_data.len() as u64 > offset {
+                return if prev == offset && prev_data.len() == data.len() {
+                    Ok(())
+                } else {
+                    Err(())
+                };
+            }
+        }
+        if let Some((&next, _)) = self.pending.range(offset + 1..).next() {
+            if next < end {
+                return Err(());
+            }
+        }
+        self.pending_len += data.len();
+        self.pending.insert(offset, data);
+        while let Some(data) = self.pending.remove(&(self.data.len() as u64)) {
+            self.pending_len -= data.len();
+            self.data.extend_from_slice(&data);
+        }
+        Ok(())
+    }
+
+    /// the gaps in the received data, optionally including the not yet
+    /// received end of the message
+    fn missing(&self, with_tail: bool) -> Vec<(u64, u64)> {
+        let length = match self.header {
+            Some((_, length)) => length,
+            None => return vec![],
+        };
+        let mut pos = self.data.len() as u64;
+        let mut ranges = vec![];
+        for (&offset, data) in self.pending.iter() {
+            if offset > pos {
+                ranges.push((pos, offset));
+            }
+            pos = offset + data.len() as u64;
+        }
+        if with_tail && pos < length {
+            ranges.push((pos, length));
+        }
+        ranges.truncate(MAX_MISSING_RANGES);
+        ranges
+    }
+
+    /// requests the missing HEADER or DATA in front of already received DATA,
+    /// after they didn't arrive for `MISSING_DELAY`
+    fn report(&mut self, mid: Mid, now: Instant) -> Option<UdpFrame> {
+        let frame = match self.header {
+            None => Some(UdpFrame::MissingHeader { mid }),
+            Some(_) => Some(self.missing(false))
+                .filter(|ranges| !ranges.is_empty())
+                .map(|ranges| UdpFrame::MissingData { mid, ranges }),
+        };
+        match frame {
+            Some(frame) => {
+                let delay = if self.reported {
+                    RESEND_TIMEOUT
+                } else {
+                    MISSING_DELAY
+                };
+                if now.duration_since(self.since) < delay {
+                    return None;
+                }
+                self.since = now;
+                self.reported = true;
+                Some(frame)
+            },
+            None => {
+                self.since = now;
+                self.reported = false;
+                None
+            },
+        }
+    }
+}
+
+impl<D, S> UdpRecvProtocol<D, S>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+    S: UnreliableSink<DataFormat = UdpDataFormat>,
+{
+    /// `send` is the [`UdpSendProtocol`] of the same channel, both need to
+    /// know about what is acknowledged by the remote.
+    pub fn new<SD>(
+        drain: D,
+        sink: S,
+        send: &UdpSendProtocol<SD>,
+        metrics: ProtocolMetricCache,
+    ) -> Self
+    where
+        SD: UnreliableDrain<DataFormat = BytesMut>,
+    {
+        Self {
+            incoming: BTreeMap::new(),
+            delivered_below: 0,
+            delivered: BTreeSet::new(),
+            buffered: 0,
+            unacked: Arc::clone(&send.unacked),
+            drain,
+            sink,
+            created: Instant::now(),
+            last_recv: None,
+            last_ping: None,
+            last_tick: None,
+            metrics,
+        }
+    }
+
+    fn is_finished(&self, mid: Mid) -> bool {
+        mid < self.delivered_below
+            || self.delivered.contains(&mid)
+            || match self.incoming.get(&mid) {
+                Some(Incoming::Message(m)) => m.is_complete(),
+                Some(_) => true,
+                None => false,
+            }
+    }
+
+    /// whether the item lies within the receive window
+    fn in_window(&self, mid: Mid) -> bool {
+        mid < self.delivered_below.saturating_add(RECV_WINDOW_ITEMS)
+    }
+
+    fn incoming_message(&mut self, mid: Mid, now: Instant) -> Result<&mut IncomingMessage, ()> {
+        match self
+            .incoming
+            .entry(mid)
+            .or_insert_with(|| Incoming::Message(IncomingMessage::new(now)))
+        {
+            Incoming::Message(m) => Ok(m),
+            _ => Err(()),
+        }
+    }
+
+    fn handle_frame(
+        &mut self,
+        frame: UdpFrame,
+        now: Instant,
+        replies: &mut Vec<UdpFrame>,
+    ) -> Result<(), ()> {
+        #[cfg(feature = "trace_pedantic")]
+        trace!(?frame, "recv");
+        match frame {
+            UdpFrame::Ping => {},
+            UdpFrame::Init { mid, frame } => {
+                if !self.is_finished(mid) {
+                    if !self.in_window(mid) {
+                        return Ok(());
+                    }
+                    let frame = InitFrame::read_frame(&mut BytesMut::from(&frame[..])).ok_or(())?;
+                    self.incoming.insert(mid, Incoming::Init(frame));
+                }
+                replies.push(UdpFrame::Finished { mid });
+            },
+            UdpFrame::Event { mid, frame } => {
+                if !self.is_finished(mid) {
+                    if !self.in_window(mid) {
+                        return Ok(());
+                    }
+                    let event = match ITFrame::read_frame(&mut BytesMut::from(&frame[..]))? {
+                        Some(ITFrame::Shutdown) => ProtocolEvent::Shutdown,
+                        // there is no encryption on UDP
+                        Some(ITFrame::OpenStream { promises, .. })
+                            if promises.contains(Promises::ENCRYPTED) =>
+                        {
+                            return Err(());
+                        },
+                        Some(ITFrame::OpenStream {
+                            sid,
+                            prio,
+                            promises,
+                            guaranteed_bandwidth,
+                        }) => ProtocolEvent::OpenStream {
+                            sid,
+                            prio: prio.min(crate::types::HIGHEST_PRIO),
+                            promises,
+                            guaranteed_bandwidth,
+                        },
+                        Some(ITFrame::CloseStream { sid }) => ProtocolEvent::CloseStream { sid },
+                        Some(ITFrame::Ack { sid, count, delay }) => {
+                            ProtocolEvent::Ack { sid, count, delay }
+                        },
+                        _ => return Err(()),
+                    };
+                    self.incoming.insert(mid, Incoming::Event(event));
+                }
+                replies.push(UdpFrame::Finished { mid });
+            },
+            UdpFrame::Header { mid, sid, length } => {
+                if !self.is_finished(mid) {
+                    if !self.in_window(mid) {
+                        return Ok(());
+                    }
+                    let m = self.incoming_message(mid, now)?;
+                    let new = m.header.is_none();
+                    m.set_header(sid, length)?;
+                    let complete = m.is_complete();
+                    if new {
+                        self.metrics.rmsg_ib(sid, length);
+                    }
+                    if !complete {
+                        return Ok(());
+                    }
+                    self.metrics.rmsg_ob(sid, RemoveReason::Finished, length);
+                }
+                replies.push(UdpFrame::Finished { mid });
+            },
+            UdpFrame::Data { mid, offset, data } => {
+                self.metrics.rdata_frames_b(data.len() as u64);
+                if !self.is_finished(mid) {
+                    if !self.in_window(mid) {
+                        return Ok(());
+                    }
+                    let received = match self.incoming.get(&mid) {
+                        Some(Incoming::Message(m)) => m.data.len() as u64,
+                        _ => 0,
+                    };
+                    let in_order = mid == self.delivered_below && offset == received;
+                    if !in_order
+                        && self.buffered + data.len() + FRAGMENT_OVERHEAD > RECV_WINDOW_BYTES
+                    {
+                        // the remote resends it once it's reported as missing
+                        return Ok(());
+                    }
+                    let m = self.incoming_message(mid, now)?;
+                    let before = m.buffered();
+                    m.insert(offset, BytesMut::from(&data[..]))?;
+                    let after = m.buffered();
+                    let finished = m.header.filter(|_| m.is_complete());
+                    self.buffered = self.buffered + after - before;
+                    match finished {
+                        Some((sid, length)) => {
+                            self.metrics.rmsg_ob(sid, RemoveReason::Finished, length)
+                        },
+                        None => return Ok(()),
+                    }
+                }
+                replies.push(UdpFrame::Finished { mid });
+            },
+            UdpFrame::Status { mid } if !self.is_finished(mid) && !self.in_window(mid) => {},
+            UdpFrame::Status { mid } => {
+                replies.push(if self.is_finished(mid) {
+                    UdpFrame::Finished { mid }
+                } else {
+                    match self.incoming.get(&mid) {
+                        Some(Incoming::Message(m)) if m.header.is_some() => UdpFrame::MissingData {
+                            mid,
+                            ranges: m.missing(true),
+                        },
+                        _ => UdpFrame::MissingHeader { mid },
+                    }
+                });
+            },
+            UdpFrame::Finished { mid } => {
+                self.unacked.lock().unwrap().remove(&mid);
+            },
+            UdpFrame::MissingHeader { mid } => {
+                if let Some(unacked) = self.unacked.lock().unwrap().get_mut(&mid) {
+                    unacked.last_sent = now;
+                    replies.push(match &unacked.item {
+                        UnackedItem::Frame(frame) => frame.clone(),
+                        UnackedItem::Message { sid, length, .. } => UdpFrame::Header {
+                            mid,
+                            sid: *sid,
+                            length: *length,
+                        },
+                    });
+                }
+            },
+            UdpFrame::MissingData { mid, ranges } => {
+                if let Some(unacked) = self.unacked.lock().unwrap().get_mut(&mid) {
+                    unacked.last_sent = now;
+                    if let UnackedItem::Message { chunks, .. } = &unacked.item {
+                        for (start, end) in ranges {
+                            replies.extend(
+                                chunks
+                                    .iter()
+                                    .filter(|(offset, data)| {
+                                        *offset < end && offset + data.len() as u64 > start
+                                    })
+                                    .map(|(offset, data)| UdpFrame::Data {
+                                        mid,
+                                        offset: *offset,
+                                        data: data.clone(),
+                                    }),
+                            );
+                        }
+                    }
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// resends unacknowledged items, requests missing parts and keeps the
+    /// channel alive
+    fn tick(&mut self, now: Instant, replies: &mut Vec<UdpFrame>) -> Result<(), ProtocolError> {
+        for (&mid, unacked) in self.unacked.lock().unwrap().iter_mut() {
+            if now.duration_since(unacked.last_sent) >= RESEND_TIMEOUT {
+                unacked.last_sent = now;
+                replies.push(match &unacked.item {
+                    UnackedItem::Frame(frame) => frame.clone(),
+                    UnackedItem::Message { .. } => UdpFrame::Status { mid },
+                });
+            }
+        }
+        for (&mid, incoming) in self.incoming.iter_mut() {
+            if let Incoming::Message(m) = incoming {
+                replies.extend(m.report(mid, now));
+            }
+        }
+        // till the remote answered, the ping is needed to get known by it
+        let ping_interval = if self.last_recv.is_some() {
+            KEEP_ALIVE_INTERVAL
+        } else {
+            RESEND_TIMEOUT
+        };
+        if self
+            .last_ping
+            .map_or(true, |last| now.duration_since(last) >= ping_interval)
+        {
+            self.last_ping = Some(now);
+            replies.push(UdpFrame::Ping);
+        }
+        if now.duration_since(self.last_recv.unwrap_or(self.created)) >= IDLE_TIMEOUT {
+            info!("remote side didn't answer in time, closing udp channel");
+            return Err(ProtocolError::Closed);
+        }
+        Ok(())
+    }
+
+    /// takes the next item that can be delivered without breaking the order
+    /// of the stream it belongs to. Init and Event items are only delivered
+    /// after every item in front of them.
+    fn next_deliverable(&mut self) -> Option<Incoming> {
+        let mut blocked_sids = vec![];
+        let mut mid = self.delivered_below;
+        let found = loop {
+            if !self.delivered.contains(&mid) {
+                match self.incoming.get(&mid) {
+                    Some(Incoming::Init(_)) | Some(Incoming::Event(_))
+                        if blocked_sids.is_empty() =>
+                    {
+                        break mid;
+                    },
+                    Some(Incoming::Message(m)) => match m.header {
+                        Some((sid, _)) if m.is_complete() && !blocked_sids.contains(&sid) => {
+                            break mid;
+                        },
+                        Some((sid, _)) => blocked_sids.push(sid),
+                        None => return None,
+                    },
+                    _ => return None,
+                }
+            }
+            mid += 1;
+        };
+        self.delivered.insert(found);
+        while self.delivered.remove(&self.delivered_below) {
+            self.delivered_below += 1;
+        }
+        let item = self.incoming.remove(&found);
+        if let Some(Incoming::Message(m)) = &item {
+            self.buffered -= m.buffered();
+        }
+        item
+    }
+
+    async fn recv_item(&mut self) -> Result<Incoming, ProtocolError> {
+        loop {
+            let now = Instant::now();
+            if self
+                .last_tick
+                .map_or(true, |last| now.duration_since(last) >= UDP_SINK_TIMEOUT)
+            {
+                self.last_tick = Some(now);
+                let mut replies = vec![];
+                self.tick(now, &mut replies)?;
+                send_frames(&mut self.drain, replies).await?;
+            }
+            if let Some(item) = self.next_deliverable() {
+                break Ok(item);
+            }
+            if let UdpDataFormat::Datagram(mut datagram) = self.sink.recv().await? {
+                let now = Instant::now();
+                self.last_recv = Some(now);
+                let mut replies = vec![];
+                while !datagram.is_empty() {
+                    let frame = match UdpFrame::read_frame(&mut datagram) {
+                        Ok(frame) => frame,
+                        Err(()) => {
+                            info!("protocol violation by remote side: malformed datagram");
+                            return Err(ProtocolError::Violated);
+                        },
+                    };
+                    if self.handle_frame(frame, now, &mut replies).is_err() {
+                        info!("protocol violation by remote side: inconsistent frame");
+                        return Err(ProtocolError::Violated);
+                    }
+                }
+                send_frames(&mut self.drain, replies).await?;
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<D> SendProtocol for UdpSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+{
+    fn notify_from_recv(&mut self, event: ProtocolEvent) {
+        match event {
+            ProtocolEvent::OpenStream {
+                sid,
+                prio,
+                promises,
+                guaranteed_bandwidth,
+            } => {
+                self.store
+                    .open_stream(sid, prio, promises, guaranteed_bandwidth);
+            },
+            ProtocolEvent::CloseStream { sid } => {
+                if !self.store.try_close_stream(sid) {
+                    #[cfg(feature = "trace_pedantic")]
+                    trace!(?sid, "hold back notify close stream");
+                    self.notify_closing_streams.push(sid);
+                }
+            },
+            ProtocolEvent::Ack { sid, count, delay } => self.store.ack(sid, count, delay),
+            _ => {},
+        }
+    }
+
+    async fn send(&mut self, event: ProtocolEvent) -> Result<(), ProtocolError> {
+        #[cfg(feature = "trace_pedantic")]
+        trace!(?event, "send");
+        match event {
+            ProtocolEvent::OpenStream {
+                sid,
+                prio,
+                promises,
+                guaranteed_bandwidth,
+            } => {
+                self.store
+                    .open_stream(sid, prio, promises, guaranteed_bandwidth);
+                let frame = self.event_frame(event.to_frame());
+                send_frames(&mut self.drain, vec![frame]).await?;
+            },
+            ProtocolEvent::CloseStream { sid } => {
+                if self.store.try_close_stream(sid) {
+                    let frame = self.event_frame(event.to_frame());
+                    send_frames(&mut self.drain, vec![frame]).await?;
+                } else {
+                    #[cfg(feature = "trace_pedantic")]
+                    trace!(?sid, "hold back close stream");
+                    self.closing_streams.push(sid);
+                }
+            },
+            ProtocolEvent::Shutdown => {
+                if self.store.is_empty() {
+                    let frame = self.event_frame(event.to_frame());
+                    send_frames(&mut self.drain, vec![frame]).await?;
+                } else {
+                    #[cfg(feature = "trace_pedantic")]
+                    trace!("hold back shutdown");
+                    self.pending_shutdown = true;
+                }
+            },
+            ProtocolEvent::Message { data, sid } => {
+                self.metrics.smsg_ib(sid, data.len() as u64);
+                self.store.add(data, self.next_mid, sid);
+                self.next_mid += 1;
+            },
+            ProtocolEvent::Ack { .. } => {
+                let frame = self.event_frame(event.to_frame());
+                send_frames(&mut self.drain, vec![frame]).await?;
+            },
+        }
+        Ok(())
+    }
+
+    async fn flush(
+        &mut self,
+        bandwidth: Bandwidth,
+        dt: Duration,
+    ) -> Result</* actual */ Bandwidth, ProtocolError> {
+        let (frames, _) = self.store.grab(bandwidth, dt);
+        let now = Instant::now();
+        let mut udp_frames = Vec::with_capacity(frames.len());
+        let mut data_frames = 0;
+        let mut data_bandwidth = 0;
+        {
+            let mut unacked = self.unacked.lock().unwrap();
+            for (_, frame) in frames {
+                match frame {
+                    OTFrame::DataHeader { mid, sid, length } => {
+                        unacked.insert(mid, Unacked {
+                            item: UnackedItem::Message {
+                                sid,
+                                length,
+                                chunks: vec![],
+                            },
+                            last_sent: now,
+                        });
+                        udp_frames.push(UdpFrame::Header { mid, sid, length });
+                    },
+                    OTFrame::Data { mid, data } => {
+                        data_bandwidth += data.len();
+                        data_frames += 1;
+                        if let Some(Unacked {
+                            item: UnackedItem::Message { chunks, .. },
+                            last_sent,
+                        }) = unacked.get_mut(&mid)
+                        {
+                            let offset = chunks
+                                .last()
+                                .map_or(0, |(offset, data)| offset + data.len() as u64);
+                            chunks.push((offset, data.clone()));
+                            *last_sent = now;
+                            udp_frames.push(UdpFrame::Data { mid, offset, data });
+                        }
+                    },
+                    _ => {},
+                }
+            }
+        }
+        send_frames(&mut self.drain, udp_frames).await?;
+        self.metrics
+            .sdata_frames_b(data_frames, data_bandwidth as u64);
+
+        let mut finished_streams = vec![];
+        for (i, &sid) in self.closing_streams.iter().enumerate() {
+            if self.store.try_close_stream(sid) {
+                #[cfg(feature = "trace_pedantic")]
+                trace!(?sid, "close stream, as it's now empty");
+                finished_streams.push(i);
+            }
+        }
+        for i in finished_streams.iter().rev() {
+            let sid = self.closing_streams.remove(*i);
+            let frame = self.event_frame(OTFrame::CloseStream { sid });
+            send_frames(&mut self.drain, vec![frame]).await?;
+        }
+
+        let mut finished_streams = vec![];
+        for (i, sid) in self.notify_closing_streams.iter().enumerate() {
+            if self.store.try_close_stream(*sid) {
+                #[cfg(feature = "trace_pedantic")]
+                trace!(?sid, "close stream, as it's now empty");
+                finished_streams.push(i);
+            }
+        }
+        for i in finished_streams.iter().rev() {
+            self.notify_closing_streams.remove(*i);
+        }
+
+        if self.pending_shutdown && self.store.is_empty() {
+            #[cfg(feature = "trace_pedantic")]
+            trace!("shutdown, as it's now empty");
+            let frame = self.event_frame(OTFrame::Shutdown);
+            send_frames(&mut self.drain, vec![frame]).await?;
+            self.pending_shutdown = false;
+        }
+        Ok(data_bandwidth as u64)
+    }
+}
+
+#[async_trait]
+impl<D, S> RecvProtocol for UdpRecvProtocol<D, S>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+    S: UnreliableSink<DataFormat = UdpDataFormat>,
+{
+    async fn recv(&mut self) -> Result<ProtocolEvent, ProtocolError> {
+        loop {
+            match self.recv_item().await? {
+                Incoming::Event(event) => break Ok(event),
+                Incoming::Message(m) => {
+                    let (sid, _) = m.header.expect("only complete messages are delivered");
+                    break Ok(ProtocolEvent::Message {
+                        sid,
+                        data: m.data.freeze(),
+                    });
+                },
+                Incoming::Init(frame) => info!(?frame, "ignoring Init frame after handshake"),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<D> ReliableDrain for UdpSendProtocol<D>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+{
+    async fn send(&mut self, frame: InitFrame) -> Result<(), ProtocolError> {
+        let mut bytes = BytesMut::with_capacity(500);
+        frame.write_bytes(&mut bytes);
+        let frame = self.item_frame(|mid| UdpFrame::Init {
+            mid,
+            frame: bytes.freeze(),
+        });
+        send_frames(&mut self.drain, vec![frame]).await
+    }
+}
+
+#[async_trait]
+impl<D, S> ReliableSink for UdpRecvProtocol<D, S>
+where
+    D: UnreliableDrain<DataFormat = BytesMut>,
+    S: UnreliableSink<DataFormat = UdpDataFormat>,
+{
+    async fn recv(&mut self) -> Result<InitFrame, ProtocolError> {
+        match self.recv_item().await? {
+            Incoming::Init(frame) => Ok(frame),
+            _ => {
+                info!("protocol violation by remote side: send data before handshake");
+                Err(ProtocolError::Violated)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_utils {
+    //UDP protocol based on Channels, which might lose datagrams
+    use super::*;
+    use crate::metrics::{ProtocolMetricCache, ProtocolMetrics};
+    use async_channel::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, Clone)]
+    pub struct UdpDrain {
+        pub sender: Sender<BytesMut>,
+        /// every n-th datagram is lost, 0 => none
+        pub lose_every: usize,
+        pub count: Arc<AtomicUsize>,
+    }
+
+    #[derive(Debug)]
+    pub struct UdpSink {
+        pub receiver: Receiver<BytesMut>,
+    }
+
+    pub type UdpProtocols = (
+        UdpSendProtocol<UdpDrain>,
+        UdpRecvProtocol<UdpDrain, UdpSink>,
+    );
+
+    /// emulate Udp protocol on Channels
+    pub fn udp_bound(lose_every: usize, metrics: Option<ProtocolMetricCache>) -> [UdpProtocols; 2] {
+        let (s1, r1) = async_channel::unbounded();
+        let (s2, r2) = async_channel::unbounded();
+        let m = metrics.unwrap_or_else(|| {
+            ProtocolMetricCache::new("udp", Arc::new(ProtocolMetrics::new().unwrap()))
+        });
+        let protocols = |sender, receiver| {
+            let drain = UdpDrain {
+                sender,
+                lose_every,
+                count: Arc::new(AtomicUsize::new(0)),
+            };
+            let sp = UdpSendProtocol::new(drain.clone(), m.clone());
+            let rp = UdpRecvProtocol::new(drain, UdpSink { receiver }, &sp, m.clone());
+            (sp, rp)
+        };
+        [protocols(s1, r2), protocols(s2, r1)]
+    }
+
+    /// keeps a recv side running, so it answers the requests of the remote
+    pub fn spawn_recv(mut r: UdpRecvProtocol<UdpDrain, UdpSink>) {
+        tokio::spawn(async move { while RecvProtocol::recv(&mut r).await.is_ok() {} });
+    }
+
+    #[async_trait]
+    impl UnreliableDrain for UdpDrain {
+        type DataFormat = BytesMut;
+
+        async fn send(&mut self, data: Self::DataFormat) -> Result<(), ProtocolError> {
+            let count = self.count.fetch_add(1, Ordering::Relaxed) + 1;
+            if self.lose_every == 0 || count % self.lose_every != 0 {
+                // like UDP, sending succeeds even if nobody is listening
+                let _ = self.sender.try_send(data);
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl UnreliableSink for UdpSink {
+        type DataFormat = UdpDataFormat;
+
+        async fn recv(&mut self) -> Result<Self::DataFormat, ProtocolError> {
+            match tokio::time::timeout(UDP_SINK_TIMEOUT, self.receiver.recv()).await {
+                Ok(Ok(datagram)) => Ok(UdpDataFormat::Datagram(datagram)),
+                Ok(Err(_)) => Err(ProtocolError::Closed),
+                Err(_) => Ok(UdpDataFormat::Idle),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Incoming, UdpFrame, RECV_WINDOW_BYTES, RECV_WINDOW_ITEMS};
+    use crate::{
+        types::{Capabilities, Pid, Promises, Sid, STREAM_ID_OFFSET1, STREAM_ID_OFFSET2},
+        udp::test_utils::*,
+        InitProtocol, ProtocolEvent, RecvProtocol, SendProtocol,
+    };
+    use bytes::Bytes;
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn handshake_all_good() {
+        let [mut p1, mut p2] = udp_bound(0, None);
+        let r1 = tokio::spawn(async move {
+            p1.initialize(true, Pid::fake(2), 1337, Capabilities::all())
+                .await
+        });
+        let r2 = tokio::spawn(async move {
+            p2.initialize(false, Pid::fake(3), 42, Capabilities::all())
+                .await
+        });
+        let (r1, r2) = tokio::join!(r1, r2);
+        assert_eq!(
+            r1.unwrap(),
+            Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42, Capabilities::all()))
+        );
+        assert_eq!(
+            r2.unwrap(),
+            Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337, Capabilities::all()))
+        );
+    }
+
+    #[tokio::test]
+    async fn handshake_with_lost_datagrams() {
+        let [mut p1, mut p2] = udp_bound(2, None);
+        // the side finishing first keeps answering till the other one finished
+        let r1 = tokio::spawn(async move {
+            let r = p1
+                .initialize(true, Pid::fake(2), 1337, Capabilities::all())
+                .await;
+            spawn_recv(p1.1);
+            r
+        });
+        let r2 = tokio::spawn(async move {
+            let r = p2
+                .initialize(false, Pid::fake(3), 42, Capabilities::all())
+                .await;
+            spawn_recv(p2.1);
+            r
+        });
+        let (r1, r2) = tokio::join!(r1, r2);
+        assert_eq!(
+            r1.unwrap(),
+            Ok((Pid::fake(3), STREAM_ID_OFFSET1, 42, Capabilities::all()))
+        );
+        assert_eq!(
+            r2.unwrap(),
+            Ok((Pid::fake(2), STREAM_ID_OFFSET2, 1337, Capabilities::all()))
+        );
+    }
+
+    #[tokio::test]
+    async fn send_short_msg() {
+        let [p1, p2] = udp_bound(0, None);
+        let (mut s, mut r) = (p1.0, p2.1);
+        let event = ProtocolEvent::OpenStream {
+            sid: Sid::new(10),
+            prio: 3u8,
+            promises: Promises::ORDERED,
+            guaranteed_bandwidth: 1_000_000,
+        };
+        s.send(event.clone()).await.unwrap();
+        assert_eq!(r.recv().await.unwrap(), event);
+        let event = ProtocolEvent::Message {
+            sid: Sid::new(10),
+            data: Bytes::from(&[188u8; 600][..]),
+        };
+        s.send(event.clone()).await.unwrap();
+        s.flush(1_000_000, Duration::from_secs(1)).await.unwrap();
+        assert_eq!(r.recv().await.unwrap(), event);
+    }
+
+    #[tokio::test]
+    async fn send_long_msgs_with_lost_datagrams() {
+        let [p1, p2] = udp_bound(3, None);
+        let (mut s, mut r) = (p1.0, p2.1);
+        spawn_recv(p1.1);
+        let sid = Sid::new(1);
+        s.send(ProtocolEvent::OpenStream {
+            sid,
+            prio: 5u8,
+            promises: Promises::ORDERED | Promises::GUARANTEED_DELIVERY,
+            guaranteed_bandwidth: 1_000_000,
+        })
+        .await
+        .unwrap();
+        let events = (0..5u8)
+            .map(|i| ProtocolEvent::Message {
+                sid,
+                data: Bytes::from(vec![i; 20_000]),
+            })
+            .collect::<Vec<_>>();
+        for event in events.iter() {
+            s.send(event.clone()).await.unwrap();
+        }
+        s.send(ProtocolEvent::CloseStream { sid }).await.unwrap();
+        s.send(ProtocolEvent::Shutdown).await.unwrap();
+        // the send side only has to flush once, the recv side takes care of
+        // lost datagrams
+        s.flush(1_000_000_000, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(matches!(
+            r.recv().await.unwrap(),
+            ProtocolEvent::OpenStream { .. }
+        ));
+        for event in events {
+            assert_eq!(r.recv().await.unwrap(), event);
+        }
+        assert_eq!(r.recv().await.unwrap(), ProtocolEvent::CloseStream { sid });
+        assert_eq!(r.recv().await.unwrap(), ProtocolEvent::Shutdown);
+    }
+
+    #[tokio::test]
+    async fn recv_window() {
+        let [_, (_, mut r)] = udp_bound(0, None);
+        let now = Instant::now();
+        let mut replies = vec![];
+        let half = RECV_WINDOW_BYTES / 2;
+        let sid = Sid::new(1);
+        let data = |mid, offset, len| UdpFrame::Data {
+            mid,
+            offset,
+            data: Bytes::from(vec![0u8; len]),
+        };
+
+        // beyond the window, neither stored nor FINISHED
+        let mid = RECV_WINDOW_ITEMS;
+        let header = UdpFrame::Header {
+            mid,
+            sid,
+            length: 0,
+        };
+        r.handle_frame(header, now, &mut replies).unwrap();
+        r.handle_frame(UdpFrame::Status { mid }, now, &mut replies)
+            .unwrap();
+        assert!(replies.is_empty());
+        assert!(r.incoming.is_empty());
+
+        r.handle_frame(data(1, 0, half), now, &mut replies).unwrap();
+        assert_eq!(r.buffered, half);
+        // would exceed the bytes of the window
+        r.handle_frame(data(2, 0, half), now, &mut replies).unwrap();
+        assert!(!r.incoming.contains_key(&2));
+        assert_eq!(r.buffered, half);
+
+        // the first undelivered item always continues
+        let length = 2 * half as u64;
+        let header = UdpFrame::Header {
+            mid: 0,
+            sid,
+            length,
+        };
+        r.handle_frame(header, now, &mut replies).unwrap();
+        r.handle_frame(data(0, 0, half), now, &mut replies).unwrap();
+        assert_eq!(r.buffered, 2 * half);
+        // but not behind a gap
+        r.handle_frame(data(0, half as u64 + 1, 1), now, &mut replies)
+            .unwrap();
+        assert!(matches!(
+            r.incoming.get(&0),
+            Some(Incoming::Message(m)) if m.pending.is_empty()
+        ));
+        assert!(replies.is_empty());
+    }
+}
@@ -1,13 +1,20 @@
+#[cfg(feature = "compression")]
+use crate::{compression::Compression, message::partial_eq_io_error};
 use crate::{
     message::{partial_eq_bincode, Message},
     participant::{A2bStreamOpen, S2bShutdownBparticipant},
+    record::{Direction, Recorder},
     scheduler::{A2sConnect, Scheduler},
 };
 use bytes::Bytes;
 use hashbrown::HashMap;
 #[cfg(feature = "compression")]
 use lz_fear::raw::DecodeError;
-use network_protocol::{Bandwidth, InitProtocolError, Pid, Prio, Promises, Sid};
+#[cfg(feature = "sim")]
+use network_protocol::sim::SimConfig;
+use network_protocol::{
+    Bandwidth, Capabilities, Identity, InitProtocolError, Pid, Prio, Promises, PublicIdentity, Sid,
+};
 #[cfg(feature = "metrics")]
 use prometheus::Registry;
 use serde::{de::DeserializeOwned, Serialize};
@@ -28,23 +35,43 @@ use tracing::*;
 
 type A2sDisconnect = Arc<Mutex<Option<mpsc::UnboundedSender<(Pid, S2bShutdownBparticipant)>>>>;
 
-/// Represents a Tcp, Quic, Udp or Mpsc connection address
+/// Represents a Tcp, Quic, Udp, WebSocket or Mpsc connection address
 #[derive(Clone, Debug)]
 pub enum ConnectAddr {
     Tcp(SocketAddr),
     Udp(SocketAddr),
     #[cfg(feature = "quic")]
     Quic(SocketAddr, quinn::ClientConfig, String),
+    /// `ws://` or `wss://` url, e.g. of a reverse proxy in front of the
+    /// [`ListenAddr::WebSocket`]
+    #[cfg(feature = "websocket")]
+    WebSocket(String),
     Mpsc(u64),
 }
 
-/// Represents a Tcp, Quic, Udp or Mpsc listen address
+impl ConnectAddr {
+    pub(crate) fn socket_addr(&self) -> Option<SocketAddr> {
+        match self {
+            ConnectAddr::Tcp(addr) | ConnectAddr::Udp(addr) => Some(*addr),
+            #[cfg(feature = "quic")]
+            ConnectAddr::Quic(addr, ..) => Some(*addr),
+            #[cfg(feature = "websocket")]
+            ConnectAddr::WebSocket(_) => None,
+            ConnectAddr::Mpsc(_) => None,
+        }
+    }
+}
+
+/// Represents a Tcp, Quic, Udp, WebSocket or Mpsc listen address
 #[derive(Clone, Debug)]
 pub enum ListenAddr {
     Tcp(SocketAddr),
     Udp(SocketAddr),
     #[cfg(feature = "quic")]
     Quic(SocketAddr, quinn::ServerConfig),
+    /// accepts plain `ws://` connections, terminate TLS in a reverse proxy
+    #[cfg(feature = "websocket")]
+    WebSocket(SocketAddr),
     Mpsc(u64),
 }
 
@@ -52,16 +79,27 @@ pub enum ListenAddr {
 /// to a remote Participant. Look at the [`connect`] and [`connected`] method of
 /// [`Networks`] on how to generate `Participants`
 ///
+/// If all channels of a `Participant` break unexpectedly, it is kept alive for
+/// a short grace period. The side which connected tries to reconnect within
+/// it and resumes the `Participant` with the secret from the handshake.
+/// Messages on [`Streams`] with [`Promises::GUARANTEED_DELIVERY`] that didn't
+/// reach the remote are send again.
+///
 /// [`Networks`]: crate::api::Network
+/// [`Streams`]: crate::api::Stream
 /// [`connect`]: Network::connect
 /// [`connected`]: Network::connected
 pub struct Participant {
     local_pid: Pid,
     remote_pid: Pid,
+    capabilities: Capabilities,
+    peer_addr: Option<SocketAddr>,
+    remote_identity: Option<PublicIdentity>,
     a2b_open_stream_s: Mutex<mpsc::UnboundedSender<A2bStreamOpen>>,
     b2a_stream_opened_r: Mutex<mpsc::UnboundedReceiver<Stream>>,
     b2a_bandwidth_stats_r: watch::Receiver<f32>,
     a2s_disconnect_s: A2sDisconnect,
+    recorder: Mutex<Option<Recorder>>,
 }
 
 /// `Streams` represents a channel to send `n` messages with a certain priority
@@ -82,15 +120,17 @@ pub struct Stream {
     local_pid: Pid,
     remote_pid: Pid,
     sid: Sid,
-    #[allow(dead_code)]
     prio: Prio,
     promises: Promises,
-    #[allow(dead_code)]
     guaranteed_bandwidth: Bandwidth,
     send_closed: Arc<AtomicBool>,
     a2b_msg_s: crossbeam_channel::Sender<(Sid, Bytes)>,
     b2a_msg_recv_r: Option<async_channel::Receiver<Bytes>>,
     a2b_close_stream_s: Option<mpsc::UnboundedSender<Sid>>,
+    recorder: Option<Recorder>,
+    record_sent: bool,
+    #[cfg(feature = "compression")]
+    compression: Option<Compression>,
 }
 
 /// Error type thrown by [`Networks`](Network) methods
@@ -120,6 +160,9 @@ pub enum ParticipantError {
     /// that failed. This is also returned when local side tries to do
     /// something while remote site gracefully disconnects
     ProtocolFailedUnrecoverable,
+    ///No channel of this Participant can keep the requested [`Promises`],
+    /// e.g. [`Promises::ENCRYPTED`] when only connected via UDP
+    UnsupportedPromises,
 }
 
 /// Error type thrown by [`Streams`](Stream) methods
@@ -131,6 +174,8 @@ pub enum StreamError {
     StreamClosed,
     #[cfg(feature = "compression")]
     Compression(DecodeError),
+    #[cfg(feature = "compression")]
+    Zstd(std::io::Error),
     Deserialize(bincode::Error),
 }
 
@@ -138,6 +183,8 @@ pub enum StreamError {
 #[derive(Debug, Clone)]
 pub struct StreamParams {
     pub(crate) promises: Promises,
+    #[cfg(feature = "compression")]
+    pub(crate) compression: Option<Compression>,
 }
 
 /// Use the `Network` to create connections to other [`Participants`]
@@ -189,6 +236,9 @@ pub struct Network {
     connect_sender: Mutex<mpsc::UnboundedSender<A2sConnect>>,
     connected_receiver: Mutex<mpsc::UnboundedReceiver<Participant>>,
     shutdown_network_s: Option<oneshot::Sender<oneshot::Sender<()>>>,
+    #[cfg(feature = "sim")]
+    simulation: Arc<Mutex<Option<SimConfig>>>,
+    identity: Arc<Mutex<Option<Arc<Identity>>>>,
 }
 
 impl Network {
@@ -264,9 +314,13 @@ impl Network {
         let p = participant_id;
         let span = tracing::info_span!("network", ?p);
         span.in_scope(|| trace!("Starting Network"));
+        let simulation = Arc::new(Mutex::new(None));
+        let identity = Arc::new(Mutex::new(None));
         let (scheduler, listen_sender, connect_sender, connected_receiver, shutdown_sender) =
             Scheduler::new(
                 participant_id,
+                Arc::clone(&simulation),
+                Arc::clone(&identity),
                 #[cfg(feature = "metrics")]
                 registry,
             );
@@ -294,9 +348,56 @@ impl Network {
             connect_sender: Mutex::new(connect_sender),
             connected_receiver: Mutex::new(connected_receiver),
             shutdown_network_s: Some(shutdown_network_s),
+            #[cfg(feature = "sim")]
+            simulation,
+            identity,
         }
     }
 
+    /// Simulates a bad network on TCP and UDP channels, e.g. to reproduce lag
+    /// bugs. It applies to channels of listeners and connections started
+    /// afterwards, `None` stops it for those. Both directions are affected,
+    /// see [`SimConfig`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// use std::time::Duration;
+    /// use tokio::runtime::Runtime;
+    /// use veloren_network::{Network, Pid, SimConfig};
+    ///
+    /// let runtime = Runtime::new().unwrap();
+    /// let network = Network::new(Pid::new(), &runtime);
+    /// runtime.block_on(network.simulate(Some(SimConfig {
+    ///     latency: Duration::from_millis(100),
+    ///     drop_rate: 0.05,
+    ///     ..SimConfig::default()
+    /// })));
+    /// ```
+    #[cfg(feature = "sim")]
+    pub async fn simulate(&self, sim: Option<SimConfig>) { *self.simulation.lock().await = sim; }
+
+    /// Proves `identity` to everyone connecting to a TCP or WebSocket
+    /// listener afterwards, so that they can detect a man in the middle with
+    /// [`connect_pinned`]. Store it with [`Identity::to_pkcs8`] to keep it
+    /// across restarts. Without one a random identity is used per channel.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use tokio::runtime::Runtime;
+    /// use veloren_network::{Identity, Network, Pid};
+    ///
+    /// let runtime = Runtime::new().unwrap();
+    /// let network = Network::new(Pid::new(), &runtime);
+    /// let identity = Identity::generate();
+    /// println!("pin {} to connect", identity.public_identity());
+    /// runtime.block_on(network.set_identity(identity));
+    /// ```
+    ///
+    /// [`connect_pinned`]: Network::connect_pinned
+    pub async fn set_identity(&self, identity: Identity) {
+        *self.identity.lock().await = Some(Arc::new(identity));
+    }
+
     /// starts listening on an [`ListenAddr`].
     /// When the method returns the `Network` is ready to listen for incoming
     /// connections OR has returned a [`NetworkError`] (e.g. port already used).
@@ -386,15 +487,39 @@ impl Network {
     ///
     /// [`Streams`]: crate::api::Stream
     /// [`ConnectAddr`]: crate::api::ConnectAddr
-    #[instrument(name="network", skip(self, address), fields(p = %self.local_pid))]
     pub async fn connect(&self, address: ConnectAddr) -> Result<Participant, NetworkError> {
+        self.connect_internal(address, None).await
+    }
+
+    /// Like [`connect`], but fails with [`InitProtocolError::WrongIdentity`]
+    /// unless the remote proves `identity`, see [`set_identity`]. This
+    /// protects the encryption of [`Promises::ENCRYPTED`] streams against a
+    /// man in the middle. Only TCP and WebSocket support this. Reconnects of
+    /// the returned [`Participant`] require the same identity.
+    ///
+    /// [`connect`]: Network::connect
+    /// [`set_identity`]: Network::set_identity
+    pub async fn connect_pinned(
+        &self,
+        address: ConnectAddr,
+        identity: PublicIdentity,
+    ) -> Result<Participant, NetworkError> {
+        self.connect_internal(address, Some(identity)).await
+    }
+
+    #[instrument(name="network", skip(self, address), fields(p = %self.local_pid))]
+    async fn connect_internal(
+        &self,
+        address: ConnectAddr,
+        pinned_identity: Option<PublicIdentity>,
+    ) -> Result<Participant, NetworkError> {
         let (pid_sender, pid_receiver) =
             oneshot::channel::<Result<Participant, NetworkConnectError>>();
-        debug!(?address, "Connect to address");
+        debug!(?address, ?pinned_identity, "Connect to address");
         self.connect_sender
             .lock()
             .await
-            .send((address, pid_sender))?;
+            .send((address, pinned_identity, pid_sender))?;
         let participant = match pid_receiver.await? {
             Ok(p) => p,
             Err(e) => return Err(NetworkError::ConnectFailed(e)),
@@ -514,9 +639,13 @@ impl Network {
 }
 
 impl Participant {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         local_pid: Pid,
         remote_pid: Pid,
+        capabilities: Capabilities,
+        peer_addr: Option<SocketAddr>,
+        remote_identity: Option<PublicIdentity>,
         a2b_open_stream_s: mpsc::UnboundedSender<A2bStreamOpen>,
         b2a_stream_opened_r: mpsc::UnboundedReceiver<Stream>,
         b2a_bandwidth_stats_r: watch::Receiver<f32>,
@@ -525,10 +654,14 @@ impl Participant {
         Self {
             local_pid,
             remote_pid,
+            capabilities,
+            peer_addr,
+            remote_identity,
             a2b_open_stream_s: Mutex::new(a2b_open_stream_s),
             b2a_stream_opened_r: Mutex::new(b2a_stream_opened_r),
             b2a_bandwidth_stats_r,
             a2s_disconnect_s: Arc::new(Mutex::new(Some(a2s_disconnect_s))),
+            recorder: Mutex::new(None),
         }
     }
 
@@ -541,7 +674,9 @@ impl Participant {
     /// * `promises` - use a combination of you prefered [`Promises`], see the
     ///   link for further documentation. You can combine them, e.g.
     ///   `Promises::ORDERED | Promises::CONSISTENCY` The Stream will then
-    ///   guarantee that those promises are met.
+    ///   guarantee that those promises are met. Without
+    ///   `Promises::GUARANTEED_DELIVERY` messages may be dropped by protocols
+    ///   which support it, e.g. QUIC sends them as datagrams.
     /// * `bandwidth` - sets a guaranteed bandwidth which is reserved for this
     ///   stream. When excess bandwidth is available it will be used. See
     ///   [`Bandwidth`] for details.
@@ -587,7 +722,8 @@ impl Participant {
         bandwidth: Bandwidth,
     ) -> Result<Stream, ParticipantError> {
         debug_assert!(prio <= network_protocol::HIGHEST_PRIO, "invalid prio");
-        let (p2a_return_stream_s, p2a_return_stream_r) = oneshot::channel::<Stream>();
+        let (p2a_return_stream_s, p2a_return_stream_r) =
+            oneshot::channel::<Result<Stream, ParticipantError>>();
         if let Err(e) = self.a2b_open_stream_s.lock().await.send((
             prio,
             promises,
@@ -598,11 +734,18 @@ impl Participant {
             return Err(ParticipantError::ParticipantDisconnected);
         }
         match p2a_return_stream_r.await {
-            Ok(stream) => {
+            Ok(Ok(mut stream)) => {
                 let sid = stream.sid;
                 trace!(?sid, "opened stream");
+                if let Some(recorder) = &*self.recorder.lock().await {
+                    stream.record(recorder.clone());
+                }
                 Ok(stream)
             },
+            Ok(Err(e)) => {
+                debug!(?e, "stream couldn't be opened");
+                Err(e)
+            },
             Err(_) => {
                 debug!("p2a_return_stream_r failed, closing participant");
                 Err(ParticipantError::ParticipantDisconnected)
@@ -610,6 +753,32 @@ impl Participant {
         }
     }
 
+    /// Opens a [`Promises::COMPRESSED`] [`Stream`] like [`open`], but its
+    /// messages are compressed with `compression` instead of lz4, e.g. zstd
+    /// with a high level for large and rarely send messages. Falls back to
+    /// lz4 if the remote doesn't support the codec, see
+    /// [`Stream::compression`].
+    ///
+    /// [`open`]: Participant::open
+    #[cfg(feature = "compression")]
+    pub async fn open_compressed(
+        &self,
+        prio: u8,
+        promises: Promises,
+        bandwidth: Bandwidth,
+        compression: Compression,
+    ) -> Result<Stream, ParticipantError> {
+        let mut stream = self
+            .open(prio, promises | compression.promises(), bandwidth)
+            .await?;
+        if let Some(negotiated) = stream.compression {
+            if negotiated.same_codec(&compression) {
+                stream.compression = Some(compression);
+            }
+        }
+        Ok(stream)
+    }
+
     /// Use this method to handle [`Streams`] opened from remote site, like the
     /// [`connected`] method of [`Network`]. This is the associated method
     /// to [`open`]. It's guaranteed that the order of [`open`] and `opened`
@@ -647,9 +816,12 @@ impl Participant {
     #[instrument(name="network", skip(self), fields(p = %self.local_pid))]
     pub async fn opened(&self) -> Result<Stream, ParticipantError> {
         match self.b2a_stream_opened_r.lock().await.recv().await {
-            Some(stream) => {
+            Some(mut stream) => {
                 let sid = stream.sid;
                 debug!(?sid, "Receive opened stream");
+                if let Some(recorder) = &*self.recorder.lock().await {
+                    stream.record(recorder.clone());
+                }
                 Ok(stream)
             },
             None => {
@@ -659,6 +831,17 @@ impl Participant {
         }
     }
 
+    /// Records the [`Messages`] of all [`Streams`] returned by [`open`] and
+    /// [`opened`] afterwards, e.g. to replay them when looking into a bug.
+    /// Streams opened before are not recorded, so call this right after
+    /// connecting. See [`Recorder`].
+    ///
+    /// [`Messages`]: crate::message::Message
+    /// [`Streams`]: crate::api::Stream
+    /// [`open`]: Participant::open
+    /// [`opened`]: Participant::opened
+    pub async fn record(&self, recorder: Recorder) { *self.recorder.lock().await = Some(recorder); }
+
     /// disconnecting a `Participant` in a async way.
     /// Use this rather than `Participant::Drop` if you want to close multiple
     /// `Participants`.
@@ -757,6 +940,30 @@ impl Participant {
 
     /// Returns the remote [`Pid`](network_protocol::Pid)
     pub fn remote_pid(&self) -> Pid { self.remote_pid }
+
+    /// Returns the [`Capabilities`] supported by both sides. [`Streams`] are
+    /// opened with the [`Promises`] they allow, e.g. without
+    /// [`Capabilities::COMPRESSION_LZ4`] a [`Promises::COMPRESSED`] Stream is
+    /// send uncompressed.
+    ///
+    /// [`Streams`]: crate::api::Stream
+    pub fn capabilities(&self) -> Capabilities { self.capabilities }
+
+    /// Returns the address of the remote side of the channel this
+    /// `Participant` was created with. It's `None` for [`ConnectAddr::Mpsc`]
+    /// and when connecting to a [`ConnectAddr::WebSocket`] url. Behind a
+    /// reverse proxy this is the address of the proxy.
+    ///
+    /// [`ConnectAddr::Mpsc`]: crate::api::ConnectAddr::Mpsc
+    /// [`ConnectAddr::WebSocket`]: crate::api::ConnectAddr::WebSocket
+    pub fn peer_addr(&self) -> Option<SocketAddr> { self.peer_addr }
+
+    /// Returns the identity the remote proved when we connected to it over TCP
+    /// or WebSocket, also without [`connect_pinned`]. Store it to pin it the
+    /// next time. It's `None` for participants which connected to us.
+    ///
+    /// [`connect_pinned`]: Network::connect_pinned
+    pub fn remote_identity(&self) -> Option<PublicIdentity> { self.remote_identity }
 }
 
 impl Stream {
@@ -784,9 +991,33 @@ impl Stream {
             a2b_msg_s,
             b2a_msg_recv_r: Some(b2a_msg_recv_r),
             a2b_close_stream_s: Some(a2b_close_stream_s),
+            recorder: None,
+            record_sent: true,
+            #[cfg(feature = "compression")]
+            compression: Compression::from_promises(promises),
         }
     }
 
+    /// Records all [`Messages`] send and received by this `Stream` from now on.
+    /// See [`Recorder`].
+    ///
+    /// [`Messages`]: crate::message::Message
+    pub fn record(&mut self, recorder: Recorder) {
+        recorder.stream(
+            self.sid,
+            self.prio,
+            self.promises,
+            self.guaranteed_bandwidth,
+        );
+        self.recorder = Some(recorder);
+    }
+
+    /// Stops recording the [`Messages`] this `Stream` sends, e.g. because they
+    /// contain credentials. Received ones are still recorded.
+    ///
+    /// [`Messages`]: crate::message::Message
+    pub fn skip_recording_sent(&mut self) { self.record_sent = false; }
+
     /// use to send a arbitrary message to the remote side, by having the remote
     /// side also opened a `Stream` linked to this. the message will be
     /// [`Serialized`], which actually is quite slow compared to most other
@@ -891,7 +1122,7 @@ impl Stream {
     ///
     /// [`send`]: Stream::send
     /// [`Participants`]: crate::api::Participant
-    /// [`compress`]: lz_fear::raw::compress2
+    /// [`compress`]: crate::compression::Compression::compress
     /// [`Message::serialize`]: crate::message::Message::serialize
     pub fn send_raw(&mut self, message: &Message) -> Result<(), StreamError> {
         if self.send_closed.load(Ordering::Relaxed) {
@@ -900,6 +1131,9 @@ impl Stream {
         #[cfg(debug_assertions)]
         message.verify(self.params());
         self.a2b_msg_s.send((self.sid, message.data.clone()))?;
+        if let Some(recorder) = self.recorder.as_ref().filter(|_| self.record_sent) {
+            recorder.message(self.sid, Direction::Sent, &message.data);
+        }
         Ok(())
     }
 
@@ -977,16 +1211,21 @@ impl Stream {
     ///
     /// [`send_raw`]: Stream::send_raw
     /// [`recv`]: Stream::recv
-    /// [`decompress`]: lz_fear::raw::decompress_raw
+    /// [`decompress`]: crate::compression::Compression::decompress
     pub async fn recv_raw(&mut self) -> Result<Message, StreamError> {
         match &mut self.b2a_msg_recv_r {
             Some(b2a_msg_recv_r) => {
                 match b2a_msg_recv_r.recv().await {
-                    Ok(data) => Ok(Message {
-                        data,
-                        #[cfg(feature = "compression")]
-                        compressed: self.promises.contains(Promises::COMPRESSED),
-                    }),
+                    Ok(data) => {
+                        if let Some(recorder) = &self.recorder {
+                            recorder.message(self.sid, Direction::Received, &data);
+                        }
+                        Ok(Message {
+                            data,
+                            #[cfg(feature = "compression")]
+                            compressed: self.compression,
+                        })
+                    },
                     Err(_) => {
                         self.b2a_msg_recv_r = None; //prevent panic
                         Err(StreamError::StreamClosed)
@@ -1035,14 +1274,19 @@ impl Stream {
     pub fn try_recv<M: DeserializeOwned>(&mut self) -> Result<Option<M>, StreamError> {
         match &mut self.b2a_msg_recv_r {
             Some(b2a_msg_recv_r) => match b2a_msg_recv_r.try_recv() {
-                Ok(data) => Ok(Some(
-                    Message {
-                        data,
-                        #[cfg(feature = "compression")]
-                        compressed: self.promises.contains(Promises::COMPRESSED),
+                Ok(data) => {
+                    if let Some(recorder) = &self.recorder {
+                        recorder.message(self.sid, Direction::Received, &data);
                     }
-                    .deserialize()?,
-                )),
+                    Ok(Some(
+                        Message {
+                            data,
+                            #[cfg(feature = "compression")]
+                            compressed: self.compression,
+                        }
+                        .deserialize()?,
+                    ))
+                },
                 Err(async_channel::TryRecvError::Empty) => Ok(None),
                 Err(async_channel::TryRecvError::Closed) => {
                     self.b2a_msg_recv_r = None; //prevent panic
@@ -1053,9 +1297,16 @@ impl Stream {
         }
     }
 
+    /// Codec used for the messages of this `Stream`, `None` if it isn't
+    /// compressed
+    #[cfg(feature = "compression")]
+    pub fn compression(&self) -> Option<Compression> { self.compression }
+
     pub fn params(&self) -> StreamParams {
         StreamParams {
             promises: self.promises,
+            #[cfg(feature = "compression")]
+            compression: self.compression,
         }
     }
 }
@@ -1226,6 +1477,8 @@ impl core::fmt::Display for StreamError {
             StreamError::StreamClosed => write!(f, "stream closed"),
             #[cfg(feature = "compression")]
             StreamError::Compression(err) => write!(f, "compression error on message: {}", err),
+            #[cfg(feature = "compression")]
+            StreamError::Zstd(err) => write!(f, "zstd error on message: {}", err),
             StreamError::Deserialize(err) => write!(f, "deserialize error on message: {}", err),
         }
     }
@@ -1238,6 +1491,9 @@ impl core::fmt::Display for ParticipantError {
             ParticipantError::ProtocolFailedUnrecoverable => {
                 write!(f, "underlying protocol failed unrecoverable")
             },
+            ParticipantError::UnsupportedPromises => {
+                write!(f, "no channel supports the requested promises")
+            },
         }
     }
 }
@@ -1272,6 +1528,8 @@ impl core::cmp::PartialEq for StreamError {
                 StreamError::StreamClosed => true,
                 #[cfg(feature = "compression")]
                 StreamError::Compression(_) => false,
+                #[cfg(feature = "compression")]
+                StreamError::Zstd(_) => false,
                 StreamError::Deserialize(_) => false,
             },
             #[cfg(feature = "compression")]
@@ -1279,12 +1537,25 @@ impl core::cmp::PartialEq for StreamError {
                 StreamError::StreamClosed => false,
                 #[cfg(feature = "compression")]
                 StreamError::Compression(other_err) => err == other_err,
+                #[cfg(feature = "compression")]
+                StreamError::Zstd(_) => false,
+                StreamError::Deserialize(_) => false,
+            },
+            #[cfg(feature = "compression")]
+            StreamError::Zstd(err) => match other {
+                StreamError::StreamClosed => false,
+                #[cfg(feature = "compression")]
+                StreamError::Compression(_) => false,
+                #[cfg(feature = "compression")]
+                StreamError::Zstd(other_err) => partial_eq_io_error(err, other_err),
                 StreamError::Deserialize(_) => false,
             },
             StreamError::Deserialize(err) => match other {
                 StreamError::StreamClosed => false,
                 #[cfg(feature = "compression")]
                 StreamError::Compression(_) => false,
+                #[cfg(feature = "compression")]
+                StreamError::Zstd(_) => false,
                 StreamError::Deserialize(other_err) => partial_eq_bincode(err, other_err),
             },
         }
@@ -1,14 +1,24 @@
 use crate::api::NetworkConnectError;
+#[cfg(not(feature = "sim"))]
+use crate::util::sim::{SimConfig, SimDrain, SimSink};
 use async_trait::async_trait;
 use bytes::BytesMut;
 use futures_util::FutureExt;
-#[cfg(feature = "quic")]
+#[cfg(any(feature = "quic", feature = "websocket"))]
 use futures_util::StreamExt;
+#[cfg(feature = "websocket")]
+use futures_util::{
+    stream::{SplitSink, SplitStream},
+    SinkExt,
+};
 use hashbrown::HashMap;
+#[cfg(feature = "sim")]
+use network_protocol::sim::{SimConfig, SimData, SimDrain, SimSink};
 use network_protocol::{
-    Bandwidth, Cid, InitProtocolError, MpscMsg, MpscRecvProtocol, MpscSendProtocol, Pid,
-    ProtocolError, ProtocolEvent, ProtocolMetricCache, ProtocolMetrics, Sid, TcpRecvProtocol,
-    TcpSendProtocol, UnreliableDrain, UnreliableSink,
+    Bandwidth, Capabilities, Cid, Identity, InitProtocolError, MpscMsg, MpscRecvProtocol,
+    MpscSendProtocol, Pid, ProtocolError, ProtocolEvent, ProtocolMetricCache, ProtocolMetrics,
+    PublicIdentity, Sid, TcpRecvProtocol, TcpSendProtocol, UdpDataFormat, UdpRecvProtocol,
+    UdpSendProtocol, UnreliableDrain, UnreliableSink, UDP_SINK_TIMEOUT,
 };
 #[cfg(feature = "quic")]
 use network_protocol::{QuicDataFormat, QuicDataFormatStream, QuicRecvProtocol, QuicSendProtocol};
@@ -19,40 +29,69 @@ use std::{
         atomic::{AtomicU64, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net,
     net::tcp::{OwnedReadHalf, OwnedWriteHalf},
     select,
-    sync::{mpsc, oneshot, Mutex},
+    sync::{mpsc, mpsc::error::TrySendError, oneshot, Mutex},
+};
+#[cfg(feature = "websocket")]
+use tokio_tungstenite::{
+    tungstenite::{client::IntoClientRequest, Message as WsMessage},
+    MaybeTlsStream, WebSocketStream,
 };
 use tracing::{error, info, trace, warn};
 
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub(crate) enum Protocols {
-    Tcp((TcpSendProtocol<TcpDrain>, TcpRecvProtocol<TcpSink>)),
+    Tcp(
+        (
+            TcpSendProtocol<SimDrain<TcpDrain>>,
+            TcpRecvProtocol<SimSink<TcpSink>>,
+        ),
+    ),
+    Udp(
+        (
+            UdpSendProtocol<SimDrain<UdpDrain>>,
+            UdpRecvProtocol<SimDrain<UdpDrain>, SimSink<UdpSink>>,
+        ),
+    ),
     Mpsc((MpscSendProtocol<MpscDrain>, MpscRecvProtocol<MpscSink>)),
     #[cfg(feature = "quic")]
     Quic((QuicSendProtocol<QuicDrain>, QuicRecvProtocol<QuicSink>)),
+    #[cfg(feature = "websocket")]
+    WebSocket(
+        (
+            TcpSendProtocol<SimDrain<WebSocketDrain>>,
+            TcpRecvProtocol<SimSink<WebSocketSink>>,
+        ),
+    ),
 }
 
 #[derive(Debug)]
 pub(crate) enum SendProtocols {
-    Tcp(TcpSendProtocol<TcpDrain>),
+    Tcp(TcpSendProtocol<SimDrain<TcpDrain>>),
+    Udp(UdpSendProtocol<SimDrain<UdpDrain>>),
     Mpsc(MpscSendProtocol<MpscDrain>),
     #[cfg(feature = "quic")]
     Quic(QuicSendProtocol<QuicDrain>),
+    #[cfg(feature = "websocket")]
+    WebSocket(TcpSendProtocol<SimDrain<WebSocketDrain>>),
 }
 
 #[derive(Debug)]
 pub(crate) enum RecvProtocols {
-    Tcp(TcpRecvProtocol<TcpSink>),
+    Tcp(TcpRecvProtocol<SimSink<TcpSink>>),
+    Udp(UdpRecvProtocol<SimDrain<UdpDrain>, SimSink<UdpSink>>),
     Mpsc(MpscRecvProtocol<MpscSink>),
     #[cfg(feature = "quic")]
     Quic(QuicRecvProtocol<QuicSink>),
+    #[cfg(feature = "websocket")]
+    WebSocket(TcpRecvProtocol<SimSink<WebSocketSink>>),
 }
 
 lazy_static::lazy_static! {
@@ -66,12 +105,30 @@ pub(crate) type C2cMpscConnect = (
     oneshot::Sender<mpsc::Sender<MpscMsg>>,
 );
 
+/// accepted channel with the address of the remote, if it has one
+pub(crate) type C2sProtocol = (Protocols, Cid, Option<SocketAddr>);
+
 impl Protocols {
     const MPSC_CHANNEL_BOUND: usize = 1000;
+    /// new UDP channels accepted at once, before [`Self::UDP_ACCEPT_RATE`]
+    /// applies
+    const UDP_ACCEPT_BURST: f64 = 64.0;
+    /// new UDP channels accepted per second. Any datagram from an unknown
+    /// address opens a channel, which only closes once the remote was silent
+    /// for a while, so spoofed addresses could otherwise open unlimited ones.
+    const UDP_ACCEPT_RATE: f64 = 16.0;
+    /// datagrams exceeding this bound are dropped, like an overflowing socket
+    /// buffer would do
+    const UDP_CHANNEL_BOUND: usize = 1000;
+    /// after listening stopped, closed channels are checked at this interval,
+    /// the socket is released once all of them closed
+    const UDP_LISTEN_CLEANUP: Duration = Duration::from_secs(1);
+    const UDP_MAX_DATAGRAM_SIZE: usize = 65_507;
 
     pub(crate) async fn with_tcp_connect(
         addr: SocketAddr,
         metrics: ProtocolMetricCache,
+        sim: Option<SimConfig>,
     ) -> Result<Self, NetworkConnectError> {
         let stream = net::TcpStream::connect(addr)
             .await
@@ -84,7 +141,7 @@ impl Protocols {
             "Connecting Tcp to: {}",
             stream.peer_addr().map_err(NetworkConnectError::Io)?
         );
-        Ok(Self::new_tcp(stream, metrics))
+        Ok(Self::new_tcp(stream, metrics, sim))
     }
 
     pub(crate) async fn with_tcp_listen(
@@ -92,7 +149,8 @@ impl Protocols {
         cids: Arc<AtomicU64>,
         metrics: Arc<ProtocolMetrics>,
         s2s_stop_listening_r: oneshot::Receiver<()>,
-        c2s_protocol_s: mpsc::UnboundedSender<(Self, Cid)>,
+        c2s_protocol_s: mpsc::UnboundedSender<C2sProtocol>,
+        sim: Option<SimConfig>,
     ) -> std::io::Result<()> {
         let listener = net::TcpListener::bind(addr).await?;
         trace!(?addr, "Tcp Listener bound");
@@ -118,23 +176,216 @@ impl Protocols {
                 let cid = cids.fetch_add(1, Ordering::Relaxed);
                 info!(?remote_addr, ?cid, "Accepting Tcp from");
                 let metrics = ProtocolMetricCache::new(&cid.to_string(), Arc::clone(&metrics));
-                let _ = c2s_protocol_s.send((Self::new_tcp(stream, metrics.clone()), cid));
+                let _ = c2s_protocol_s.send((
+                    Self::new_tcp(stream, metrics, sim.clone()),
+                    cid,
+                    Some(remote_addr),
+                ));
             }
         });
         Ok(())
     }
 
-    pub(crate) fn new_tcp(stream: tokio::net::TcpStream, metrics: ProtocolMetricCache) -> Self {
+    pub(crate) fn new_tcp(
+        stream: tokio::net::TcpStream,
+        metrics: ProtocolMetricCache,
+        sim: Option<SimConfig>,
+    ) -> Self {
         let (r, w) = stream.into_split();
-        let sp = TcpSendProtocol::new(TcpDrain { half: w }, metrics.clone());
-        let rp = TcpRecvProtocol::new(
-            TcpSink {
-                half: r,
-                buffer: BytesMut::new(),
+        let drain = TcpDrain { half: w };
+        let sink = TcpSink {
+            half: r,
+            buffer: BytesMut::new(),
+        };
+        let (drain, sink) = simulate(drain, sink, sim, true);
+        let sp = TcpSendProtocol::new(drain, metrics.clone());
+        let rp = TcpRecvProtocol::new(sink, metrics);
+        Protocols::Tcp((sp, rp))
+    }
+
+    pub(crate) async fn with_udp_connect(
+        addr: SocketAddr,
+        metrics: ProtocolMetricCache,
+        sim: Option<SimConfig>,
+    ) -> Result<Self, NetworkConnectError> {
+        use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+        let bindsock = match addr {
+            SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+        };
+        let socket = net::UdpSocket::bind(bindsock)
+            .await
+            .map_err(NetworkConnectError::Io)?;
+        socket
+            .connect(addr)
+            .await
+            .map_err(NetworkConnectError::Io)?;
+        info!("Connecting Udp to: {}", addr);
+        let socket = Arc::new(socket);
+        let (datagram_s, datagram_r) = mpsc::channel(Self::UDP_CHANNEL_BOUND);
+        let reader = Arc::clone(&socket);
+        tokio::spawn(async move {
+            let mut buffer = vec![0u8; Self::UDP_MAX_DATAGRAM_SIZE];
+            loop {
+                let len = select! {
+                    next = reader.recv(&mut buffer) => match next {
+                        Ok(len) => len,
+                        Err(e) => {
+                            trace!(?e, "UdpSocket Error, closing channel");
+                            break;
+                        },
+                    },
+                    _ = datagram_s.closed() => break,
+                };
+                if let Err(TrySendError::Closed(_)) =
+                    datagram_s.try_send(BytesMut::from(&buffer[..len]))
+                {
+                    break;
+                }
+            }
+        });
+        Ok(Self::new_udp(
+            UdpDrain {
+                socket,
+                remote: None,
             },
+            datagram_r,
             metrics,
-        );
-        Protocols::Tcp((sp, rp))
+            sim,
+        ))
+    }
+
+    pub(crate) async fn with_udp_listen(
+        addr: SocketAddr,
+        cids: Arc<AtomicU64>,
+        metrics: Arc<ProtocolMetrics>,
+        s2s_stop_listening_r: oneshot::Receiver<()>,
+        c2s_protocol_s: mpsc::UnboundedSender<C2sProtocol>,
+        sim: Option<SimConfig>,
+    ) -> std::io::Result<()> {
+        let socket = Arc::new(net::UdpSocket::bind(addr).await?);
+        trace!(?addr, "Udp Listener bound");
+        let mut end_receiver = s2s_stop_listening_r.fuse();
+        tokio::spawn(async move {
+            // all channels share the socket, datagrams are dispatched by their
+            // remote address
+            let mut channels: HashMap<SocketAddr, mpsc::Sender<BytesMut>> = HashMap::new();
+            let mut accept_limit = UdpAcceptLimit::new();
+            let mut buffer = vec![0u8; Self::UDP_MAX_DATAGRAM_SIZE];
+            let mut listening = true;
+            loop {
+                let data = select! {
+                    next = socket.recv_from(&mut buffer) => Some(next),
+                    _ = &mut end_receiver => {
+                        listening = false;
+                        None
+                    },
+                    _ = tokio::time::sleep(Self::UDP_LISTEN_CLEANUP), if !listening => None,
+                };
+                if !listening {
+                    // established channels keep working, till they are closed
+                    channels.retain(|_, datagram_s| !datagram_s.is_closed());
+                    if channels.is_empty() {
+                        break;
+                    }
+                }
+                let (len, remote_addr) = match data {
+                    Some(Ok(data)) => data,
+                    Some(Err(e)) => {
+                        trace!(?e, "UdpSocket Error, ignoring datagram");
+                        continue;
+                    },
+                    None => continue,
+                };
+                let datagram = BytesMut::from(&buffer[..len]);
+                if let Some(datagram_s) = channels.get(&remote_addr) {
+                    match datagram_s.try_send(datagram) {
+                        Ok(()) | Err(TrySendError::Full(_)) => continue,
+                        Err(TrySendError::Closed(datagram)) => {
+                            if !listening {
+                                continue;
+                            }
+                            trace!(?remote_addr, "Udp channel closed, accepting again");
+                            channels.remove(&remote_addr);
+                            Self::accept_udp(
+                                &socket,
+                                &mut channels,
+                                &mut accept_limit,
+                                remote_addr,
+                                datagram,
+                                &cids,
+                                &metrics,
+                                &c2s_protocol_s,
+                                &sim,
+                            );
+                        },
+                    }
+                } else if listening {
+                    channels.retain(|_, datagram_s| !datagram_s.is_closed());
+                    Self::accept_udp(
+                        &socket,
+                        &mut channels,
+                        &mut accept_limit,
+                        remote_addr,
+                        datagram,
+                        &cids,
+                        &metrics,
+                        &c2s_protocol_s,
+                        &sim,
+                    );
+                }
+            }
+            trace!(?addr, "Udp Listener stopped");
+        });
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn accept_udp(
+        socket: &Arc<net::UdpSocket>,
+        channels: &mut HashMap<SocketAddr, mpsc::Sender<BytesMut>>,
+        accept_limit: &mut UdpAcceptLimit,
+        remote_addr: SocketAddr,
+        datagram: BytesMut,
+        cids: &AtomicU64,
+        metrics: &Arc<ProtocolMetrics>,
+        c2s_protocol_s: &mpsc::UnboundedSender<C2sProtocol>,
+        sim: &Option<SimConfig>,
+    ) {
+        // the remote keeps pinging till it's answered, so it gets in later
+        if !accept_limit.try_accept() {
+            trace!(?remote_addr, "Too many new Udp channels, ignoring datagram");
+            return;
+        }
+        let (datagram_s, datagram_r) = mpsc::channel(Self::UDP_CHANNEL_BOUND);
+        let _ = datagram_s.try_send(datagram);
+        channels.insert(remote_addr, datagram_s);
+        let cid = cids.fetch_add(1, Ordering::Relaxed);
+        info!(?remote_addr, ?cid, "Accepting Udp from");
+        let metrics = ProtocolMetricCache::new(&cid.to_string(), Arc::clone(metrics));
+        let drain = UdpDrain {
+            socket: Arc::clone(socket),
+            remote: Some(remote_addr),
+        };
+        let _ = c2s_protocol_s.send((
+            Self::new_udp(drain, datagram_r, metrics, sim.clone()),
+            cid,
+            Some(remote_addr),
+        ));
+    }
+
+    pub(crate) fn new_udp(
+        drain: UdpDrain,
+        receiver: mpsc::Receiver<BytesMut>,
+        metrics: ProtocolMetricCache,
+        sim: Option<SimConfig>,
+    ) -> Self {
+        let sink = UdpSink { receiver };
+        let (drain, sink) = simulate(drain, sink, sim, false);
+        let sp = UdpSendProtocol::new(drain.clone(), metrics.clone());
+        let rp = UdpRecvProtocol::new(drain, sink, &sp, metrics);
+        Protocols::Udp((sp, rp))
     }
 
     pub(crate) async fn with_mpsc_connect(
@@ -178,7 +429,7 @@ impl Protocols {
         cids: Arc<AtomicU64>,
         metrics: Arc<ProtocolMetrics>,
         s2s_stop_listening_r: oneshot::Receiver<()>,
-        c2s_protocol_s: mpsc::UnboundedSender<(Self, Cid)>,
+        c2s_protocol_s: mpsc::UnboundedSender<C2sProtocol>,
     ) -> std::io::Result<()> {
         let (mpsc_s, mut mpsc_r) = mpsc::unbounded_channel();
         MPSC_POOL.lock().await.insert(addr, mpsc_s);
@@ -201,6 +452,7 @@ impl Protocols {
                 let _ = c2s_protocol_s.send((
                     Self::new_mpsc(local_to_remote_s, remote_to_local_r, metrics.clone()),
                     cid,
+                    None,
                 ));
             }
             warn!("MpscStream Failed, stopping");
@@ -271,7 +523,7 @@ impl Protocols {
         cids: Arc<AtomicU64>,
         metrics: Arc<ProtocolMetrics>,
         s2s_stop_listening_r: oneshot::Receiver<()>,
-        c2s_protocol_s: mpsc::UnboundedSender<(Self, Cid)>,
+        c2s_protocol_s: mpsc::UnboundedSender<C2sProtocol>,
     ) -> std::io::Result<()> {
         let (_endpoint, mut listener) = match quinn::Endpoint::server(server_config, addr) {
             Ok(v) => v,
@@ -298,7 +550,7 @@ impl Protocols {
                 let metrics = ProtocolMetricCache::new(&cid.to_string(), Arc::clone(&metrics));
                 match Protocols::new_quic(connection, true, metrics).await {
                     Ok(quic) => {
-                        let _ = c2s_protocol_s.send((quic, cid));
+                        let _ = c2s_protocol_s.send((quic, cid, Some(remote_addr)));
                     },
                     Err(e) => {
                         trace!(?e, "failed to start quic");
@@ -343,6 +595,7 @@ impl Protocols {
             QuicSink {
                 con: connection.connection,
                 bi: connection.bi_streams,
+                datagrams: connection.datagrams,
                 recvstreams_r,
                 recvstreams_s,
                 sendstreams_s,
@@ -352,14 +605,205 @@ impl Protocols {
         Ok(Protocols::Quic((sp, rp)))
     }
 
+    #[cfg(feature = "websocket")]
+    pub(crate) async fn with_websocket_connect(
+        url: String,
+        metrics: ProtocolMetricCache,
+        sim: Option<SimConfig>,
+    ) -> Result<Self, NetworkConnectError> {
+        let ws_err = |e: tokio_tungstenite::tungstenite::Error| {
+            trace!(?e, "error with websocket connection");
+            NetworkConnectError::Io(io::Error::new(io::ErrorKind::ConnectionAborted, e))
+        };
+        let request = url.as_str().into_client_request().map_err(ws_err)?;
+        let host = url_host(request.uri()).unwrap_or_default().to_owned();
+        let port = request
+            .uri()
+            .port_u16()
+            .unwrap_or(match request.uri().scheme_str() {
+                Some("wss") => 443,
+                _ => 80,
+            });
+        let stream = net::TcpStream::connect((host.as_str(), port))
+            .await
+            .and_then(|s| {
+                s.set_nodelay(true)?;
+                Ok(s)
+            })
+            .map_err(NetworkConnectError::Io)?;
+        info!("Connecting WebSocket to: {}", url);
+        let (socket, _) = tokio_tungstenite::client_async_tls(request, stream)
+            .await
+            .map_err(ws_err)?;
+        Ok(Self::new_websocket(socket, metrics, sim))
+    }
+
+    #[cfg(feature = "websocket")]
+    pub(crate) async fn with_websocket_listen(
+        addr: SocketAddr,
+        cids: Arc<AtomicU64>,
+        metrics: Arc<ProtocolMetrics>,
+        s2s_stop_listening_r: oneshot::Receiver<()>,
+        c2s_protocol_s: mpsc::UnboundedSender<C2sProtocol>,
+        sim: Option<SimConfig>,
+    ) -> std::io::Result<()> {
+        let listener = net::TcpListener::bind(addr).await?;
+        trace!(?addr, "WebSocket Listener bound");
+        let mut end_receiver = s2s_stop_listening_r.fuse();
+        tokio::spawn(async move {
+            while let Some(data) = select! {
+                    next = listener.accept().fuse() => Some(next),
+                    _ = &mut end_receiver => None,
+            } {
+                let (stream, remote_addr) = match data {
+                    Ok((s, p)) => (s, p),
+                    Err(e) => {
+                        trace!(?e, "TcpStream Error, ignoring connection attempt");
+                        continue;
+                    },
+                };
+                if let Err(e) = stream.set_nodelay(true) {
+                    warn!(
+                        ?e,
+                        "Failed to set TCP_NODELAY, client may have degraded latency"
+                    );
+                }
+                let cids = Arc::clone(&cids);
+                let metrics = Arc::clone(&metrics);
+                let c2s_protocol_s = c2s_protocol_s.clone();
+                let sim = sim.clone();
+                // don't block other clients while one does the http upgrade
+                tokio::spawn(async move {
+                    let stream = MaybeTlsStream::Plain(stream);
+                    let socket = match tokio_tungstenite::accept_async(stream).await {
+                        Ok(socket) => socket,
+                        Err(e) => {
+                            tracing::debug!(?e, ?remote_addr, "skipping connection attempt");
+                            return;
+                        },
+                    };
+                    let cid = cids.fetch_add(1, Ordering::Relaxed);
+                    info!(?remote_addr, ?cid, "Accepting WebSocket from");
+                    let metrics = ProtocolMetricCache::new(&cid.to_string(), metrics);
+                    let _ = c2s_protocol_s.send((
+                        Self::new_websocket(socket, metrics, sim),
+                        cid,
+                        Some(remote_addr),
+                    ));
+                });
+            }
+        });
+        Ok(())
+    }
+
+    /// WebSocket is a reliable, ordered transport like TCP, so it uses the TCP
+    /// protocol, each drained chunk is send as one binary frame.
+    #[cfg(feature = "websocket")]
+    pub(crate) fn new_websocket(
+        socket: WebSocket,
+        metrics: ProtocolMetricCache,
+        sim: Option<SimConfig>,
+    ) -> Self {
+        let (w, r) = socket.split();
+        let drain = WebSocketDrain { half: w };
+        let sink = WebSocketSink { half: r };
+        let (drain, sink) = simulate(drain, sink, sim, true);
+        let sp = TcpSendProtocol::new(drain, metrics.clone());
+        let rp = TcpRecvProtocol::new(sink, metrics);
+        Protocols::WebSocket((sp, rp))
+    }
+
     pub(crate) fn split(self) -> (SendProtocols, RecvProtocols) {
         match self {
             Protocols::Tcp((s, r)) => (SendProtocols::Tcp(s), RecvProtocols::Tcp(r)),
+            Protocols::Udp((s, r)) => (SendProtocols::Udp(s), RecvProtocols::Udp(r)),
             Protocols::Mpsc((s, r)) => (SendProtocols::Mpsc(s), RecvProtocols::Mpsc(r)),
             #[cfg(feature = "quic")]
             Protocols::Quic((s, r)) => (SendProtocols::Quic(s), RecvProtocols::Quic(r)),
+            #[cfg(feature = "websocket")]
+            Protocols::WebSocket((s, r)) => {
+                (SendProtocols::WebSocket(s), RecvProtocols::WebSocket(r))
+            },
+        }
+    }
+
+    /// `identity` is proven to the remote when listening, `pinned` is required
+    /// from the remote when connecting. Returns `false` if this protocol can't
+    /// verify a `pinned` identity.
+    pub(crate) fn set_identity(
+        &mut self,
+        identity: Option<Arc<Identity>>,
+        pinned: Option<PublicIdentity>,
+    ) -> bool {
+        match self {
+            Protocols::Tcp((s, _)) => {
+                s.set_identity(identity);
+                s.pin_identity(pinned);
+                true
+            },
+            #[cfg(feature = "websocket")]
+            Protocols::WebSocket((s, _)) => {
+                s.set_identity(identity);
+                s.pin_identity(pinned);
+                true
+            },
+            _ => pinned.is_none(),
         }
     }
+
+    /// the identity the remote proved in the handshake, `None` when listening
+    pub(crate) fn remote_identity(&self) -> Option<PublicIdentity> {
+        match self {
+            Protocols::Tcp((s, _)) => s.remote_identity(),
+            #[cfg(feature = "websocket")]
+            Protocols::WebSocket((s, _)) => s.remote_identity(),
+            _ => None,
+        }
+    }
+}
+
+/// The host of a WebSocket url, IPv6 addresses without the brackets so that
+/// they can be connected to
+#[cfg(feature = "websocket")]
+fn url_host(uri: &tokio_tungstenite::tungstenite::http::Uri) -> Option<&str> {
+    let host = uri.host()?;
+    Some(
+        host.strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+            .unwrap_or(host),
+    )
+}
+
+/// Wraps `drain` and `sink` to simulate a bad network if `sim` is set,
+/// `reliable` ones keep the order and never lose data
+#[cfg(feature = "sim")]
+fn simulate<D, S>(
+    drain: D,
+    sink: S,
+    sim: Option<SimConfig>,
+    reliable: bool,
+) -> (SimDrain<D>, SimSink<S>)
+where
+    D: UnreliableDrain + 'static,
+    D::DataFormat: SimData,
+    S: UnreliableSink + 'static,
+    S::DataFormat: SimData,
+{
+    match sim {
+        Some(sim) => (
+            SimDrain::new(drain, sim.clone(), reliable),
+            SimSink::new(sink, sim, reliable),
+        ),
+        None => (SimDrain::Direct(drain), SimSink::Direct(sink)),
+    }
+}
+
+#[cfg(not(feature = "sim"))]
+fn simulate<D, S>(drain: D, sink: S, sim: Option<SimConfig>, _reliable: bool) -> (D, S) {
+    if let Some(sim) = sim {
+        match sim {}
+    }
+    (drain, sink)
 }
 
 #[async_trait]
@@ -369,12 +813,31 @@ impl network_protocol::InitProtocol for Protocols {
         initializer: bool,
         local_pid: Pid,
         secret: u128,
-    ) -> Result<(Pid, Sid, u128), InitProtocolError> {
+        capabilities: Capabilities,
+    ) -> Result<(Pid, Sid, u128, Capabilities), InitProtocolError> {
         match self {
-            Protocols::Tcp(p) => p.initialize(initializer, local_pid, secret).await,
-            Protocols::Mpsc(p) => p.initialize(initializer, local_pid, secret).await,
+            Protocols::Tcp(p) => {
+                p.initialize(initializer, local_pid, secret, capabilities)
+                    .await
+            },
+            Protocols::Udp(p) => {
+                p.initialize(initializer, local_pid, secret, capabilities)
+                    .await
+            },
+            Protocols::Mpsc(p) => {
+                p.initialize(initializer, local_pid, secret, capabilities)
+                    .await
+            },
             #[cfg(feature = "quic")]
-            Protocols::Quic(p) => p.initialize(initializer, local_pid, secret).await,
+            Protocols::Quic(p) => {
+                p.initialize(initializer, local_pid, secret, capabilities)
+                    .await
+            },
+            #[cfg(feature = "websocket")]
+            Protocols::WebSocket(p) => {
+                p.initialize(initializer, local_pid, secret, capabilities)
+                    .await
+            },
         }
     }
 }
@@ -384,18 +847,24 @@ impl network_protocol::SendProtocol for SendProtocols {
     fn notify_from_recv(&mut self, event: ProtocolEvent) {
         match self {
             SendProtocols::Tcp(s) => s.notify_from_recv(event),
+            SendProtocols::Udp(s) => s.notify_from_recv(event),
             SendProtocols::Mpsc(s) => s.notify_from_recv(event),
             #[cfg(feature = "quic")]
             SendProtocols::Quic(s) => s.notify_from_recv(event),
+            #[cfg(feature = "websocket")]
+            SendProtocols::WebSocket(s) => s.notify_from_recv(event),
         }
     }
 
     async fn send(&mut self, event: ProtocolEvent) -> Result<(), ProtocolError> {
         match self {
             SendProtocols::Tcp(s) => s.send(event).await,
+            SendProtocols::Udp(s) => s.send(event).await,
             SendProtocols::Mpsc(s) => s.send(event).await,
             #[cfg(feature = "quic")]
             SendProtocols::Quic(s) => s.send(event).await,
+            #[cfg(feature = "websocket")]
+            SendProtocols::WebSocket(s) => s.send(event).await,
         }
     }
 
@@ -406,9 +875,12 @@ impl network_protocol::SendProtocol for SendProtocols {
     ) -> Result<Bandwidth, ProtocolError> {
         match self {
             SendProtocols::Tcp(s) => s.flush(bandwidth, dt).await,
+            SendProtocols::Udp(s) => s.flush(bandwidth, dt).await,
             SendProtocols::Mpsc(s) => s.flush(bandwidth, dt).await,
             #[cfg(feature = "quic")]
             SendProtocols::Quic(s) => s.flush(bandwidth, dt).await,
+            #[cfg(feature = "websocket")]
+            SendProtocols::WebSocket(s) => s.flush(bandwidth, dt).await,
         }
     }
 }
@@ -418,9 +890,12 @@ impl network_protocol::RecvProtocol for RecvProtocols {
     async fn recv(&mut self) -> Result<ProtocolEvent, ProtocolError> {
         match self {
             RecvProtocols::Tcp(r) => r.recv().await,
+            RecvProtocols::Udp(r) => r.recv().await,
             RecvProtocols::Mpsc(r) => r.recv().await,
             #[cfg(feature = "quic")]
             RecvProtocols::Quic(r) => r.recv().await,
+            #[cfg(feature = "websocket")]
+            RecvProtocols::WebSocket(r) => r.recv().await,
         }
     }
 }
@@ -464,6 +939,128 @@ impl UnreliableSink for TcpSink {
     }
 }
 
+///////////////////////////////////////
+//// UDP
+#[derive(Debug, Clone)]
+pub struct UdpDrain {
+    socket: Arc<net::UdpSocket>,
+    /// `None` if the socket is connected to the remote
+    remote: Option<SocketAddr>,
+}
+
+#[derive(Debug)]
+pub struct UdpSink {
+    receiver: mpsc::Receiver<BytesMut>,
+}
+
+#[async_trait]
+impl UnreliableDrain for UdpDrain {
+    type DataFormat = BytesMut;
+
+    async fn send(&mut self, data: Self::DataFormat) -> Result<(), ProtocolError> {
+        let result = match self.remote {
+            Some(remote) => self.socket.send_to(&data, remote).await,
+            None => self.socket.send(&data).await,
+        };
+        match result {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ProtocolError::Closed),
+        }
+    }
+}
+
+#[async_trait]
+impl UnreliableSink for UdpSink {
+    type DataFormat = UdpDataFormat;
+
+    async fn recv(&mut self) -> Result<Self::DataFormat, ProtocolError> {
+        match tokio::time::timeout(UDP_SINK_TIMEOUT, self.receiver.recv()).await {
+            Ok(Some(datagram)) => Ok(UdpDataFormat::Datagram(datagram)),
+            Ok(None) => Err(ProtocolError::Closed),
+            Err(_) => Ok(UdpDataFormat::Idle),
+        }
+    }
+}
+
+/// Token bucket limiting how fast a UDP listener accepts new channels
+#[derive(Debug)]
+struct UdpAcceptLimit {
+    tokens: f64,
+    last: Instant,
+}
+
+impl UdpAcceptLimit {
+    fn new() -> Self {
+        Self {
+            tokens: Protocols::UDP_ACCEPT_BURST,
+            last: Instant::now(),
+        }
+    }
+
+    fn try_accept(&mut self) -> bool {
+        let now = Instant::now();
+        let refill = now.duration_since(self.last).as_secs_f64() * Protocols::UDP_ACCEPT_RATE;
+        self.tokens = (self.tokens + refill).min(Protocols::UDP_ACCEPT_BURST);
+        self.last = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+///////////////////////////////////////
+//// WEBSOCKET
+#[cfg(feature = "websocket")]
+pub(crate) type WebSocket = WebSocketStream<MaybeTlsStream<net::TcpStream>>;
+
+#[cfg(feature = "websocket")]
+#[derive(Debug)]
+pub struct WebSocketDrain {
+    half: SplitSink<WebSocket, WsMessage>,
+}
+
+#[cfg(feature = "websocket")]
+#[derive(Debug)]
+pub struct WebSocketSink {
+    half: SplitStream<WebSocket>,
+}
+
+#[cfg(feature = "websocket")]
+#[async_trait]
+impl UnreliableDrain for WebSocketDrain {
+    type DataFormat = BytesMut;
+
+    async fn send(&mut self, data: Self::DataFormat) -> Result<(), ProtocolError> {
+        match self.half.send(WsMessage::Binary(data.to_vec())).await {
+            Ok(()) => Ok(()),
+            Err(_) => Err(ProtocolError::Closed),
+        }
+    }
+}
+
+#[cfg(feature = "websocket")]
+#[async_trait]
+impl UnreliableSink for WebSocketSink {
+    type DataFormat = BytesMut;
+
+    async fn recv(&mut self) -> Result<Self::DataFormat, ProtocolError> {
+        loop {
+            match self.half.next().await {
+                Some(Ok(WsMessage::Binary(data))) => return Ok(BytesMut::from(&data[..])),
+                // pings are answered by tungstenite itself
+                Some(Ok(WsMessage::Ping(_) | WsMessage::Pong(_))) => continue,
+                Some(Ok(WsMessage::Text(_))) => return Err(ProtocolError::Violated),
+                Some(Ok(WsMessage::Close(_) | WsMessage::Frame(_))) | Some(Err(_)) | None => {
+                    return Err(ProtocolError::Closed);
+                },
+            }
+        }
+    }
+}
+
 ///////////////////////////////////////
 //// MPSC
 #[derive(Debug)]
@@ -523,6 +1120,7 @@ pub struct QuicSink {
     #[allow(dead_code)]
     con: quinn::Connection,
     bi: quinn::IncomingBiStreams,
+    datagrams: quinn::Datagrams,
     recvstreams_r: mpsc::UnboundedReceiver<QuicStream>,
     recvstreams_s: mpsc::UnboundedSender<QuicStream>,
     sendstreams_s: mpsc::UnboundedSender<quinn::SendStream>,
@@ -551,7 +1149,17 @@ impl UnreliableDrain for QuicDrain {
     async fn send(&mut self, data: Self::DataFormat) -> Result<(), ProtocolError> {
         match match data.stream {
             QuicDataFormatStream::Main => self.main.write_all(&data.data).await,
-            QuicDataFormatStream::Unreliable => unimplemented!(),
+            QuicDataFormatStream::Unreliable => {
+                return match self.con.send_datagram(data.data.freeze()) {
+                    Ok(()) => Ok(()),
+                    Err(quinn::SendDatagramError::ConnectionLost(_)) => Err(ProtocolError::Closed),
+                    Err(e) => {
+                        // datagrams may get lost anyway, the stream didn't promise delivery
+                        trace!(?e, "dropping datagram");
+                        Ok(())
+                    },
+                };
+            },
             QuicDataFormatStream::Reliable(sid) => {
                 use hashbrown::hash_map::Entry;
                 //tracing::trace!(?sid, "Reliable");
@@ -603,6 +1211,15 @@ impl UnreliableSink for QuicSink {
                 biased;
                 Some(n) = self.bi.next().fuse() => (Some(n), None),
                 Some(n) = self.recvstreams_r.recv().fuse() => (None, Some(n)),
+                Some(datagram) = self.datagrams.next().fuse() => {
+                    return match datagram {
+                        Ok(data) => Ok(QuicDataFormat {
+                            stream: QuicDataFormatStream::Unreliable,
+                            data: BytesMut::from(&data[..]),
+                        }),
+                        Err(_) => Err(ProtocolError::Closed),
+                    };
+                },
             };
 
             if let Some(remote_stream) = a {
@@ -668,8 +1285,8 @@ mod tests {
         let client = TcpStream::connect("127.0.0.1:5000").await.unwrap();
         let (_listener, server) = r1.await.unwrap();
         let metrics = ProtocolMetricCache::new("0", Arc::new(ProtocolMetrics::new().unwrap()));
-        let client = Protocols::new_tcp(client, metrics.clone());
-        let server = Protocols::new_tcp(server, metrics);
+        let client = Protocols::new_tcp(client, metrics.clone(), None);
+        let server = Protocols::new_tcp(server, metrics, None);
         let (mut s, _) = client.split();
         let (_, mut r) = server.split();
         let event = ProtocolEvent::OpenStream {
@@ -717,8 +1334,8 @@ mod tests {
         let client = TcpStream::connect("127.0.0.1:5001").await.unwrap();
         let (_listener, server) = r1.await.unwrap();
         let metrics = ProtocolMetricCache::new("0", Arc::new(ProtocolMetrics::new().unwrap()));
-        let client = Protocols::new_tcp(client, metrics.clone());
-        let server = Protocols::new_tcp(server, metrics);
+        let client = Protocols::new_tcp(client, metrics.clone(), None);
+        let server = Protocols::new_tcp(server, metrics, None);
         let (s, _) = client.split();
         let (_, mut r) = server.split();
         let e = tokio::spawn(async move { r.recv().await });
@@ -727,4 +1344,17 @@ mod tests {
         assert!(e.is_err());
         assert_eq!(e.unwrap_err(), ProtocolError::Closed);
     }
+
+    #[cfg(feature = "websocket")]
+    #[test]
+    fn websocket_url_host() {
+        for (url, host) in [
+            ("ws://[::1]:14004", "::1"),
+            ("ws://127.0.0.1:14004", "127.0.0.1"),
+            ("wss://example.com/veloren", "example.com"),
+        ] {
+            let request = url.into_client_request().unwrap();
+            assert_eq!(url_host(request.uri()), Some(host));
+        }
+    }
 }
@@ -0,0 +1,154 @@
+use crate::api::StreamError;
+use network_protocol::Promises;
+use std::io::{self, Read};
+
+/// Codec used for [`Promises::COMPRESSED`] [`Streams`]. The codec is chosen
+/// when opening a `Stream` with [`open_compressed`] and known to the remote
+/// via its [`Promises`], the level only matters for the sending side.
+///
+/// [`Streams`]: crate::api::Stream
+/// [`open_compressed`]: crate::api::Participant::open_compressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// very fast with a decent ratio, the default
+    Lz4,
+    /// better ratio for larger messages, e.g. terrain. `level` goes from 1
+    /// (fast) to 22 (small), see [`Compression::ZSTD_DEFAULT_LEVEL`]
+    Zstd { level: i32 },
+}
+
+impl Default for Compression {
+    fn default() -> Self { Self::Lz4 }
+}
+
+impl Compression {
+    pub const ZSTD_DEFAULT_LEVEL: i32 = 3;
+    /// larger zstd messages fail to decompress, so that a malicious remote
+    /// can't make us allocate arbitrary amounts of memory
+    pub const ZSTD_MAX_DECOMPRESSED_LEN: usize = 64 * 1024 * 1024;
+
+    /// codec of a `Stream` with these `promises`, `None` if it isn't
+    /// compressed
+    pub(crate) fn from_promises(promises: Promises) -> Option<Self> {
+        if !promises.contains(Promises::COMPRESSED) {
+            None
+        } else if promises.contains(Promises::COMPRESSED_ZSTD) {
+            Some(Self::Zstd {
+                level: Self::ZSTD_DEFAULT_LEVEL,
+            })
+        } else {
+            Some(Self::Lz4)
+        }
+    }
+
+    /// `Promises` which tell the remote about this codec
+    pub(crate) fn promises(&self) -> Promises {
+        match self {
+            Self::Lz4 => Promises::COMPRESSED,
+            Self::Zstd { .. } => Promises::COMPRESSED | Promises::COMPRESSED_ZSTD,
+        }
+    }
+
+    /// whether data compressed by `self` can be decompressed by `other`
+    pub(crate) fn same_codec(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    pub fn compress(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Lz4 => {
+                let mut compressed_data = Vec::with_capacity(data.len() / 4 + 10);
+                let mut table = lz_fear::raw::U32Table::default();
+                lz_fear::raw::compress2(data, 0, &mut table, &mut compressed_data).unwrap();
+                compressed_data
+            },
+            //this will never fail when writing into memory
+            Self::Zstd { level } => zstd::bulk::compress(data, *level).unwrap(),
+        }
+    }
+
+    pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, StreamError> {
+        match self {
+            Self::Lz4 => {
+                let mut uncompressed_data = Vec::with_capacity(data.len() * 2);
+                lz_fear::raw::decompress_raw(data, &[0; 0], &mut uncompressed_data, usize::MAX)
+                    .map_err(StreamError::Compression)?;
+                Ok(uncompressed_data)
+            },
+            Self::Zstd { .. } => {
+                decompress_zstd(data, Self::ZSTD_MAX_DECOMPRESSED_LEN).map_err(StreamError::Zstd)
+            },
+        }
+    }
+}
+
+fn decompress_zstd(data: &[u8], max_len: usize) -> io::Result<Vec<u8>> {
+    let mut uncompressed_data = Vec::with_capacity(data.len() * 2);
+    // read one byte more to tell a message of exactly `max_len` from a larger one
+    zstd::stream::read::Decoder::new(data)?
+        .take(max_len as u64 + 1)
+        .read_to_end(&mut uncompressed_data)?;
+    if uncompressed_data.len() > max_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("decompressed message is larger than {} bytes", max_len),
+        ));
+    }
+    Ok(uncompressed_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let data = b"Hello World, Hello World, Hello World".repeat(20);
+        for compression in [
+            Compression::Lz4,
+            Compression::Zstd { level: 1 },
+            Compression::Zstd { level: 19 },
+        ] {
+            let compressed = compression.compress(&data);
+            assert!(compressed.len() < data.len(), "{:?}", compression);
+            assert_eq!(compression.decompress(&compressed).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn promises() {
+        assert_eq!(Compression::from_promises(Promises::ORDERED), None);
+        for compression in [Compression::Lz4, Compression::Zstd {
+            level: Compression::ZSTD_DEFAULT_LEVEL,
+        }] {
+            assert_eq!(
+                Compression::from_promises(compression.promises() | Promises::ORDERED),
+                Some(compression)
+            );
+        }
+    }
+
+    #[test]
+    fn corrupt_zstd() {
+        let compression = Compression::Zstd { level: 3 };
+        let mut compressed = compression.compress(&[42u8; 1000]);
+        compressed.truncate(compressed.len() / 2);
+        assert!(matches!(
+            compression.decompress(&compressed),
+            Err(StreamError::Zstd(_))
+        ));
+        assert!(!compression.same_codec(&Compression::Lz4));
+    }
+
+    #[test]
+    fn zstd_bomb() {
+        let compressed = Compression::Zstd { level: 3 }.compress(&[0u8; 100_000]);
+        assert!(compressed.len() < 1000);
+        assert_eq!(
+            decompress_zstd(&compressed, 100_000).unwrap().len(),
+            100_000
+        );
+        let err = decompress_zstd(&compressed, 99_999).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -12,7 +12,8 @@
 //! Say you have an application that wants to communicate with other application
 //! over a Network or on the same computer. Now each application instances the
 //! struct [`Network`] once with a new [`Pid`]. The Pid is necessary to identify
-//! other [`Networks`] over the network protocols (e.g. TCP, UDP, QUIC, MPSC)
+//! other [`Networks`] over the network protocols (e.g. TCP, UDP, QUIC,
+//! WebSocket, MPSC)
 //!
 //! To connect to another application, you must know it's [`ConnectAddr`]. One
 //! side will call [`connect`], the other [`connected`]. If successful both
@@ -100,9 +101,11 @@
 
 mod api;
 mod channel;
+#[cfg(feature = "compression")] mod compression;
 mod message;
 mod metrics;
 mod participant;
+mod record;
 mod scheduler;
 mod util;
 
@@ -110,5 +113,12 @@ pub use api::{
     ConnectAddr, ListenAddr, Network, NetworkConnectError, NetworkError, Participant,
     ParticipantError, Stream, StreamError, StreamParams,
 };
+#[cfg(feature = "compression")]
+pub use compression::Compression;
 pub use message::Message;
-pub use network_protocol::{InitProtocolError, Pid, Promises};
+#[cfg(feature = "sim")]
+pub use network_protocol::sim::SimConfig;
+pub use network_protocol::{
+    Capabilities, Identity, InitProtocolError, Pid, Promises, PublicIdentity,
+};
+pub use record::{Direction, RecordedMessage, RecordedStream, Recorder, Recording};
@@ -1,7 +1,7 @@
 use crate::api::{StreamError, StreamParams};
-use bytes::Bytes;
 #[cfg(feature = "compression")]
-use network_protocol::Promises;
+use crate::compression::Compression;
+use bytes::Bytes;
 use serde::{de::DeserializeOwned, Serialize};
 use std::io;
 #[cfg(all(feature = "compression", debug_assertions))]
@@ -17,7 +17,7 @@ use tracing::warn;
 pub struct Message {
     pub(crate) data: Bytes,
     #[cfg(feature = "compression")]
-    pub(crate) compressed: bool,
+    pub(crate) compressed: Option<Compression>,
 }
 
 impl Message {
@@ -32,7 +32,7 @@ impl Message {
     ///
     /// [`send_raw`]: crate::api::Stream::send_raw
     /// [`Participants`]: crate::api::Participant
-    /// [`compress`]: lz_fear::raw::compress2
+    /// [`compress`]: crate::compression::Compression::compress
     /// [`Message::serialize`]: crate::message::Message::serialize
     ///
     /// [`Streams`]: crate::api::Stream
@@ -41,15 +41,11 @@ impl Message {
         let serialized_data = bincode::serialize(message).unwrap();
 
         #[cfg(feature = "compression")]
-        let compressed = stream_params.promises.contains(Promises::COMPRESSED);
+        let compressed = stream_params.compression;
         #[cfg(feature = "compression")]
-        let data = if compressed {
-            let mut compressed_data = Vec::with_capacity(serialized_data.len() / 4 + 10);
-            let mut table = lz_fear::raw::U32Table::default();
-            lz_fear::raw::compress2(&serialized_data, 0, &mut table, &mut compressed_data).unwrap();
-            compressed_data
-        } else {
-            serialized_data
+        let data = match compressed {
+            Some(compression) => compression.compress(&serialized_data),
+            None => serialized_data,
         };
         #[cfg(not(feature = "compression"))]
         let data = serialized_data;
@@ -103,21 +99,9 @@ impl Message {
         let uncompressed_data = self.data;
 
         #[cfg(feature = "compression")]
-        let uncompressed_data = if self.compressed {
-            {
-                let mut uncompressed_data = Vec::with_capacity(self.data.len() * 2);
-                if let Err(e) = lz_fear::raw::decompress_raw(
-                    &self.data,
-                    &[0; 0],
-                    &mut uncompressed_data,
-                    usize::MAX,
-                ) {
-                    return Err(StreamError::Compression(e));
-                }
-                Bytes::from(uncompressed_data)
-            }
-        } else {
-            self.data
+        let uncompressed_data = match self.compressed {
+            Some(compression) => Bytes::from(compression.decompress(&self.data)?),
+            None => self.data,
         };
 
         match bincode::deserialize(&uncompressed_data) {
@@ -131,10 +115,15 @@ impl Message {
         #[cfg(not(feature = "compression"))]
         let _params = params;
         #[cfg(feature = "compression")]
-        if self.compressed != params.promises.contains(Promises::COMPRESSED) {
+        let matches = match (self.compressed, params.compression) {
+            (Some(msg), Some(stream)) => msg.same_codec(&stream),
+            (msg, stream) => msg.is_none() && stream.is_none(),
+        };
+        #[cfg(feature = "compression")]
+        if !matches {
             warn!(
                 ?params,
-                "verify failed, msg is {} and it doesn't match with stream", self.compressed
+                "verify failed, msg is {:?} and it doesn't match with stream", self.compressed
             );
         }
     }
@@ -172,19 +161,24 @@ pub(crate) fn partial_eq_bincode(first: &bincode::ErrorKind, second: &bincode::E
 #[cfg(test)]
 mod tests {
     use crate::{api::StreamParams, message::*};
+    use network_protocol::Promises;
 
     fn stub_stream(compressed: bool) -> StreamParams {
         #[cfg(feature = "compression")]
-        let promises = if compressed {
-            Promises::COMPRESSED
+        let compression = if compressed {
+            Some(Compression::Lz4)
         } else {
-            Promises::empty()
+            None
         };
 
         #[cfg(not(feature = "compression"))]
-        let promises = Promises::empty();
+        let _compressed = compressed;
 
-        StreamParams { promises }
+        StreamParams {
+            promises: Promises::empty(),
+            #[cfg(feature = "compression")]
+            compression,
+        }
     }
 
     #[test]
@@ -258,4 +252,17 @@ mod tests {
         let msg = Message::serialize(&msg, stub_stream(true));
         assert_eq!(msg.data.len(), 1331);
     }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn serialize_compress_zstd() {
+        let params = StreamParams {
+            promises: Promises::COMPRESSED | Promises::COMPRESSED_ZSTD,
+            compression: Some(Compression::Zstd { level: 19 }),
+        };
+        let msg = vec!["assets/data/plants/flowers/greenrose.ron"; 20];
+        let compressed = Message::serialize(&msg, params);
+        assert!(compressed.data.len() < 100);
+        assert_eq!(compressed.deserialize::<Vec<String>>().unwrap(), msg);
+    }
 }
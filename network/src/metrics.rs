@@ -10,6 +10,8 @@ pub(crate) enum ProtocolInfo {
     Udp(SocketAddr),
     #[cfg(feature = "quic")]
     Quic(SocketAddr),
+    #[cfg(feature = "websocket")]
+    WebSocket(SocketAddr),
     Mpsc(u64),
 }
 
@@ -20,6 +22,8 @@ impl From<ListenAddr> for ProtocolInfo {
             ListenAddr::Udp(s) => ProtocolInfo::Udp(s),
             #[cfg(feature = "quic")]
             ListenAddr::Quic(s, _) => ProtocolInfo::Quic(s),
+            #[cfg(feature = "websocket")]
+            ListenAddr::WebSocket(s) => ProtocolInfo::WebSocket(s),
             ListenAddr::Mpsc(s) => ProtocolInfo::Mpsc(s),
         }
     }
@@ -251,6 +255,8 @@ fn protocolconnect_name(protocol: &ConnectAddr) -> &str {
         ConnectAddr::Mpsc(_) => "mpsc",
         #[cfg(feature = "quic")]
         ConnectAddr::Quic(_, _, _) => "quic",
+        #[cfg(feature = "websocket")]
+        ConnectAddr::WebSocket(_) => "websocket",
     }
 }
 
@@ -262,6 +268,8 @@ fn protocollisten_name(protocol: &ListenAddr) -> &str {
         ListenAddr::Mpsc(_) => "mpsc",
         #[cfg(feature = "quic")]
         ListenAddr::Quic(_, _) => "quic",
+        #[cfg(feature = "websocket")]
+        ListenAddr::WebSocket(_) => "websocket",
     }
 }
 
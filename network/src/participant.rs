@@ -8,12 +8,13 @@ use bytes::Bytes;
 use futures_util::{FutureExt, StreamExt};
 use hashbrown::HashMap;
 use network_protocol::{
-    Bandwidth, Cid, Pid, Prio, Promises, ProtocolEvent, RecvProtocol, SendProtocol, Sid,
-    _internal::SortedVec,
+    Bandwidth, Capabilities, Cid, Pid, Prio, Promises, ProtocolEvent, RecvProtocol, SendProtocol,
+    Sid, _internal::SortedVec,
 };
 use std::{
+    collections::VecDeque,
     sync::{
-        atomic::{AtomicBool, AtomicI32, Ordering},
+        atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering},
         Arc,
     },
     time::{Duration, Instant},
@@ -26,10 +27,17 @@ use tokio::{
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tracing::*;
 
-pub(crate) type A2bStreamOpen = (Prio, Promises, Bandwidth, oneshot::Sender<Stream>);
+pub(crate) type A2bStreamOpen = (
+    Prio,
+    Promises,
+    Bandwidth,
+    oneshot::Sender<Result<Stream, ParticipantError>>,
+);
 pub(crate) type S2bCreateChannel = (Cid, Sid, Protocols, oneshot::Sender<()>);
 pub(crate) type S2bShutdownBparticipant = (Duration, oneshot::Sender<Result<(), ParticipantError>>);
 pub(crate) type B2sPrioStatistic = (Pid, u64, u64);
+/// `true` once all channels of a participant broke, `false` once it got resumed
+pub(crate) type B2sResume = (Pid, bool);
 
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -42,10 +50,49 @@ struct ChannelInfo {
 struct StreamInfo {
     #[allow(dead_code)]
     prio: Prio,
-    #[allow(dead_code)]
     promises: Promises,
     send_closed: Arc<AtomicBool>,
     b2a_msg_recv_s: Mutex<async_channel::Sender<Bytes>>,
+    received: AtomicU64,
+    opened: Instant,
+    /// microseconds after `opened` the last message was received
+    last_received: AtomicU64,
+}
+
+impl StreamInfo {
+    /// messages received so far and the time since the last of them arrived
+    fn received(&self) -> (u64, Duration) {
+        let count = self.received.load(Ordering::Relaxed);
+        let last = Duration::from_micros(self.last_received.load(Ordering::Relaxed));
+        (count, self.opened.elapsed().saturating_sub(last))
+    }
+}
+
+/// Messages of a stream with [`Promises::GUARANTEED_DELIVERY`] which the
+/// remote didn't acknowledge yet. They are send again after a channel broke.
+#[derive(Debug, Default)]
+struct ReplayBuffer {
+    acked: u64,
+    messages: VecDeque<Bytes>,
+    /// total length of `messages`
+    bytes: usize,
+    /// waiting for the remote to tell us where to continue
+    resuming: bool,
+}
+
+impl ReplayBuffer {
+    fn push(&mut self, data: Bytes) {
+        self.bytes += data.len();
+        self.messages.push_back(data);
+    }
+
+    fn acknowledge(&mut self, count: u64) {
+        let n = (count.saturating_sub(self.acked) as usize).min(self.messages.len());
+        for data in self.messages.drain(..n) {
+            self.bytes -= data.len();
+        }
+        self.acked += n as u64;
+    }
 }
 
 #[derive(Debug)]
@@ -69,19 +116,31 @@ pub struct BParticipant {
     remote_pid: Pid,
     remote_pid_string: String, //optimisation
     offset_sid: Sid,
+    capabilities: Capabilities,
     channels: Arc<RwLock<HashMap<Cid, Mutex<ChannelInfo>>>>,
     streams: RwLock<HashMap<Sid, StreamInfo>>,
     run_channels: Option<ControlChannels>,
     shutdown_barrier: AtomicI32,
+    shutdown_requested: AtomicBool,
+    resume_deadline: Mutex<Option<Instant>>,
     metrics: Arc<NetworkMetrics>,
     open_stream_channels: Arc<Mutex<Option<OpenStreamInfo>>>,
 }
 
 impl BParticipant {
+    const ACK_INTERVAL: Duration = Duration::from_millis(500);
     // We use integer instead of Barrier to not block mgr from freeing at the end
     const BARR_CHANNEL: i32 = 1;
     const BARR_RECV: i32 = 4;
     const BARR_SEND: i32 = 2;
+    /// Unacknowledged bytes of all streams after which the remote is
+    /// considered stuck and the participant is closed
+    const MAX_REPLAY_BYTES: usize = 64 * 1024 * 1024;
+    /// Unacknowledged messages of all streams, see [`Self::MAX_REPLAY_BYTES`]
+    const MAX_REPLAY_MESSAGES: usize = 1_000_000;
+    /// How long a participant is kept alive after all its channels broke, so
+    /// that the remote can resume it with its handshake secret
+    pub(crate) const RESUME_GRACE: Duration = Duration::from_secs(20);
     const TICK_TIME: Duration = Duration::from_millis(Self::TICK_TIME_MS);
     const TICK_TIME_MS: u64 = 5;
 
@@ -90,6 +149,7 @@ impl BParticipant {
         local_pid: Pid,
         remote_pid: Pid,
         offset_sid: Sid,
+        capabilities: Capabilities,
         metrics: Arc<NetworkMetrics>,
     ) -> (
         Self,
@@ -119,11 +179,14 @@ impl BParticipant {
                 remote_pid,
                 remote_pid_string: remote_pid.to_string(),
                 offset_sid,
+                capabilities,
                 channels: Arc::new(RwLock::new(HashMap::new())),
                 streams: RwLock::new(HashMap::new()),
                 shutdown_barrier: AtomicI32::new(
                     Self::BARR_CHANNEL + Self::BARR_SEND + Self::BARR_RECV,
                 ),
+                shutdown_requested: AtomicBool::new(false),
+                resume_deadline: Mutex::new(None),
                 run_channels,
                 metrics,
                 open_stream_channels: Arc::new(Mutex::new(None)),
@@ -136,19 +199,27 @@ impl BParticipant {
         )
     }
 
-    pub async fn run(mut self, b2s_prio_statistic_s: mpsc::UnboundedSender<B2sPrioStatistic>) {
+    pub async fn run(
+        mut self,
+        b2s_prio_statistic_s: mpsc::UnboundedSender<B2sPrioStatistic>,
+        b2s_resume_s: mpsc::UnboundedSender<B2sResume>,
+    ) {
         let (b2b_add_send_protocol_s, b2b_add_send_protocol_r) =
             mpsc::unbounded_channel::<(Cid, SendProtocols)>();
         let (b2b_add_recv_protocol_s, b2b_add_recv_protocol_r) =
             mpsc::unbounded_channel::<(Cid, RecvProtocols)>();
         let (b2b_close_send_protocol_s, b2b_close_send_protocol_r) =
             async_channel::unbounded::<Cid>();
+        let (b2b_lost_send_protocol_s, b2b_lost_send_protocol_r) =
+            async_channel::unbounded::<Cid>();
         let (b2b_force_close_recv_protocol_s, b2b_force_close_recv_protocol_r) =
             async_channel::unbounded::<Cid>();
         let (b2b_notify_send_of_recv_open_s, b2b_notify_send_of_recv_open_r) =
             crossbeam_channel::unbounded::<(Cid, Sid, Prio, Promises, u64)>();
         let (b2b_notify_send_of_recv_close_s, b2b_notify_send_of_recv_close_r) =
             crossbeam_channel::unbounded::<(Cid, Sid)>();
+        let (b2b_notify_send_of_recv_ack_s, b2b_notify_send_of_recv_ack_r) =
+            crossbeam_channel::unbounded::<(Cid, Sid, u64, Duration)>();
 
         let (a2b_close_stream_s, a2b_close_stream_r) = mpsc::unbounded_channel::<Sid>();
         let (a2b_msg_s, a2b_msg_r) = crossbeam_channel::unbounded::<(Sid, Bytes)>();
@@ -166,9 +237,13 @@ impl BParticipant {
                 a2b_msg_r,
                 b2b_add_send_protocol_r,
                 b2b_close_send_protocol_r,
+                b2b_lost_send_protocol_r,
                 b2b_notify_send_of_recv_open_r,
                 b2b_notify_send_of_recv_close_r,
+                b2b_notify_send_of_recv_ack_r,
+                b2b_force_close_recv_protocol_s.clone(),
                 b2s_prio_statistic_s,
+                b2s_resume_s.clone(),
                 run_channels.b2a_bandwidth_stats_s,
             )
             .instrument(tracing::info_span!("send")),
@@ -177,8 +252,11 @@ impl BParticipant {
                 b2b_add_recv_protocol_r,
                 b2b_force_close_recv_protocol_r,
                 b2b_close_send_protocol_s.clone(),
+                b2b_lost_send_protocol_s,
                 b2b_notify_send_of_recv_open_s,
                 b2b_notify_send_of_recv_close_s,
+                b2b_notify_send_of_recv_ack_s,
+                b2s_resume_s,
             )
             .instrument(tracing::info_span!("recv")),
             self.create_channel_mgr(
@@ -194,6 +272,38 @@ impl BParticipant {
         );
     }
 
+    #[cfg(feature = "websocket")]
+    fn is_websocket(p: &SendProtocols) -> bool { matches!(p, SendProtocols::WebSocket(_)) }
+
+    #[cfg(not(feature = "websocket"))]
+    fn is_websocket(_: &SendProtocols) -> bool { false }
+
+    /// Adjusts the promises of a new stream to the negotiated
+    /// [`Capabilities`], `None` if the remote can't keep them
+    fn negotiate_promises(capabilities: Capabilities, mut promises: Promises) -> Option<Promises> {
+        if promises.contains(Promises::ENCRYPTED)
+            && !capabilities.contains(Capabilities::ENCRYPTION)
+        {
+            return None;
+        }
+        if !capabilities.contains(Capabilities::COMPRESSION_ZSTD) {
+            // fall back to lz4
+            promises.remove(Promises::COMPRESSED_ZSTD);
+        }
+        if !promises.contains(Promises::COMPRESSED_ZSTD)
+            && !capabilities.contains(Capabilities::COMPRESSION_LZ4)
+        {
+            promises.remove(Promises::COMPRESSED);
+        }
+        if !promises.contains(Promises::COMPRESSED) {
+            promises.remove(Promises::COMPRESSED_ZSTD);
+        }
+        if !capabilities.contains(Capabilities::UNRELIABLE) {
+            promises.insert(Promises::GUARANTEED_DELIVERY);
+        }
+        Some(promises)
+    }
+
     fn best_protocol(all: &SortedVec<Cid, SendProtocols>, promises: Promises) -> Option<Cid> {
         // check for mpsc
         all.data.iter().find(|(_, p)| matches!(p, SendProtocols::Mpsc(_))).map(|(c, _)| *c).or_else(
@@ -205,6 +315,15 @@ impl BParticipant {
             } else {
                 None
             }
+        ).or_else(
+            || if network_protocol::UdpSendProtocol::<crate::channel::UdpDrain>::supported_promises()
+                .contains(promises)
+            {
+                // check for udp
+                all.data.iter().find(|(_, p)| matches!(p, SendProtocols::Udp(_))).map(|(c, _)| *c)
+            } else {
+                None
+            }
         ).or_else(
             // check for quic, TODO: evaluate to order quic BEFORE tcp once its stable
             || if network_protocol::QuicSendProtocol::<crate::channel::QuicDrain>::supported_promises()
@@ -215,7 +334,19 @@ impl BParticipant {
                 None
             }
         ).or_else(
-            || {
+            // check for websocket, only used when nothing better is available
+            || if network_protocol::TcpSendProtocol::<crate::channel::TcpDrain>::supported_promises()
+                .contains(promises)
+            {
+                all.data.iter().find(|(_, p)| Self::is_websocket(p)).map(|(c, _)| *c)
+            } else {
+                None
+            }
+        ).or_else(
+            || if promises.contains(Promises::ENCRYPTED) {
+                // never send plaintext when encryption was requested
+                None
+            } else {
                 warn!("couldn't satisfy promises");
                 all.data.first().map(|(c, _)| *c)
             }
@@ -231,6 +362,7 @@ impl BParticipant {
         a2b_msg_r: crossbeam_channel::Receiver<(Sid, Bytes)>,
         mut b2b_add_protocol_r: mpsc::UnboundedReceiver<(Cid, SendProtocols)>,
         b2b_close_send_protocol_r: async_channel::Receiver<Cid>,
+        b2b_lost_send_protocol_r: async_channel::Receiver<Cid>,
         b2b_notify_send_of_recv_open_r: crossbeam_channel::Receiver<(
             Cid,
             Sid,
@@ -239,13 +371,19 @@ impl BParticipant {
             Bandwidth,
         )>,
         b2b_notify_send_of_recv_close_r: crossbeam_channel::Receiver<(Cid, Sid)>,
+        b2b_notify_send_of_recv_ack_r: crossbeam_channel::Receiver<(Cid, Sid, u64, Duration)>,
+        b2b_force_close_recv_protocol_s: async_channel::Sender<Cid>,
         _b2s_prio_statistic_s: mpsc::UnboundedSender<B2sPrioStatistic>,
+        b2s_resume_s: mpsc::UnboundedSender<B2sResume>,
         b2a_bandwidth_stats_s: watch::Sender<f32>,
     ) {
         let mut sorted_send_protocols = SortedVec::<Cid, SendProtocols>::default();
         let mut sorted_stream_protocols = SortedVec::<Sid, Cid>::default();
+        let mut replay_buffers = HashMap::<Sid, ReplayBuffer>::new();
+        let mut acked_counts = HashMap::<Sid, u64>::new();
         let mut interval = tokio::time::interval(Self::TICK_TIME);
         let mut last_instant = Instant::now();
+        let mut last_ack = Instant::now();
         let mut stream_ids = self.offset_sid;
         let mut part_bandwidth = 0.0f32;
         trace!("workaround, actively wait for first protocol");
@@ -253,31 +391,57 @@ impl BParticipant {
             sorted_send_protocols.insert(c, p)
         }
         loop {
-            let (open, close, _, addp, remp) = select!(
-                Some(n) = a2b_open_stream_r.recv().fuse() => (Some(n), None, None, None, None),
-                Some(n) = a2b_close_stream_r.recv().fuse() => (None, Some(n), None, None, None),
-                _ = interval.tick() => (None, None, Some(()), None, None),
-                Some(n) = b2b_add_protocol_r.recv().fuse() => (None, None, None, Some(n), None),
-                Ok(n) = b2b_close_send_protocol_r.recv().fuse() => (None, None, None, None, Some(n)),
+            // while waiting for a resume, streams can't be opened or closed
+            let connected = !sorted_send_protocols.data.is_empty();
+            let (open, close, _, addp, remp, lostp) = select!(
+                Some(n) = a2b_open_stream_r.recv().fuse(), if connected => (Some(n), None, None, None, None, None),
+                Some(n) = a2b_close_stream_r.recv().fuse(), if connected => (None, Some(n), None, None, None, None),
+                _ = interval.tick() => (None, None, Some(()), None, None, None),
+                Some(n) = b2b_add_protocol_r.recv().fuse() => (None, None, None, Some(n), None, None),
+                Ok(n) = b2b_close_send_protocol_r.recv().fuse() => (None, None, None, None, Some(n), None),
+                Ok(n) = b2b_lost_send_protocol_r.recv().fuse() => (None, None, None, None, None, Some(n)),
             );
 
             if let Some((cid, p)) = addp {
                 debug!(?cid, "add protocol");
                 sorted_send_protocols.insert(cid, p);
+                self.resumed(&b2s_resume_s).await;
             }
 
             //verify that we have at LEAST 1 channel before continuing
             if sorted_send_protocols.data.is_empty() {
-                warn!("no channel");
-                tokio::time::sleep(Self::TICK_TIME * 1000).await; //TODO: failover
+                if self.resume_expired().await {
+                    break;
+                }
                 continue;
             }
 
+            let open = open.and_then(|(prio, promises, guaranteed_bandwidth, return_s)| {
+                match Self::negotiate_promises(self.capabilities, promises) {
+                    Some(promises)
+                        if Self::best_protocol(&sorted_send_protocols, promises).is_some() =>
+                    {
+                        Some((prio, promises, guaranteed_bandwidth, return_s))
+                    },
+                    _ => {
+                        debug!(?promises, "no channel can keep the promises of the stream");
+                        let _ = return_s.send(Err(ParticipantError::UnsupportedPromises));
+                        None
+                    },
+                }
+            });
+
             //let (cid, active) = sorted_send_protocols.data.iter_mut().next().unwrap();
             //used for error handling
             let mut cid = u64::MAX;
 
             let active_err = async {
+                // an `Ack` might already be received on a channel we don't know yet
+                while let Ok((c, p)) = b2b_add_protocol_r.try_recv() {
+                    debug!(?c, "add protocol");
+                    sorted_send_protocols.insert(c, p);
+                }
+
                 if let Some((prio, promises, guaranteed_bandwidth, return_s)) = open {
                     let sid = stream_ids;
                     stream_ids += Sid::from(1);
@@ -296,7 +460,10 @@ impl BParticipant {
                     };
 
                     sorted_stream_protocols.insert(sid, cid);
-                    return_s.send(stream).unwrap();
+                    if promises.contains(Promises::GUARANTEED_DELIVERY) {
+                        replay_buffers.insert(sid, ReplayBuffer::default());
+                    }
+                    return_s.send(Ok(stream)).unwrap();
                     sorted_send_protocols
                         .get_mut(&cid)
                         .unwrap()
@@ -311,6 +478,9 @@ impl BParticipant {
                     match sorted_send_protocols.get_mut(&cid) {
                         Some(p) => {
                             sorted_stream_protocols.insert(sid, cid);
+                            if promises.contains(Promises::GUARANTEED_DELIVERY) {
+                                replay_buffers.insert(sid, ReplayBuffer::default());
+                            }
                             p.notify_from_recv(ProtocolEvent::OpenStream {
                                 sid,
                                 prio,
@@ -322,8 +492,97 @@ impl BParticipant {
                     };
                 }
 
+                // move streams of broken channels to a working one
+                let orphaned = sorted_stream_protocols
+                    .data
+                    .iter()
+                    .filter(|(_, c)| sorted_send_protocols.get(c).is_none())
+                    .map(|(sid, _)| *sid)
+                    .collect::<Vec<_>>();
+                for sid in orphaned {
+                    let (promises, (received, delay)) = match self.streams.read().await.get(&sid) {
+                        Some(si) => (si.promises, si.received()),
+                        None => (Promises::empty(), (0, Duration::ZERO)),
+                    };
+                    cid = match Self::best_protocol(&sorted_send_protocols, promises) {
+                        Some(cid) => cid,
+                        None => {
+                            // wait for a channel that can keep the promises
+                            debug!(?sid, ?promises, "no channel can resume the stream yet");
+                            continue;
+                        },
+                    };
+                    debug!(?sid, ?cid, "resume stream on another channel");
+                    *sorted_stream_protocols.get_mut(&sid).unwrap() = cid;
+                    if let Some(rb) = replay_buffers.get_mut(&sid) {
+                        // hold back messages till the remote tells us what it got
+                        rb.resuming = true;
+                        acked_counts.insert(sid, received);
+                        let event = ProtocolEvent::Ack {
+                            sid,
+                            count: received,
+                            delay,
+                        };
+                        sorted_send_protocols
+                            .get_mut(&cid)
+                            .unwrap()
+                            .send(event)
+                            .await?;
+                    }
+                }
+
+                for (c, sid, count, delay) in b2b_notify_send_of_recv_ack_r.try_iter() {
+                    // the channel of the stream uses it to estimate its bandwidth
+                    if let Some(p) = sorted_stream_protocols
+                        .get(&sid)
+                        .and_then(|c| sorted_send_protocols.get_mut(c))
+                    {
+                        p.notify_from_recv(ProtocolEvent::Ack { sid, count, delay });
+                    }
+                    let rb = match replay_buffers.get_mut(&sid) {
+                        Some(rb) => rb,
+                        None => continue,
+                    };
+                    rb.acknowledge(count);
+                    if !rb.resuming || sorted_stream_protocols.get(&sid) != Some(&c) {
+                        continue;
+                    }
+                    let p = match sorted_send_protocols.get_mut(&c) {
+                        Some(p) => p,
+                        None => continue,
+                    };
+                    cid = c;
+                    rb.resuming = false;
+                    trace!(?sid, ?count, replayed = rb.messages.len(), "resumed stream");
+                    for data in rb.messages.iter() {
+                        let event = ProtocolEvent::Message {
+                            data: data.clone(),
+                            sid,
+                        };
+                        p.send(event).await?;
+                    }
+                    // the remote might also wait to know where to continue
+                    let (received, delay) = match self.streams.read().await.get(&sid) {
+                        Some(si) => si.received(),
+                        None => (0, Duration::ZERO),
+                    };
+                    acked_counts.insert(sid, received);
+                    p.send(ProtocolEvent::Ack {
+                        sid,
+                        count: received,
+                        delay,
+                    })
+                    .await?;
+                }
+
                 // get all messages and assign it to a channel
                 for (sid, buffer) in a2b_msg_r.try_iter() {
+                    if let Some(rb) = replay_buffers.get_mut(&sid) {
+                        rb.push(buffer.clone());
+                        if rb.resuming {
+                            continue;
+                        }
+                    }
                     cid = *sorted_stream_protocols.get(&sid).unwrap();
                     let event = ProtocolEvent::Message { data: buffer, sid };
                     sorted_send_protocols
@@ -338,6 +597,8 @@ impl BParticipant {
                     match sorted_send_protocols.get_mut(&cid) {
                         Some(p) => {
                             let _ = sorted_stream_protocols.delete(&sid);
+                            replay_buffers.remove(&sid);
+                            acked_counts.remove(&sid);
                             p.notify_from_recv(ProtocolEvent::CloseStream { sid });
                         },
                         None => warn!(?cid, "couldn't notify close protocol, doesn't exist"),
@@ -347,6 +608,8 @@ impl BParticipant {
                 if let Some(sid) = close {
                     trace!(?stream_ids, "delete stream");
                     self.delete_stream(sid).await;
+                    replay_buffers.remove(&sid);
+                    acked_counts.remove(&sid);
                     // Fire&Forget the protocol will take care to verify that this Frame is delayed
                     // till the last msg was received!
                     if let Some(c) = sorted_stream_protocols.delete(&sid) {
@@ -360,6 +623,34 @@ impl BParticipant {
                     }
                 }
 
+                // let the remote forget about messages we got
+                if last_ack.elapsed() > Self::ACK_INTERVAL {
+                    last_ack = Instant::now();
+                    let counts = self
+                        .streams
+                        .read()
+                        .await
+                        .iter()
+                        .filter(|(_, si)| si.promises.contains(Promises::GUARANTEED_DELIVERY))
+                        .map(|(sid, si)| (*sid, si.received()))
+                        .collect::<Vec<_>>();
+                    for (sid, (count, delay)) in counts {
+                        if acked_counts.get(&sid).copied().unwrap_or_default() == count {
+                            continue;
+                        }
+                        if let Some(c) = sorted_stream_protocols.get(&sid) {
+                            cid = *c;
+                            acked_counts.insert(sid, count);
+                            let event = ProtocolEvent::Ack { sid, count, delay };
+                            sorted_send_protocols
+                                .get_mut(&cid)
+                                .unwrap()
+                                .send(event)
+                                .await?;
+                        }
+                    }
+                }
+
                 let send_time = Instant::now();
                 let diff = send_time.duration_since(last_instant);
                 last_instant = send_time;
@@ -381,11 +672,50 @@ impl BParticipant {
                 info!(?cid, ?e, "protocol failed, shutting down channel");
                 // remote recv will now fail, which will trigger remote send which will trigger
                 // recv
-                trace!("TODO: for now decide to FAIL this participant and not wait for a failover");
-                sorted_send_protocols.delete(&cid).unwrap();
-                self.metrics.channels_disconnected(&self.remote_pid_string);
-                if sorted_send_protocols.data.is_empty() {
-                    break;
+                if let Some(prot) = sorted_send_protocols.delete(&cid) {
+                    self.metrics.channels_disconnected(&self.remote_pid_string);
+                    let resumable = !matches!(prot, SendProtocols::Mpsc(_));
+                    if sorted_send_protocols.data.is_empty()
+                        && !self.wait_for_resume(resumable, &b2s_resume_s).await
+                    {
+                        break;
+                    }
+                }
+            }
+
+            let (replay_bytes, replay_messages) = replay_buffers
+                .values()
+                .fold((0, 0), |(b, m), rb| (b + rb.bytes, m + rb.messages.len()));
+            if replay_bytes > Self::MAX_REPLAY_BYTES || replay_messages > Self::MAX_REPLAY_MESSAGES
+            {
+                warn!(
+                    ?replay_bytes,
+                    ?replay_messages,
+                    "remote doesn't acknowledge messages, closing participant"
+                );
+                self.shutdown_requested.store(true, Ordering::SeqCst);
+                for si in self.streams.read().await.values() {
+                    si.send_closed.store(true, Ordering::SeqCst);
+                }
+                for (cid, p) in sorted_send_protocols.data.iter_mut() {
+                    self.metrics.channels_disconnected(&self.remote_pid_string);
+                    let _ = p.send(ProtocolEvent::Shutdown).await;
+                    if let Err(e) = b2b_force_close_recv_protocol_s.send(*cid).await {
+                        debug!(?e, ?cid, "recv_mgr was already closed simultaneously");
+                    }
+                }
+                break;
+            }
+
+            if let Some(cid) = lostp {
+                debug!(?cid, "lost protocol");
+                if sorted_send_protocols.delete(&cid).is_some() {
+                    self.metrics.channels_disconnected(&self.remote_pid_string);
+                    if sorted_send_protocols.data.is_empty()
+                        && !self.wait_for_resume(true, &b2s_resume_s).await
+                    {
+                        break;
+                    }
                 }
             }
 
@@ -413,12 +743,14 @@ impl BParticipant {
             .fetch_sub(Self::BARR_SEND, Ordering::SeqCst);
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn recv_mgr(
         &self,
         b2a_stream_opened_s: mpsc::UnboundedSender<Stream>,
         mut b2b_add_protocol_r: mpsc::UnboundedReceiver<(Cid, RecvProtocols)>,
         b2b_force_close_recv_protocol_r: async_channel::Receiver<Cid>,
         b2b_close_send_protocol_s: async_channel::Sender<Cid>,
+        b2b_lost_send_protocol_s: async_channel::Sender<Cid>,
         b2b_notify_send_of_recv_open_r: crossbeam_channel::Sender<(
             Cid,
             Sid,
@@ -427,6 +759,8 @@ impl BParticipant {
             Bandwidth,
         )>,
         b2b_notify_send_of_recv_close_s: crossbeam_channel::Sender<(Cid, Sid)>,
+        b2b_notify_send_of_recv_ack_s: crossbeam_channel::Sender<(Cid, Sid, u64, Duration)>,
+        b2s_resume_s: mpsc::UnboundedSender<B2sResume>,
     ) {
         let mut recv_protocols: HashMap<Cid, JoinHandle<()>> = HashMap::new();
         // we should be able to directly await futures imo
@@ -460,6 +794,7 @@ impl BParticipant {
                 Some(n) = hacky_recv_r.recv().fuse() => (Some(n), None, None),
                 Some(n) = b2b_add_protocol_r.recv().fuse() => (None, Some(n), None),
                 Ok(n) = b2b_force_close_recv_protocol_r.recv().fuse() => (None, None, Some(n)),
+                _ = tokio::time::sleep(Self::TICK_TIME * 20), if recv_protocols.is_empty() => (None, None, None),
                 else => {
                     error!("recv_mgr -> something is seriously wrong!, end recv_mgr");
                     break;
@@ -469,6 +804,7 @@ impl BParticipant {
             if let Some((cid, p)) = addp {
                 debug!(?cid, "add protocol");
                 retrigger(cid, p, &mut recv_protocols);
+                self.resumed(&b2s_resume_s).await;
             };
             if let Some(cid) = remp {
                 // no need to stop the send_mgr here as it has been canceled before
@@ -511,12 +847,20 @@ impl BParticipant {
                         let lock = self.streams.read().await;
                         match lock.get(&sid) {
                             Some(stream) => {
+                                let since_open = stream.opened.elapsed().as_micros() as u64;
+                                stream.last_received.store(since_open, Ordering::Relaxed);
+                                stream.received.fetch_add(1, Ordering::Relaxed);
                                 let _ = stream.b2a_msg_recv_s.lock().await.send(data).await;
                             },
                             None => defered_orphan.log(sid),
                         };
                         retrigger(cid, p, &mut recv_protocols);
                     },
+                    Ok(ProtocolEvent::Ack { sid, count, delay }) => {
+                        trace!(?sid, ?count, ?delay, "ack");
+                        let _ = b2b_notify_send_of_recv_ack_s.send((cid, sid, count, delay));
+                        retrigger(cid, p, &mut recv_protocols);
+                    },
                     Ok(ProtocolEvent::Shutdown) => {
                         info!(?cid, "shutdown protocol");
                         if let Err(e) = b2b_close_send_protocol_s.send(cid).await {
@@ -528,10 +872,19 @@ impl BParticipant {
                     },
                     Err(e) => {
                         info!(?e, ?cid, "protocol failed, shutting down channel");
-                        if let Err(e) = b2b_close_send_protocol_s.send(cid).await {
+                        // a broken network connection might get resumed, so don't flush it
+                        let resumable = !matches!(p, RecvProtocols::Mpsc(_));
+                        let b2b_s = if resumable {
+                            &b2b_lost_send_protocol_s
+                        } else {
+                            &b2b_close_send_protocol_s
+                        };
+                        if let Err(e) = b2b_s.send(cid).await {
                             debug!(?e, ?cid, "send_mgr was already closed simultaneously");
                         }
-                        if remove_c(&mut recv_protocols, &cid) {
+                        if remove_c(&mut recv_protocols, &cid)
+                            && !self.wait_for_resume(resumable, &b2s_resume_s).await
+                        {
                             break;
                         }
                     },
@@ -543,6 +896,10 @@ impl BParticipant {
                     warn!(?sid, ?cnt, "recv messages with orphan stream");
                 }
             }
+
+            if recv_protocols.is_empty() && self.resume_expired().await {
+                break;
+            }
         }
         trace!("receiving no longer possible, closing all streams");
         for (_, si) in self.streams.write().await.drain() {
@@ -580,8 +937,11 @@ impl BParticipant {
                     );
                     drop(lock);
                     let (send, recv) = protocol.split();
-                    b2b_add_send_protocol_s.send((cid, send)).unwrap();
-                    b2b_add_recv_protocol_s.send((cid, recv)).unwrap();
+                    if b2b_add_send_protocol_s.send((cid, send)).is_err()
+                        || b2b_add_recv_protocol_s.send((cid, recv)).is_err()
+                    {
+                        debug!(?cid, "participant closed in the meantime, dropping channel");
+                    }
                     b2s_create_channel_done_s.send(()).unwrap();
                     if channel_no > 5 {
                         debug!(?channel_no, "metrics will overwrite channel #5");
@@ -642,6 +1002,7 @@ impl BParticipant {
 
         let awaited = s2b_shutdown_bparticipant_r.await.ok();
         debug!("participant_shutdown_mgr triggered. Closing all streams for send");
+        self.shutdown_requested.store(true, Ordering::SeqCst);
         {
             let lock = self.streams.read().await;
             for si in lock.values() {
@@ -706,6 +1067,39 @@ impl BParticipant {
         trace!("Stop participant_shutdown_mgr");
     }
 
+    /// Called when the last channel broke. Returns whether the participant
+    /// should stay alive for [`Self::RESUME_GRACE`] and wait for the remote
+    /// to reconnect.
+    async fn wait_for_resume(
+        &self,
+        resumable: bool,
+        b2s_resume_s: &mpsc::UnboundedSender<B2sResume>,
+    ) -> bool {
+        if !resumable || self.shutdown_requested.load(Ordering::SeqCst) {
+            return false;
+        }
+        let mut deadline = self.resume_deadline.lock().await;
+        if deadline.is_none() {
+            info!("lost all channels, waiting for participant to resume");
+            *deadline = Some(Instant::now() + Self::RESUME_GRACE);
+            let _ = b2s_resume_s.send((self.remote_pid, true));
+        }
+        true
+    }
+
+    async fn resumed(&self, b2s_resume_s: &mpsc::UnboundedSender<B2sResume>) {
+        if self.resume_deadline.lock().await.take().is_some() {
+            info!("participant resumed");
+            let _ = b2s_resume_s.send((self.remote_pid, false));
+        }
+    }
+
+    /// `true` if no channel got resumed in time or shutdown was requested
+    async fn resume_expired(&self) -> bool {
+        self.shutdown_requested.load(Ordering::SeqCst)
+            || matches!(*self.resume_deadline.lock().await, Some(deadline) if Instant::now() > deadline)
+    }
+
     /// Stopping API and participant usage
     /// Protocol will take care of the order of the frame
     async fn delete_stream(&self, sid: Sid) {
@@ -736,6 +1130,9 @@ impl BParticipant {
             promises,
             send_closed: Arc::clone(&send_closed),
             b2a_msg_recv_s: Mutex::new(b2a_msg_recv_s),
+            received: AtomicU64::new(0),
+            opened: Instant::now(),
+            last_received: AtomicU64::new(0),
         });
         self.metrics.streams_opened(&self.remote_pid_string);
 
@@ -797,6 +1194,7 @@ mod tests {
 
         let (b2s_prio_statistic_s, b2s_prio_statistic_r) =
             mpsc::unbounded_channel::<B2sPrioStatistic>();
+        let (b2s_resume_s, _) = mpsc::unbounded_channel::<B2sResume>();
 
         let (
             bparticipant,
@@ -811,10 +1209,16 @@ mod tests {
             let sid = Sid::new(1000);
             let metrics = Arc::new(NetworkMetrics::new(&local_pid).unwrap());
 
-            BParticipant::new(local_pid, remote_pid, sid, Arc::clone(&metrics))
+            BParticipant::new(
+                local_pid,
+                remote_pid,
+                sid,
+                Capabilities::all(),
+                Arc::clone(&metrics),
+            )
         });
 
-        let handle = runtime_clone.spawn(bparticipant.run(b2s_prio_statistic_s));
+        let handle = runtime_clone.spawn(bparticipant.run(b2s_prio_statistic_s, b2s_resume_s));
         (
             runtime_clone,
             a2b_open_stream_s,
@@ -1019,4 +1423,57 @@ mod tests {
         drop((a2b_open_stream_s, b2a_stream_opened_r, b2s_prio_statistic_r));
         drop(runtime);
     }
+
+    #[test]
+    fn replay_buffer_acknowledge() {
+        let mut rb = ReplayBuffer::default();
+        for i in 0..5u8 {
+            rb.push(Bytes::from(vec![i; 2]));
+        }
+        assert_eq!(rb.bytes, 10);
+        rb.acknowledge(2);
+        assert_eq!(rb.acked, 2);
+        assert_eq!(rb.bytes, 6);
+        assert_eq!(rb.messages.front(), Some(&Bytes::from(vec![2; 2])));
+        // outdated acks are ignored
+        rb.acknowledge(1);
+        assert_eq!(rb.messages.len(), 3);
+        rb.acknowledge(10);
+        assert_eq!(rb.acked, 5);
+        assert_eq!(rb.bytes, 0);
+        assert!(rb.messages.is_empty());
+    }
+
+    #[test]
+    fn negotiate_promises() {
+        let promises = Promises::ORDERED | Promises::COMPRESSED;
+        assert_eq!(
+            BParticipant::negotiate_promises(Capabilities::all(), promises),
+            Some(promises)
+        );
+        assert_eq!(
+            BParticipant::negotiate_promises(Capabilities::ENCRYPTION, promises),
+            Some(Promises::ORDERED | Promises::GUARANTEED_DELIVERY)
+        );
+        assert_eq!(
+            BParticipant::negotiate_promises(Capabilities::empty(), Promises::ENCRYPTED),
+            None
+        );
+        let zstd = Promises::COMPRESSED | Promises::COMPRESSED_ZSTD;
+        assert_eq!(
+            BParticipant::negotiate_promises(Capabilities::all(), zstd),
+            Some(zstd)
+        );
+        assert_eq!(
+            BParticipant::negotiate_promises(
+                Capabilities::UNRELIABLE | Capabilities::COMPRESSION_LZ4,
+                zstd
+            ),
+            Some(Promises::COMPRESSED)
+        );
+        assert_eq!(
+            BParticipant::negotiate_promises(Capabilities::UNRELIABLE, zstd),
+            Some(Promises::empty())
+        );
+    }
 }
@@ -0,0 +1,249 @@
+use crate::{api::StreamParams, message::Message};
+use bytes::Bytes;
+use network_protocol::{Bandwidth, Prio, Promises, Sid};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// start of every recording, the last byte is the format version
+const MAGIC: &[u8; 8] = b"VNETREC1";
+
+/// Whether a recorded [`Message`] was send or received by the recording side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// Writes the [`Messages`] of [`Streams`] to a file, together with the time
+/// since the `Recorder` was created. Use [`Participant::record`] to record
+/// all `Streams` of a [`Participant`] or [`Stream::record`] for a single one.
+/// A `Recorder` can be cloned to write multiple `Streams` into one file.
+/// Load the file with [`Recording::load`].
+///
+/// [`Messages`]: crate::message::Message
+/// [`Streams`]: crate::api::Stream
+/// [`Stream::record`]: crate::api::Stream::record
+/// [`Participant`]: crate::api::Participant
+/// [`Participant::record`]: crate::api::Participant::record
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    inner: Arc<Mutex<RecorderInner>>,
+}
+
+#[derive(Debug)]
+struct RecorderInner {
+    writer: BufWriter<File>,
+    start: Instant,
+    /// stop writing after the first error, instead of spamming the log
+    failed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Entry {
+    Stream {
+        sid: u64,
+        prio: Prio,
+        promises: u8,
+        guaranteed_bandwidth: Bandwidth,
+    },
+    Message {
+        time: Duration,
+        sid: u64,
+        direction: Direction,
+        data: Vec<u8>,
+    },
+}
+
+/// A recorded [`Stream`](crate::api::Stream), `sid` is only unique within its
+/// [`Recording`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedStream {
+    pub sid: u64,
+    pub prio: Prio,
+    pub promises: Promises,
+    pub guaranteed_bandwidth: Bandwidth,
+}
+
+/// A recorded [`Message`], `data` is still compressed if the stream was
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedMessage {
+    pub time: Duration,
+    pub sid: u64,
+    pub direction: Direction,
+    pub data: Bytes,
+}
+
+/// All [`RecordedStream`]s and [`RecordedMessage`]s in the order they were
+/// recorded
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Recording {
+    pub streams: Vec<RecordedStream>,
+    pub messages: Vec<RecordedMessage>,
+}
+
+impl Recorder {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.flush()?;
+        Ok(Self {
+            inner: Arc::new(Mutex::new(RecorderInner {
+                writer,
+                start: Instant::now(),
+                failed: false,
+            })),
+        })
+    }
+
+    pub(crate) fn stream(
+        &self,
+        sid: Sid,
+        prio: Prio,
+        promises: Promises,
+        guaranteed_bandwidth: Bandwidth,
+    ) {
+        self.write(|_| Entry::Stream {
+            sid: sid.get_u64(),
+            prio,
+            promises: promises.bits(),
+            guaranteed_bandwidth,
+        });
+    }
+
+    pub(crate) fn message(&self, sid: Sid, direction: Direction, data: &Bytes) {
+        self.write(|time| Entry::Message {
+            time,
+            sid: sid.get_u64(),
+            direction,
+            data: data.to_vec(),
+        });
+    }
+
+    fn write(&self, entry: impl FnOnce(Duration) -> Entry) {
+        let mut inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(_) => return,
+        };
+        if inner.failed {
+            return;
+        }
+        let entry = entry(inner.start.elapsed());
+        // flush every entry, the recording is most useful when the app crashes
+        let result = bincode::serialize_into(&mut inner.writer, &entry)
+            .map_err(|e| e.to_string())
+            .and_then(|_| inner.writer.flush().map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            warn!(?e, "Writing the network recording failed, stop recording");
+            inner.failed = true;
+        }
+    }
+}
+
+impl Recording {
+    /// A recording which ends within an entry, e.g. because the app crashed
+    /// while writing it, is loaded till that entry.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a network recording",
+            ));
+        }
+        let mut recording = Self::default();
+        loop {
+            match bincode::deserialize_from(&mut reader) {
+                Ok(Entry::Stream {
+                    sid,
+                    prio,
+                    promises,
+                    guaranteed_bandwidth,
+                }) => recording.streams.push(RecordedStream {
+                    sid,
+                    prio,
+                    promises: Promises::from_bits_truncate(promises),
+                    guaranteed_bandwidth,
+                }),
+                Ok(Entry::Message {
+                    time,
+                    sid,
+                    direction,
+                    data,
+                }) => recording.messages.push(RecordedMessage {
+                    time,
+                    sid,
+                    direction,
+                    data: Bytes::from(data),
+                }),
+                Err(e) => match *e {
+                    bincode::ErrorKind::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                    e => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+                },
+            }
+        }
+        Ok(recording)
+    }
+}
+
+impl RecordedMessage {
+    /// `Message` for a [`Stream`](crate::api::Stream) with the params of the
+    /// recorded one, e.g. to replay it with [`send_raw`]
+    ///
+    /// [`send_raw`]: crate::api::Stream::send_raw
+    pub fn to_message(&self, params: StreamParams) -> Message {
+        #[cfg(not(feature = "compression"))]
+        let _params = params;
+        Message {
+            data: self.data.clone(),
+            #[cfg(feature = "compression")]
+            compressed: params.compression,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_and_load() {
+        let path = std::env::temp_dir().join(format!("veloren-record-{}", std::process::id()));
+        let recorder = Recorder::create(&path).unwrap();
+        recorder.stream(Sid::new(3), 4, Promises::ORDERED, 1000);
+        recorder.message(Sid::new(3), Direction::Sent, &Bytes::from_static(b"hello"));
+        recorder.message(
+            Sid::new(3),
+            Direction::Received,
+            &Bytes::from_static(b"world"),
+        );
+        drop(recorder);
+
+        let recording = Recording::load(&path).unwrap();
+        assert_eq!(recording.streams, vec![RecordedStream {
+            sid: 3,
+            prio: 4,
+            promises: Promises::ORDERED,
+            guaranteed_bandwidth: 1000,
+        }]);
+        assert_eq!(recording.messages.len(), 2);
+        assert_eq!(recording.messages[0].direction, Direction::Sent);
+        assert_eq!(recording.messages[1].data, Bytes::from_static(b"world"));
+        assert!(recording.messages[0].time <= recording.messages[1].time);
+
+        // a crash while writing leaves a partial entry behind
+        let len = std::fs::metadata(&path).unwrap().len();
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len - 2).unwrap();
+        assert_eq!(Recording::load(&path).unwrap().messages.len(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
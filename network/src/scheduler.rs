@@ -1,21 +1,31 @@
+#[cfg(not(feature = "sim"))]
+use crate::util::sim::SimConfig;
 use crate::{
     api::{ConnectAddr, ListenAddr, NetworkConnectError, Participant},
     channel::Protocols,
     metrics::{NetworkMetrics, ProtocolInfo},
-    participant::{B2sPrioStatistic, BParticipant, S2bCreateChannel, S2bShutdownBparticipant},
+    participant::{
+        B2sPrioStatistic, B2sResume, BParticipant, S2bCreateChannel, S2bShutdownBparticipant,
+    },
 };
 use futures_util::StreamExt;
 use hashbrown::HashMap;
-use network_protocol::{Cid, Pid, ProtocolMetricCache, ProtocolMetrics};
+#[cfg(feature = "sim")]
+use network_protocol::sim::SimConfig;
+use network_protocol::{
+    Capabilities, Cid, Identity, InitProtocolError, Pid, ProtocolMetricCache, ProtocolMetrics,
+    PublicIdentity,
+};
 #[cfg(feature = "metrics")]
 use prometheus::Registry;
 use rand::Rng;
 use std::{
+    net::SocketAddr,
     sync::{
         atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tokio::{
     io,
@@ -35,15 +45,21 @@ use tracing::*;
 
 #[derive(Debug)]
 struct ParticipantInfo {
+    /// also used to resume the participant over a new channel
     secret: u128,
-    #[allow(dead_code)]
     s2b_create_channel_s: mpsc::UnboundedSender<S2bCreateChannel>,
     s2b_shutdown_bparticipant_s: Option<oneshot::Sender<S2bShutdownBparticipant>>,
+    /// set if we connected actively and thus need to reconnect
+    connect_addr: Option<ConnectAddr>,
+    /// required from the remote when reconnecting
+    pinned_identity: Option<PublicIdentity>,
+    channels_lost: bool,
 }
 
 type A2sListen = (ListenAddr, oneshot::Sender<io::Result<()>>);
 pub(crate) type A2sConnect = (
     ConnectAddr,
+    Option<PublicIdentity>,
     oneshot::Sender<Result<Participant, NetworkConnectError>>,
 );
 type A2sDisconnect = (Pid, S2bShutdownBparticipant);
@@ -55,6 +71,7 @@ struct ControlChannels {
     a2s_scheduler_shutdown_r: oneshot::Receiver<()>,
     a2s_disconnect_r: mpsc::UnboundedReceiver<A2sDisconnect>,
     b2s_prio_statistic_r: mpsc::UnboundedReceiver<B2sPrioStatistic>,
+    b2s_resume_r: mpsc::UnboundedReceiver<B2sResume>,
 }
 
 #[derive(Debug, Clone)]
@@ -62,6 +79,7 @@ struct ParticipantChannels {
     s2a_connected_s: mpsc::UnboundedSender<Participant>,
     a2s_disconnect_s: mpsc::UnboundedSender<A2sDisconnect>,
     b2s_prio_statistic_s: mpsc::UnboundedSender<B2sPrioStatistic>,
+    b2s_resume_s: mpsc::UnboundedSender<B2sResume>,
 }
 
 #[derive(Debug)]
@@ -76,11 +94,28 @@ pub struct Scheduler {
     channel_listener: Mutex<HashMap<ProtocolInfo, oneshot::Sender<()>>>,
     metrics: Arc<NetworkMetrics>,
     protocol_metrics: Arc<ProtocolMetrics>,
+    /// applied to TCP and UDP channels when they are created
+    simulation: Arc<Mutex<Option<SimConfig>>>,
+    /// proven to remotes connecting to our TCP and WebSocket listeners
+    identity: Arc<Mutex<Option<Arc<Identity>>>>,
 }
 
 impl Scheduler {
+    const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// [`Capabilities`] offered to every remote during the handshake
+    fn local_capabilities() -> Capabilities {
+        let capabilities = Capabilities::ENCRYPTION | Capabilities::UNRELIABLE;
+        #[cfg(feature = "compression")]
+        let capabilities =
+            capabilities | Capabilities::COMPRESSION_LZ4 | Capabilities::COMPRESSION_ZSTD;
+        capabilities
+    }
+
     pub fn new(
         local_pid: Pid,
+        simulation: Arc<Mutex<Option<SimConfig>>>,
+        identity: Arc<Mutex<Option<Arc<Identity>>>>,
         #[cfg(feature = "metrics")] registry: Option<&Registry>,
     ) -> (
         Self,
@@ -96,6 +131,7 @@ impl Scheduler {
         let (a2s_disconnect_s, a2s_disconnect_r) = mpsc::unbounded_channel::<A2sDisconnect>();
         let (b2s_prio_statistic_s, b2s_prio_statistic_r) =
             mpsc::unbounded_channel::<B2sPrioStatistic>();
+        let (b2s_resume_s, b2s_resume_r) = mpsc::unbounded_channel::<B2sResume>();
 
         let run_channels = Some(ControlChannels {
             a2s_listen_r,
@@ -103,12 +139,14 @@ impl Scheduler {
             a2s_scheduler_shutdown_r,
             a2s_disconnect_r,
             b2s_prio_statistic_r,
+            b2s_resume_r,
         });
 
         let participant_channels = ParticipantChannels {
             s2a_connected_s,
             a2s_disconnect_s,
             b2s_prio_statistic_s,
+            b2s_resume_s,
         };
 
         let metrics = Arc::new(NetworkMetrics::new(&local_pid).unwrap());
@@ -137,6 +175,8 @@ impl Scheduler {
                 channel_listener: Mutex::new(HashMap::new()),
                 metrics,
                 protocol_metrics,
+                simulation,
+                identity,
             },
             a2s_listen_s,
             a2s_connect_s,
@@ -156,6 +196,7 @@ impl Scheduler {
             self.connect_mgr(run_channels.a2s_connect_r),
             self.disconnect_mgr(run_channels.a2s_disconnect_r),
             self.prio_adj_mgr(run_channels.b2s_prio_statistic_r),
+            self.reconnect_mgr(run_channels.b2s_resume_r),
             self.scheduler_shutdown_mgr(run_channels.a2s_scheduler_shutdown_r),
         );
     }
@@ -186,6 +227,7 @@ impl Scheduler {
                     #[cfg(feature = "metrics")]
                     mcache.inc();
 
+                    let sim = self.simulation.lock().await.clone();
                     let res = match address {
                         ListenAddr::Tcp(addr) => {
                             Protocols::with_tcp_listen(
@@ -194,6 +236,7 @@ impl Scheduler {
                                 metrics,
                                 s2s_stop_listening_r,
                                 c2s_protocol_s,
+                                sim,
                             )
                             .await
                         },
@@ -209,6 +252,29 @@ impl Scheduler {
                             )
                             .await
                         },
+                        #[cfg(feature = "websocket")]
+                        ListenAddr::WebSocket(addr) => {
+                            Protocols::with_websocket_listen(
+                                addr,
+                                cids,
+                                metrics,
+                                s2s_stop_listening_r,
+                                c2s_protocol_s,
+                                sim,
+                            )
+                            .await
+                        },
+                        ListenAddr::Udp(addr) => {
+                            Protocols::with_udp_listen(
+                                addr,
+                                cids,
+                                metrics,
+                                s2s_stop_listening_r,
+                                c2s_protocol_s,
+                                sim,
+                            )
+                            .await
+                        },
                         ListenAddr::Mpsc(addr) => {
                             Protocols::with_mpsc_listen(
                                 addr,
@@ -219,12 +285,12 @@ impl Scheduler {
                             )
                             .await
                         },
-                        _ => unimplemented!(),
                     };
                     let _ = s2a_listen_result_s.send(res);
 
-                    while let Some((prot, cid)) = c2s_protocol_r.recv().await {
-                        self.init_protocol(prot, cid, None, true).await;
+                    while let Some((prot, cid, peer_addr)) = c2s_protocol_r.recv().await {
+                        self.init_protocol(prot, cid, None, true, None, None, peer_addr)
+                            .await;
                     }
                 }
             })
@@ -234,33 +300,107 @@ impl Scheduler {
 
     async fn connect_mgr(&self, mut a2s_connect_r: mpsc::UnboundedReceiver<A2sConnect>) {
         trace!("Start connect_mgr");
-        while let Some((addr, pid_sender)) = a2s_connect_r.recv().await {
+        while let Some((addr, pinned_identity, pid_sender)) = a2s_connect_r.recv().await {
             let cid = self.channel_ids.fetch_add(1, Ordering::Relaxed);
             let metrics =
                 ProtocolMetricCache::new(&cid.to_string(), Arc::clone(&self.protocol_metrics));
             self.metrics.connect_request(&addr);
-            let protocol = match addr {
-                ConnectAddr::Tcp(addr) => Protocols::with_tcp_connect(addr, metrics).await,
-                #[cfg(feature = "quic")]
-                ConnectAddr::Quic(addr, ref config, name) => {
-                    Protocols::with_quic_connect(addr, config.clone(), name, metrics).await
-                },
-                ConnectAddr::Mpsc(addr) => Protocols::with_mpsc_connect(addr, metrics).await,
-                _ => unimplemented!(),
-            };
-            let protocol = match protocol {
+            let protocol = match self.connect(addr.clone(), metrics).await {
                 Ok(p) => p,
                 Err(e) => {
                     pid_sender.send(Err(e)).unwrap();
                     continue;
                 },
             };
-            self.init_protocol(protocol, cid, Some(pid_sender), false)
-                .await;
+            let peer_addr = addr.socket_addr();
+            self.init_protocol(
+                protocol,
+                cid,
+                Some(pid_sender),
+                false,
+                Some(addr),
+                pinned_identity,
+                peer_addr,
+            )
+            .await;
         }
         trace!("Stop connect_mgr");
     }
 
+    async fn connect(
+        &self,
+        addr: ConnectAddr,
+        metrics: ProtocolMetricCache,
+    ) -> Result<Protocols, NetworkConnectError> {
+        let sim = self.simulation.lock().await.clone();
+        match addr {
+            ConnectAddr::Tcp(addr) => Protocols::with_tcp_connect(addr, metrics, sim).await,
+            ConnectAddr::Udp(addr) => Protocols::with_udp_connect(addr, metrics, sim).await,
+            #[cfg(feature = "quic")]
+            ConnectAddr::Quic(addr, ref config, name) => {
+                Protocols::with_quic_connect(addr, config.clone(), name, metrics).await
+            },
+            #[cfg(feature = "websocket")]
+            ConnectAddr::WebSocket(url) => {
+                Protocols::with_websocket_connect(url, metrics, sim).await
+            },
+            ConnectAddr::Mpsc(addr) => Protocols::with_mpsc_connect(addr, metrics).await,
+        }
+    }
+
+    /// Participants we connected to lose all channels when e.g. the wifi drops,
+    /// try to connect again till the BParticipant gives up.
+    async fn reconnect_mgr(&self, b2s_resume_r: mpsc::UnboundedReceiver<B2sResume>) {
+        trace!("Start reconnect_mgr");
+        let b2s_resume_r = UnboundedReceiverStream::new(b2s_resume_r);
+        b2s_resume_r
+            .for_each_concurrent(None, |(pid, lost)| async move {
+                let (addr, pinned_identity) = match self.participants.lock().await.get_mut(&pid) {
+                    Some(pi) => {
+                        pi.channels_lost = lost;
+                        (pi.connect_addr.clone(), pi.pinned_identity)
+                    },
+                    None => (None, None),
+                };
+                let addr = match addr {
+                    Some(addr) if lost => addr,
+                    _ => return,
+                };
+                let deadline = Instant::now() + BParticipant::RESUME_GRACE;
+                while Instant::now() < deadline {
+                    match self.participants.lock().await.get(&pid) {
+                        Some(pi) if pi.channels_lost => (),
+                        _ => break,
+                    }
+                    let cid = self.channel_ids.fetch_add(1, Ordering::Relaxed);
+                    let metrics = ProtocolMetricCache::new(
+                        &cid.to_string(),
+                        Arc::clone(&self.protocol_metrics),
+                    );
+                    debug!(?pid, ?cid, "trying to resume participant");
+                    self.metrics.connect_request(&addr);
+                    match self.connect(addr.clone(), metrics).await {
+                        Ok(protocol) => {
+                            self.init_protocol(
+                                protocol,
+                                cid,
+                                None,
+                                false,
+                                Some(addr.clone()),
+                                pinned_identity,
+                                addr.socket_addr(),
+                            )
+                            .await
+                        },
+                        Err(e) => debug!(?pid, ?e, "reconnect failed"),
+                    }
+                    tokio::time::sleep(Self::RECONNECT_INTERVAL).await;
+                }
+            })
+            .await;
+        trace!("Stop reconnect_mgr");
+    }
+
     async fn disconnect_mgr(&self, a2s_disconnect_r: mpsc::UnboundedReceiver<A2sDisconnect>) {
         trace!("Start disconnect_mgr");
 
@@ -372,12 +512,16 @@ impl Scheduler {
         trace!("Stop scheduler_shutdown_mgr");
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn init_protocol(
         &self,
         mut protocol: Protocols,
         cid: Cid,
         s2a_return_pid_s: Option<oneshot::Sender<Result<Participant, NetworkConnectError>>>,
         send_handshake: bool,
+        connect_addr: Option<ConnectAddr>,
+        pinned_identity: Option<PublicIdentity>,
+        peer_addr: Option<SocketAddr>,
     ) {
         //channels are unknown till PID is known!
         /* When A connects to a NETWORK, we, the listener answers with a Handshake.
@@ -394,17 +538,33 @@ impl Scheduler {
         let metrics = Arc::clone(&self.metrics);
         let local_pid = self.local_pid;
         let local_secret = self.local_secret;
+        // we prove our identity when listening and check the pinned one when connecting
+        let identity = if send_handshake {
+            self.identity.lock().await.clone()
+        } else {
+            None
+        };
         // this is necessary for UDP to work at all and to remove code duplication
         tokio::spawn(
             async move {
                 trace!(?cid, "Open channel and be ready for Handshake");
                 use network_protocol::InitProtocol;
-                let init_result = protocol
-                    .initialize(send_handshake, local_pid, local_secret)
-                    .instrument(tracing::info_span!("handshake", ?cid))
-                    .await;
+                let init_result = if protocol.set_identity(identity, pinned_identity) {
+                    protocol
+                        .initialize(
+                            send_handshake,
+                            local_pid,
+                            local_secret,
+                            Self::local_capabilities(),
+                        )
+                        .instrument(tracing::info_span!("handshake", ?cid))
+                        .await
+                } else {
+                    info!(?cid, "This protocol can't verify the pinned identity");
+                    Err(InitProtocolError::WrongIdentity(None))
+                };
                 match init_result {
-                    Ok((pid, sid, secret)) => {
+                    Ok((pid, sid, secret, capabilities)) => {
                         trace!(
                             ?cid,
                             ?pid,
@@ -420,11 +580,20 @@ impl Scheduler {
                                 s2b_create_channel_s,
                                 s2b_shutdown_bparticipant_s,
                                 b2a_bandwidth_stats_r,
-                            ) = BParticipant::new(local_pid, pid, sid, Arc::clone(&metrics));
+                            ) = BParticipant::new(
+                                local_pid,
+                                pid,
+                                sid,
+                                capabilities,
+                                Arc::clone(&metrics),
+                            );
 
                             let participant = Participant::new(
                                 local_pid,
                                 pid,
+                                capabilities,
+                                peer_addr,
+                                protocol.remote_identity(),
                                 a2b_open_stream_s,
                                 b2a_stream_opened_r,
                                 b2a_bandwidth_stats_r,
@@ -437,13 +606,19 @@ impl Scheduler {
                                 secret,
                                 s2b_create_channel_s: s2b_create_channel_s.clone(),
                                 s2b_shutdown_bparticipant_s: Some(s2b_shutdown_bparticipant_s),
+                                connect_addr,
+                                pinned_identity,
+                                channels_lost: false,
                             });
                             drop(participants);
                             trace!("dropped participants lock");
                             let p = pid;
                             tokio::spawn(
                                 bparticipant
-                                    .run(participant_channels.b2s_prio_statistic_s)
+                                    .run(
+                                        participant_channels.b2s_prio_statistic_s,
+                                        participant_channels.b2s_resume_s,
+                                    )
                                     .instrument(tracing::info_span!("remote", ?p)),
                             );
                             //create a new channel within BParticipant and wait for it to run
@@ -490,11 +665,26 @@ impl Scheduler {
                                 }
                                 return;
                             }
-                            error!(
-                                ?cid,
-                                "Ufff i cant answer the pid_oneshot. as i need to create the SAME \
-                                 participant. maybe switch to ARC"
-                            );
+                            if s2a_return_pid_s.is_some() {
+                                error!(
+                                    ?cid,
+                                    "Ufff i cant answer the pid_oneshot. as i need to create the \
+                                     SAME participant. maybe switch to ARC"
+                                );
+                                return;
+                            }
+                            debug!(?cid, ?pid, "resuming participant on a new channel");
+                            let s2b_create_channel_s = pi.s2b_create_channel_s.clone();
+                            drop(participants);
+                            let (b2s_create_channel_done_s, b2s_create_channel_done_r) =
+                                oneshot::channel();
+                            if s2b_create_channel_s
+                                .send((cid, sid, protocol, b2s_create_channel_done_s))
+                                .is_err()
+                                || b2s_create_channel_done_r.await.is_err()
+                            {
+                                debug!(?cid, ?pid, "participant got closed before resuming");
+                            }
                         }
                         //From now on this CHANNEL can receiver other frames!
                         // move directly to participant!
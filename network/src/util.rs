@@ -47,3 +47,15 @@ impl<T: Eq + Hash> DeferredTracer<T> {
         }
     }
 }
+
+/// Stand-ins for `network_protocol::sim` without the `sim` feature. As no
+/// [`SimConfig`] can exist, nothing is simulated.
+#[cfg(not(feature = "sim"))]
+pub(crate) mod sim {
+    #[derive(Debug, Clone)]
+    pub(crate) enum SimConfig {}
+
+    pub(crate) type SimDrain<D> = D;
+
+    pub(crate) type SimSink<S> = S;
+}
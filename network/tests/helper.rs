@@ -8,10 +8,16 @@ use std::{
     thread,
     time::Duration,
 };
-use tokio::runtime::Runtime;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    runtime::Runtime,
+    sync::Notify,
+};
 use tracing::*;
 use tracing_subscriber::EnvFilter;
-use veloren_network::{ConnectAddr, ListenAddr, Network, Participant, Pid, Promises, Stream};
+use veloren_network::{
+    ConnectAddr, ListenAddr, Network, Participant, Pid, Promises, SimConfig, Stream,
+};
 
 // sleep time when only internal rust calculations are done
 #[allow(dead_code)]
@@ -53,6 +59,21 @@ pub fn setup(tracing: bool, sleep: u64) -> (u64, u64) {
     (0, 0)
 }
 
+/// A bad, but usable link. Setting `NETWORK_SIMULATION` runs all tests using
+/// [`network_participant_stream`] over it.
+#[allow(dead_code)]
+pub fn lossy() -> SimConfig {
+    SimConfig {
+        latency: Duration::from_millis(30),
+        jitter: Duration::from_millis(20),
+        drop_rate: 0.05,
+        duplicate_rate: 0.01,
+        reorder_rate: 0.02,
+        bandwidth: 1_000_000,
+        seed: 1337,
+    }
+}
+
 #[allow(dead_code)]
 pub fn network_participant_stream(
     addr: (ListenAddr, ConnectAddr),
@@ -64,11 +85,30 @@ pub fn network_participant_stream(
     Network,
     Participant,
     Stream,
+) {
+    let sim = std::env::var_os("NETWORK_SIMULATION").map(|_| lossy());
+    network_participant_stream_simulated(addr, sim)
+}
+
+#[allow(dead_code)]
+pub fn network_participant_stream_simulated(
+    addr: (ListenAddr, ConnectAddr),
+    sim: Option<SimConfig>,
+) -> (
+    Arc<Runtime>,
+    Network,
+    Participant,
+    Stream,
+    Network,
+    Participant,
+    Stream,
 ) {
     let runtime = Arc::new(Runtime::new().unwrap());
     let (n_a, p1_a, s1_a, n_b, p1_b, s1_b) = runtime.block_on(async {
         let n_a = Network::new(Pid::fake(0), &runtime);
         let n_b = Network::new(Pid::fake(1), &runtime);
+        n_a.simulate(sim.clone()).await;
+        n_b.simulate(sim).await;
 
         n_a.listen(addr.0).await.unwrap();
         let p1_b = n_b.connect(addr.1).await.unwrap();
@@ -82,18 +122,61 @@ pub fn network_participant_stream(
     (runtime, n_a, p1_a, s1_a, n_b, p1_b, s1_b)
 }
 
+lazy_static! {
+    static ref TCP_PORTS: AtomicU16 = AtomicU16::new(5000);
+}
+
+/// Setting `NETWORK_WEBSOCKET` runs all tests using [`tcp`] over
+/// [`websocket`] instead.
 #[allow(dead_code)]
 pub fn tcp() -> (ListenAddr, ConnectAddr) {
-    lazy_static! {
-        static ref PORTS: AtomicU16 = AtomicU16::new(5000);
+    if std::env::var_os("NETWORK_WEBSOCKET").is_some() {
+        return websocket();
     }
-    let port = PORTS.fetch_add(1, Ordering::Relaxed);
+    let port = TCP_PORTS.fetch_add(1, Ordering::Relaxed);
     (
         ListenAddr::Tcp(SocketAddr::from(([127, 0, 0, 1], port))),
         ConnectAddr::Tcp(SocketAddr::from(([127, 0, 0, 1], port))),
     )
 }
 
+/// Like [`tcp`], but the connect address is a proxy that cuts all its
+/// connections when the returned [`Notify`] is triggered, like a dropped wifi
+/// would. Needs to be called inside the runtime.
+#[allow(dead_code)]
+pub async fn tcp_proxy() -> (ListenAddr, ConnectAddr, Arc<Notify>) {
+    let target = SocketAddr::from(([127, 0, 0, 1], TCP_PORTS.fetch_add(1, Ordering::Relaxed)));
+    let proxy = SocketAddr::from(([127, 0, 0, 1], TCP_PORTS.fetch_add(1, Ordering::Relaxed)));
+    let listener = TcpListener::bind(proxy).await.unwrap();
+    let cut = Arc::new(Notify::new());
+    let cut_connections = Arc::clone(&cut);
+    tokio::spawn(async move {
+        while let Ok((mut inbound, _)) = listener.accept().await {
+            let cut = Arc::clone(&cut_connections);
+            tokio::spawn(async move {
+                let mut outbound = match TcpStream::connect(target).await {
+                    Ok(outbound) => outbound,
+                    Err(_) => return,
+                };
+                tokio::select! {
+                    _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound) => (),
+                    _ = cut.notified() => trace!("cut proxied connection"),
+                }
+            });
+        }
+    });
+    (ListenAddr::Tcp(target), ConnectAddr::Tcp(proxy), cut)
+}
+
+#[allow(dead_code)]
+pub fn websocket() -> (ListenAddr, ConnectAddr) {
+    let port = TCP_PORTS.fetch_add(1, Ordering::Relaxed);
+    (
+        ListenAddr::WebSocket(SocketAddr::from(([127, 0, 0, 1], port))),
+        ConnectAddr::WebSocket(format!("ws://127.0.0.1:{}", port)),
+    )
+}
+
 lazy_static! {
     static ref UDP_PORTS: AtomicU16 = AtomicU16::new(5000);
 }
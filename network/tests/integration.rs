@@ -1,10 +1,16 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 use tokio::runtime::Runtime;
-use veloren_network::{NetworkError, StreamError};
+use veloren_network::{NetworkConnectError, NetworkError, StreamError};
 mod helper;
-use helper::{mpsc, network_participant_stream, quic, tcp, udp, SLEEP_EXTERNAL, SLEEP_INTERNAL};
+use helper::{
+    mpsc, network_participant_stream, network_participant_stream_simulated, quic, tcp, tcp_proxy,
+    udp, websocket, SLEEP_EXTERNAL, SLEEP_INTERNAL,
+};
 use std::io::ErrorKind;
-use veloren_network::{ConnectAddr, ListenAddr, Network, Pid, Promises};
+use veloren_network::{
+    ConnectAddr, Direction, Identity, InitProtocolError, ListenAddr, Network, Pid, Promises,
+    Recorder, Recording,
+};
 
 #[test]
 fn stream_simple() {
@@ -90,7 +96,6 @@ fn stream_simple_quic_3msg() {
 }
 
 #[test]
-#[ignore]
 fn stream_simple_udp() {
     let (_, _) = helper::setup(false, 0);
     let (r, _n_a, _p_a, mut s1_a, _n_b, _p_b, mut s1_b) = network_participant_stream(udp());
@@ -101,7 +106,6 @@ fn stream_simple_udp() {
 }
 
 #[test]
-#[ignore]
 fn stream_simple_udp_3msg() {
     let (_, _) = helper::setup(false, 0);
     let (r, _n_a, _p_a, mut s1_a, _n_b, _p_b, mut s1_b) = network_participant_stream(udp());
@@ -115,6 +119,89 @@ fn stream_simple_udp_3msg() {
     drop((_n_a, _n_b, _p_a, _p_b)); //clean teardown
 }
 
+#[test]
+fn stream_simple_websocket() {
+    let (_, _) = helper::setup(false, 0);
+    let (r, _n_a, _p_a, mut s1_a, _n_b, _p_b, mut s1_b) = network_participant_stream(websocket());
+
+    s1_a.send("Hello World").unwrap();
+    assert_eq!(r.block_on(s1_b.recv()), Ok("Hello World".to_string()));
+    drop((_n_a, _n_b, _p_a, _p_b)); //clean teardown
+}
+
+#[test]
+fn stream_simple_websocket_3msg() {
+    let (_, _) = helper::setup(false, 0);
+    let (r, _n_a, _p_a, mut s1_a, _n_b, _p_b, mut s1_b) = network_participant_stream(websocket());
+
+    s1_a.send("Hello World").unwrap();
+    s1_a.send(1337).unwrap();
+    assert_eq!(r.block_on(s1_b.recv()), Ok("Hello World".to_string()));
+    assert_eq!(r.block_on(s1_b.recv()), Ok(1337));
+    s1_a.send("3rdMessage").unwrap();
+    assert_eq!(r.block_on(s1_b.recv()), Ok("3rdMessage".to_string()));
+    drop((_n_a, _n_b, _p_a, _p_b)); //clean teardown
+}
+
+#[test]
+fn stream_simple_websocket_encrypted() {
+    let (_, _) = helper::setup(false, 0);
+    let (r, _n_a, p_a, _, _n_b, p_b, _) = network_participant_stream(websocket());
+
+    let mut s2_a = r
+        .block_on(p_a.open(4, Promises::ORDERED | Promises::ENCRYPTED, 0))
+        .unwrap();
+    let mut s2_b = r.block_on(p_b.opened()).unwrap();
+    s2_a.send("Hello World").unwrap();
+    assert_eq!(r.block_on(s2_b.recv()), Ok("Hello World".to_string()));
+    drop((_n_a, _n_b, p_a, p_b)); //clean teardown
+}
+
+#[test]
+fn stream_simple_3msg_simulated_tcp() {
+    let (_, _) = helper::setup(false, 0);
+    let (r, _n_a, _p_a, mut s1_a, _n_b, _p_b, mut s1_b) =
+        network_participant_stream_simulated(tcp(), Some(helper::lossy()));
+
+    s1_a.send("Hello World").unwrap();
+    s1_a.send(1337).unwrap();
+    assert_eq!(r.block_on(s1_b.recv()), Ok("Hello World".to_string()));
+    assert_eq!(r.block_on(s1_b.recv()), Ok(1337));
+    s1_a.send(vec![42u8; 100_000]).unwrap();
+    assert_eq!(r.block_on(s1_b.recv()), Ok(vec![42u8; 100_000]));
+    drop((_n_a, _n_b, _p_a, _p_b)); //clean teardown
+}
+
+#[test]
+fn stream_simple_3msg_simulated_udp() {
+    let (_, _) = helper::setup(false, 0);
+    let (r, _n_a, _p_a, mut s1_a, _n_b, _p_b, mut s1_b) =
+        network_participant_stream_simulated(udp(), Some(helper::lossy()));
+
+    s1_a.send("Hello World").unwrap();
+    s1_a.send(1337).unwrap();
+    assert_eq!(r.block_on(s1_b.recv()), Ok("Hello World".to_string()));
+    assert_eq!(r.block_on(s1_b.recv()), Ok(1337));
+    s1_a.send(vec![42u8; 100_000]).unwrap();
+    assert_eq!(r.block_on(s1_b.recv()), Ok(vec![42u8; 100_000]));
+    drop((_n_a, _n_b, _p_a, _p_b)); //clean teardown
+}
+
+#[test]
+fn stream_simple_3msg_simulated_websocket() {
+    let (_, _) = helper::setup(false, 0);
+    let (r, _n_a, _p_a, mut s1_a, _n_b, _p_b, mut s1_b) =
+        network_participant_stream_simulated(websocket(), Some(helper::lossy()));
+
+    s1_a.send("Hello World").unwrap();
+    s1_a.send(1337).unwrap();
+    assert_eq!(r.block_on(s1_b.recv()), Ok("Hello World".to_string()));
+    assert_eq!(r.block_on(s1_b.recv()), Ok(1337));
+    s1_a.send(vec![42u8; 100_000]).unwrap();
+    assert_eq!(r.block_on(s1_b.recv()), Ok(vec![42u8; 100_000]));
+    drop((_n_a, _n_b, _p_a, _p_b)); //clean teardown
+}
+
 #[test]
 #[ignore]
 fn tcp_and_udp_2_connections() -> std::result::Result<(), Box<dyn std::error::Error>> {
@@ -143,7 +230,6 @@ fn tcp_and_udp_2_connections() -> std::result::Result<(), Box<dyn std::error::Er
 }
 
 #[test]
-#[ignore]
 fn failed_listen_on_used_ports() -> std::result::Result<(), Box<dyn std::error::Error>> {
     let (_, _) = helper::setup(false, 0);
     let r = Arc::new(Runtime::new().unwrap());
@@ -262,3 +348,128 @@ fn multiple_try_recv() {
     assert_eq!(s1_b.try_recv::<String>(), Err(StreamError::StreamClosed));
     drop((_n_a, _n_b, _p_a, _p_b)); //clean teardown
 }
+
+#[test]
+fn peer_addr() {
+    let (_, _) = helper::setup(false, 0);
+    let addr = udp();
+    let connect_addr = match addr.1 {
+        ConnectAddr::Udp(addr) => addr,
+        _ => unreachable!(),
+    };
+    let (_r, _n_a, p_a, _s1_a, _n_b, p_b, _s1_b) = network_participant_stream(addr);
+    assert_eq!(p_b.peer_addr(), Some(connect_addr));
+    let remote = p_a.peer_addr().unwrap();
+    assert!(remote.ip().is_loopback());
+    assert_ne!(remote.port(), connect_addr.port());
+    drop((_n_a, _n_b, p_a, p_b)); //clean teardown
+
+    let (_r, _n_a, p_a, _s1_a, _n_b, p_b, _s1_b) = network_participant_stream(mpsc());
+    assert_eq!(p_a.peer_addr(), None);
+    assert_eq!(p_b.peer_addr(), None);
+    drop((_n_a, _n_b, p_a, p_b)); //clean teardown
+}
+
+#[test]
+fn stream_resumes_after_broken_channel() {
+    let (_, _) = helper::setup(false, 0);
+    let r = Arc::new(Runtime::new().unwrap());
+    let (n_a, _p_a, mut s1_a, n_b, _p_b, mut s1_b, cut) = r.block_on(async {
+        let (listen, connect, cut) = tcp_proxy().await;
+        let n_a = Network::new(Pid::fake(0), &r);
+        let n_b = Network::new(Pid::fake(1), &r);
+        n_a.listen(listen).await.unwrap();
+        let p_b = n_b.connect(connect).await.unwrap();
+        let p_a = n_a.connected().await.unwrap();
+        let s1_a = p_a
+            .open(4, Promises::ORDERED | Promises::GUARANTEED_DELIVERY, 0)
+            .await
+            .unwrap();
+        let s1_b = p_b.opened().await.unwrap();
+        (n_a, p_a, s1_a, n_b, p_b, s1_b, cut)
+    });
+
+    for i in 0..500u32 {
+        s1_a.send(i).unwrap();
+    }
+    r.block_on(async {
+        for i in 0..100u32 {
+            assert_eq!(s1_b.recv().await, Ok(i));
+        }
+    });
+    // break the channel while messages are still in flight, `n_b` reconnects
+    cut.notify_waiters();
+    for i in 500..1000u32 {
+        s1_a.send(i).unwrap();
+    }
+    r.block_on(async {
+        // resuming has to happen within the grace period of 20s
+        let received = tokio::time::timeout(Duration::from_secs(20), async {
+            for i in 100..1000u32 {
+                assert_eq!(s1_b.recv().await, Ok(i));
+            }
+        });
+        received.await.expect("stream wasn't resumed");
+    });
+    std::thread::sleep(SLEEP_EXTERNAL);
+    // replayed messages the remote already had must not show up twice
+    assert_eq!(s1_b.try_recv::<u32>(), Ok(None));
+    drop((n_a, n_b, _p_a, _p_b)); //clean teardown
+}
+
+#[test]
+fn remote_identity() {
+    let (_, _) = helper::setup(false, 0);
+    let r = Arc::new(Runtime::new().unwrap());
+    let identity = Identity::generate();
+    let public_identity = identity.public_identity();
+    let wrong_identity = Identity::generate().public_identity();
+    let (listen, connect) = tcp();
+    let n_a = Network::new(Pid::fake(0), &r);
+    let n_b = Network::new(Pid::fake(1), &r);
+    let n_c = Network::new(Pid::fake(2), &r);
+    let n_d = Network::new(Pid::fake(3), &r);
+    r.block_on(async {
+        n_a.set_identity(identity).await;
+        n_a.listen(listen).await.unwrap();
+        // trust on first use
+        let p_b = n_b.connect(connect.clone()).await.unwrap();
+        let p_a = n_a.connected().await.unwrap();
+        assert_eq!(p_b.remote_identity(), Some(public_identity));
+        assert_eq!(p_a.remote_identity(), None);
+
+        assert!(
+            n_c.connect_pinned(connect.clone(), public_identity)
+                .await
+                .is_ok()
+        );
+        let _p_a = n_a.connected().await.unwrap();
+        match n_d.connect_pinned(connect, wrong_identity).await {
+            Err(NetworkError::ConnectFailed(NetworkConnectError::Handshake(
+                InitProtocolError::WrongIdentity(Some(identity)),
+            ))) => assert_eq!(identity, public_identity),
+            _ => panic!("connected to a remote with the wrong identity"),
+        }
+    });
+    drop((n_a, n_b, n_c, n_d)); //clean teardown
+}
+
+#[test]
+fn stream_skip_recording_sent() {
+    let (_, _) = helper::setup(false, 0);
+    let (r, _n_a, _p_a, mut s1_a, _n_b, _p_b, mut s1_b) = network_participant_stream(tcp());
+    let path = std::env::temp_dir().join(format!("veloren-record-skip-{}", std::process::id()));
+    s1_a.record(Recorder::create(&path).unwrap());
+    s1_a.skip_recording_sent();
+
+    s1_a.send("secret").unwrap();
+    s1_b.send("Hello World").unwrap();
+    assert_eq!(r.block_on(s1_b.recv()), Ok("secret".to_string()));
+    assert_eq!(r.block_on(s1_a.recv()), Ok("Hello World".to_string()));
+
+    let recording = Recording::load(&path).unwrap();
+    assert_eq!(recording.messages.len(), 1);
+    assert_eq!(recording.messages[0].direction, Direction::Received);
+    std::fs::remove_file(&path).unwrap();
+    drop((_n_a, _n_b, _p_a, _p_b)); //clean teardown
+}
@@ -39,6 +39,74 @@ impl core::fmt::Display for RetrieveError {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub enum StorageError {
+    /// The server didn't give plugins access to a storage
+    Unavailable,
+    BackendError(String),
+    BincodeError(String),
+    InvalidType,
+}
+
+impl core::fmt::Display for StorageError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            StorageError::Unavailable => {
+                write!(f, "StorageError: No storage is available to plugins")
+            },
+            StorageError::BackendError(e) => {
+                write!(f, "StorageError: The storage failed: {}", e)
+            },
+            StorageError::BincodeError(e) => {
+                write!(f, "StorageError: Bincode error: {}", e)
+            },
+            StorageError::InvalidType => {
+                write!(
+                    f,
+                    "StorageError: This type wasn't expected as the result for this StorageAction"
+                )
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum TimerError {
+    /// A delay was negative or an interval wasn't positive, or one of them
+    /// wasn't finite
+    InvalidDuration(f64),
+    /// The module already has this many timers, the maximum
+    TooManyTimers(usize),
+    BincodeError(String),
+    InvalidType,
+}
+
+impl core::fmt::Display for TimerError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TimerError::InvalidDuration(d) => {
+                write!(f, "TimerError: {} isn't a valid timer duration", d)
+            },
+            TimerError::TooManyTimers(max) => {
+                write!(
+                    f,
+                    "TimerError: A module can't have more than {} timers",
+                    max
+                )
+            },
+            TimerError::BincodeError(e) => {
+                write!(f, "TimerError: Bincode error: {}", e)
+            },
+            TimerError::InvalidType => {
+                write!(
+                    f,
+                    "TimerError: This type wasn't expected as the result for this TimerAction"
+                )
+            },
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum EcsAccessError {
     EcsPointerNotAvailable,
@@ -1,6 +1,9 @@
 pub extern crate common;
 
-pub use common::comp::Health;
+pub use common::{
+    combat::{KillSource, KillType},
+    comp::Health,
+};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 pub use common::{resources::GameMode, uid::Uid};
@@ -31,10 +34,19 @@ pub use event::*;
 /// ```
 #[derive(Deserialize, Serialize, Debug)]
 pub enum Action {
+    /// Gracefully shuts the server down, warning connected players first
     ServerClose,
+    /// Prints a message in the server logs
     Print(String),
+    /// Sends a chat message to a single player
     PlayerSendMessage(Uid, String),
+    /// Kills an entity, as if it had lost all its health
     KillEntity(Uid),
+    /// Declares a chat command handled by this plugin, replacing any command
+    /// of the same name it declared before. This should be emitted while
+    /// handling [`event::PluginLoadEvent`] so that connecting clients know
+    /// about the command.
+    RegisterCommand(CommandSpec),
 }
 
 /// The [`Retrieve`] enum represents read of the ECS is sync and blocking.
@@ -68,6 +80,19 @@ pub enum Action {
 pub enum Retrieve {
     GetPlayerName(Uid),
     GetEntityHealth(Uid),
+    GetEntityPosition(Uid),
+    GetEntityVelocity(Uid),
+    GetEntityInventory(Uid),
+    GetEntityStats(Uid),
+    GetEntityGroup(Uid),
+    /// Entities whose position is within `radius` blocks of `center`. The
+    /// radius is limited to 2048 blocks, a negative or non-finite one is an
+    /// error
+    GetEntitiesInRadius {
+        center: [f32; 3],
+        radius: f32,
+    },
+    GetBlock([i32; 3]),
 }
 
 /// The [`RetrieveResult`] struct is generated while using the `retrieve_action`
@@ -95,12 +120,343 @@ pub enum Retrieve {
 pub enum RetrieveResult {
     GetPlayerName(String),
     GetEntityHealth(Health),
+    GetEntityPosition([f32; 3]),
+    GetEntityVelocity([f32; 3]),
+    GetEntityInventory(InventoryInfo),
+    GetEntityStats(StatsInfo),
+    /// The members of the group of the entity, `None` if it isn't in a group
+    GetEntityGroup(Option<Vec<Uid>>),
+    GetEntitiesInRadius(Vec<Uid>),
+    /// `None` if the block isn't loaded
+    GetBlock(Option<BlockInfo>),
+}
+
+/// The [`StorageAction`] enum represents an operation on the persistent
+/// key/value storage of the plugin. Values are kept across restarts, and each
+/// plugin only sees its own keys. Like [`Retrieve`], it is sync and blocking.
+///
+/// You should always prefer using the `storage_*` functions of the plugin
+/// runtime, which take care of encoding the values.
+#[derive(Deserialize, Serialize, Debug)]
+pub enum StorageAction {
+    Get(String),
+    Set(String, Vec<u8>),
+    Delete(String),
+    ListKeys,
+}
+
+/// The [`StorageResult`] enum is generated while using the `storage_action`
+/// function
+#[derive(Serialize, Deserialize, Debug)]
+pub enum StorageResult {
+    Get(Option<Vec<u8>>),
+    Set,
+    Delete,
+    ListKeys(Vec<String>),
+}
+
+/// The [`TimerAction`] enum schedules or cancels timers, which fire
+/// [`event::TimerEvent`] on the module that scheduled them. Durations are in
+/// seconds of server time. Like [`Retrieve`], it is sync and blocking.
+///
+/// You should always prefer using the timer functions of the plugin runtime,
+/// which take care of encoding the payloads.
+#[derive(Deserialize, Serialize, Debug)]
+pub enum TimerAction {
+    /// Fires once after `delay`, then every `interval` if it is set
+    Schedule {
+        delay: f64,
+        interval: Option<f64>,
+        payload: Vec<u8>,
+    },
+    Cancel(TimerId),
+}
+
+/// The [`TimerResult`] enum is generated while using the `timer_action`
+/// function
+#[derive(Serialize, Deserialize, Debug)]
+pub enum TimerResult {
+    Schedule(TimerId),
+    /// `false` if there was no such timer, for instance because a one-shot
+    /// timer already fired
+    Cancel(bool),
+}
+
+/// Identifies a timer among the ones of a plugin module
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct TimerId(pub u64);
+
+/// The [`BlockInfo`] struct is a plain description of a terrain block, as seen
+/// by plugins
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct BlockInfo {
+    /// The kind of the block, for instance `Rock` or `Air`
+    pub kind: String,
+    /// The sprite occupying the block, if any
+    pub sprite: Option<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<common::terrain::Block> for BlockInfo {
+    fn from(block: common::terrain::Block) -> Self {
+        Self {
+            kind: block.kind().to_string(),
+            sprite: block.get_sprite().map(|sprite| sprite.to_string()),
+        }
+    }
+}
+
+/// The [`ItemInfo`] struct is a plain description of a stack of items, as seen
+/// by plugins
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct ItemInfo {
+    /// The asset identifier of the item, for instance
+    /// `common.items.weapons.sword.starter`
+    pub item_definition_id: String,
+    pub name: String,
+    pub amount: u32,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<&common::comp::Item> for ItemInfo {
+    fn from(item: &common::comp::Item) -> Self {
+        Self {
+            item_definition_id: item.item_definition_id().to_owned(),
+            name: item.name().to_owned(),
+            amount: item.amount(),
+        }
+    }
+}
+
+/// The [`InventoryInfo`] struct describes the content of an inventory
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct InventoryInfo {
+    /// The items in the inventory slots, empty slots are skipped
+    pub items: Vec<ItemInfo>,
+    /// The items in the loadout of the entity
+    pub equipped: Vec<ItemInfo>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<&common::comp::Inventory> for InventoryInfo {
+    fn from(inventory: &common::comp::Inventory) -> Self {
+        Self {
+            items: inventory.slots().flatten().map(ItemInfo::from).collect(),
+            equipped: inventory.equipped_items().map(ItemInfo::from).collect(),
+        }
+    }
+}
+
+/// The [`SkillGroupInfo`] struct describes the progress of an entity in a
+/// skill group
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct SkillGroupInfo {
+    /// The kind of the skill group, for instance `General` or `Weapon(Sword)`
+    pub kind: String,
+    pub exp: u16,
+    pub available_sp: u16,
+    pub earned_sp: u16,
+}
+
+/// The [`StatsInfo`] struct describes the stats and skills of an entity
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StatsInfo {
+    pub name: String,
+    pub damage_reduction: f32,
+    pub move_speed_modifier: f32,
+    pub attack_speed_modifier: f32,
+    /// Empty if the entity has no skill set
+    pub skill_groups: Vec<SkillGroupInfo>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StatsInfo {
+    pub fn new(stats: &common::comp::Stats, skill_set: Option<&common::comp::SkillSet>) -> Self {
+        Self {
+            name: stats.name.clone(),
+            damage_reduction: stats.damage_reduction,
+            move_speed_modifier: stats.move_speed_modifier,
+            attack_speed_modifier: stats.attack_speed_modifier,
+            skill_groups: skill_set
+                .map(|skill_set| {
+                    skill_set
+                        .skill_groups
+                        .iter()
+                        .map(|group| SkillGroupInfo {
+                            kind: format!("{:?}", group.skill_group_kind),
+                            exp: group.exp,
+                            available_sp: group.available_sp,
+                            earned_sp: group.earned_sp,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// The [`CommandSpec`] struct declares a chat command handled by a plugin,
+/// either in the `commands` list of its `plugin.toml` or with
+/// [`Action::RegisterCommand`]. The command is listed in `/help`, tab completed
+/// by clients and delivered only to the plugin declaring it.
+///
+/// # Usage:
+/// ```toml
+/// [[commands]]
+/// name = "warp"
+/// description = "Teleports a player to a saved location"
+/// needs_role = "moderator"
+/// args = [{ any = { label = "location" } }, { player_name = { optional = true } }]
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CommandSpec {
+    /// The keyword used to run the command, without the leading `/`
+    pub name: String,
+    /// A one-line message that explains what the command does
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub args: Vec<CommandArgument>,
+    /// The role a player needs to run the command, anyone can run it if unset
+    #[serde(default)]
+    pub needs_role: Option<CommandRole>,
+}
+
+/// The role a player needs to run a plugin command
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandRole {
+    Moderator,
+    Admin,
+}
+
+/// An argument of a plugin command, used for the help message and tab
+/// completion. Arguments are still given to the plugin as plain strings.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandArgument {
+    /// The alias of a player
+    PlayerName {
+        #[serde(default)]
+        optional: bool,
+    },
+    /// A float, `suggestion` is offered by tab completion
+    Float {
+        label: String,
+        #[serde(default)]
+        suggestion: f32,
+        #[serde(default)]
+        optional: bool,
+    },
+    /// An integer, `suggestion` is offered by tab completion
+    Integer {
+        label: String,
+        #[serde(default)]
+        suggestion: i32,
+        #[serde(default)]
+        optional: bool,
+    },
+    /// Any string that doesn't contain spaces
+    Any {
+        label: String,
+        #[serde(default)]
+        optional: bool,
+    },
+    /// The name of another command
+    Command {
+        #[serde(default)]
+        optional: bool,
+    },
+    /// The rest of the input, this must be the last argument
+    Message {
+        #[serde(default)]
+        optional: bool,
+    },
+    /// One of the given values
+    Enum {
+        label: String,
+        values: Vec<String>,
+        #[serde(default)]
+        optional: bool,
+    },
+    /// `true` or `false`
+    Boolean {
+        label: String,
+        #[serde(default)]
+        optional: bool,
+    },
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<CommandRole> for common::comp::AdminRole {
+    fn from(role: CommandRole) -> Self {
+        match role {
+            CommandRole::Moderator => Self::Moderator,
+            CommandRole::Admin => Self::Admin,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<&CommandArgument> for common::cmd::ArgumentSpec<String> {
+    fn from(arg: &CommandArgument) -> Self {
+        use common::cmd::Requirement;
+        let requirement = |optional: bool| {
+            if optional {
+                Requirement::Optional
+            } else {
+                Requirement::Required
+            }
+        };
+        match arg {
+            CommandArgument::PlayerName { optional } => Self::PlayerName(requirement(*optional)),
+            CommandArgument::Float {
+                label,
+                suggestion,
+                optional,
+            } => Self::Float(label.clone(), *suggestion, requirement(*optional)),
+            CommandArgument::Integer {
+                label,
+                suggestion,
+                optional,
+            } => Self::Integer(label.clone(), *suggestion, requirement(*optional)),
+            CommandArgument::Any { label, optional } => {
+                Self::Any(label.clone(), requirement(*optional))
+            },
+            CommandArgument::Command { optional } => Self::Command(requirement(*optional)),
+            CommandArgument::Message { optional } => Self::Message(requirement(*optional)),
+            CommandArgument::Enum {
+                label,
+                values,
+                optional,
+            } => Self::Enum(label.clone(), values.clone(), requirement(*optional)),
+            CommandArgument::Boolean { label, optional } => {
+                Self::Boolean(label.clone(), "true".to_owned(), requirement(*optional))
+            },
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<&CommandSpec> for common::cmd::ServerChatCommand {
+    fn from(spec: &CommandSpec) -> Self {
+        Self {
+            keyword: spec.name.clone(),
+            args: spec.args.iter().map(Into::into).collect(),
+            description: spec.description.clone(),
+            needs_role: spec.needs_role.map(Into::into),
+        }
+    }
 }
 
 /// This trait is implement by all events and ensure type safety of FFI.
 pub trait Event: Serialize + DeserializeOwned + Send + Sync {
     type Response: Serialize + DeserializeOwned + Send + Sync;
 
+    /// Whether the event is delivered to one plugin after the other, following
+    /// the dependency order, instead of to all plugins at the same time
+    const ORDERED: bool = false;
+
     fn get_event_name(&self) -> String;
 }
 
@@ -112,6 +468,10 @@ pub mod event {
     /// This event is called when a chat command is run.
     /// Your event should be named `on_command_<Your command>`
     ///
+    /// Commands declared with a [`CommandSpec`] are only sent to the plugin
+    /// declaring them, once the player was checked to have the required role.
+    /// Undeclared commands are sent to every plugin.
+    ///
     /// If you return an Error the displayed message will be the error message
     /// in red You can return a Vec<String> that will be print to player
     /// chat as info
@@ -176,6 +536,8 @@ pub mod event {
     impl Event for PlayerJoinEvent {
         type Response = PlayerJoinResult;
 
+        const ORDERED: bool = true;
+
         fn get_event_name(&self) -> String { "on_join".to_owned() }
     }
 
@@ -217,9 +579,225 @@ pub mod event {
     impl Event for PluginLoadEvent {
         type Response = ();
 
+        const ORDERED: bool = true;
+
         fn get_event_name(&self) -> String { "on_load".to_owned() }
     }
 
+    /// This event is called when a player leaves the server
+    /// Your event should be named `on_player_leave`
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[event_handler]
+    /// pub fn on_player_leave(leave: PlayerLeaveEvent) {
+    ///     emit_action(Action::Print(format!("{} left", leave.player_name)));
+    /// }
+    /// ```
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct PlayerLeaveEvent {
+        pub player_name: String,
+        pub player_id: [u8; 16],
+        pub player: Player,
+    }
+
+    impl Event for PlayerLeaveEvent {
+        type Response = ();
+
+        fn get_event_name(&self) -> String { "on_player_leave".to_owned() }
+    }
+
+    /// This event is called when an entity dies, players and NPCs alike
+    /// Your event should be named `on_entity_death`
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[event_handler]
+    /// pub fn on_entity_death(death: EntityDeathEvent) {
+    ///     if let KillSource::Player(killer, _) = death.killer {
+    ///         emit_action(Action::PlayerSendMessage(killer, "Well done!".to_owned()));
+    ///     }
+    /// }
+    /// ```
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct EntityDeathEvent {
+        pub entity: Uid,
+        pub killer: KillSource,
+    }
+
+    impl Event for EntityDeathEvent {
+        type Response = ();
+
+        fn get_event_name(&self) -> String { "on_entity_death".to_owned() }
+    }
+
+    /// This event is called when a player sends a chat message, before it is
+    /// broadcast
+    /// Your event should be named `on_chat_message`
+    ///
+    /// You can return `Cancel` to drop the message or `Rewrite` to change its
+    /// content. If several plugins answer, a `Cancel` always wins.
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[event_handler]
+    /// pub fn on_chat_message(chat: ChatMessageEvent) -> ChatMessageResult {
+    ///     ChatMessageResult::Rewrite(chat.message.to_uppercase())
+    /// }
+    /// ```
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct ChatMessageEvent {
+        pub player: Player,
+        pub message: String,
+    }
+
+    impl Event for ChatMessageEvent {
+        type Response = ChatMessageResult;
+
+        const ORDERED: bool = true;
+
+        fn get_event_name(&self) -> String { "on_chat_message".to_owned() }
+    }
+
+    /// This is the return type of an `on_chat_message` event. See
+    /// [`ChatMessageEvent`]
+    ///
+    /// Variants:
+    ///  - `None` will let the message through unchanged.
+    ///  - `Cancel` will drop the message.
+    ///  - `Rewrite` will replace the content of the message.
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub enum ChatMessageResult {
+        None,
+        Cancel,
+        Rewrite(String),
+    }
+
+    impl Default for ChatMessageResult {
+        fn default() -> Self { Self::None }
+    }
+
+    /// This event is called when a player breaks a block, either by mining it
+    /// or in build mode
+    /// Your event should be named `on_block_break`
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[event_handler]
+    /// pub fn on_block_break(event: BlockBreakEvent) {
+    ///     emit_action(Action::Print(format!("Block broken at {:?}", event.pos)));
+    /// }
+    /// ```
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BlockBreakEvent {
+        pub player: Player,
+        pub pos: [i32; 3],
+        pub block: BlockInfo,
+    }
+
+    impl Event for BlockBreakEvent {
+        type Response = ();
+
+        fn get_event_name(&self) -> String { "on_block_break".to_owned() }
+    }
+
+    /// This event is called when a player places a block in build mode
+    /// Your event should be named `on_block_place`
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[event_handler]
+    /// pub fn on_block_place(event: BlockPlaceEvent) {
+    ///     emit_action(Action::Print(format!("Block placed at {:?}", event.pos)));
+    /// }
+    /// ```
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct BlockPlaceEvent {
+        pub player: Player,
+        pub pos: [i32; 3],
+        pub block: BlockInfo,
+    }
+
+    impl Event for BlockPlaceEvent {
+        type Response = ();
+
+        fn get_event_name(&self) -> String { "on_block_place".to_owned() }
+    }
+
+    /// This event is called when a player enters the game with a character
+    /// Your event should be named `on_character_selected`
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[event_handler]
+    /// pub fn on_character_selected(event: CharacterSelectedEvent) {
+    ///     emit_action(Action::PlayerSendMessage(event.player.id, "Welcome back!".to_owned()));
+    /// }
+    /// ```
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct CharacterSelectedEvent {
+        pub player: Player,
+        pub character_id: i64,
+    }
+
+    impl Event for CharacterSelectedEvent {
+        type Response = ();
+
+        fn get_event_name(&self) -> String { "on_character_selected".to_owned() }
+    }
+
+    /// This event is called once every server tick
+    /// Your event should be named `on_tick`
+    ///
+    /// Keep this handler cheap, it runs many times per second.
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[event_handler]
+    /// pub fn on_tick(tick: TickEvent) {
+    ///     if tick.tick % 3000 == 0 {
+    ///         emit_action(Action::Print("Still alive!".to_owned()));
+    ///     }
+    /// }
+    /// ```
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct TickEvent {
+        pub tick: u64,
+        pub time: f64,
+        pub dt: f32,
+    }
+
+    impl Event for TickEvent {
+        type Response = ();
+
+        fn get_event_name(&self) -> String { "on_tick".to_owned() }
+    }
+
+    /// This event is called when a timer scheduled with
+    /// [`TimerAction::Schedule`] fires
+    /// Your event should be named `on_timer`
+    ///
+    /// # Example
+    /// ```ignore
+    /// #[event_handler]
+    /// pub fn on_timer(timer: TimerEvent) {
+    ///     if let Ok(message) = timer_payload::<String>(&timer) {
+    ///         emit_action(Action::Print(message));
+    ///     }
+    /// }
+    /// ```
+    #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+    pub struct TimerEvent {
+        pub id: TimerId,
+        pub payload: Vec<u8>,
+    }
+
+    impl Event for TimerEvent {
+        type Response = ();
+
+        fn get_event_name(&self) -> String { "on_timer".to_owned() }
+    }
+
     // impl Default for PlayerJoinResult {
     //     fn default() -> Self {
     //         Self::None
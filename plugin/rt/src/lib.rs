@@ -1,9 +1,13 @@
 pub extern crate plugin_derive;
 
 pub mod retrieve;
+pub mod storage;
+pub mod timer;
 
-use api::RetrieveError;
+use api::{RetrieveError, StorageError, TimerError};
 pub use retrieve::*;
+pub use storage::*;
+pub use timer::*;
 
 use std::convert::TryInto;
 
@@ -18,6 +22,8 @@ use serde::{de::DeserializeOwned, Serialize};
 extern "C" {
     fn raw_emit_actions(ptr: i64, len: i64);
     fn raw_retrieve_action(ptr: i64, len: i64) -> i64;
+    fn raw_storage_action(ptr: i64, len: i64) -> i64;
+    fn raw_timer_action(ptr: i64, len: i64) -> i64;
     pub fn dbg(i: i32);
 }
 
@@ -39,6 +45,42 @@ pub fn retrieve_action<T: DeserializeOwned>(_actions: &api::Retrieve) -> Result<
     unreachable!()
 }
 
+pub fn storage_action(_action: &api::StorageAction) -> Result<api::StorageResult, StorageError> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let ret = bincode::serialize(&_action).expect("Can't serialize action in storage");
+        unsafe {
+            let ptr = raw_storage_action(to_i64(ret.as_ptr() as _), to_i64(ret.len() as _));
+            let ptr = from_i64(ptr);
+            let len =
+                u64::from_le_bytes(std::slice::from_raw_parts(ptr as _, 8).try_into().unwrap());
+            let a = ::std::slice::from_raw_parts((ptr + 8) as _, len as _);
+            bincode::deserialize::<Result<api::StorageResult, StorageError>>(&a)
+                .map_err(|x| StorageError::BincodeError(x.to_string()))?
+        }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    unreachable!()
+}
+
+pub fn timer_action(_action: &api::TimerAction) -> Result<api::TimerResult, TimerError> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let ret = bincode::serialize(&_action).expect("Can't serialize action in timer");
+        unsafe {
+            let ptr = raw_timer_action(to_i64(ret.as_ptr() as _), to_i64(ret.len() as _));
+            let ptr = from_i64(ptr);
+            let len =
+                u64::from_le_bytes(std::slice::from_raw_parts(ptr as _, 8).try_into().unwrap());
+            let a = ::std::slice::from_raw_parts((ptr + 8) as _, len as _);
+            bincode::deserialize::<Result<api::TimerResult, TimerError>>(&a)
+                .map_err(|x| TimerError::BincodeError(x.to_string()))?
+        }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    unreachable!()
+}
+
 pub fn emit_action(action: api::Action) { emit_actions(vec![action]) }
 
 pub fn emit_actions(_actions: Vec<api::Action>) {
@@ -1,4 +1,4 @@
-use plugin_api::{Health, RetrieveError};
+use plugin_api::{BlockInfo, Health, InventoryInfo, RetrieveError, StatsInfo, Uid};
 
 use crate::api::{Retrieve, RetrieveResult};
 
@@ -10,18 +10,109 @@ pub trait GetEntityHealth {
     fn get_entity_health(&self) -> Result<Health, RetrieveError>;
 }
 
-impl GetEntityHealth for crate::api::event::Player {
-    fn get_entity_health(&self) -> Result<Health, RetrieveError> {
-        if let RetrieveResult::GetEntityHealth(e) =
-            crate::retrieve_action(&Retrieve::GetEntityHealth(self.id))?
-        {
-            Ok(e)
-        } else {
-            Err(RetrieveError::InvalidType)
+pub trait GetEntityPosition {
+    fn get_entity_position(&self) -> Result<[f32; 3], RetrieveError>;
+}
+
+pub trait GetEntityVelocity {
+    fn get_entity_velocity(&self) -> Result<[f32; 3], RetrieveError>;
+}
+
+pub trait GetEntityInventory {
+    fn get_entity_inventory(&self) -> Result<InventoryInfo, RetrieveError>;
+}
+
+pub trait GetEntityStats {
+    fn get_entity_stats(&self) -> Result<StatsInfo, RetrieveError>;
+}
+
+pub trait GetEntityGroup {
+    /// Returns the members of the group of the entity, including itself, or
+    /// `None` if it isn't in a group
+    fn get_entity_group(&self) -> Result<Option<Vec<Uid>>, RetrieveError>;
+}
+
+/// Implements the getters for a type representing an entity, from the
+/// expression giving its `Uid`
+macro_rules! impl_entity_getters {
+    ($ty:ty, $self:ident => $uid:expr) => {
+        impl GetEntityHealth for $ty {
+            fn get_entity_health(&$self) -> Result<Health, RetrieveError> {
+                if let RetrieveResult::GetEntityHealth(e) =
+                    crate::retrieve_action(&Retrieve::GetEntityHealth($uid))?
+                {
+                    Ok(e)
+                } else {
+                    Err(RetrieveError::InvalidType)
+                }
+            }
         }
-    }
+
+        impl GetEntityPosition for $ty {
+            fn get_entity_position(&$self) -> Result<[f32; 3], RetrieveError> {
+                if let RetrieveResult::GetEntityPosition(e) =
+                    crate::retrieve_action(&Retrieve::GetEntityPosition($uid))?
+                {
+                    Ok(e)
+                } else {
+                    Err(RetrieveError::InvalidType)
+                }
+            }
+        }
+
+        impl GetEntityVelocity for $ty {
+            fn get_entity_velocity(&$self) -> Result<[f32; 3], RetrieveError> {
+                if let RetrieveResult::GetEntityVelocity(e) =
+                    crate::retrieve_action(&Retrieve::GetEntityVelocity($uid))?
+                {
+                    Ok(e)
+                } else {
+                    Err(RetrieveError::InvalidType)
+                }
+            }
+        }
+
+        impl GetEntityInventory for $ty {
+            fn get_entity_inventory(&$self) -> Result<InventoryInfo, RetrieveError> {
+                if let RetrieveResult::GetEntityInventory(e) =
+                    crate::retrieve_action(&Retrieve::GetEntityInventory($uid))?
+                {
+                    Ok(e)
+                } else {
+                    Err(RetrieveError::InvalidType)
+                }
+            }
+        }
+
+        impl GetEntityStats for $ty {
+            fn get_entity_stats(&$self) -> Result<StatsInfo, RetrieveError> {
+                if let RetrieveResult::GetEntityStats(e) =
+                    crate::retrieve_action(&Retrieve::GetEntityStats($uid))?
+                {
+                    Ok(e)
+                } else {
+                    Err(RetrieveError::InvalidType)
+                }
+            }
+        }
+
+        impl GetEntityGroup for $ty {
+            fn get_entity_group(&$self) -> Result<Option<Vec<Uid>>, RetrieveError> {
+                if let RetrieveResult::GetEntityGroup(e) =
+                    crate::retrieve_action(&Retrieve::GetEntityGroup($uid))?
+                {
+                    Ok(e)
+                } else {
+                    Err(RetrieveError::InvalidType)
+                }
+            }
+        }
+    };
 }
 
+impl_entity_getters!(crate::api::event::Player, self => self.id);
+impl_entity_getters!(Uid, self => *self);
+
 impl GetPlayerName for crate::api::event::Player {
     fn get_player_name(&self) -> Result<String, RetrieveError> {
         if let RetrieveResult::GetPlayerName(e) =
@@ -33,3 +124,23 @@ impl GetPlayerName for crate::api::event::Player {
         }
     }
 }
+
+/// Returns the entities whose position is within `radius` blocks of `center`
+pub fn get_entities_in_radius(center: [f32; 3], radius: f32) -> Result<Vec<Uid>, RetrieveError> {
+    if let RetrieveResult::GetEntitiesInRadius(e) =
+        crate::retrieve_action(&Retrieve::GetEntitiesInRadius { center, radius })?
+    {
+        Ok(e)
+    } else {
+        Err(RetrieveError::InvalidType)
+    }
+}
+
+/// Returns the block at the given position, or `None` if it isn't loaded
+pub fn get_block(pos: [i32; 3]) -> Result<Option<BlockInfo>, RetrieveError> {
+    if let RetrieveResult::GetBlock(e) = crate::retrieve_action(&Retrieve::GetBlock(pos))? {
+        Ok(e)
+    } else {
+        Err(RetrieveError::InvalidType)
+    }
+}
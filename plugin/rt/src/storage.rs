@@ -0,0 +1,44 @@
+use plugin_api::StorageError;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::api::{StorageAction, StorageResult};
+
+/// Reads the value stored under `key` by this plugin, if any
+pub fn storage_get<T: DeserializeOwned>(key: &str) -> Result<Option<T>, StorageError> {
+    if let StorageResult::Get(value) = crate::storage_action(&StorageAction::Get(key.to_owned()))? {
+        value
+            .map(|bytes| bincode::deserialize(&bytes))
+            .transpose()
+            .map_err(|e| StorageError::BincodeError(e.to_string()))
+    } else {
+        Err(StorageError::InvalidType)
+    }
+}
+
+/// Stores `value` under `key`, replacing the previous value
+pub fn storage_set<T: Serialize>(key: &str, value: &T) -> Result<(), StorageError> {
+    let bytes = bincode::serialize(value).map_err(|e| StorageError::BincodeError(e.to_string()))?;
+    if let StorageResult::Set = crate::storage_action(&StorageAction::Set(key.to_owned(), bytes))? {
+        Ok(())
+    } else {
+        Err(StorageError::InvalidType)
+    }
+}
+
+/// Removes the value stored under `key`
+pub fn storage_delete(key: &str) -> Result<(), StorageError> {
+    if let StorageResult::Delete = crate::storage_action(&StorageAction::Delete(key.to_owned()))? {
+        Ok(())
+    } else {
+        Err(StorageError::InvalidType)
+    }
+}
+
+/// Lists the keys stored by this plugin
+pub fn storage_keys() -> Result<Vec<String>, StorageError> {
+    if let StorageResult::ListKeys(keys) = crate::storage_action(&StorageAction::ListKeys)? {
+        Ok(keys)
+    } else {
+        Err(StorageError::InvalidType)
+    }
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use plugin_api::TimerError;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::api::{event::TimerEvent, TimerAction, TimerId, TimerResult};
+
+fn schedule<T: Serialize>(
+    delay: Duration,
+    interval: Option<Duration>,
+    payload: &T,
+) -> Result<TimerId, TimerError> {
+    let payload =
+        bincode::serialize(payload).map_err(|e| TimerError::BincodeError(e.to_string()))?;
+    if let TimerResult::Schedule(id) = crate::timer_action(&TimerAction::Schedule {
+        delay: delay.as_secs_f64(),
+        interval: interval.map(|interval| interval.as_secs_f64()),
+        payload,
+    })? {
+        Ok(id)
+    } else {
+        Err(TimerError::InvalidType)
+    }
+}
+
+/// Fires `on_timer` once, `delay` after now, with the given payload
+pub fn schedule_timer<T: Serialize>(delay: Duration, payload: &T) -> Result<TimerId, TimerError> {
+    schedule(delay, None, payload)
+}
+
+/// Fires `on_timer` every `interval`, starting `delay` after now, with the
+/// given payload
+pub fn schedule_repeating_timer<T: Serialize>(
+    delay: Duration,
+    interval: Duration,
+    payload: &T,
+) -> Result<TimerId, TimerError> {
+    schedule(delay, Some(interval), payload)
+}
+
+/// Stops a timer, returns `false` if there was no such timer
+pub fn cancel_timer(id: TimerId) -> Result<bool, TimerError> {
+    if let TimerResult::Cancel(cancelled) = crate::timer_action(&TimerAction::Cancel(id))? {
+        Ok(cancelled)
+    } else {
+        Err(TimerError::InvalidType)
+    }
+}
+
+/// Decodes the payload given when the timer was scheduled
+pub fn timer_payload<T: DeserializeOwned>(event: &TimerEvent) -> Result<T, TimerError> {
+    bincode::deserialize(&event.payload).map_err(|e| TimerError::BincodeError(e.to_string()))
+}
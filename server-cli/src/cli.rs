@@ -36,6 +36,35 @@ pub enum Shutdown {
     Cancel,
 }
 
+#[cfg(feature = "plugins")]
+#[derive(Clone, Debug, StructOpt)]
+pub enum Plugin {
+    /// Lists the loaded plugins and their modules
+    List,
+    /// Loads a plugin again from its tarball
+    Reload {
+        /// Name of the plugin to reload
+        name: String,
+    },
+    /// Unloads a plugin
+    Unload {
+        /// Name of the plugin to unload
+        name: String,
+    },
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum Backup {
+    /// Lists the database backups, oldest first
+    List,
+    /// Replaces the database with a backup, only possible while no players
+    /// are connected
+    Restore {
+        /// File name of the backup, as shown by `backup list`
+        name: String,
+    },
+}
+
 #[derive(Clone, Debug, StructOpt)]
 pub enum SharedCommand {
     /// Perform operations on the admin list
@@ -67,6 +96,17 @@ pub enum Message {
     },
     /// Disconnects all connected clients
     DisconnectAllClients,
+    /// Manage the database backups
+    Backup {
+        #[structopt(subcommand)]
+        command: Backup,
+    },
+    /// Manage the loaded plugins
+    #[cfg(feature = "plugins")]
+    Plugin {
+        #[structopt(subcommand)]
+        command: Plugin,
+    },
 }
 
 #[derive(StructOpt)]
@@ -9,8 +9,10 @@ mod settings;
 mod shutdown_coordinator;
 mod tui_runner;
 mod tuilog;
+#[cfg(feature = "plugins")]
+use crate::cli::Plugin;
 use crate::{
-    cli::{Admin, ArgvApp, ArgvCommand, Message, SharedCommand, Shutdown},
+    cli::{Admin, ArgvApp, ArgvCommand, Backup, Message, SharedCommand, Shutdown},
     shutdown_coordinator::ShutdownCoordinator,
     tui_runner::Tui,
     tuilog::TuiLog,
@@ -25,7 +27,7 @@ use std::{
     time::Duration,
 };
 use structopt::StructOpt;
-use tracing::{info, trace};
+use tracing::{error, info, trace};
 
 lazy_static::lazy_static! {
     pub static ref LOG: TuiLog<'static> = TuiLog::default();
@@ -195,6 +197,10 @@ fn main() -> io::Result<()> {
                 Event::ClientConnected { entity: _ } => info!("Client connected!"),
                 Event::ClientDisconnected { entity: _ } => info!("Client disconnected!"),
                 Event::Chat { entity: _, msg } => info!("[Client] {}", msg),
+                Event::Shutdown {
+                    grace_period,
+                    reason,
+                } => shutdown_coordinator.initiate_shutdown(&mut server, grace_period, reason),
             }
         }
 
@@ -246,6 +252,39 @@ fn main() -> io::Result<()> {
                     Message::DisconnectAllClients => {
                         server.disconnect_all_clients();
                     },
+                    Message::Backup { command } => match command {
+                        Backup::List => match server.list_backups() {
+                            Ok(backups) => {
+                                info!("{} database backup(s)", backups.len());
+                                for backup in backups {
+                                    info!("{} ({} KiB)", backup.name, backup.size / 1024);
+                                }
+                            },
+                            Err(e) => error!(?e, "Failed to list database backups"),
+                        },
+                        Backup::Restore { name } => match server.restore_backup(&name) {
+                            Ok(()) => info!("Restored database backup {}", name),
+                            Err(e) => error!("Failed to restore database backup {}: {}", name, e),
+                        },
+                    },
+                    #[cfg(feature = "plugins")]
+                    Message::Plugin { command } => match command {
+                        Plugin::List => {
+                            let plugins = server.plugin_list();
+                            info!("{} plugin(s) loaded", plugins.len());
+                            for plugin in plugins {
+                                info!("{}", plugin);
+                            }
+                        },
+                        Plugin::Reload { name } => match server.reload_plugin(&name) {
+                            Ok(()) => info!("Reloaded plugin {}", name),
+                            Err(e) => error!(?e, "Failed to reload plugin {}", name),
+                        },
+                        Plugin::Unload { name } => match server.unload_plugin(&name) {
+                            Ok(()) => info!("Unloaded plugin {}", name),
+                            Err(e) => error!(?e, "Failed to unload plugin {}", name),
+                        },
+                    },
                 },
                 Err(mpsc::TryRecvError::Empty) | Err(mpsc::TryRecvError::Disconnected) => {},
             }
@@ -0,0 +1,793 @@
+//! Token authenticated JSON API for administration tools like web dashboards
+//! or chat bridges, served next to the metrics endpoint when
+//! [`Settings::admin_api`] is set.
+//!
+//! Every token acts on behalf of an admin, and a call is only allowed if that
+//! admin's role would allow the equivalent chat command.  Requests are parsed
+//! on the tokio runtime and applied by the main thread during the next tick.
+//! Calls are recorded in the audit log as the `api` command.
+//!
+//! - `GET /players`
+//! - `POST /kick` with `{"alias", "reason"}`
+//! - `POST /ban` with `{"username", "reason", "duration_secs", "overwrite"}`
+//! - `POST /unban` with `{"username"}`
+//! - `POST /broadcast` with `{"message"}`
+//! - `GET /settings/<name>`
+//! - `PUT /settings/<name>` with the whole setting, as returned by GET, needs
+//!   the admin role
+//! - `POST /shutdown` with `{"grace_period_secs", "reason"}`
+//!
+//! `<name>` is one of `whitelist`, `banlist`, `admins` or `description`.
+//! Requests authenticate with an `Authorization: Bearer <token>` header, and
+//! errors are returned as `{"error": "..."}`.
+//!
+//! [`Settings::admin_api`]: crate::settings::Settings::admin_api
+
+use crate::{
+    events::Event,
+    login_provider::LoginProvider,
+    persistence::audit_log::{AuditEntry, AuditLog},
+    settings::{
+        AdminApiSettings, AdminApiToken, Admins, Ban, BanAction, BanInfo, Banlist, EditableSetting,
+        ServerDescription, SettingError, Whitelist,
+    },
+    Server,
+};
+use authc::Uuid;
+use chrono::Utc;
+use common::{
+    comp,
+    event::{EventBus, ServerEvent},
+};
+use common_net::msg::{DisconnectReason, ServerGeneral};
+use core::{convert::Infallible, time::Duration};
+use crossbeam_channel::{Receiver, Sender};
+use hyper::{
+    header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use specs::{Join, WorldExt};
+use std::{path::Path, sync::Arc};
+use tokio::{
+    runtime::Runtime,
+    sync::{oneshot, Notify},
+};
+use tracing::{error, info, warn};
+use vek::*;
+
+/// Request bodies longer than this are left out of the audit log
+const MAX_AUDITED_BODY_LEN: usize = 1024;
+
+#[derive(Deserialize)]
+struct KickRequest {
+    alias: String,
+    #[serde(default)]
+    reason: String,
+}
+
+#[derive(Deserialize)]
+struct BanRequest {
+    username: String,
+    #[serde(default)]
+    reason: String,
+    /// The ban is permanent if this is None
+    duration_secs: Option<u64>,
+    #[serde(default)]
+    overwrite: bool,
+}
+
+#[derive(Deserialize)]
+struct UnbanRequest {
+    username: String,
+}
+
+#[derive(Deserialize)]
+struct BroadcastRequest {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct ShutdownRequest {
+    #[serde(default)]
+    grace_period_secs: u64,
+    #[serde(default = "default_shutdown_reason")]
+    reason: String,
+}
+
+fn default_shutdown_reason() -> String { "The server is shutting down".to_owned() }
+
+#[derive(Serialize)]
+struct PlayerInfo {
+    alias: String,
+    uuid: Uuid,
+    role: Option<comp::AdminRole>,
+    position: Option<Vec3<f32>>,
+}
+
+#[derive(Clone, Copy)]
+enum SettingKind {
+    Whitelist,
+    Banlist,
+    Admins,
+    Description,
+}
+
+enum Call {
+    ListPlayers,
+    Kick(KickRequest),
+    Ban(BanRequest),
+    Unban(UnbanRequest),
+    Broadcast(BroadcastRequest),
+    GetSetting(SettingKind),
+    SetSetting(SettingKind, Vec<u8>),
+    Shutdown(ShutdownRequest),
+}
+
+impl Call {
+    fn needs_role(&self) -> comp::AdminRole {
+        match self {
+            // replacing a whole setting can drop bans and roles of admins
+            Call::SetSetting(..) | Call::Shutdown(_) => comp::AdminRole::Admin,
+            _ => comp::AdminRole::Moderator,
+        }
+    }
+}
+
+/// An authenticated call, waiting to be applied by the main thread
+struct ApiCall {
+    username: String,
+    call: Call,
+    /// Method, path and body of the request, for the audit log
+    arguments: Vec<String>,
+    response: oneshot::Sender<ApiResponse>,
+}
+
+struct ApiResponse {
+    status: StatusCode,
+    body: Value,
+}
+
+impl ApiResponse {
+    fn ok(body: Value) -> Self {
+        Self {
+            status: StatusCode::OK,
+            body,
+        }
+    }
+
+    fn error(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.into() }),
+        }
+    }
+
+    fn into_http(self) -> Response<Body> {
+        let mut response = Response::new(Body::from(self.body.to_string()));
+        *response.status_mut() = self.status;
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        response
+    }
+}
+
+type CallResult = Result<Value, (StatusCode, String)>;
+
+pub(crate) struct AdminApi {
+    calls: Receiver<ApiCall>,
+    shutdown: Arc<Notify>,
+}
+
+impl AdminApi {
+    pub(crate) fn start(settings: &AdminApiSettings, runtime: &Runtime) -> Self {
+        let (calls_s, calls_r) = crossbeam_channel::unbounded();
+        let shutdown = Arc::new(Notify::new());
+        runtime.spawn(serve(settings.clone(), calls_s, Arc::clone(&shutdown)));
+        Self {
+            calls: calls_r,
+            shutdown,
+        }
+    }
+}
+
+impl Drop for AdminApi {
+    fn drop(&mut self) { self.shutdown.notify_one(); }
+}
+
+async fn serve(settings: AdminApiSettings, calls: Sender<ApiCall>, shutdown: Arc<Notify>) {
+    let tokens: Arc<[AdminApiToken]> = settings.tokens.into();
+    let make_service = make_service_fn(move |_| {
+        let tokens = Arc::clone(&tokens);
+        let calls = calls.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle_request(request, Arc::clone(&tokens), calls.clone())
+            }))
+        }
+    });
+
+    let server = match hyper::Server::try_bind(&settings.address) {
+        Ok(builder) => builder.serve(make_service),
+        Err(error) => {
+            error!(
+                ?error,
+                "Failed to bind the admin API to {}", settings.address
+            );
+            return;
+        },
+    };
+    info!("Admin API is listening on {}", settings.address);
+    if let Err(error) = server.with_graceful_shutdown(shutdown.notified()).await {
+        error!(?error, "Admin API stopped");
+    }
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    tokens: Arc<[AdminApiToken]>,
+    calls: Sender<ApiCall>,
+) -> Result<Response<Body>, Infallible> {
+    let username = match authenticate(&request, &tokens) {
+        Some(username) => username,
+        None => {
+            return Ok(
+                ApiResponse::error(StatusCode::UNAUTHORIZED, "Missing or invalid token")
+                    .into_http(),
+            );
+        },
+    };
+
+    let (parts, body) = request.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(error) => {
+            return Ok(ApiResponse::error(
+                StatusCode::BAD_REQUEST,
+                format!("Failed to read the request body: {}", error),
+            )
+            .into_http());
+        },
+    };
+    let call = match parse_call(&parts.method, parts.uri.path(), &body) {
+        Ok(call) => call,
+        Err(response) => return Ok(response.into_http()),
+    };
+
+    let mut arguments = vec![parts.method.to_string(), parts.uri.path().to_owned()];
+    if !body.is_empty() {
+        arguments.push(if body.len() <= MAX_AUDITED_BODY_LEN {
+            String::from_utf8_lossy(&body).into_owned()
+        } else {
+            format!("<{} bytes>", body.len())
+        });
+    }
+
+    let unavailable = || {
+        ApiResponse::error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The server is shutting down",
+        )
+    };
+    let (response_s, response_r) = oneshot::channel();
+    let response = if calls
+        .send(ApiCall {
+            username,
+            call,
+            arguments,
+            response: response_s,
+        })
+        .is_ok()
+    {
+        response_r.await.unwrap_or_else(|_| unavailable())
+    } else {
+        unavailable()
+    };
+    Ok(response.into_http())
+}
+
+/// Returns the username the token of this request acts for
+fn authenticate(request: &Request<Body>, tokens: &[AdminApiToken]) -> Option<String> {
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")?;
+    tokens
+        .iter()
+        .find(|t| !t.token.is_empty() && constant_time_eq(t.token.as_bytes(), token.as_bytes()))
+        .map(|t| t.username.clone())
+}
+
+/// Doesn't return early, so the response time doesn't tell how much of a
+/// token was guessed right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+fn parse_call(method: &Method, path: &str, body: &[u8]) -> Result<Call, ApiResponse> {
+    fn json<T: DeserializeOwned>(body: &[u8]) -> Result<T, ApiResponse> {
+        serde_json::from_slice(body).map_err(|error| {
+            ApiResponse::error(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid request body: {}", error),
+            )
+        })
+    }
+
+    fn setting(name: &str) -> Result<SettingKind, ApiResponse> {
+        match name {
+            "whitelist" => Ok(SettingKind::Whitelist),
+            "banlist" => Ok(SettingKind::Banlist),
+            "admins" => Ok(SettingKind::Admins),
+            "description" => Ok(SettingKind::Description),
+            _ => Err(ApiResponse::error(
+                StatusCode::NOT_FOUND,
+                format!("Unknown setting: {}", name),
+            )),
+        }
+    }
+
+    let segments = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    Ok(match (method, segments.as_slice()) {
+        (&Method::GET, ["players"]) => Call::ListPlayers,
+        (&Method::POST, ["kick"]) => Call::Kick(json(body)?),
+        (&Method::POST, ["ban"]) => Call::Ban(json(body)?),
+        (&Method::POST, ["unban"]) => Call::Unban(json(body)?),
+        (&Method::POST, ["broadcast"]) => Call::Broadcast(json(body)?),
+        (&Method::GET, ["settings", name]) => Call::GetSetting(setting(name)?),
+        (&Method::PUT, ["settings", name]) => Call::SetSetting(setting(name)?, body.to_vec()),
+        (&Method::POST, ["shutdown"]) => Call::Shutdown(json(body)?),
+        _ => {
+            return Err(ApiResponse::error(
+                StatusCode::NOT_FOUND,
+                "Unknown endpoint",
+            ));
+        },
+    })
+}
+
+impl Server {
+    /// Applies the calls made to the admin API since the last tick
+    pub(crate) fn handle_admin_api_calls(&mut self, frontend_events: &mut Vec<Event>) {
+        let calls = match &self.admin_api {
+            Some(admin_api) => admin_api.calls.try_iter().collect::<Vec<_>>(),
+            None => return,
+        };
+        for ApiCall {
+            username,
+            call,
+            arguments,
+            response,
+        } in calls
+        {
+            // The caller may have disconnected in the meantime, which is fine.
+            let _ = response.send(apply_call(self, username, call, arguments, frontend_events));
+        }
+    }
+}
+
+fn apply_call(
+    server: &mut Server,
+    username: String,
+    call: Call,
+    arguments: Vec<String>,
+    frontend_events: &mut Vec<Event>,
+) -> ApiResponse {
+    let actor_uuid = match server
+        .state
+        .ecs()
+        .read_resource::<LoginProvider>()
+        .username_to_uuid(&username)
+    {
+        Ok(uuid) => uuid,
+        Err(_) => {
+            return ApiResponse::error(
+                StatusCode::FORBIDDEN,
+                format!("Unable to determine UUID for username {:?}", username),
+            );
+        },
+    };
+    let actor_role = match server
+        .editable_settings()
+        .admins
+        .get(&actor_uuid)
+        .map(|record| comp::AdminRole::from(record.role))
+    {
+        Some(role) if role >= call.needs_role() => role,
+        _ => {
+            return ApiResponse::error(
+                StatusCode::FORBIDDEN,
+                format!("{} doesn't have permission for this call", username),
+            );
+        },
+    };
+
+    let actor = (actor_uuid, username.as_str(), actor_role);
+    let result = match call {
+        Call::ListPlayers => list_players(server),
+        Call::Kick(request) => kick(server, actor, request),
+        Call::Ban(request) => ban(server, actor, request),
+        Call::Unban(request) => unban(server, actor, request),
+        Call::Broadcast(request) => {
+            server.notify_players(ServerGeneral::server_msg(
+                comp::ChatType::Meta,
+                request.message,
+            ));
+            Ok(Value::Null)
+        },
+        Call::GetSetting(kind) => get_setting(server, kind),
+        Call::SetSetting(kind, body) => set_setting(server, kind, &body),
+        Call::Shutdown(request) => {
+            info!(
+                "Server shutdown requested by {} through the admin API",
+                username
+            );
+            frontend_events.push(Event::Shutdown {
+                grace_period: Duration::from_secs(request.grace_period_secs),
+                reason: request.reason,
+            });
+            Ok(Value::Null)
+        },
+    };
+
+    let entry = AuditEntry {
+        timestamp: Utc::now(),
+        actor_uuid,
+        actor_alias: username,
+        target: None,
+        command: "api".to_owned(),
+        arguments,
+        error: result.as_ref().err().map(|(_, error)| error.clone()),
+    };
+    server.state.ecs().read_resource::<AuditLog>().record(entry);
+
+    match result {
+        Ok(body) => ApiResponse::ok(body),
+        Err((status, error)) => ApiResponse::error(status, error),
+    }
+}
+
+fn list_players(server: &Server) -> CallResult {
+    let ecs = server.state.ecs();
+    let players = (
+        &ecs.read_storage::<comp::Player>(),
+        ecs.read_storage::<comp::Pos>().maybe(),
+        ecs.read_storage::<comp::Admin>().maybe(),
+    )
+        .join()
+        .map(|(player, pos, admin)| PlayerInfo {
+            alias: player.alias.clone(),
+            uuid: player.uuid(),
+            role: admin.map(|admin| admin.0),
+            position: pos.map(|pos| pos.0),
+        })
+        .collect::<Vec<_>>();
+    to_json(&players)
+}
+
+/// Whether the admin with `actor_role` may act on the player with `uuid`,
+/// which must have a lower permanent role
+fn is_above_role(server: &Server, actor_role: comp::AdminRole, uuid: Uuid) -> bool {
+    server
+        .editable_settings()
+        .admins
+        .get(&uuid)
+        .map_or(true, |record| {
+            comp::AdminRole::from(record.role) < actor_role
+        })
+}
+
+fn kick_player(server: &Server, entity: specs::Entity, reason: &str) {
+    server.notify_client(
+        entity,
+        ServerGeneral::Disconnect(DisconnectReason::Kicked(reason.to_owned())),
+    );
+    server
+        .state
+        .ecs()
+        .read_resource::<EventBus<ServerEvent>>()
+        .emit_now(ServerEvent::ClientDisconnect(
+            entity,
+            comp::DisconnectReason::Kicked,
+        ));
+}
+
+fn find_player(server: &Server, uuid: Uuid) -> Option<specs::Entity> {
+    let ecs = server.state.ecs();
+    (&ecs.entities(), &ecs.read_storage::<comp::Player>())
+        .join()
+        .find(|(_, player)| player.uuid() == uuid)
+        .map(|(entity, _)| entity)
+}
+
+fn find_username(server: &Server, username: &str) -> Result<Uuid, (StatusCode, String)> {
+    server
+        .state
+        .ecs()
+        .read_resource::<LoginProvider>()
+        .username_to_uuid(username)
+        .map_err(|_| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("Unable to determine UUID for username {:?}", username),
+            )
+        })
+}
+
+fn kick(
+    server: &Server,
+    (_, _, actor_role): (Uuid, &str, comp::AdminRole),
+    request: KickRequest,
+) -> CallResult {
+    let ecs = server.state.ecs();
+    let (entity, uuid) = (&ecs.entities(), &ecs.read_storage::<comp::Player>())
+        .join()
+        .find(|(_, player)| player.alias == request.alias)
+        .map(|(entity, player)| (entity, player.uuid()))
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("Player {:?} not found!", request.alias),
+            )
+        })?;
+    if !is_above_role(server, actor_role, uuid) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Cannot kick players with roles as high as your own.".to_owned(),
+        ));
+    }
+    kick_player(server, entity, &request.reason);
+    Ok(Value::Null)
+}
+
+fn ban(
+    server: &Server,
+    (actor_uuid, actor_username, actor_role): (Uuid, &str, comp::AdminRole),
+    request: BanRequest,
+) -> CallResult {
+    let player_uuid = find_username(server, &request.username)?;
+
+    let now = Utc::now();
+    let end_date = request
+        .duration_secs
+        .and_then(|secs| chrono::Duration::from_std(Duration::from_secs(secs)).ok())
+        // On overflow (someone adding some ridiculous timespan), just make the ban infinite.
+        .and_then(|duration| now.checked_add_signed(duration));
+
+    let ban = Ban {
+        reason: request.reason.clone(),
+        info: Some(BanInfo {
+            performed_by: actor_uuid,
+            performed_by_username: actor_username.to_owned(),
+            performed_by_role: actor_role.into(),
+        }),
+        end_date,
+    };
+
+    let result = server.editable_settings_mut().banlist.ban_action(
+        server.data_dir().as_ref(),
+        now,
+        player_uuid,
+        request.username.clone(),
+        BanAction::Ban(ban),
+        request.overwrite,
+    );
+    edit_result(result, || {
+        format!("{} is already on the banlist", request.username)
+    })?;
+
+    // If the player is online kick them, unless they have a role at least as high
+    // as the actor (like with /ban, they can stay until they disconnect).
+    if let Some(entity) = find_player(server, player_uuid) {
+        if is_above_role(server, actor_role, player_uuid) {
+            kick_player(server, entity, &request.reason);
+        }
+    }
+    Ok(Value::Null)
+}
+
+fn unban(
+    server: &Server,
+    (actor_uuid, actor_username, actor_role): (Uuid, &str, comp::AdminRole),
+    request: UnbanRequest,
+) -> CallResult {
+    let player_uuid = find_username(server, &request.username)?;
+
+    let unban = BanAction::Unban(BanInfo {
+        performed_by: actor_uuid,
+        performed_by_username: actor_username.to_owned(),
+        performed_by_role: actor_role.into(),
+    });
+
+    let result = server.editable_settings_mut().banlist.ban_action(
+        server.data_dir().as_ref(),
+        Utc::now(),
+        player_uuid,
+        request.username.clone(),
+        unban,
+        false,
+    );
+    edit_result(result, || {
+        format!("{} was already unbanned", request.username)
+    })?;
+    Ok(Value::Null)
+}
+
+fn get_setting(server: &Server, kind: SettingKind) -> CallResult {
+    let settings = server.editable_settings();
+    match kind {
+        SettingKind::Whitelist => to_json(&settings.whitelist),
+        SettingKind::Banlist => to_json(&settings.banlist),
+        SettingKind::Admins => to_json(&settings.admins),
+        SettingKind::Description => to_json(&settings.server_description),
+    }
+}
+
+fn set_setting(server: &Server, kind: SettingKind, body: &[u8]) -> CallResult {
+    let mut settings = server.editable_settings_mut();
+    let data_dir = server.data_dir();
+    let data_dir = data_dir.as_ref();
+    match kind {
+        SettingKind::Whitelist => replace::<Whitelist>(&mut settings.whitelist, data_dir, body),
+        SettingKind::Banlist => replace::<Banlist>(&mut settings.banlist, data_dir, body),
+        SettingKind::Description => {
+            replace::<ServerDescription>(&mut settings.server_description, data_dir, body)
+        },
+        SettingKind::Admins => {
+            let old_admins = settings.admins.clone();
+            replace::<Admins>(&mut settings.admins, data_dir, body)?;
+            // Update the roles of online players whose permanent role changed. Other
+            // players keep their temporary roles.
+            let ecs = server.state.ecs();
+            let mut admin_comps = ecs.write_storage::<comp::Admin>();
+            for (entity, player) in (&ecs.entities(), &ecs.read_storage::<comp::Player>()).join() {
+                let old_role = old_admins.get(&player.uuid()).map(|record| record.role);
+                match settings
+                    .admins
+                    .get(&player.uuid())
+                    .map(|record| record.role)
+                {
+                    new_role if new_role == old_role => {},
+                    Some(role) => {
+                        let _ = admin_comps.insert(entity, comp::Admin(role.into()));
+                    },
+                    None => {
+                        admin_comps.remove(entity);
+                    },
+                }
+            }
+            Ok(Value::Null)
+        },
+    }
+}
+
+/// Replaces `setting` with the one in `body`, validating and saving it like
+/// any other edit
+fn replace<S: EditableSetting + DeserializeOwned>(
+    setting: &mut S,
+    data_dir: &Path,
+    body: &[u8],
+) -> CallResult {
+    let new_setting: S = serde_json::from_slice(body).map_err(|error| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid {}: {}", S::FILENAME, error),
+        )
+    })?;
+    let result = setting.edit(data_dir, |setting| {
+        *setting = new_setting;
+        Some(())
+    });
+    edit_result(result.map(|((), result)| result), String::new)?;
+    Ok(Value::Null)
+}
+
+fn edit_result<S: EditableSetting>(
+    result: Option<Result<(), SettingError<S>>>,
+    unchanged: impl FnOnce() -> String,
+) -> Result<(), (StatusCode, String)> {
+    match result {
+        None => Err((StatusCode::CONFLICT, unchanged())),
+        Some(Ok(())) => Ok(()),
+        Some(Err(SettingError::Io(error))) => {
+            warn!(?error, "Failed to write settings file to disk");
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!(
+                    "Failed to write settings file to disk, but succeeded in memory: {}",
+                    error
+                ),
+            ))
+        },
+        Some(Err(SettingError::Integrity(error))) => Err((
+            StatusCode::BAD_REQUEST,
+            format!("Invalid setting: {:?}", error),
+        )),
+    }
+}
+
+fn to_json<T: Serialize + ?Sized>(value: &T) -> CallResult {
+    serde_json::to_value(value).map_err(|error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to serialize: {}", error),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_calls() {
+        assert!(matches!(
+            parse_call(&Method::GET, "/players/", b""),
+            Ok(Call::ListPlayers)
+        ));
+        assert!(matches!(
+            parse_call(&Method::POST, "/kick", br#"{"alias": "Griefer"}"#),
+            Ok(Call::Kick(KickRequest { alias, reason })) if alias == "Griefer" && reason.is_empty()
+        ));
+        assert!(matches!(
+            parse_call(&Method::PUT, "/settings/admins", b"{}"),
+            Ok(call) if call.needs_role() == comp::AdminRole::Admin
+        ));
+        assert!(matches!(
+            parse_call(&Method::PUT, "/settings/banlist", b"{}"),
+            Ok(call) if call.needs_role() == comp::AdminRole::Admin
+        ));
+        assert!(matches!(
+            parse_call(&Method::GET, "/settings/banlist", b""),
+            Ok(call) if call.needs_role() == comp::AdminRole::Moderator
+        ));
+        assert!(matches!(
+            parse_call(&Method::GET, "/settings/unknown", b""),
+            Err(response) if response.status == StatusCode::NOT_FOUND
+        ));
+        assert!(matches!(
+            parse_call(&Method::DELETE, "/players", b""),
+            Err(response) if response.status == StatusCode::NOT_FOUND
+        ));
+        assert!(matches!(
+            parse_call(&Method::POST, "/ban", b"{}"),
+            Err(response) if response.status == StatusCode::BAD_REQUEST
+        ));
+    }
+
+    #[test]
+    fn authenticate_tokens() {
+        let tokens = [
+            AdminApiToken {
+                token: "secret".to_owned(),
+                username: "admin".to_owned(),
+            },
+            AdminApiToken {
+                token: String::new(),
+                username: "nobody".to_owned(),
+            },
+        ];
+        let request = |authorization: &str| {
+            Request::builder()
+                .header(AUTHORIZATION, authorization)
+                .body(Body::empty())
+                .unwrap()
+        };
+        assert_eq!(
+            authenticate(&request("Bearer secret"), &tokens).as_deref(),
+            Some("admin")
+        );
+        assert_eq!(authenticate(&request("Bearer secreT"), &tokens), None);
+        assert_eq!(authenticate(&request("Bearer "), &tokens), None);
+        assert_eq!(authenticate(&request("secret"), &tokens), None);
+        assert_eq!(authenticate(&Request::new(Body::empty()), &tokens), None);
+    }
+}
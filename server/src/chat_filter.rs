@@ -0,0 +1,117 @@
+use crate::settings::ChatFilterMode;
+use std::borrow::Cow;
+
+/// Checks chat messages against the banned words of the server
+#[derive(Debug, Default)]
+pub struct ChatFilter {
+    banned_words: Vec<String>,
+    mode: ChatFilterMode,
+}
+
+impl ChatFilter {
+    pub fn new(banned_words: Vec<String>, mode: ChatFilterMode) -> Self {
+        let banned_words = banned_words
+            .iter()
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_lowercase())
+            .collect();
+
+        ChatFilter { banned_words, mode }
+    }
+
+    /// Returns the message as it should be sent, or `None` if it must not be
+    /// sent at all. Matching is case insensitive.
+    pub fn filter<'a>(&self, message: &'a str) -> Option<Cow<'a, str>> {
+        if self.mode == ChatFilterMode::Disabled || self.banned_words.is_empty() {
+            return Some(Cow::Borrowed(message));
+        }
+
+        // Lowercasing can change the length of a char, so remember which char
+        // of the original message each byte of the lowercase one belongs to.
+        let mut lowercase = String::with_capacity(message.len());
+        let mut origin = Vec::with_capacity(message.len());
+        for (i, c) in message.chars().enumerate() {
+            for lower in c.to_lowercase() {
+                lowercase.push(lower);
+                origin.extend(std::iter::repeat(i).take(lower.len_utf8()));
+            }
+        }
+
+        let mut censored = vec![false; message.chars().count()];
+        let mut found = false;
+        for word in self.banned_words.iter() {
+            for (start, _) in lowercase.match_indices(word.as_str()) {
+                if self.mode == ChatFilterMode::Block {
+                    return None;
+                }
+                found = true;
+                for i in &origin[start..start + word.len()] {
+                    censored[*i] = true;
+                }
+            }
+        }
+
+        if found {
+            Some(Cow::Owned(
+                message
+                    .chars()
+                    .zip(censored)
+                    .map(|(c, censored)| if censored { '*' } else { c })
+                    .collect(),
+            ))
+        } else {
+            Some(Cow::Borrowed(message))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(mode: ChatFilterMode) -> ChatFilter {
+        ChatFilter::new(vec!["bad".to_owned(), "WoRsE".to_owned()], mode)
+    }
+
+    #[test]
+    fn disabled() {
+        let message = "this is bad";
+        assert_eq!(
+            filter(ChatFilterMode::Disabled).filter(message).as_deref(),
+            Some(message)
+        );
+    }
+
+    #[test]
+    fn block() {
+        let filter = filter(ChatFilterMode::Block);
+        assert_eq!(filter.filter("this is BAD"), None);
+        assert_eq!(filter.filter("even worse"), None);
+        assert_eq!(filter.filter("all good").as_deref(), Some("all good"));
+    }
+
+    #[test]
+    fn replace_case_insensitive() {
+        let filter = filter(ChatFilterMode::Replace);
+        assert_eq!(
+            filter.filter("Bad, badder, WORSE!").as_deref(),
+            Some("***, ***der, *****!")
+        );
+    }
+
+    #[test]
+    fn replace_non_ascii() {
+        let filter = ChatFilter::new(vec!["straße".to_owned()], ChatFilterMode::Replace);
+        // 'İ' lowercases to two chars, which must not shift the replacement
+        assert_eq!(
+            filter.filter("İ STRASSE Straße").as_deref(),
+            Some("İ STRASSE ******")
+        );
+    }
+
+    #[test]
+    fn empty_words_ignored() {
+        let filter = ChatFilter::new(vec![String::new()], ChatFilterMode::Block);
+        assert_eq!(filter.filter("hello").as_deref(), Some("hello"));
+    }
+}
@@ -25,6 +25,8 @@ pub struct Client {
     character_screen_stream: Mutex<Stream>,
     in_game_stream: Mutex<Stream>,
     terrain_stream: Mutex<Stream>,
+    plugin_stream: Mutex<Stream>,
+    physics_stream: Mutex<Stream>,
 
     general_stream_params: StreamParams,
     ping_stream_params: StreamParams,
@@ -32,6 +34,8 @@ pub struct Client {
     character_screen_stream_params: StreamParams,
     in_game_stream_params: StreamParams,
     terrain_stream_params: StreamParams,
+    plugin_stream_params: StreamParams,
+    physics_stream_params: StreamParams,
 }
 
 pub struct PreparedMsg {
@@ -55,6 +59,8 @@ impl Client {
         character_screen_stream: Stream,
         in_game_stream: Stream,
         terrain_stream: Stream,
+        plugin_stream: Stream,
+        physics_stream: Stream,
     ) -> Self {
         let general_stream_params = general_stream.params();
         let ping_stream_params = ping_stream.params();
@@ -62,6 +68,8 @@ impl Client {
         let character_screen_stream_params = character_screen_stream.params();
         let in_game_stream_params = in_game_stream.params();
         let terrain_stream_params = terrain_stream.params();
+        let plugin_stream_params = plugin_stream.params();
+        let physics_stream_params = physics_stream.params();
         Client {
             client_type,
             participant: Some(participant),
@@ -73,12 +81,16 @@ impl Client {
             character_screen_stream: Mutex::new(character_screen_stream),
             in_game_stream: Mutex::new(in_game_stream),
             terrain_stream: Mutex::new(terrain_stream),
+            plugin_stream: Mutex::new(plugin_stream),
+            physics_stream: Mutex::new(physics_stream),
             general_stream_params,
             ping_stream_params,
             register_stream_params,
             character_screen_stream_params,
             in_game_stream_params,
             terrain_stream_params,
+            plugin_stream_params,
+            physics_stream_params,
         }
     }
 
@@ -118,14 +130,20 @@ impl Client {
                     | ServerGeneral::TerrainBlockUpdates(_) => {
                         self.terrain_stream.lock().unwrap().send(g)
                     },
+                    //Ingame related, physics
+                    ServerGeneral::PhysicsSync { .. } => {
+                        self.physics_stream.lock().unwrap().send(g)
+                    },
                     // Always possible
                     ServerGeneral::PlayerListUpdate(_)
                     | ServerGeneral::ChatMsg(_)
                     | ServerGeneral::ChatMode(_)
+                    | ServerGeneral::ChatCommands(_)
                     | ServerGeneral::SetPlayerEntity(_)
                     | ServerGeneral::TimeOfDay(_, _)
                     | ServerGeneral::EntitySync(_)
                     | ServerGeneral::CompSync(_)
+                    | ServerGeneral::ForcedPhysicsSync { .. }
                     | ServerGeneral::CreateEntity(_)
                     | ServerGeneral::DeleteEntity(_)
                     | ServerGeneral::Disconnect(_)
@@ -133,6 +151,7 @@ impl Client {
                 }
             },
             ServerMsg::Ping(m) => self.ping_stream.lock().unwrap().send(m),
+            ServerMsg::Plugin(m) => self.plugin_stream.lock().unwrap().send(m),
         }
     }
 
@@ -150,6 +169,8 @@ impl Client {
             3 => self.general_stream.lock().unwrap().send_raw(&msg.message),
             4 => self.ping_stream.lock().unwrap().send_raw(&msg.message),
             5 => self.terrain_stream.lock().unwrap().send_raw(&msg.message),
+            6 => self.plugin_stream.lock().unwrap().send_raw(&msg.message),
+            7 => self.physics_stream.lock().unwrap().send_raw(&msg.message),
             _ => unreachable!("invalid stream id"),
         }
     }
@@ -190,14 +211,20 @@ impl Client {
                     | ServerGeneral::TerrainBlockUpdates(_) => {
                         PreparedMsg::new(5, &g, &self.terrain_stream_params)
                     },
+                    //Ingame related, physics
+                    ServerGeneral::PhysicsSync { .. } => {
+                        PreparedMsg::new(7, &g, &self.physics_stream_params)
+                    },
                     // Always possible
                     ServerGeneral::PlayerListUpdate(_)
                     | ServerGeneral::ChatMsg(_)
                     | ServerGeneral::ChatMode(_)
+                    | ServerGeneral::ChatCommands(_)
                     | ServerGeneral::SetPlayerEntity(_)
                     | ServerGeneral::TimeOfDay(_, _)
                     | ServerGeneral::EntitySync(_)
                     | ServerGeneral::CompSync(_)
+                    | ServerGeneral::ForcedPhysicsSync { .. }
                     | ServerGeneral::CreateEntity(_)
                     | ServerGeneral::DeleteEntity(_)
                     | ServerGeneral::Disconnect(_)
@@ -207,6 +234,7 @@ impl Client {
                 }
             },
             ServerMsg::Ping(m) => PreparedMsg::new(4, &m, &self.ping_stream_params),
+            ServerMsg::Plugin(m) => PreparedMsg::new(6, &m, &self.plugin_stream_params),
         }
     }
 
@@ -222,6 +250,8 @@ impl Client {
             3 => self.general_stream.lock().unwrap().try_recv(),
             4 => self.ping_stream.lock().unwrap().try_recv(),
             5 => self.terrain_stream.lock().unwrap().try_recv(),
+            6 => self.plugin_stream.lock().unwrap().try_recv(),
+            7 => self.physics_stream.lock().unwrap().try_recv(),
             _ => unreachable!("invalid stream id"),
         }
     }
@@ -5,8 +5,10 @@
 use crate::{
     client::Client,
     login_provider::LoginProvider,
+    persistence::audit_log::{AuditEntry, AuditFilter, AuditLog},
     settings::{
-        Ban, BanAction, BanInfo, EditableSetting, SettingError, WhitelistInfo, WhitelistRecord,
+        Ban, BanAction, BanInfo, EditableSetting, IpRange, MuteInfo, MuteRecord, SettingError,
+        WhitelistInfo, WhitelistRecord,
     },
     sys::terrain::NpcData,
     wiring,
@@ -51,7 +53,7 @@ use hashbrown::{HashMap, HashSet};
 use humantime::Duration as HumanDuration;
 use rand::Rng;
 use specs::{storage::StorageEntry, Builder, Entity as EcsEntity, Join, WorldExt};
-use std::{str::FromStr, sync::Arc};
+use std::{net::IpAddr, str::FromStr, sync::Arc};
 use vek::*;
 use wiring::{Circuit, Wire, WiringAction, WiringActionEffect, WiringElement};
 use world::util::Sampler;
@@ -102,6 +104,23 @@ fn do_command(
     target: EcsEntity,
     args: Vec<String>,
     cmd: &ChatCommand,
+) -> CmdResult<()> {
+    // Privileged commands are recorded in the audit log, including failed
+    // attempts.
+    let audit_args = cmd.needs_role().map(|_| args.clone());
+    let result = run_command(server, client, target, args, cmd);
+    if let Some(audit_args) = audit_args {
+        audit_command(server, client, target, audit_args, cmd, &result);
+    }
+    result
+}
+
+fn run_command(
+    server: &mut Server,
+    client: EcsEntity,
+    target: EcsEntity,
+    args: Vec<String>,
+    cmd: &ChatCommand,
 ) -> CmdResult<()> {
     // Make sure your role is at least high enough to execute this command.
     if cmd.needs_role() > server.entity_admin_role(client) {
@@ -116,7 +135,9 @@ fn do_command(
         ChatCommand::Airship => handle_spawn_airship,
         ChatCommand::Alias => handle_alias,
         ChatCommand::ApplyBuff => handle_apply_buff,
+        ChatCommand::Audit => handle_audit,
         ChatCommand::Ban => handle_ban,
+        ChatCommand::BanIp => handle_ban_ip,
         ChatCommand::BattleMode => handle_battlemode,
         ChatCommand::BattleModeForce => handle_battlemode_force,
         ChatCommand::Build => handle_build,
@@ -152,9 +173,11 @@ fn do_command(
         ChatCommand::MakeNpc => handle_make_npc,
         ChatCommand::MakeSprite => handle_make_sprite,
         ChatCommand::Motd => handle_motd,
+        ChatCommand::Mute => handle_mute,
         ChatCommand::Object => handle_object,
         ChatCommand::PermitBuild => handle_permit_build,
         ChatCommand::Players => handle_players,
+        ChatCommand::Plugin => handle_plugin,
         ChatCommand::Region => handle_region,
         ChatCommand::RemoveLights => handle_remove_lights,
         ChatCommand::RevokeBuild => handle_revoke_build,
@@ -172,6 +195,8 @@ fn do_command(
         ChatCommand::Time => handle_time,
         ChatCommand::Tp => handle_tp,
         ChatCommand::Unban => handle_unban,
+        ChatCommand::UnbanIp => handle_unban_ip,
+        ChatCommand::Unmute => handle_unmute,
         ChatCommand::Version => handle_version,
         ChatCommand::Waypoint => handle_waypoint,
         ChatCommand::Wiring => handle_spawn_wiring,
@@ -183,6 +208,51 @@ fn do_command(
     handler(server, client, target, args, cmd)
 }
 
+fn audit_command(
+    server: &Server,
+    client: EcsEntity,
+    target: EcsEntity,
+    args: Vec<String>,
+    cmd: &ChatCommand,
+    result: &CmdResult<()>,
+) {
+    let ecs = server.state.ecs();
+    let players = ecs.read_storage::<comp::Player>();
+    let actor = if let Some(actor) = players.get(client) {
+        actor
+    } else {
+        return;
+    };
+    let target = if target != client {
+        players
+            .get(target)
+            .map(|player| player.alias.clone())
+            .or_else(|| {
+                ecs.read_storage::<comp::Stats>()
+                    .get(target)
+                    .map(|stats| stats.name.clone())
+            })
+            .or_else(|| {
+                ecs.read_storage::<Uid>()
+                    .get(target)
+                    .map(|uid| format!("uid {}", uid))
+            })
+    } else {
+        None
+    };
+
+    let entry = AuditEntry {
+        timestamp: Utc::now(),
+        actor_uuid: actor.uuid(),
+        actor_alias: actor.alias.clone(),
+        target,
+        command: cmd.keyword().to_owned(),
+        arguments: args,
+        error: result.as_ref().err().cloned(),
+    };
+    ecs.read_resource::<AuditLog>().record(entry);
+}
+
 // Fallibly get position of entity with the given descriptor (used for error
 // message).
 fn position(server: &Server, entity: EcsEntity, descriptor: &str) -> CmdResult<comp::Pos> {
@@ -1516,6 +1586,55 @@ fn handle_revoke_build_all(
     Ok(())
 }
 
+#[cfg(feature = "plugins")]
+fn handle_plugin(
+    server: &mut Server,
+    client: EcsEntity,
+    _target: EcsEntity,
+    args: Vec<String>,
+    action: &ChatCommand,
+) -> CmdResult<()> {
+    let msg = match parse_args!(args, String, String) {
+        (Some(subcommand), _) if subcommand == "list" => {
+            let plugins = server.plugin_list();
+            if plugins.is_empty() {
+                "No plugins are loaded".to_owned()
+            } else {
+                format!("Loaded plugins:\n{}", plugins.join("\n"))
+            }
+        },
+        (Some(subcommand), Some(name)) if subcommand == "reload" => {
+            server
+                .reload_plugin(&name)
+                .map_err(|e| format!("Failed to reload plugin {}: {:?}", name, e))?;
+            format!("Reloaded plugin {}", name)
+        },
+        (Some(subcommand), Some(name)) if subcommand == "unload" => {
+            server
+                .unload_plugin(&name)
+                .map_err(|e| format!("Failed to unload plugin {}: {:?}", name, e))?;
+            format!("Unloaded plugin {}", name)
+        },
+        _ => return Err(action.help_string()),
+    };
+    server.notify_client(
+        client,
+        ServerGeneral::server_msg(ChatType::CommandInfo, msg),
+    );
+    Ok(())
+}
+
+#[cfg(not(feature = "plugins"))]
+fn handle_plugin(
+    _server: &mut Server,
+    _client: EcsEntity,
+    _target: EcsEntity,
+    _args: Vec<String>,
+    _action: &ChatCommand,
+) -> CmdResult<()> {
+    Err("This server was built without plugin support".to_owned())
+}
+
 fn handle_players(
     server: &mut Server,
     client: EcsEntity,
@@ -1680,6 +1799,17 @@ fn handle_help(
             client,
             ServerGeneral::server_msg(ChatType::CommandInfo, cmd.help_string()),
         )
+    } else if let Some(cmd) = args.first().and_then(|keyword| {
+        let keyword = keyword.strip_prefix('/').unwrap_or(keyword);
+        server
+            .plugin_commands()
+            .into_iter()
+            .find(|cmd| cmd.keyword == keyword)
+    }) {
+        server.notify_client(
+            client,
+            ServerGeneral::server_msg(ChatType::CommandInfo, cmd.help_string()),
+        )
     } else {
         let mut message = String::new();
         let entity_role = server.entity_admin_role(client);
@@ -1691,6 +1821,14 @@ fn handle_help(
                 message += &cmd.help_string();
                 message += "\n";
             });
+        server
+            .plugin_commands()
+            .iter()
+            .filter(|cmd| cmd.needs_role <= entity_role)
+            .for_each(|cmd| {
+                message += &cmd.help_string();
+                message += "\n";
+            });
         message += "Additionally, you can use the following shortcuts:";
         ChatCommand::iter()
             .filter_map(|cmd| cmd.short_keyword().map(|k| (k, cmd)))
@@ -3172,6 +3310,134 @@ fn handle_ban(
     }
 }
 
+/// Ranges with a shorter prefix than this (IPv4, IPv6) can only be banned by
+/// admins, as they are likely to hit many unrelated players.
+const MIN_MODERATOR_IP_PREFIX: (u8, u8) = (24, 48);
+
+/// Parses an address or range of addresses, or finds the address the online
+/// player with this alias is connected from.  Also returns the player, if
+/// any.
+fn find_ip_range(server: &Server, target: &str) -> CmdResult<(IpRange, Option<(EcsEntity, Uuid)>)> {
+    if let Ok(range) = target.parse::<IpRange>() {
+        return Ok((range, None));
+    }
+    let ecs = server.state.ecs();
+    let player = find_alias(ecs, target)
+        .map_err(|_| format!("{:?} is neither an address nor an online player", target))?;
+    ecs.read_storage::<Client>()
+        .get(player.0)
+        .and_then(|client| client.participant.as_ref())
+        .and_then(|participant| participant.peer_addr())
+        .map(|addr| (IpRange::single(addr.ip()), Some(player)))
+        .ok_or_else(|| format!("Address of {:?} is unknown", target))
+}
+
+fn handle_ban_ip(
+    server: &mut Server,
+    client: EcsEntity,
+    _target: EcsEntity,
+    args: Vec<String>,
+    action: &ChatCommand,
+) -> CmdResult<()> {
+    if let (Some(target), overwrite, parse_duration, reason_opt) =
+        parse_args!(args, String, bool, HumanDuration, String)
+    {
+        let reason = reason_opt.unwrap_or_default();
+        let overwrite = overwrite.unwrap_or(false);
+
+        let (range, player) = find_ip_range(server, &target)?;
+
+        let client_uuid = uuid(server, client, "client")?;
+        let client_username = uuid_to_username(server, client, client_uuid)?;
+        let client_role = real_role(server, client_uuid, "client")?;
+
+        if let Some(player) = player {
+            verify_above_role(
+                server,
+                (client, client_uuid),
+                player,
+                "Cannot ban the address of players with roles higher than your own.",
+            )?;
+        }
+        let min_prefix = match range.addr() {
+            IpAddr::V4(_) => MIN_MODERATOR_IP_PREFIX.0,
+            IpAddr::V6(_) => MIN_MODERATOR_IP_PREFIX.1,
+        };
+        if range.prefix_len() < min_prefix && client_role < AdminRole::Admin {
+            return Err(format!(
+                "Only admins can ban ranges wider than /{}",
+                min_prefix
+            ));
+        }
+
+        let now = Utc::now();
+        let end_date = parse_duration
+            .map(|duration| chrono::Duration::from_std(duration.into()))
+            .transpose()
+            .map_err(|err| format!("Error converting to duration: {}", err))?
+            // On overflow (someone adding some ridiculous timespan), just make the ban infinite.
+            .and_then(|duration| now.checked_add_signed(duration));
+
+        let ban_info = BanInfo {
+            performed_by: client_uuid,
+            performed_by_username: client_username,
+            performed_by_role: client_role.into(),
+        };
+
+        let ban = Ban {
+            reason: reason.clone(),
+            info: Some(ban_info),
+            end_date,
+        };
+
+        let edit = server
+            .editable_settings_mut()
+            .banlist
+            .ip_ban_action(
+                server.data_dir().as_ref(),
+                now,
+                range,
+                player.map_or_else(String::new, |_| target.clone()),
+                BanAction::Ban(ban),
+                overwrite,
+            )
+            .map(|result| {
+                (
+                    format!("Added {} to the banlist with reason: {}", range, reason),
+                    result,
+                )
+            });
+
+        edit_setting_feedback(server, client, edit, || {
+            format!("{} is already on the banlist", range)
+        })?;
+        // Kick everyone connected from a banned address (this may fail for players with
+        // a higher role, they can stay until they disconnect).
+        let ecs = server.state.ecs();
+        let targets = (
+            &ecs.entities(),
+            &ecs.read_storage::<comp::Player>(),
+            &ecs.read_storage::<Client>(),
+        )
+            .join()
+            .filter(|(_, _, target_client)| {
+                target_client
+                    .participant
+                    .as_ref()
+                    .and_then(|participant| participant.peer_addr())
+                    .map_or(false, |addr| range.contains(addr.ip()))
+            })
+            .map(|(entity, player, _)| (entity, player.uuid()))
+            .collect::<Vec<_>>();
+        for target in targets {
+            let _ = kick_player(server, (client, client_uuid), target, &reason);
+        }
+        Ok(())
+    } else {
+        Err(action.help_string())
+    }
+}
+
 fn handle_battlemode(
     server: &mut Server,
     client: EcsEntity,
@@ -3356,6 +3622,223 @@ fn handle_unban(
     }
 }
 
+fn handle_unban_ip(
+    server: &mut Server,
+    client: EcsEntity,
+    _target: EcsEntity,
+    args: Vec<String>,
+    action: &ChatCommand,
+) -> CmdResult<()> {
+    if let Some(address) = parse_args!(args, String) {
+        let range = address.parse::<IpRange>()?;
+
+        let client_uuid = uuid(server, client, "client")?;
+        let client_username = uuid_to_username(server, client, client_uuid)?;
+        let client_role = real_role(server, client_uuid, "client")?;
+
+        let now = Utc::now();
+
+        let ban_info = BanInfo {
+            performed_by: client_uuid,
+            performed_by_username: client_username,
+            performed_by_role: client_role.into(),
+        };
+
+        let unban = BanAction::Unban(ban_info);
+
+        let edit = server
+            .editable_settings_mut()
+            .banlist
+            .ip_ban_action(
+                server.data_dir().as_ref(),
+                now,
+                range,
+                String::new(),
+                unban,
+                false,
+            )
+            .map(|result| (format!("{} was successfully unbanned", range), result));
+
+        edit_setting_feedback(server, client, edit, || {
+            format!("{} was already unbanned", range)
+        })
+    } else {
+        Err(action.help_string())
+    }
+}
+
+fn handle_mute(
+    server: &mut Server,
+    client: EcsEntity,
+    _target: EcsEntity,
+    args: Vec<String>,
+    action: &ChatCommand,
+) -> CmdResult<()> {
+    if let (Some(username), parse_duration, reason_opt) =
+        parse_args!(args, String, HumanDuration, String)
+    {
+        let reason = reason_opt.unwrap_or_default();
+
+        let player_uuid = find_username(server, &username)?;
+
+        let client_uuid = uuid(server, client, "client")?;
+        let client_username = uuid_to_username(server, client, client_uuid)?;
+        let client_role = real_role(server, client_uuid, "client")?;
+
+        // Only allow muting players with a lower permanent role.
+        if server
+            .editable_settings()
+            .admins
+            .get(&player_uuid)
+            .map_or(false, |record| record.role >= client_role.into())
+        {
+            return Err(format!("permission denied to mute user: {}", username));
+        }
+
+        let now = Utc::now();
+        let end_date = parse_duration
+            .map(|duration| chrono::Duration::from_std(duration.into()))
+            .transpose()
+            .map_err(|err| format!("Error converting to duration: {}", err))?
+            // On overflow (someone adding some ridiculous timespan), just make the mute infinite.
+            .and_then(|duration| now.checked_add_signed(duration));
+
+        let record = MuteRecord {
+            reason: reason.clone(),
+            date: now,
+            end_date,
+            info: MuteInfo {
+                username_when_muted: username.clone(),
+                muted_by: client_uuid,
+                muted_by_username: client_username,
+                muted_by_role: client_role.into(),
+            },
+        };
+
+        let edit = server
+            .editable_settings_mut()
+            .mutelist
+            .edit(server.data_dir().as_ref(), |m| {
+                // A mute by a higher role can only be replaced by that role.
+                if m.active_mute(&player_uuid, now)
+                    .map_or(false, |mute| mute.info.muted_by_role > client_role.into())
+                {
+                    None
+                } else {
+                    m.insert(player_uuid, record);
+                    Some(format!("Muted {} with reason: {}", username, reason))
+                }
+            });
+        edit_setting_feedback(server, client, edit, || {
+            format!("permission denied to change the mute of user: {}", username)
+        })?;
+
+        let ecs = server.state.ecs();
+        if let Ok(target_player) = find_uuid(ecs, player_uuid) {
+            server.notify_client(
+                target_player,
+                ServerGeneral::server_msg(
+                    ChatType::CommandError,
+                    format!("You have been muted. Reason: {}", reason),
+                ),
+            );
+        }
+        Ok(())
+    } else {
+        Err(action.help_string())
+    }
+}
+
+fn handle_unmute(
+    server: &mut Server,
+    client: EcsEntity,
+    _target: EcsEntity,
+    args: Vec<String>,
+    action: &ChatCommand,
+) -> CmdResult<()> {
+    if let Some(username) = parse_args!(args, String) {
+        let player_uuid = find_username(server, &username)?;
+
+        let client_uuid = uuid(server, client, "client")?;
+        let client_role = real_role(server, client_uuid, "client")?;
+
+        let now = Utc::now();
+        let mut err_info = "not muted: ";
+        let edit = server
+            .editable_settings_mut()
+            .mutelist
+            .edit(server.data_dir().as_ref(), |m| {
+                let mute = m.active_mute(&player_uuid, now)?;
+                if mute.info.muted_by_role <= client_role.into() {
+                    m.remove(&player_uuid);
+                    Some(format!("{} was successfully unmuted", username))
+                } else {
+                    err_info = "permission denied to unmute user: ";
+                    None
+                }
+            });
+        edit_setting_feedback(server, client, edit, || format!("{}{}", err_info, username))?;
+
+        let ecs = server.state.ecs();
+        if let Ok(target_player) = find_uuid(ecs, player_uuid) {
+            server.notify_client(
+                target_player,
+                ServerGeneral::server_msg(ChatType::CommandInfo, "You have been unmuted"),
+            );
+        }
+        Ok(())
+    } else {
+        Err(action.help_string())
+    }
+}
+
+fn handle_audit(
+    server: &mut Server,
+    client: EcsEntity,
+    _target: EcsEntity,
+    args: Vec<String>,
+    _action: &ChatCommand,
+) -> CmdResult<()> {
+    const MAX_ENTRIES: u32 = 50;
+
+    let (player, command, since, until) =
+        parse_args!(args, String, String, HumanDuration, HumanDuration);
+    let player = player.filter(|player| player != "*");
+    let command = command
+        .filter(|command| command != "*")
+        .map(|command| {
+            command
+                .trim_start_matches('/')
+                .parse::<ChatCommand>()
+                .map(|command| command.keyword().to_owned())
+                .map_err(|_| format!("Unknown command: /{}", command))
+        })
+        .transpose()?;
+    let now = Utc::now();
+    let ago = |duration: Option<HumanDuration>| {
+        duration
+            .map(|duration| chrono::Duration::from_std(duration.into()))
+            .transpose()
+            .map_err(|err| format!("Error converting to duration: {}", err))
+            .map(|duration| duration.and_then(|duration| now.checked_sub_signed(duration)))
+    };
+
+    let filter = AuditFilter {
+        player,
+        command,
+        since: ago(since)?,
+        until: ago(until)?,
+        limit: MAX_ENTRIES,
+    };
+    // The entries are sent to the client once they are read
+    server
+        .state
+        .ecs()
+        .read_resource::<AuditLog>()
+        .query(client, filter);
+    Ok(())
+}
+
 fn handle_server_physics(
     server: &mut Server,
     client: EcsEntity,
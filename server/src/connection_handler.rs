@@ -26,7 +26,7 @@ pub(crate) struct ConnectionHandler {
 /// to the Server main thread sometimes though to get the current server_info
 /// and time
 impl ConnectionHandler {
-    pub fn new(network: Network, runtime: &Runtime) -> Self {
+    pub fn new(network: Network, runtime: &Runtime, unreliable_physics_sync: bool) -> Self {
         let network = Arc::new(network);
         let network_clone = Arc::clone(&network);
         let (stop_sender, stop_receiver) = oneshot::channel();
@@ -40,6 +40,7 @@ impl ConnectionHandler {
             client_sender,
             info_requester_sender,
             stop_receiver,
+            unreliable_physics_sync,
         )));
 
         Self {
@@ -56,6 +57,7 @@ impl ConnectionHandler {
         client_sender: Sender<IncomingClient>,
         info_requester_sender: Sender<Sender<ServerInfoPacket>>,
         stop_receiver: oneshot::Receiver<()>,
+        unreliable_physics_sync: bool,
     ) {
         let mut stop_receiver = stop_receiver.fuse();
         loop {
@@ -79,7 +81,7 @@ impl ConnectionHandler {
 
             match select!(
                 _ = &mut stop_receiver => None,
-                e = Self::init_participant(participant, client_sender, info_requester_sender).fuse() => Some(e),
+                e = Self::init_participant(participant, client_sender, info_requester_sender, unreliable_physics_sync).fuse() => Some(e),
             ) {
                 None => break,
                 Some(Ok(())) => (),
@@ -92,20 +94,32 @@ impl ConnectionHandler {
         participant: Participant,
         client_sender: Sender<IncomingClient>,
         info_requester_sender: Sender<Sender<ServerInfoPacket>>,
+        unreliable_physics_sync: bool,
     ) -> Result<(), Box<dyn std::error::Error>> {
         debug!("New Participant connected to the server");
         let (sender, receiver) = bounded(1);
         info_requester_sender.send(sender)?;
 
-        let reliable = Promises::ORDERED | Promises::CONSISTENCY;
+        let reliable = Promises::ORDERED | Promises::CONSISTENCY | Promises::GUARANTEED_DELIVERY;
         let reliablec = reliable | Promises::COMPRESSED;
+        // stale physics updates are dropped instead of delaying newer ones
+        let physics = if unreliable_physics_sync {
+            Promises::ORDERED | Promises::CONSISTENCY | Promises::COMPRESSED
+        } else {
+            reliablec
+        };
+
+        // credentials and chat go over these
+        let encrypted = reliablec | Promises::ENCRYPTED;
 
-        let general_stream = participant.open(3, reliablec, 500).await?;
+        let general_stream = participant.open(3, encrypted, 500).await?;
         let ping_stream = participant.open(2, reliable, 500).await?;
-        let mut register_stream = participant.open(3, reliablec, 500).await?;
+        let mut register_stream = participant.open(3, encrypted, 500).await?;
         let character_screen_stream = participant.open(3, reliablec, 500).await?;
         let in_game_stream = participant.open(3, reliablec, 100_000).await?;
         let terrain_stream = participant.open(4, reliable, 20_000).await?;
+        let plugin_stream = participant.open(5, reliablec, 0).await?;
+        let physics_stream = participant.open(3, physics, 50_000).await?;
 
         let server_data = receiver.recv()?;
 
@@ -133,6 +147,8 @@ impl ConnectionHandler {
             character_screen_stream,
             in_game_stream,
             terrain_stream,
+            plugin_stream,
+            physics_stream,
         );
 
         client_sender.send(client)?;
@@ -19,8 +19,11 @@ use common::{
     util::Dir,
 };
 use common_net::{msg::ServerGeneral, sync::WorldSyncExt};
+#[cfg(feature = "plugins")]
+use plugin_api::event::CharacterSelectedEvent;
 use specs::{Builder, Entity as EcsEntity, WorldExt};
 use std::time::Duration;
+#[cfg(feature = "plugins")] use tracing::error;
 use vek::{Rgb, Vec3};
 
 pub fn handle_initialize_character(
@@ -29,6 +32,16 @@ pub fn handle_initialize_character(
     character_id: CharacterId,
 ) {
     server.state.initialize_character_data(entity, character_id);
+
+    #[cfg(feature = "plugins")]
+    if let Some(uid) = server.state.ecs().uid_from_entity(entity) {
+        if let Err(e) = server.state.execute_plugin_event(&CharacterSelectedEvent {
+            player: plugin_api::event::Player { id: uid },
+            character_id,
+        }) {
+            error!(?e, "Error occured while executing `on_character_selected`");
+        }
+    }
 }
 
 #[allow(clippy::type_complexity)]
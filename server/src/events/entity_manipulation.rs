@@ -33,6 +33,8 @@ use common_net::{msg::ServerGeneral, sync::WorldSyncExt};
 use common_state::BlockChange;
 use comp::chat::GenericChatMsg;
 use hashbrown::HashSet;
+#[cfg(feature = "plugins")]
+use plugin_api::event::EntityDeathEvent;
 use rand::Rng;
 use specs::{
     join::Join, saveload::MarkerAllocator, Builder, Entity as EcsEntity, Entity, WorldExt,
@@ -160,35 +162,41 @@ pub fn handle_destroy(server: &mut Server, entity: EcsEntity, last_change: Healt
         }
     }
 
+    let kill_source = match (last_change.cause, last_change.by.map(|x| x.uid())) {
+        (Some(DamageSource::Melee), Some(by)) => get_attacker_name(KillType::Melee, by),
+        (Some(DamageSource::Projectile), Some(by)) => get_attacker_name(KillType::Projectile, by),
+        (Some(DamageSource::Explosion), Some(by)) => get_attacker_name(KillType::Explosion, by),
+        (Some(DamageSource::Energy), Some(by)) => get_attacker_name(KillType::Energy, by),
+        (Some(DamageSource::Buff(buff_kind)), Some(by)) => {
+            get_attacker_name(KillType::Buff(buff_kind), by)
+        },
+        (Some(DamageSource::Other), Some(by)) => get_attacker_name(KillType::Other, by),
+        (Some(DamageSource::Falling), _) => KillSource::FallDamage,
+        // HealthSource::Suicide => KillSource::Suicide,
+        _ => KillSource::Other,
+    };
+
     // Chat message
     // If it was a player that died
     if let Some(_player) = state.ecs().read_storage::<Player>().get(entity) {
         if let Some(uid) = state.ecs().read_storage::<Uid>().get(entity) {
-            let kill_source = match (last_change.cause, last_change.by.map(|x| x.uid())) {
-                (Some(DamageSource::Melee), Some(by)) => get_attacker_name(KillType::Melee, by),
-                (Some(DamageSource::Projectile), Some(by)) => {
-                    get_attacker_name(KillType::Projectile, by)
-                },
-                (Some(DamageSource::Explosion), Some(by)) => {
-                    get_attacker_name(KillType::Explosion, by)
-                },
-                (Some(DamageSource::Energy), Some(by)) => get_attacker_name(KillType::Energy, by),
-                (Some(DamageSource::Buff(buff_kind)), Some(by)) => {
-                    get_attacker_name(KillType::Buff(buff_kind), by)
-                },
-                (Some(DamageSource::Other), Some(by)) => get_attacker_name(KillType::Other, by),
-                (Some(DamageSource::Falling), _) => KillSource::FallDamage,
-                // HealthSource::Suicide => KillSource::Suicide,
-                _ => KillSource::Other,
-            };
-
             state.send_chat(GenericChatMsg {
-                chat_type: comp::ChatType::Kill(kill_source, *uid),
+                chat_type: comp::ChatType::Kill(kill_source.clone(), *uid),
                 message: "".to_string(),
             });
         }
     }
 
+    #[cfg(feature = "plugins")]
+    if let Some(uid) = state.ecs().read_storage::<Uid>().get(entity).copied() {
+        if let Err(e) = state.execute_plugin_event(&EntityDeathEvent {
+            entity: uid,
+            killer: kill_source,
+        }) {
+            error!(?e, "Error occured while executing `on_entity_death`");
+        }
+    }
+
     // Award EXP to damage contributors
     //
     // NOTE: Debug logging is disabled by default for this module - to enable it add
@@ -32,6 +32,8 @@ use crate::{
 use crate::pet::tame_pet;
 use hashbrown::{HashMap, HashSet};
 use lazy_static::lazy_static;
+#[cfg(feature = "plugins")]
+use plugin_api::event::BlockBreakEvent;
 use serde::Deserialize;
 use std::iter::FromIterator;
 
@@ -393,6 +395,17 @@ pub fn handle_mine_block(
                     pos,
                     color: block.get_color(),
                 });
+
+            #[cfg(feature = "plugins")]
+            if let Some(uid) = state.ecs().uid_from_entity(entity) {
+                if let Err(e) = state.execute_plugin_event(&BlockBreakEvent {
+                    player: plugin_api::event::Player { id: uid },
+                    pos: pos.into_array(),
+                    block: block.into(),
+                }) {
+                    error!(?e, "Error occured while executing `on_block_break`");
+                }
+            }
         }
     }
 }
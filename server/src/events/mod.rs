@@ -21,6 +21,7 @@ use inventory_manip::handle_inventory;
 use invite::{handle_invite, handle_invite_response};
 use player::{handle_client_disconnect, handle_exit_ingame};
 use specs::{Builder, Entity as EcsEntity, WorldExt};
+use std::time::Duration;
 use trade::{cancel_trade_for, handle_process_trade_action};
 
 mod entity_creation;
@@ -31,6 +32,7 @@ mod interaction;
 mod inventory_manip;
 mod invite;
 mod player;
+#[cfg(feature = "plugins")] mod plugin;
 mod trade;
 
 pub enum Event {
@@ -44,6 +46,11 @@ pub enum Event {
         entity: Option<EcsEntity>,
         msg: String,
     },
+    /// The server was asked to shut down gracefully, e.g. by a plugin
+    Shutdown {
+        grace_period: Duration,
+        reason: String,
+    },
 }
 
 impl Server {
@@ -54,6 +61,14 @@ impl Server {
         let mut commands = Vec::new();
         let mut chat_messages = Vec::new();
 
+        // Actions emitted by plugins are applied before the other events so they
+        // take effect in the order plugins sent them
+        #[cfg(feature = "plugins")]
+        {
+            frontend_events.append(&mut plugin::handle_plugin_actions(self));
+            plugin::handle_plugin_tick(self);
+        }
+
         let events = self
             .state
             .ecs()
@@ -245,6 +260,11 @@ impl Server {
         }
 
         for msg in chat_messages {
+            #[cfg(feature = "plugins")]
+            let msg = match plugin::filter_chat_message(self, msg) {
+                Some(msg) => msg,
+                None => continue,
+            };
             self.state.send_chat(msg);
         }
 
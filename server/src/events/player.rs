@@ -12,6 +12,8 @@ use common::{
 use common_base::span;
 use common_net::msg::{PlayerListUpdate, PresenceKind, ServerGeneral};
 use common_state::State;
+#[cfg(feature = "plugins")]
+use plugin_api::event::PlayerLeaveEvent;
 use specs::{saveload::MarkerAllocator, Builder, Entity as EcsEntity, Join, WorldExt};
 use tracing::{debug, error, trace, warn, Instrument};
 
@@ -168,7 +170,7 @@ pub fn handle_client_disconnect(
 
     // Tell other clients to remove from player list
     // And send a disconnected message
-    if let (Some(uid), Some(_)) = (
+    if let (Some(uid), Some(_player)) = (
         state.read_storage::<Uid>().get(entity),
         state.read_storage::<comp::Player>().get(entity),
     ) {
@@ -177,6 +179,15 @@ pub fn handle_client_disconnect(
         state.notify_players(ServerGeneral::PlayerListUpdate(PlayerListUpdate::Remove(
             *uid,
         )));
+
+        #[cfg(feature = "plugins")]
+        if let Err(e) = state.execute_plugin_event(&PlayerLeaveEvent {
+            player_name: _player.alias.clone(),
+            player_id: *_player.uuid().as_bytes(),
+            player: plugin_api::event::Player { id: *uid },
+        }) {
+            error!(?e, "Error occured while executing `on_player_leave`");
+        }
     }
 
     // Sync the player's character data to the database
@@ -0,0 +1,135 @@
+use super::Event;
+use crate::{Server, Tick};
+use common::{
+    comp,
+    event::{EventBus, ServerEvent},
+    resources::{DeltaTime, Time},
+    uid::UidAllocator,
+};
+use common_base::span;
+use common_net::{msg::ServerGeneral, sync::WorldSyncExt};
+use common_state::plugin::PluginMgr;
+use plugin_api::{
+    event::{ChatMessageEvent, ChatMessageResult, Player, TickEvent},
+    Action,
+};
+use specs::{saveload::MarkerAllocator, WorldExt};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Time given to players before the server closes when a plugin requests it
+const PLUGIN_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Applies the actions emitted by plugins since the last tick, in the order
+/// they were emitted. Actions affecting the game are turned into
+/// `ServerEvent`s, while a shutdown request is forwarded to the frontend.
+pub fn handle_plugin_actions(server: &Server) -> Vec<Event> {
+    span!(_guard, "handle_plugin_actions");
+    let ecs = server.state.ecs();
+    let actions = ecs.read_resource::<PluginMgr>().take_actions();
+    let mut frontend_events = Vec::new();
+    if actions.is_empty() {
+        return frontend_events;
+    }
+
+    let uid_allocator = ecs.read_resource::<UidAllocator>();
+    let server_event_bus = ecs.read_resource::<EventBus<ServerEvent>>();
+    let mut server_emitter = server_event_bus.emitter();
+    let healths = ecs.read_storage::<comp::Health>();
+    let time = *ecs.read_resource::<Time>();
+
+    for action in actions {
+        match action {
+            Action::ServerClose => {
+                info!("Server shutdown requested by plugin");
+                frontend_events.push(Event::Shutdown {
+                    grace_period: PLUGIN_SHUTDOWN_GRACE_PERIOD,
+                    reason: "The server is shutting down".to_owned(),
+                });
+            },
+            Action::Print(msg) => info!("{}", msg),
+            Action::PlayerSendMessage(uid, msg) => {
+                match uid_allocator.retrieve_entity_internal(uid.0) {
+                    Some(entity) => server.notify_client(
+                        entity,
+                        ServerGeneral::server_msg(comp::ChatType::CommandInfo, msg),
+                    ),
+                    None => warn!(?uid, "Plugin tried to send a message to an unknown entity"),
+                }
+            },
+            Action::KillEntity(uid) => {
+                match uid_allocator
+                    .retrieve_entity_internal(uid.0)
+                    .and_then(|entity| Some((entity, healths.get(entity)?)))
+                {
+                    // The stats system emits `ServerEvent::Destroy` once health reaches zero
+                    Some((entity, health)) => server_emitter.emit(ServerEvent::HealthChange {
+                        entity,
+                        change: comp::HealthChange {
+                            amount: -health.maximum(),
+                            by: None,
+                            cause: None,
+                            time,
+                        },
+                    }),
+                    None => warn!(?uid, "Plugin tried to kill an entity without health"),
+                }
+            },
+            // Commands are registered by the plugin runtime as soon as they are emitted
+            Action::RegisterCommand(_) => {},
+        }
+    }
+
+    frontend_events
+}
+
+/// Fires the `on_tick` event on every plugin
+pub fn handle_plugin_tick(server: &Server) {
+    span!(_guard, "handle_plugin_tick");
+    let ecs = server.state.ecs();
+    let event = TickEvent {
+        tick: ecs.read_resource::<Tick>().0,
+        time: ecs.read_resource::<Time>().0,
+        dt: ecs.read_resource::<DeltaTime>().0,
+    };
+    if let Err(e) = server.state.execute_plugin_event(&event) {
+        error!(?e, "Error occured while executing `on_tick`");
+    }
+}
+
+/// Runs the `on_chat_message` event for messages sent by players, letting
+/// plugins rewrite them. Returns `None` if a plugin cancelled the message.
+pub fn filter_chat_message(
+    server: &Server,
+    mut msg: comp::UnresolvedChatMsg,
+) -> Option<comp::UnresolvedChatMsg> {
+    let ecs = server.state.ecs();
+    let sender = match msg.uid() {
+        Some(uid)
+            if ecs.entity_from_uid(uid.0).map_or(false, |entity| {
+                ecs.read_storage::<comp::Player>().contains(entity)
+            }) =>
+        {
+            uid
+        },
+        // NPC and server messages aren't sent to plugins
+        _ => return Some(msg),
+    };
+
+    match server.state.execute_plugin_event(&ChatMessageEvent {
+        player: Player { id: sender },
+        message: msg.message.clone(),
+    }) {
+        Ok(results) => {
+            for result in results {
+                match result {
+                    ChatMessageResult::None => {},
+                    ChatMessageResult::Cancel => return None,
+                    ChatMessageResult::Rewrite(message) => msg.message = message,
+                }
+            }
+        },
+        Err(e) => error!(?e, "Error occured while executing `on_chat_message`"),
+    }
+    Some(msg)
+}
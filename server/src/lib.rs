@@ -12,8 +12,10 @@
 )]
 #![cfg_attr(not(feature = "worldgen"), feature(const_panic))]
 
+pub mod admin_api;
 pub mod alias_validator;
 mod character_creator;
+pub mod chat_filter;
 pub mod chunk_generator;
 pub mod client;
 pub mod cmd;
@@ -48,7 +50,9 @@ pub use crate::{
 #[cfg(feature = "persistent_world")]
 use crate::terrain_persistence::TerrainPersistence;
 use crate::{
+    admin_api::AdminApi,
     alias_validator::AliasValidator,
+    chat_filter::ChatFilter,
     chunk_generator::ChunkGenerator,
     client::Client,
     cmd::ChatCommandExt,
@@ -66,7 +70,7 @@ use common::{
     assets::AssetExt,
     calendar::Calendar,
     character::CharacterId,
-    cmd::ChatCommand,
+    cmd::{ChatCommand, ServerChatCommand},
     comp::{self, item::MaterialStatManifest},
     event::{EventBus, ServerEvent},
     recipe::default_recipe_book,
@@ -79,14 +83,15 @@ use common::{
 use common_ecs::run_now;
 use common_net::{
     msg::{
-        ClientType, DisconnectReason, ServerGeneral, ServerInfo, ServerInit, ServerMsg, WorldMapMsg,
+        ClientPluginInfo, ClientType, DisconnectReason, ServerGeneral, ServerInfo, ServerInit,
+        ServerMsg, WorldMapMsg,
     },
     sync::WorldSyncExt,
 };
 use common_state::{BuildAreas, State};
 use common_systems::add_local_systems;
 use metrics::{EcsSystemMetrics, PhysicsMetrics, TickMetrics};
-use network::{ListenAddr, Network, Pid};
+use network::{Identity, ListenAddr, Network, Pid};
 use persistence::{
     character_loader::{CharacterLoader, CharacterLoaderResponseKind},
     character_updater::CharacterUpdater,
@@ -107,7 +112,11 @@ use tracing::{debug, error, info, trace, warn};
 use vek::*;
 
 use crate::{
-    persistence::{DatabaseSettings, SqlLogMode},
+    persistence::{
+        audit_log::AuditLog,
+        backup::{BackupFile, DatabaseBackup, BACKUP_DIR},
+        DatabaseSettings, SqlLogMode,
+    },
     sys::terrain,
 };
 use hashbrown::HashMap;
@@ -115,8 +124,9 @@ use std::sync::RwLock;
 
 #[cfg(feature = "plugins")]
 use {
-    common::uid::UidAllocator,
-    common_state::plugin::{memory_manager::EcsWorld, PluginMgr},
+    crate::persistence::plugin_storage::DatabasePluginStorage,
+    common::{terrain::TerrainGrid, uid::UidAllocator, CachedSpatialGrid},
+    common_state::plugin::{errors::PluginError, memory_manager::EcsWorld, PluginMgr},
 };
 
 use common::comp::Anchor;
@@ -139,6 +149,11 @@ impl Default for SpawnPoint {
 // various mechanics working fluidly (i.e: not unloading nearby entities).
 pub const MIN_VD: u32 = 6;
 
+// Number of ticks between two checks for changed plugin tarballs, roughly two
+// seconds at the default tick rate
+#[cfg(feature = "plugins")]
+const PLUGIN_RELOAD_CHECK_TICKS: u64 = 60;
+
 // Tick count used for throttling network updates
 // Note this doesn't account for dt (so update rate changes with tick rate)
 #[derive(Copy, Clone, Default)]
@@ -196,6 +211,8 @@ pub struct Server {
     runtime: Arc<Runtime>,
 
     metrics_shutdown: Arc<Notify>,
+    admin_api: Option<AdminApi>,
+    _database_backup: Option<DatabaseBackup>,
     database_settings: Arc<RwLock<DatabaseSettings>>,
     disconnect_all_clients_requested: bool,
 }
@@ -230,6 +247,14 @@ impl Server {
         let physics_metrics = PhysicsMetrics::new(&registry).unwrap();
         let battlemode_buffer = BattleModeBuffer::default();
 
+        #[cfg(feature = "plugins")]
+        let mut state = State::server_with_plugins(
+            settings.plugin_limits,
+            Some(Arc::new(DatabasePluginStorage::new(Arc::clone(
+                &database_settings,
+            )))),
+        );
+        #[cfg(not(feature = "plugins"))]
         let mut state = State::server();
         state.ecs_mut().insert(battlemode_buffer);
         state.ecs_mut().insert(settings.clone());
@@ -298,6 +323,12 @@ impl Server {
             Arc::<RwLock<DatabaseSettings>>::clone(&database_settings),
         )?);
 
+        state
+            .ecs_mut()
+            .insert(AuditLog::new(Arc::<RwLock<DatabaseSettings>>::clone(
+                &database_settings,
+            )));
+
         // System schedulers to control execution of systems
         state
             .ecs_mut()
@@ -343,6 +374,9 @@ impl Server {
         let banned_words_count = banned_words.len();
         tracing::debug!(?banned_words_count);
         tracing::trace!(?banned_words);
+        state
+            .ecs_mut()
+            .insert(ChatFilter::new(banned_words.clone(), settings.chat_filter));
         state.ecs_mut().insert(AliasValidator::new(banned_words));
 
         #[cfg(feature = "worldgen")]
@@ -450,6 +484,17 @@ impl Server {
         state.ecs_mut().insert(DeletedEntities::default());
 
         let network = Network::new_with_registry(Pid::new(), &runtime, &registry);
+        #[cfg(feature = "network_simulation")]
+        if let Some(sim) = &settings.network_simulation {
+            warn!(?sim, "Simulating a bad network to all clients");
+            runtime.block_on(network.simulate(Some(sim.into())));
+        }
+        let identity = load_network_identity(data_dir);
+        info!(
+            identity = %identity.public_identity(),
+            "Clients can pin this network identity to detect a man in the middle"
+        );
+        runtime.block_on(network.set_identity(identity));
         let metrics_shutdown = Arc::new(Notify::new());
         let metrics_shutdown_clone = Arc::clone(&metrics_shutdown);
         let addr = settings.metrics_address;
@@ -461,6 +506,17 @@ impl Server {
             )
             .await
         });
+        let admin_api = settings
+            .admin_api
+            .as_ref()
+            .map(|admin_api| AdminApi::start(admin_api, &runtime));
+        let database_backup = settings.database_backups.clone().map(|backups| {
+            DatabaseBackup::start(
+                backups,
+                Arc::<RwLock<DatabaseSettings>>::clone(&database_settings),
+                data_dir.join(BACKUP_DIR),
+            )
+        });
         runtime.block_on(network.listen(ListenAddr::Tcp(settings.gameserver_address)))?;
         runtime.block_on(network.listen(ListenAddr::Mpsc(14004)))?;
         if let Some(quic) = &settings.quic_files {
@@ -506,7 +562,11 @@ impl Server {
                 },
             }
         }
-        let connection_handler = ConnectionHandler::new(network, &runtime);
+        if let Some(addr) = settings.websocket_address {
+            runtime.block_on(network.listen(ListenAddr::WebSocket(addr)))?;
+        }
+        let connection_handler =
+            ConnectionHandler::new(network, &runtime, settings.unreliable_physics_sync);
 
         // Initiate real-time world simulation
         #[cfg(feature = "worldgen")]
@@ -524,6 +584,8 @@ impl Server {
             runtime,
 
             metrics_shutdown,
+            admin_api,
+            _database_backup: database_backup,
             database_settings,
             disconnect_all_clients_requested: false,
         };
@@ -632,12 +694,20 @@ impl Server {
         // 1) Build up a list of events for this frame, to be passed to the frontend.
         let mut frontend_events = Vec::new();
 
-        // 2)
+        // 2) Reload the plugins that changed on disk
+        #[cfg(feature = "plugins")]
+        if self.settings().plugin_auto_reload
+            && self.state.ecs().read_resource::<Tick>().0 % PLUGIN_RELOAD_CHECK_TICKS == 0
+            && self.state.reload_changed_plugins()
+        {
+            self.notify_plugin_commands();
+        }
 
         let before_new_connections = Instant::now();
 
         // 3) Handle inputs from clients
         self.handle_new_connections(&mut frontend_events);
+        self.handle_admin_api_calls(&mut frontend_events);
 
         let before_state_tick = Instant::now();
 
@@ -874,6 +944,29 @@ impl Server {
         drop(character_loader);
         drop(character_updater);
 
+        // Send the results of audit log queries to the admins who asked for them
+        for response in self.state.ecs().read_resource::<AuditLog>().messages() {
+            let msg = match response.result {
+                Ok(entries) if entries.is_empty() => ServerGeneral::server_msg(
+                    comp::ChatType::CommandInfo,
+                    "No matching audit log entries",
+                ),
+                Ok(entries) => ServerGeneral::server_msg(
+                    comp::ChatType::CommandInfo,
+                    entries
+                        .iter()
+                        .map(|entry| entry.to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                ),
+                Err(error) => ServerGeneral::server_msg(
+                    comp::ChatType::CommandError,
+                    format!("Failed to read the audit log: {}", error),
+                ),
+            };
+            self.notify_client(response.entity, msg);
+        }
+
         {
             // Check for new chunks; cancel and regenerate all chunks if the asset has been
             // reloaded. Note that all of these assignments are no-ops, so the
@@ -1017,6 +1110,7 @@ impl Server {
                     .ecs()
                     .read_resource::<comp::item::tool::AbilityMap>())
                     .clone(),
+                client_plugins: self.client_plugins(),
             })?;
         Ok(Some(entity))
     }
@@ -1141,6 +1235,14 @@ impl Server {
                     uid: self.state.ecs().read_component().into(),
                     uid_allocator: &self.state.ecs().read_resource::<UidAllocator>().into(),
                     player: self.state.ecs().read_component().into(),
+                    pos: self.state.ecs().read_component().into(),
+                    vel: self.state.ecs().read_component().into(),
+                    inventory: self.state.ecs().read_component().into(),
+                    stats: self.state.ecs().read_component().into(),
+                    skill_set: self.state.ecs().read_component().into(),
+                    group: self.state.ecs().read_component().into(),
+                    spatial_grid: &self.state.ecs().read_resource::<CachedSpatialGrid>().into(),
+                    terrain: &self.state.ecs().read_resource::<TerrainGrid>().into(),
                 };
                 let uid = if let Some(uid) = ecs_world.uid.get(entity).copied() {
                     uid
@@ -1154,14 +1256,31 @@ impl Server {
                     );
                     return;
                 };
-                let rs = plugin_manager.execute_event(
-                    &ecs_world,
-                    &plugin_api::event::ChatCommandEvent {
-                        command: name.clone(),
-                        command_args: args.clone(),
-                        player: plugin_api::event::Player { id: uid },
+                let event = plugin_api::event::ChatCommandEvent {
+                    command: name.clone(),
+                    command_args: args.clone(),
+                    player: plugin_api::event::Player { id: uid },
+                };
+                // Declared commands only go to their plugin, other commands are sent to every
+                // plugin handling `on_command_<name>`
+                let rs = match plugin_manager.find_command(&name) {
+                    Some((plugin, command)) => {
+                        if command.needs_role.map(comp::AdminRole::from)
+                            > self.entity_admin_role(entity)
+                        {
+                            self.notify_client(
+                                entity,
+                                ServerGeneral::server_msg(
+                                    comp::ChatType::CommandError,
+                                    format!("You don't have permission to use '/{}'.", name),
+                                ),
+                            );
+                            return;
+                        }
+                        plugin.execute_event(&ecs_world, &event)
                     },
-                );
+                    None => plugin_manager.execute_event(&ecs_world, &event),
+                };
                 match rs {
                     Ok(e) => {
                         if e.is_empty() {
@@ -1233,6 +1352,80 @@ impl Server {
         self.state.ecs().read_storage::<Client>().join().count() as i64
     }
 
+    /// Describes the loaded plugins and their modules, one line per plugin
+    #[cfg(feature = "plugins")]
+    pub fn plugin_list(&self) -> Vec<String> {
+        self.state
+            .ecs()
+            .read_resource::<PluginMgr>()
+            .plugins()
+            .iter()
+            .map(|plugin| {
+                format!(
+                    "{} ({})",
+                    plugin.name(),
+                    plugin
+                        .modules()
+                        .map(|module| module.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "plugins")]
+    pub fn reload_plugin(&self, name: &str) -> Result<(), PluginError> {
+        let result = self.state.reload_plugin(name);
+        // A failed reload unloads the plugin, so its commands may be gone either way
+        self.notify_plugin_commands();
+        result
+    }
+
+    #[cfg(feature = "plugins")]
+    pub fn unload_plugin(&self, name: &str) -> Result<(), PluginError> {
+        self.state.unload_plugin(name)?;
+        self.notify_plugin_commands();
+        Ok(())
+    }
+
+    /// The chat commands provided by plugins, empty when built without plugin
+    /// support
+    pub fn plugin_commands(&self) -> Vec<ServerChatCommand> {
+        #[cfg(feature = "plugins")]
+        {
+            self.state.ecs().read_resource::<PluginMgr>().commands()
+        }
+        #[cfg(not(feature = "plugins"))]
+        {
+            Vec::new()
+        }
+    }
+
+    /// The plugins connecting clients are asked to load, empty when built
+    /// without plugin support
+    pub fn client_plugins(&self) -> Vec<ClientPluginInfo> {
+        #[cfg(feature = "plugins")]
+        {
+            self.state
+                .ecs()
+                .read_resource::<PluginMgr>()
+                .client_plugins()
+        }
+        #[cfg(not(feature = "plugins"))]
+        {
+            Vec::new()
+        }
+    }
+
+    /// Tells all the clients about the chat commands currently provided by
+    /// plugins
+    #[cfg(feature = "plugins")]
+    fn notify_plugin_commands(&self) {
+        self.state
+            .notify_players(ServerGeneral::ChatCommands(self.plugin_commands()));
+    }
+
     /// NOTE: Do *not* allow this to be called from any command that doesn't go
     /// through the CLI!
     pub fn add_admin(&mut self, username: &str, role: comp::AdminRole) {
@@ -1334,6 +1527,48 @@ impl Server {
         info!("Disconnecting all clients due to local console command");
         self.disconnect_all_clients_requested = true;
     }
+
+    /// Lists the database backups, oldest first
+    pub fn list_backups(&self) -> std::io::Result<Vec<BackupFile>> {
+        persistence::backup::list_backups(&self.data_dir().path.join(BACKUP_DIR))
+    }
+
+    /// Replaces the database with the backup called `name`. This is refused
+    /// while players are connected, as saving their characters would write
+    /// over the restored data.
+    pub fn restore_backup(&mut self, name: &str) -> Result<(), String> {
+        let players = self.number_of_players();
+        if players > 0 {
+            return Err(format!(
+                "Refusing to restore a backup while {} players are connected",
+                players
+            ));
+        }
+        if self
+            .state
+            .ecs()
+            .read_resource::<CharacterUpdater>()
+            .characters_pending_logout()
+            .next()
+            .is_some()
+        {
+            return Err(
+                "Characters of players who just left are still being saved, try again shortly"
+                    .to_owned(),
+            );
+        }
+
+        let database_settings = self
+            .database_settings
+            .read()
+            .expect("DatabaseSettings RwLock was poisoned")
+            .clone();
+        persistence::backup::restore_backup(
+            &database_settings,
+            &self.data_dir().path.join(BACKUP_DIR),
+            name,
+        )
+    }
 }
 
 impl Drop for Server {
@@ -1382,6 +1617,57 @@ pub fn handle_edit<T, S: settings::EditableSetting>(
     }
 }
 
+/// Relative to the data dir
+const NETWORK_IDENTITY_FILE: &str = "network_identity.pk8";
+
+/// Loads the identity the server proves to connecting clients, a new one is
+/// created on the first start. Keeping it across restarts lets clients pin it.
+fn load_network_identity(data_dir: &std::path::Path) -> Identity {
+    let path = data_dir.join(NETWORK_IDENTITY_FILE);
+    match std::fs::read(&path) {
+        Ok(pkcs8) => match Identity::from_pkcs8(&pkcs8) {
+            Some(identity) => return identity,
+            None => {
+                // don't overwrite it, maybe it can be recovered
+                error!(
+                    "{} is not a valid network identity, using a temporary one",
+                    path.display()
+                );
+                return Identity::generate();
+            },
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {},
+        Err(e) => {
+            error!(
+                ?e,
+                "Failed to read the network identity {}, using a temporary one",
+                path.display()
+            );
+            return Identity::generate();
+        },
+    }
+
+    let identity = Identity::generate();
+    let write = || -> std::io::Result<()> {
+        use std::io::Write;
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        // it's the private key, only we may read it
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options.open(&path)?.write_all(identity.to_pkcs8())
+    };
+    match write() {
+        Ok(()) => info!("Created a new network identity in {}", path.display()),
+        Err(e) => error!(
+            ?e,
+            "Failed to save the network identity to {}",
+            path.display()
+        ),
+    }
+    identity
+}
+
 /// If successful returns the Some(uuid) of the added admin
 ///
 /// NOTE: Do *not* allow this to be called from any command that doesn't go
@@ -1,4 +1,4 @@
-use crate::settings::{AdminRecord, BanEntry, WhitelistRecord};
+use crate::settings::{AdminRecord, Ban, Banlist, WhitelistRecord};
 use authc::{AuthClient, AuthClientError, AuthToken, Uuid};
 use chrono::Utc;
 use common::comp::AdminRole;
@@ -6,7 +6,7 @@ use common_net::msg::RegisterError;
 use hashbrown::HashMap;
 use specs::Component;
 use specs_idvs::IdvStorage;
-use std::{str::FromStr, sync::Arc};
+use std::{net::IpAddr, str::FromStr, sync::Arc};
 use tokio::{runtime::Runtime, sync::oneshot};
 use tracing::{error, info};
 
@@ -105,7 +105,8 @@ impl LoginProvider {
         #[cfg(feature = "plugins")] plugin_manager: &PluginMgr,
         admins: &HashMap<Uuid, AdminRecord>,
         whitelist: &HashMap<Uuid, WhitelistRecord>,
-        banlist: &HashMap<Uuid, BanEntry>,
+        banlist: &Banlist,
+        peer_ip: Option<IpAddr>,
     ) -> Option<Result<(String, Uuid), RegisterError>> {
         match pending.pending_r.try_recv() {
             Ok(Err(e)) => Some(Err(e)),
@@ -113,23 +114,28 @@ impl LoginProvider {
                 let now = Utc::now();
                 // Hardcoded admins can always log in.
                 let admin = admins.get(&uuid);
-                if let Some(ban) = banlist
-                    .get(&uuid)
-                    .and_then(|ban_record| ban_record.current.action.ban())
-                {
-                    // Make sure the ban is active, and that we can't override it.
-                    //
-                    // If we are an admin and our role is at least as high as the role of the
-                    // person who banned us, we can override the ban; we negate this to find
-                    // people who cannot override it.
+                // Make sure the ban is active, and that we can't override it.
+                //
+                // If we are an admin and our role is at least as high as the role of the
+                // person who banned us, we can override the ban; we negate this to find
+                // people who cannot override it.
+                let applies = |ban: &Ban| {
                     let exceeds_ban_role = |admin: &AdminRecord| {
                         Into::<AdminRole>::into(admin.role)
                             >= Into::<AdminRole>::into(ban.performed_by_role())
                     };
-                    if !ban.is_expired(now) && !admin.map_or(false, exceeds_ban_role) {
-                        // Pull reason string out of ban record and send a copy of it
-                        return Some(Err(RegisterError::Banned(ban.reason.clone())));
-                    }
+                    !ban.is_expired(now) && !admin.map_or(false, exceeds_ban_role)
+                };
+                let uuid_ban = banlist
+                    .uuid_bans()
+                    .get(&uuid)
+                    .and_then(|ban_record| ban_record.current.action.ban());
+                // Bans of the address apply to every account connecting from it.
+                let ip_ban =
+                    || peer_ip.and_then(|ip| banlist.ip_bans_of(ip).find(|&ban| applies(ban)));
+                if let Some(ban) = uuid_ban.filter(|&ban| applies(ban)).or_else(ip_ban) {
+                    // Pull reason string out of ban record and send a copy of it
+                    return Some(Err(RegisterError::Banned(ban.reason.clone())));
                 }
 
                 // non-admins can only join if the whitelist is empty (everyone can join)
@@ -0,0 +1,13 @@
+-- Creates the audit log of commands which need an admin role
+CREATE TABLE "audit_log" (
+      "audit_log_id" INTEGER PRIMARY KEY,
+      "timestamp" INTEGER NOT NULL,
+      "actor_uuid" TEXT NOT NULL,
+      "actor_alias" TEXT NOT NULL,
+      "target" TEXT,
+      "command" TEXT NOT NULL,
+      "arguments" TEXT NOT NULL,
+      "error" TEXT
+);
+
+CREATE INDEX "audit_log_timestamp" ON "audit_log"("timestamp");
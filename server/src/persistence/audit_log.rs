@@ -0,0 +1,323 @@
+//! Durable log of commands which need an admin role, kept in the `audit_log`
+//! table so that it is backed up along with character data.
+
+use super::{establish_connection, ConnectionMode, DatabaseSettings};
+use authc::Uuid;
+use chrono::{DateTime, TimeZone, Utc};
+use crossbeam_channel::{self, TryIter};
+use rusqlite::{types::Type, Connection, Row, ToSql};
+use std::{
+    fmt,
+    sync::{Arc, RwLock},
+};
+use tracing::{error, warn};
+
+/// A single execution of a privileged command
+#[derive(Clone, Debug)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    pub actor_uuid: Uuid,
+    pub actor_alias: String,
+    /// Who the command was executed on, if it was not the actor (e.g. with
+    /// /sudo): the alias of a player, otherwise the name or uid of the entity
+    pub target: Option<String>,
+    pub command: String,
+    pub arguments: Vec<String>,
+    /// `None` if the command succeeded
+    pub error: Option<String>,
+}
+
+impl fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            self.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            self.actor_alias
+        )?;
+        if let Some(target) = &self.target {
+            write!(f, " on {}", target)?;
+        }
+        write!(
+            f,
+            ": /{} {} -> {}",
+            self.command,
+            self.arguments.join(" "),
+            self.error.as_deref().unwrap_or("ok"),
+        )
+    }
+}
+
+/// Which entries to return from [`AuditLog::query`], `None` matches anything
+#[derive(Clone, Debug)]
+pub struct AuditFilter {
+    /// Alias or uuid of the actor, or the target
+    pub player: Option<String>,
+    pub command: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Maximum number of entries returned, the most recent ones are kept
+    pub limit: u32,
+}
+
+pub(crate) type AuditQueryResult = Result<Vec<AuditEntry>, String>;
+
+enum AuditLogRequest {
+    Record(AuditEntry),
+    Query(specs::Entity, AuditFilter),
+}
+
+/// Result of a query for the entity which requested it
+pub struct AuditLogResponse {
+    pub entity: specs::Entity,
+    pub result: AuditQueryResult,
+}
+
+/// Writes and reads the audit log in a background thread, so that the server
+/// tick doesn't wait for the database.
+///
+/// Query results are polled on each server tick in the format
+/// [`AuditLogResponse`]
+pub struct AuditLog {
+    request_tx: crossbeam_channel::Sender<AuditLogRequest>,
+    response_rx: crossbeam_channel::Receiver<AuditLogResponse>,
+}
+
+impl AuditLog {
+    pub fn new(settings: Arc<RwLock<DatabaseSettings>>) -> Self {
+        let (request_tx, request_rx) = crossbeam_channel::unbounded::<AuditLogRequest>();
+        let (response_tx, response_rx) = crossbeam_channel::unbounded::<AuditLogResponse>();
+
+        let builder = std::thread::Builder::new().name("persistence_audit_log".into());
+        builder
+            .spawn(move || {
+                let mut conn = establish_connection(
+                    &*settings
+                        .read()
+                        .expect("DatabaseSettings RwLock was poisoned"),
+                    ConnectionMode::ReadWrite,
+                );
+
+                for request in request_rx {
+                    conn.update_log_mode(&settings);
+
+                    match request {
+                        AuditLogRequest::Record(entry) => {
+                            if let Err(error) = record(&conn, &entry) {
+                                warn!(?error, ?entry, "Failed to write audit log entry");
+                            }
+                        },
+                        AuditLogRequest::Query(entity, filter) => {
+                            let result = query(&conn, &filter).map_err(|e| e.to_string());
+                            if let Err(e) = response_tx.send(AuditLogResponse { entity, result }) {
+                                error!(?e, "Could not send audit log response");
+                            }
+                        },
+                    }
+                }
+            })
+            .unwrap();
+
+        Self {
+            request_tx,
+            response_rx,
+        }
+    }
+
+    pub fn record(&self, entry: AuditEntry) {
+        if let Err(e) = self.request_tx.send(AuditLogRequest::Record(entry)) {
+            error!(?e, "Could not send audit log entry");
+        }
+    }
+
+    /// Looks up the matching entries, oldest first. The result is returned
+    /// to `entity` by [`AuditLog::messages`].
+    pub fn query(&self, entity: specs::Entity, filter: AuditFilter) {
+        if let Err(e) = self.request_tx.send(AuditLogRequest::Query(entity, filter)) {
+            error!(?e, "Could not send audit log query");
+        }
+    }
+
+    /// Returns a non-blocking iterator over AuditLogResponse messages
+    pub fn messages(&self) -> TryIter<AuditLogResponse> { self.response_rx.try_iter() }
+}
+
+fn record(connection: &Connection, entry: &AuditEntry) -> Result<(), rusqlite::Error> {
+    let arguments = serde_json::to_string(&entry.arguments)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    let mut stmt = connection.prepare_cached(
+        "
+        INSERT
+        INTO    audit_log (timestamp, actor_uuid, actor_alias, target, command,
+                           arguments, error)
+        VALUES  (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )?;
+    stmt.execute(&[
+        &entry.timestamp.timestamp() as &dyn ToSql,
+        &entry.actor_uuid.to_string(),
+        &entry.actor_alias,
+        &entry.target,
+        &entry.command,
+        &arguments,
+        &entry.error,
+    ])
+    .map(|_| ())
+}
+
+fn query(
+    connection: &Connection,
+    filter: &AuditFilter,
+) -> Result<Vec<AuditEntry>, rusqlite::Error> {
+    let mut stmt = connection.prepare_cached(
+        "
+        SELECT  timestamp, actor_uuid, actor_alias, target, command, arguments, error
+        FROM    audit_log
+        WHERE   (?1 IS NULL OR actor_alias = ?1 OR actor_uuid = ?1 OR target = ?1)
+        AND     (?2 IS NULL OR command = ?2)
+        AND     (?3 IS NULL OR timestamp >= ?3)
+        AND     (?4 IS NULL OR timestamp <= ?4)
+        ORDER BY audit_log_id DESC
+        LIMIT   ?5",
+    )?;
+    let mut entries = stmt
+        .query_map(
+            &[
+                &filter.player as &dyn ToSql,
+                &filter.command,
+                &filter.since.map(|since| since.timestamp()),
+                &filter.until.map(|until| until.timestamp()),
+                &filter.limit,
+            ],
+            entry_from_row,
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.reverse();
+    Ok(entries)
+}
+
+fn entry_from_row(row: &Row) -> Result<AuditEntry, rusqlite::Error> {
+    let actor_uuid: String = row.get(1)?;
+    let arguments: String = row.get(5)?;
+    Ok(AuditEntry {
+        timestamp: Utc.timestamp(row.get(0)?, 0),
+        actor_uuid: Uuid::parse_str(&actor_uuid)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(1, Type::Text, Box::new(e)))?,
+        actor_alias: row.get(2)?,
+        target: row.get(3)?,
+        command: row.get(4)?,
+        arguments: serde_json::from_str(&arguments)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(5, Type::Text, Box::new(e)))?,
+        error: row.get(6)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn audit_db() -> Connection {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(include_str!("../migrations/V47__audit_log.sql"))
+            .unwrap();
+        let entry = |secs, alias: &str, uuid, target: Option<&str>, command: &str| AuditEntry {
+            timestamp: Utc.timestamp(secs, 0),
+            actor_uuid: Uuid::from_u128(uuid),
+            actor_alias: alias.to_owned(),
+            target: target.map(str::to_owned),
+            command: command.to_owned(),
+            arguments: vec!["a b".to_owned(), "c".to_owned()],
+            error: None,
+        };
+        for entry in [
+            entry(100, "alice", 1, None, "ban"),
+            entry(200, "bob", 2, Some("carol"), "kick"),
+            entry(300, "alice", 1, Some("bob"), "kick"),
+            entry(400, "dave", 3, None, "ban_ip"),
+        ] {
+            record(&connection, &entry).unwrap();
+        }
+        connection
+    }
+
+    fn filter() -> AuditFilter {
+        AuditFilter {
+            player: None,
+            command: None,
+            since: None,
+            until: None,
+            limit: 50,
+        }
+    }
+
+    fn times(entries: Vec<AuditEntry>) -> Vec<i64> {
+        entries
+            .iter()
+            .map(|entry| entry.timestamp.timestamp())
+            .collect()
+    }
+
+    #[test]
+    fn query_all() {
+        let connection = audit_db();
+        let entries = query(&connection, &filter()).unwrap();
+        assert_eq!(times(entries.clone()), vec![100, 200, 300, 400]);
+        assert_eq!(entries[1].actor_uuid, Uuid::from_u128(2));
+        assert_eq!(entries[1].target.as_deref(), Some("carol"));
+        assert_eq!(entries[1].arguments, vec!["a b", "c"]);
+    }
+
+    #[test]
+    fn query_by_player() {
+        let connection = audit_db();
+        let by = |player: &str| {
+            times(
+                query(&connection, &AuditFilter {
+                    player: Some(player.to_owned()),
+                    ..filter()
+                })
+                .unwrap(),
+            )
+        };
+        assert_eq!(by("alice"), vec![100, 300]);
+        // as target
+        assert_eq!(by("bob"), vec![200, 300]);
+        assert_eq!(by(&Uuid::from_u128(3).to_string()), vec![400]);
+        assert_eq!(by("eve"), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn query_by_command() {
+        let connection = audit_db();
+        let entries = query(&connection, &AuditFilter {
+            command: Some("kick".to_owned()),
+            ..filter()
+        })
+        .unwrap();
+        assert_eq!(times(entries), vec![200, 300]);
+    }
+
+    #[test]
+    fn query_by_time() {
+        let connection = audit_db();
+        let entries = query(&connection, &AuditFilter {
+            since: Some(Utc.timestamp(200, 0)),
+            until: Some(Utc.timestamp(300, 0)),
+            ..filter()
+        })
+        .unwrap();
+        assert_eq!(times(entries), vec![200, 300]);
+    }
+
+    #[test]
+    fn query_keeps_most_recent() {
+        let connection = audit_db();
+        let entries = query(&connection, &AuditFilter {
+            player: Some("alice".to_owned()),
+            limit: 1,
+            ..filter()
+        })
+        .unwrap();
+        assert_eq!(times(entries), vec![300]);
+    }
+}
@@ -0,0 +1,389 @@
+//! Periodic online backups of the database into `backups` in the data
+//! directory.
+//!
+//! Backups are taken with SQLite's online backup API, which produces a
+//! consistent copy while the server keeps writing to the database.
+
+use super::{
+    establish_connection, try_run_migrations, ConnectionMode, DatabaseSettings, DB_FILENAME,
+};
+use crate::settings::DatabaseBackupSettings;
+use chrono::Utc;
+use crossbeam_channel::RecvTimeoutError;
+use rusqlite::{backup::Progress, Connection, DatabaseName, OpenFlags, NO_PARAMS};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    thread::JoinHandle,
+    time::{Duration, SystemTime},
+};
+use tracing::{error, info, warn};
+
+/// Relative to the data dir
+pub const BACKUP_DIR: &str = "backups";
+
+const BACKUP_PREFIX: &str = "backup-";
+const BACKUP_EXTENSION: &str = ".sqlite";
+/// Relative to the backup dir, a restored backup is prepared in there
+const RESTORE_DIR: &str = "restore";
+
+/// A backup file in the backup directory
+#[derive(Clone, Debug)]
+pub struct BackupFile {
+    pub name: String,
+    /// In bytes
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// Takes a backup in a background thread whenever the configured interval has
+/// passed since the last one. The thread stops when this is dropped.
+pub struct DatabaseBackup {
+    shutdown_tx: Option<crossbeam_channel::Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl DatabaseBackup {
+    pub fn start(
+        settings: DatabaseBackupSettings,
+        database_settings: Arc<RwLock<DatabaseSettings>>,
+        backup_dir: PathBuf,
+    ) -> Self {
+        let (shutdown_tx, shutdown_rx) = crossbeam_channel::bounded::<()>(0);
+
+        let builder = std::thread::Builder::new().name("persistence_backup".into());
+        let handle = builder
+            .spawn(move || {
+                // Carry on from the last backup so that servers which restart more often
+                // than the interval are still backed up
+                let mut wait = list_backups(&backup_dir)
+                    .ok()
+                    .and_then(|backups| backups.last().map(|backup| backup.modified))
+                    .and_then(|modified| modified.elapsed().ok())
+                    .map_or(Duration::ZERO, |elapsed| {
+                        settings.interval.saturating_sub(elapsed)
+                    });
+
+                while let Err(RecvTimeoutError::Timeout) = shutdown_rx.recv_timeout(wait) {
+                    wait = settings.interval;
+                    let database_settings = database_settings
+                        .read()
+                        .expect("DatabaseSettings RwLock was poisoned")
+                        .clone();
+                    match create_backup(&database_settings, &backup_dir) {
+                        Ok(path) => info!("Created database backup {}", path.display()),
+                        Err(e) => error!(?e, "Failed to create database backup"),
+                    }
+                    prune_backups(&backup_dir, settings.retention);
+                }
+            })
+            .unwrap();
+
+        Self {
+            shutdown_tx: Some(shutdown_tx),
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for DatabaseBackup {
+    fn drop(&mut self) {
+        drop(self.shutdown_tx.take());
+        if let Err(e) = self.handle.take().unwrap().join() {
+            error!(?e, "Error from joining database backup thread");
+        }
+    }
+}
+
+/// Returns the backups in `backup_dir`, oldest first
+pub fn list_backups(backup_dir: &Path) -> std::io::Result<Vec<BackupFile>> {
+    if !backup_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = fs::read_dir(backup_dir)?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(BACKUP_PREFIX) || !name.ends_with(BACKUP_EXTENSION) {
+                return None;
+            }
+            let metadata = entry.metadata().ok()?;
+            Some(BackupFile {
+                name,
+                size: metadata.len(),
+                modified: metadata.modified().ok()?,
+            })
+        })
+        .collect::<Vec<_>>();
+    // The names contain the UTC time of the backup, so this sorts them by age
+    backups.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(backups)
+}
+
+fn create_backup(
+    database_settings: &DatabaseSettings,
+    backup_dir: &Path,
+) -> Result<PathBuf, String> {
+    fs::create_dir_all(backup_dir).map_err(|e| e.to_string())?;
+
+    let name = format!(
+        "{}{}{}",
+        BACKUP_PREFIX,
+        Utc::now().format("%Y-%m-%dT%H-%M-%S"),
+        BACKUP_EXTENSION
+    );
+    let path = backup_dir.join(name);
+    // Write to a temporary file first so that an interrupted backup is never
+    // listed or restored
+    let tmp_path = path.with_extension("sqlite.tmp");
+
+    let connection = establish_connection(database_settings, ConnectionMode::ReadOnly);
+    if let Err(e) = connection.backup(DatabaseName::Main, &tmp_path, None) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e.to_string());
+    }
+    fs::rename(&tmp_path, &path).map_err(|e| e.to_string())?;
+
+    Ok(path)
+}
+
+/// Deletes the oldest backups until at most `retention` are left, 0 keeps all
+/// of them
+fn prune_backups(backup_dir: &Path, retention: usize) {
+    if retention == 0 {
+        return;
+    }
+    let backups = match list_backups(backup_dir) {
+        Ok(backups) => backups,
+        Err(e) => {
+            warn!(?e, "Failed to list database backups, not pruning them");
+            return;
+        },
+    };
+
+    let excess = backups.len().saturating_sub(retention);
+    for backup in &backups[..excess] {
+        let path = backup_dir.join(&backup.name);
+        match fs::remove_file(&path) {
+            Ok(()) => info!("Deleted old database backup {}", path.display()),
+            Err(e) => warn!(
+                ?e,
+                "Failed to delete old database backup {}",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Replaces the content of the database with the backup called `name`.
+///
+/// The backup is copied, migrated and checked first, so that the database is
+/// left untouched if any of that fails. If replacing the content fails, the
+/// previous content is put back.
+///
+/// The caller has to make sure that nothing is written to the database in the
+/// meantime, i.e. no players are connected.
+pub fn restore_backup(
+    database_settings: &DatabaseSettings,
+    backup_dir: &Path,
+    name: &str,
+) -> Result<(), String> {
+    // Only accept names of existing backups, which also keeps paths outside of
+    // the backup dir out
+    let backups = list_backups(backup_dir).map_err(|e| e.to_string())?;
+    if !backups.iter().any(|backup| backup.name == name) {
+        return Err(format!("There is no backup called {}", name));
+    }
+
+    let restore_dir = backup_dir.join(RESTORE_DIR);
+    let result = prepare_and_restore(database_settings, &backup_dir.join(name), &restore_dir);
+    if restore_dir.exists() {
+        if let Err(e) = fs::remove_dir_all(&restore_dir) {
+            warn!(?e, "Failed to clean up {}", restore_dir.display());
+        }
+    }
+    result
+}
+
+fn prepare_and_restore(
+    database_settings: &DatabaseSettings,
+    path: &Path,
+    restore_dir: &Path,
+) -> Result<(), String> {
+    if restore_dir.exists() {
+        fs::remove_dir_all(restore_dir).map_err(|e| e.to_string())?;
+    }
+    // The copy lives in a database dir of its own, so that the usual
+    // migrations can be run on it
+    let restore_settings = DatabaseSettings {
+        db_dir: restore_dir.to_owned(),
+        sql_log_mode: database_settings.sql_log_mode,
+    };
+    let restore_path = restore_dir.join(DB_FILENAME);
+    quick_check(path).map_err(|e| format!("Backup is corrupted: {}", e))?;
+    fs::create_dir_all(restore_dir).map_err(|e| e.to_string())?;
+    fs::copy(path, &restore_path).map_err(|e| e.to_string())?;
+
+    // The backup may have been taken by an older version of the server
+    try_run_migrations(&restore_settings)?;
+    quick_check(&restore_path).map_err(|e| format!("Migrated backup is corrupted: {}", e))?;
+
+    let previous_path = restore_dir.join("previous.sqlite");
+    let mut connection = establish_connection(database_settings, ConnectionMode::ReadWrite);
+    connection
+        .backup(DatabaseName::Main, &previous_path, None)
+        .map_err(|e| format!("Failed to save the current database: {}", e))?;
+    if let Err(e) =
+        connection
+            .connection
+            .restore(DatabaseName::Main, &restore_path, None::<fn(Progress)>)
+    {
+        error!(
+            ?e,
+            "Failed to restore backup, putting back the previous database"
+        );
+        connection
+            .connection
+            .restore(DatabaseName::Main, &previous_path, None::<fn(Progress)>)
+            .map_err(|e| format!("Failed to put back the previous database: {}", e))?;
+        return Err(e.to_string());
+    }
+    Ok(())
+}
+
+fn quick_check(path: &Path) -> Result<(), String> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| e.to_string())?;
+    let check: String = connection
+        .query_row("PRAGMA quick_check", NO_PARAMS, |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if check != "ok" {
+        return Err(check);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty directory in the system temp dir, unique to `name` and this
+    /// process
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("veloren-backup-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn names(backup_dir: &Path) -> Vec<String> {
+        list_backups(backup_dir)
+            .unwrap()
+            .into_iter()
+            .map(|backup| backup.name)
+            .collect()
+    }
+
+    fn database(dir: &Path) -> DatabaseSettings {
+        let settings = DatabaseSettings {
+            db_dir: dir.join("db"),
+            sql_log_mode: Default::default(),
+        };
+        try_run_migrations(&settings).unwrap();
+        settings
+    }
+
+    fn audit_entries(settings: &DatabaseSettings) -> i64 {
+        establish_connection(settings, ConnectionMode::ReadOnly)
+            .query_row("SELECT COUNT(*) FROM audit_log", NO_PARAMS, |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    fn add_audit_entry(settings: &DatabaseSettings) {
+        establish_connection(settings, ConnectionMode::ReadWrite)
+            .execute(
+                "INSERT INTO audit_log (timestamp, actor_uuid, actor_alias, command, arguments)
+                 VALUES (0, '', 'admin', 'kick', '[]')",
+                NO_PARAMS,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn list_only_backups() {
+        let dir = temp_dir("list");
+        assert!(names(&dir.join("missing")).is_empty());
+        for name in [
+            "backup-2021-02-01T00-00-00.sqlite",
+            "backup-2021-01-01T00-00-00.sqlite",
+            "backup-2021-03-01T00-00-00.sqlite.tmp",
+            "db.sqlite",
+        ] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        assert_eq!(names(&dir), vec![
+            "backup-2021-01-01T00-00-00.sqlite",
+            "backup-2021-02-01T00-00-00.sqlite",
+        ]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn prune_oldest() {
+        let dir = temp_dir("prune");
+        for day in 1..=3 {
+            let name = format!("backup-2021-01-0{}T00-00-00.sqlite", day);
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        prune_backups(&dir, 0);
+        assert_eq!(names(&dir).len(), 3);
+        prune_backups(&dir, 2);
+        assert_eq!(names(&dir), vec![
+            "backup-2021-01-02T00-00-00.sqlite",
+            "backup-2021-01-03T00-00-00.sqlite",
+        ]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn restore() {
+        let dir = temp_dir("restore");
+        let backup_dir = dir.join(BACKUP_DIR);
+        let settings = database(&dir);
+        add_audit_entry(&settings);
+        let backup = create_backup(&settings, &backup_dir).unwrap();
+        let name = backup.file_name().unwrap().to_str().unwrap();
+        add_audit_entry(&settings);
+        assert_eq!(audit_entries(&settings), 2);
+
+        assert!(restore_backup(&settings, &backup_dir, "db.sqlite").is_err());
+        assert_eq!(audit_entries(&settings), 2);
+
+        restore_backup(&settings, &backup_dir, name).unwrap();
+        assert_eq!(audit_entries(&settings), 1);
+        assert!(!backup_dir.join(RESTORE_DIR).exists());
+        assert_eq!(names(&backup_dir), vec![name]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn restore_corrupted() {
+        let dir = temp_dir("restore-corrupted");
+        let backup_dir = dir.join(BACKUP_DIR);
+        let settings = database(&dir);
+        add_audit_entry(&settings);
+        let name = "backup-2021-01-01T00-00-00.sqlite";
+        fs::create_dir_all(&backup_dir).unwrap();
+        fs::write(backup_dir.join(name), vec![42; 4096]).unwrap();
+
+        assert!(restore_backup(&settings, &backup_dir, name).is_err());
+        assert_eq!(audit_entries(&settings), 1);
+        assert!(!backup_dir.join(RESTORE_DIR).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
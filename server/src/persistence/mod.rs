@@ -1,5 +1,7 @@
 //! DB operations and schema migrations
 
+pub mod audit_log;
+pub mod backup;
 pub(in crate::persistence) mod character;
 pub mod character_loader;
 pub mod character_updater;
@@ -7,6 +9,8 @@ mod diesel_to_rusqlite;
 pub mod error;
 mod json_models;
 mod models;
+#[cfg(feature = "plugins")]
+pub mod plugin_storage;
 
 use crate::persistence::character_updater::PetPersistenceData;
 use common::comp;
@@ -97,6 +101,9 @@ fn set_log_mode(connection: &mut Connection, sql_log_mode: SqlLogMode) {
     };
 }
 
+/// Name of the database file in [`DatabaseSettings::db_dir`]
+pub(crate) const DB_FILENAME: &str = "db.sqlite";
+
 #[derive(Clone)]
 pub struct DatabaseSettings {
     pub db_dir: PathBuf,
@@ -153,20 +160,27 @@ impl ToString for SqlLogMode {
 
 /// Runs any pending database migrations. This is executed during server startup
 pub fn run_migrations(settings: &DatabaseSettings) {
+    // If migrations fail to run, the server cannot start since the database will
+    // not be in the required state.
+    try_run_migrations(settings).expect("Database migrations failed, server startup aborted");
+}
+
+/// Like [`run_migrations`], but returns an error instead of panicking, for
+/// when the server is already running
+pub(crate) fn try_run_migrations(settings: &DatabaseSettings) -> Result<(), String> {
     let mut conn = establish_connection(settings, ConnectionMode::ReadWrite);
 
     diesel_to_rusqlite::migrate_from_diesel(&mut conn)
-        .expect("One-time migration from Diesel to Refinery failed");
+        .map_err(|e| format!("One-time migration from Diesel to Refinery failed: {}", e))?;
 
-    // If migrations fail to run, the server cannot start since the database will
-    // not be in the required state.
     let report: Report = embedded::migrations::runner()
         .set_abort_divergent(false)
         .run(&mut conn.connection)
-        .expect("Database migrations failed, server startup aborted");
+        .map_err(|e| format!("Database migrations failed: {}", e))?;
 
     let applied_migrations = report.applied_migrations().len();
     info!("Applied {} database migrations", applied_migrations);
+    Ok(())
 }
 
 // These callbacks use info logging because they are never enabled by default,
@@ -198,11 +212,11 @@ pub(crate) fn establish_connection(
             ConnectionMode::ReadOnly => OpenFlags::SQLITE_OPEN_READ_ONLY,
         };
 
-    let connection = Connection::open_with_flags(&settings.db_dir.join("db.sqlite"), open_flags)
+    let connection = Connection::open_with_flags(&settings.db_dir.join(DB_FILENAME), open_flags)
         .unwrap_or_else(|err| {
             panic!(
                 "Error connecting to {}, Error: {:?}",
-                settings.db_dir.join("db.sqlite").display(),
+                settings.db_dir.join(DB_FILENAME).display(),
                 err
             )
         });
@@ -0,0 +1,96 @@
+//! Persistent key/value storage of plugins, kept in the `plugin_storage` table
+//! so that it is saved and backed up along with character data.
+
+use super::{establish_connection, ConnectionMode, DatabaseSettings, VelorenConnection};
+use common_state::plugin::storage::PluginStorage;
+use rusqlite::{OptionalExtension, ToSql};
+use std::sync::{Arc, Mutex, RwLock};
+
+pub struct DatabasePluginStorage {
+    connection: Mutex<VelorenConnection>,
+    settings: Arc<RwLock<DatabaseSettings>>,
+}
+
+impl DatabasePluginStorage {
+    pub fn new(settings: Arc<RwLock<DatabaseSettings>>) -> Self {
+        let connection = establish_connection(
+            &*settings
+                .read()
+                .expect("DatabaseSettings RwLock was poisoned"),
+            ConnectionMode::ReadWrite,
+        );
+        Self {
+            connection: Mutex::new(connection),
+            settings,
+        }
+    }
+
+    fn with_connection<T>(
+        &self,
+        f: impl FnOnce(&VelorenConnection) -> Result<T, rusqlite::Error>,
+    ) -> Result<T, String> {
+        let mut connection = self
+            .connection
+            .lock()
+            .map_err(|_| "Plugin storage connection was poisoned".to_owned())?;
+        connection.update_log_mode(&self.settings);
+        f(&connection).map_err(|e| e.to_string())
+    }
+}
+
+impl PluginStorage for DatabasePluginStorage {
+    fn get(&self, plugin: &str, key: &str) -> Result<Option<Vec<u8>>, String> {
+        self.with_connection(|connection| {
+            let mut stmt = connection.prepare_cached(
+                "
+                SELECT  value
+                FROM    plugin_storage
+                WHERE   plugin = ?1
+                AND     key = ?2",
+            )?;
+            stmt.query_row(&[plugin, key], |row| row.get(0)).optional()
+        })
+    }
+
+    fn set(&self, plugin: &str, key: &str, value: &[u8]) -> Result<(), String> {
+        self.with_connection(|connection| {
+            let mut stmt = connection.prepare_cached(
+                "
+                REPLACE
+                INTO    plugin_storage (plugin, key, value)
+                VALUES  (?1, ?2, ?3)",
+            )?;
+            stmt.execute(&[&plugin as &dyn ToSql, &key, &value])
+                .map(|_| ())
+        })
+    }
+
+    fn delete(&self, plugin: &str, key: &str) -> Result<(), String> {
+        self.with_connection(|connection| {
+            let mut stmt = connection.prepare_cached(
+                "
+                DELETE
+                FROM    plugin_storage
+                WHERE   plugin = ?1
+                AND     key = ?2",
+            )?;
+            stmt.execute(&[plugin, key]).map(|_| ())
+        })
+    }
+
+    fn keys(&self, plugin: &str) -> Result<Vec<String>, String> {
+        self.with_connection(|connection| {
+            let mut stmt = connection.prepare_cached(
+                "
+                SELECT  key
+                FROM    plugin_storage
+                WHERE   plugin = ?1
+                ORDER BY key",
+            )?;
+            let keys = stmt
+                .query_map(&[plugin], |row| row.get(0))?
+                .collect::<Result<Vec<String>, _>>();
+            keys
+        })
+    }
+}
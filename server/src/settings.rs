@@ -1,6 +1,7 @@
 pub mod admin;
 pub mod banlist;
 mod editable;
+pub mod mutelist;
 pub mod server_description;
 pub mod whitelist;
 
@@ -8,8 +9,10 @@ pub use editable::{EditableSetting, Error as SettingError};
 
 pub use admin::{AdminRecord, Admins};
 pub use banlist::{
-    Ban, BanAction, BanEntry, BanError, BanErrorKind, BanInfo, BanKind, BanRecord, Banlist,
+    Ban, BanAction, BanEntry, BanError, BanErrorKind, BanInfo, BanKind, BanRecord, BanTarget,
+    Banlist, IpRange,
 };
+pub use mutelist::{MuteInfo, MuteRecord, Mutelist};
 pub use server_description::ServerDescription;
 pub use whitelist::{Whitelist, WhitelistInfo, WhitelistRecord};
 
@@ -18,11 +21,15 @@ use common::{
     calendar::{Calendar, CalendarEvent},
     resources::BattleMode,
 };
+#[cfg(feature = "plugins")]
+use common_state::plugin::limits::PluginLimits;
 use core::time::Duration;
+#[cfg(feature = "network_simulation")]
+use network::SimConfig;
 use portpicker::pick_unused_port;
 use serde::{Deserialize, Serialize};
 use std::{
-    fs,
+    fmt, fs,
     net::SocketAddr,
     path::{Path, PathBuf},
 };
@@ -34,6 +41,7 @@ const CONFIG_DIR: &str = "server_config";
 const SETTINGS_FILENAME: &str = "settings.ron";
 const WHITELIST_FILENAME: &str = "whitelist.ron";
 const BANLIST_FILENAME: &str = "banlist.ron";
+const MUTELIST_FILENAME: &str = "mutelist.ron";
 const SERVER_DESCRIPTION_FILENAME: &str = "description.ron";
 const ADMINS_FILENAME: &str = "admins.ron";
 
@@ -43,6 +51,49 @@ pub struct X509FilePair {
     pub key: PathBuf,
 }
 
+/// A token accepted by the admin API, see [`crate::admin_api`]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AdminApiToken {
+    pub token: String,
+    /// Calls made with this token are performed as this admin
+    pub username: String,
+}
+
+// The settings are logged on startup, so keep the token out of it
+impl fmt::Debug for AdminApiToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminApiToken")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdminApiSettings {
+    pub address: SocketAddr,
+    pub tokens: Vec<AdminApiToken>,
+}
+
+/// Periodic copies of the database, taken while the server is running, see
+/// [`crate::persistence::backup`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DatabaseBackupSettings {
+    /// Time between two backups
+    pub interval: Duration,
+    /// Number of backups to keep, older ones are deleted. 0 keeps all of them
+    pub retention: usize,
+}
+
+impl Default for DatabaseBackupSettings {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(6 * 3600),
+            retention: 8,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Deserialize, Serialize)]
 pub enum ServerBattleMode {
     Global(BattleMode),
@@ -65,6 +116,36 @@ impl ServerBattleMode {
     }
 }
 
+/// Simulates a bad connection to all clients, see
+/// `Network::simulate`. Only used with the `network_simulation` feature.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSimulation {
+    pub latency_ms: u64,
+    pub jitter_ms: u64,
+    pub drop_rate: f64,
+    pub duplicate_rate: f64,
+    pub reorder_rate: f64,
+    /// in bytes per second, `0` means unlimited
+    pub bandwidth: u64,
+    pub seed: u64,
+}
+
+#[cfg(feature = "network_simulation")]
+impl From<&NetworkSimulation> for SimConfig {
+    fn from(sim: &NetworkSimulation) -> Self {
+        Self {
+            latency: Duration::from_millis(sim.latency_ms),
+            jitter: Duration::from_millis(sim.jitter_ms),
+            drop_rate: sim.drop_rate,
+            duplicate_rate: sim.duplicate_rate,
+            reorder_rate: sim.reorder_rate,
+            bandwidth: sim.bandwidth,
+            seed: sim.seed,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum CalendarMode {
     None,
@@ -88,6 +169,21 @@ impl CalendarMode {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChatFilterMode {
+    /// Chat messages are sent as they are
+    Disabled,
+    /// Chat messages containing a banned word are not sent, the sender is
+    /// notified instead
+    Block,
+    /// Banned words are replaced with asterisks
+    Replace,
+}
+
+impl Default for ChatFilterMode {
+    fn default() -> Self { Self::Disabled }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Settings {
@@ -95,6 +191,11 @@ pub struct Settings {
     pub metrics_address: SocketAddr,
     pub auth_server_address: Option<String>,
     pub quic_files: Option<X509FilePair>,
+    /// Additionally accept clients over WebSocket, e.g. behind a reverse proxy
+    /// which only forwards HTTP(S)
+    pub websocket_address: Option<SocketAddr>,
+    /// Serve a JSON API for administration tools, disabled when None
+    pub admin_api: Option<AdminApiSettings>,
     pub max_players: usize,
     pub world_seed: u32,
     pub battle_mode: ServerBattleMode,
@@ -105,17 +206,35 @@ pub struct Settings {
     pub map_file: Option<FileOpts>,
     pub max_view_distance: Option<u32>,
     pub banned_words_files: Vec<PathBuf>,
+    /// What happens to chat messages containing a word from
+    /// `banned_words_files`
+    pub chat_filter: ChatFilterMode,
     pub max_player_group_size: u32,
     pub client_timeout: Duration,
     pub spawn_town: Option<String>,
     pub safe_spawn: bool,
     pub max_player_for_kill_broadcast: Option<usize>,
     pub calendar_mode: CalendarMode,
+    /// Fuel and memory allowed to each plugin module
+    #[cfg(feature = "plugins")]
+    pub plugin_limits: PluginLimits,
+    /// Reload plugins when their tarball changes on disk
+    #[cfg(feature = "plugins")]
+    pub plugin_auto_reload: bool,
+    /// Send position, velocity and orientation updates without guaranteed
+    /// delivery, so on QUIC a lost update never holds back newer ones
+    pub unreliable_physics_sync: bool,
+    /// Back up the database to `backups` in the data directory, disabled when
+    /// None
+    pub database_backups: Option<DatabaseBackupSettings>,
 
     /// Experimental feature. No guaranteed forwards-compatibility, may be
     /// removed at *any time* with no migration.
     #[serde(default, skip_serializing)]
     pub experimental_terrain_persistence: bool,
+    /// Development only, ignored without the `network_simulation` feature.
+    #[serde(default, skip_serializing)]
+    pub network_simulation: Option<NetworkSimulation>,
 }
 
 impl Default for Settings {
@@ -125,6 +244,8 @@ impl Default for Settings {
             metrics_address: SocketAddr::from(([0; 4], 14005)),
             auth_server_address: Some("https://auth.veloren.net".into()),
             quic_files: None,
+            websocket_address: None,
+            admin_api: None,
             world_seed: DEFAULT_WORLD_SEED,
             server_name: "Veloren Alpha".into(),
             max_players: 100,
@@ -133,13 +254,21 @@ impl Default for Settings {
             map_file: None,
             max_view_distance: Some(65),
             banned_words_files: Vec::new(),
+            chat_filter: ChatFilterMode::default(),
             max_player_group_size: 6,
             calendar_mode: CalendarMode::Auto,
             client_timeout: Duration::from_secs(40),
             spawn_town: None,
             safe_spawn: true,
             max_player_for_kill_broadcast: None,
+            #[cfg(feature = "plugins")]
+            plugin_limits: PluginLimits::default(),
+            #[cfg(feature = "plugins")]
+            plugin_auto_reload: false,
+            unreliable_physics_sync: false,
+            database_backups: Some(DatabaseBackupSettings::default()),
             experimental_terrain_persistence: false,
+            network_simulation: None,
         }
     }
 }
@@ -207,6 +336,8 @@ impl Settings {
             )),
             auth_server_address: None,
             quic_files: None,
+            websocket_address: None,
+            admin_api: None,
             // If loading the default map file, make sure the seed is also default.
             world_seed: if load.map_file.is_some() {
                 load.world_seed
@@ -252,6 +383,7 @@ const MIGRATION_UPGRADE_GUARANTEE: &str = "Any valid file of an old verison shou
 pub struct EditableSettings {
     pub whitelist: Whitelist,
     pub banlist: Banlist,
+    pub mutelist: Mutelist,
     pub server_description: ServerDescription,
     pub admins: Admins,
 }
@@ -261,6 +393,7 @@ impl EditableSettings {
         Self {
             whitelist: Whitelist::load(data_dir),
             banlist: Banlist::load(data_dir),
+            mutelist: Mutelist::load(data_dir),
             server_description: ServerDescription::load(data_dir),
             admins: Admins::load(data_dir),
         }
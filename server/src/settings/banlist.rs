@@ -12,7 +12,7 @@ use serde::{Deserialize, Serialize};
 /// BanlistRaw, the TryFrom<BanlistRaw> for Banlist, the previously most recent
 /// module, and add a new module for the latest version!  Please respect the
 /// migration upgrade guarantee found in the parent module with any upgrade.
-pub use self::v1::*;
+pub use self::v2::*;
 
 /// Versioned settings files, one per version (v0 is only here as an example; we
 /// do not expect to see any actual v0 settings files).
@@ -20,12 +20,13 @@ pub use self::v1::*;
 pub enum BanlistRaw {
     V0(v0::Banlist),
     V1(v1::Banlist),
+    V2(v2::Banlist),
 }
 
 impl From<Banlist> for BanlistRaw {
     fn from(value: Banlist) -> Self {
         // Replace variant with that of current latest version.
-        Self::V1(value)
+        Self::V2(value)
     }
 }
 
@@ -37,9 +38,10 @@ impl TryFrom<BanlistRaw> for (Version, Banlist) {
         Ok(match value {
             // Old versions
             V0(value) => (Version::Old, value.try_into()?),
+            V1(value) => (Version::Old, value.try_into()?),
             // Latest version (move to old section using the pattern of other old version when it
             // is no longer latest).
-            V1(mut value) => (value.validate()?, value),
+            V2(mut value) => (value.validate()?, value),
         })
     }
 }
@@ -73,12 +75,21 @@ pub enum BanErrorKind {
     PermissionDenied(BanKind),
 }
 
+/// What a ban applies to.
+#[derive(Clone, Copy, Debug)]
+pub enum BanTarget {
+    /// The account with this uuid
+    Uuid(Uuid),
+    /// Every connection from an address in this range
+    Ip(IpRange),
+}
+
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct BanError {
     kind: BanErrorKind,
-    /// Uuid of affected user
-    uuid: Uuid,
+    /// Affected user or addresses
+    target: BanTarget,
     /// Username of affected user (as of ban/unban time).
     username: String,
 }
@@ -190,16 +201,377 @@ mod v0 {
 }
 
 mod v1 {
-    use super::{v0 as prev, BanError, BanErrorKind, BanKind, Final};
+    use super::{
+        v0 as prev, v2 as next, BanError, BanErrorKind, BanKind, BanTarget, Final,
+        MIGRATION_UPGRADE_GUARANTEE,
+    };
+    use crate::settings::editable::{EditableSetting, Version};
+    use authc::Uuid;
+    use chrono::{prelude::*, Utc};
+    use common::comp::AdminRole;
+    use core::{
+        convert::{TryFrom, TryInto},
+        ops::Deref,
+    };
+    use hashbrown::HashMap;
+    use serde::{Deserialize, Serialize};
+    use tracing::warn;
+
+    /// Important: even if the role we are storing here appears to be identical
+    /// to one used in another versioned store (like admin::Role), we *must*
+    /// have our own versioned copy!  This ensures that if there's an update
+    /// to the role somewhere else, the conversion function between them
+    /// will break, letting people make an intelligent decision.
+    ///
+    /// In particular, *never remove variants from this enum* (or any other enum
+    /// in a versioned settings file) without bumping the version and
+    /// writing a migration that understands how to properly deal with
+    /// existing instances of the old variant (you can delete From instances
+    /// for the old variants at this point).  Otherwise, we will lose
+    /// compatibility with old settings files, since we won't be able to
+    /// deserialize them!
+    #[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+    pub enum Role {
+        Moderator = 0,
+        Admin = 1,
+    }
+
+    impl From<AdminRole> for Role {
+        fn from(value: AdminRole) -> Self {
+            match value {
+                AdminRole::Moderator => Self::Moderator,
+                AdminRole::Admin => Self::Admin,
+            }
+        }
+    }
+
+    impl From<Role> for AdminRole {
+        fn from(value: Role) -> Self {
+            match value {
+                Role::Moderator => Self::Moderator,
+                Role::Admin => Self::Admin,
+            }
+        }
+    }
+
+    #[derive(Clone, Deserialize, Serialize)]
+    /// NOTE: May not be present if performed from the command line or from a
+    /// legacy file.
+    pub struct BanInfo {
+        pub performed_by: Uuid,
+        /// NOTE: May not be up to date, if we allow username changes.
+        pub performed_by_username: String,
+        /// NOTE: Role of the banning user at the time of the ban.
+        pub performed_by_role: Role,
+    }
+
+    #[derive(Clone, Deserialize, Serialize)]
+    pub struct Ban {
+        pub reason: String,
+        /// NOTE: Should only be None for migrations from legacy data.
+        pub info: Option<BanInfo>,
+        /// NOTE: Should always be higher than start_date, if both are
+        /// present!
+        pub end_date: Option<DateTime<Utc>>,
+    }
+
+    impl Ban {
+        /// Returns true if the ban is expired, false otherwise.
+        pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+            self.end_date.map_or(false, |end_date| end_date <= now)
+        }
+
+        pub fn performed_by_role(&self) -> Role {
+            self.info.as_ref().map(|info| info.performed_by_role)
+                // We know all legacy bans were performed by an admin, since we had no other roles
+                // at the time.
+                .unwrap_or(Role::Admin)
+        }
+    }
+
+    type Unban = BanInfo;
+
+    #[derive(Clone, Deserialize, Serialize)]
+    pub enum BanAction {
+        Unban(Unban),
+        Ban(Ban),
+    }
+
+    #[derive(Clone, Deserialize, Serialize)]
+    pub struct BanRecord {
+        /// Username of the user upon whom the action was performed, when it was
+        /// performed.
+        pub username_when_performed: String,
+        pub action: BanAction,
+        /// NOTE: When migrating from legacy versions, this will just be the
+        /// time of the first migration (only applies to BanRecord).
+        pub date: DateTime<Utc>,
+    }
+
+    impl BanRecord {
+        /// Returns true if this record represents an expired ban, false
+        /// otherwise.
+        fn is_expired(&self, now: DateTime<Utc>) -> bool {
+            match &self.action {
+                BanAction::Ban(ban) => ban.is_expired(now),
+                BanAction::Unban(_) => true,
+            }
+        }
+
+        /// The history vector in a BanEntry is stored forwards (from oldest
+        /// entry to newest), so `prev_record` is the previous entry in
+        /// this vector when iterating forwards (by array index).
+        ///
+        /// Errors are:
+        ///
+        /// AlreadyUnbanned if an unban comes after anything but a ban.
+        ///
+        /// Permission(Unban) if an unban attempt is by a user with a lower role
+        /// level than the original banning party.
+        ///
+        /// PermissionDenied(Ban) if a ban length is made shorter by a user with
+        /// a role level than the original banning party.
+        ///
+        /// InvalidDateRange if the end date of the ban exceeds the start date.
+        fn validate(&self, prev_record: Option<&BanRecord>) -> Result<(), BanErrorKind> {
+            // Check to make sure the actions temporally line up--if they don't, we will
+            // prevent warn an administrator (since this may indicate a system
+            // clock issue and could require manual editing to resolve).
+            // However, we will not actually invalidate the ban list for this, in case
+            // this would otherwise prevent people from adding a new ban.
+            //
+            // We also deliberately leave the bad order intact, in case this reflects
+            // history more accurately than the system clock does.
+            if let Some(prev_record) = prev_record {
+                if prev_record.date > self.date {
+                    warn!(
+                        "Ban list history is inconsistent, or a just-added ban was behind a \
+                         historical entry in the ban
+                          record; please investigate the contents of the file (might indicate a \
+                         system clock change?)."
+                    );
+                }
+            }
+            let ban = match (&self.action, prev_record.map(|record| &record.action)) {
+                // A ban is always valid if it follows an unban.
+                (BanAction::Ban(ban), None) | (BanAction::Ban(ban), Some(BanAction::Unban(_))) => {
+                    ban
+                },
+                // A ban record following a ban is valid if either the role of the person doing the
+                // banning is at least the privilege level of the person who did the ban, or the
+                // ban's new end time is at least the previous end time.
+                (BanAction::Ban(new_ban), Some(BanAction::Ban(old_ban))) => {
+                    match (new_ban.end_date, old_ban.end_date) {
+                        // New role ≥ old role
+                        _ if new_ban.performed_by_role() >= old_ban.performed_by_role() => new_ban,
+                        // Permanent ban retracted to temp ban.
+                        (Some(_), None) => {
+                            return Err(BanErrorKind::PermissionDenied(BanKind::Ban));
+                        },
+                        // Temp ban retracted to shorter temp ban.
+                        (Some(new_date), Some(old_date)) if new_date < old_date => {
+                            return Err(BanErrorKind::PermissionDenied(BanKind::Ban));
+                        },
+                        // Anything else (extension to permanent ban, or temp ban extension to
+                        // longer temp ban).
+                        _ => new_ban,
+                    }
+                },
+                // An unban record is invalid if it does not follow a ban.
+                (BanAction::Unban(_), None) | (BanAction::Unban(_), Some(BanAction::Unban(_))) => {
+                    return Err(BanErrorKind::AlreadyUnbanned);
+                },
+                // An unban record following a ban is valid if the role of the person doing the
+                // unbanning is at least the privilege level of the person who did the ban.
+                (BanAction::Unban(unban), Some(BanAction::Ban(ban))) => {
+                    if unban.performed_by_role >= ban.performed_by_role() {
+                        return Ok(());
+                    } else {
+                        return Err(BanErrorKind::PermissionDenied(BanKind::Unban));
+                    }
+                },
+            };
+
+            // End date of a ban must be at least as big as the start date.
+            if let Some(end_date) = ban.end_date {
+                if self.date > end_date {
+                    return Err(BanErrorKind::InvalidDateRange {
+                        start_date: self.date,
+                        end_date,
+                    });
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(Clone, Deserialize, Serialize)]
+    pub struct BanEntry {
+        /// The latest ban record for this user.
+        pub current: BanRecord,
+        /// Historical ban records for this user, stored in order from oldest to
+        /// newest.
+        pub history: Vec<BanRecord>,
+        /// A *hint* about whether the system thinks this entry is expired,
+        /// mostly to make it easier for someone manually going through
+        /// a file to see whether an entry is currently in effect or
+        /// not.  This is based off the contents of `current`.
+        pub expired: bool,
+    }
+
+    impl Deref for BanEntry {
+        type Target = BanRecord;
+
+        fn deref(&self) -> &Self::Target { &self.current }
+    }
+
+    impl BanEntry {
+        /// Both validates, and updates the hint bit if it's inconsistent with
+        /// reality.
+        ///
+        /// If we were invalid, returns an error.  Otherwise, returns Ok(v),
+        /// where v is Latest if the hint bit was modified, Old
+        /// otherwise.
+        fn validate(
+            &mut self,
+            now: DateTime<Utc>,
+            uuid: Uuid,
+        ) -> Result<Version, <Final as EditableSetting>::Error> {
+            let make_error = |current_entry: &BanRecord| {
+                let username = current_entry.username_when_performed.clone();
+                move |kind| BanError {
+                    kind,
+                    target: BanTarget::Uuid(uuid),
+                    username,
+                }
+            };
+            // First, go forwards through history (also forwards in terms of the iterator
+            // direction), validating each entry in turn.
+            let mut prev_entry = None;
+            for current_entry in &self.history {
+                current_entry
+                    .validate(prev_entry)
+                    .map_err(make_error(current_entry))?;
+                prev_entry = Some(current_entry);
+            }
+
+            // History has now been validated, so validate the current entry.
+            self.current
+                .validate(prev_entry)
+                .map_err(make_error(&self.current))?;
+
+            // Make sure the expired hint is correct, and if not indicate that we should
+            // resave the file.
+            let is_expired = self.current.is_expired(now);
+            if self.expired != is_expired {
+                self.expired = is_expired;
+                Ok(Version::Old)
+            } else {
+                Ok(Version::Latest)
+            }
+        }
+    }
+
+    #[derive(Clone, Deserialize, Serialize, Default)]
+    #[serde(transparent)]
+    pub struct Banlist(pub(super) HashMap<Uuid, BanEntry>);
+
+    impl Deref for Banlist {
+        type Target = HashMap<Uuid, BanEntry>;
+
+        fn deref(&self) -> &Self::Target { &self.0 }
+    }
+
+    impl Banlist {
+        /// One-off migration from the previous version.  This must be
+        /// guaranteed to produce a valid settings file as long as it is
+        /// called with a valid settings file from the previous version.
+        pub(super) fn migrate(prev: prev::Banlist) -> Self {
+            // The ban start date for migrations from legacy is the current one; we could
+            // record that they actually have an unknown start date, but this
+            // would just complicate the format.
+            let date = Utc::now();
+            Banlist(
+                prev.0
+                    .into_iter()
+                    .map(
+                        |(
+                            uid,
+                            prev::BanRecord {
+                                username_when_banned,
+                                reason,
+                            },
+                        )| {
+                            (uid, BanEntry {
+                                current: BanRecord {
+                                    username_when_performed: username_when_banned,
+                                    // We only recorded unbans pre-migration.
+                                    action: BanAction::Ban(Ban {
+                                        reason,
+                                        // We don't know who banned this user pre-migration.
+                                        info: None,
+                                        // All bans pre-migration are of unlimited duration.
+                                        end_date: None,
+                                    }),
+                                    date,
+                                },
+                                // Old bans never expire, so set the expiration hint to false.
+                                expired: false,
+                                // There is no known ban history yet.
+                                history: Vec::new(),
+                            })
+                        },
+                    )
+                    .collect(),
+            )
+        }
+
+        /// Perform any needed validation on this banlist that can't be done
+        /// using parsing.
+        ///
+        /// The returned version being "Old" indicates the loaded setting has
+        /// been modified during validation (this is why validate takes
+        /// `&mut self`).
+        pub(super) fn validate(&mut self) -> Result<Version, <Final as EditableSetting>::Error> {
+            let mut version = Version::Latest;
+            let now = Utc::now();
+            for (&uuid, value) in self.0.iter_mut() {
+                if matches!(value.validate(now, uuid)?, Version::Old) {
+                    // Update detected.
+                    version = Version::Old;
+                }
+            }
+            Ok(version)
+        }
+    }
+
+    /// Pretty much every TryFrom implementation except that of the very last
+    /// version should look exactly like this.
+    impl TryFrom<Banlist> for Final {
+        type Error = <Final as EditableSetting>::Error;
+
+        #[allow(clippy::useless_conversion)]
+        fn try_from(mut value: Banlist) -> Result<Final, Self::Error> {
+            value.validate()?;
+            Ok(next::Banlist::migrate(value)
+                .try_into()
+                .expect(MIGRATION_UPGRADE_GUARANTEE))
+        }
+    }
+}
+
+mod v2 {
+    use super::{v1 as prev, BanError, BanErrorKind, BanKind, BanTarget, Final};
     use crate::settings::editable::{EditableSetting, Error, Version};
     use authc::Uuid;
     use chrono::{prelude::*, Utc};
     use common::comp::AdminRole;
-    use core::{mem, ops::Deref};
+    use core::{convert::TryFrom, fmt, hash::Hash, mem, ops::Deref, str::FromStr};
     use hashbrown::{hash_map, HashMap};
     use serde::{Deserialize, Serialize};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
     use tracing::warn;
-    /* use super::v2 as next; */
+    /* use super::v3 as next; */
 
     /// Important: even if the role we are storing here appears to be identical
     /// to one used in another versioned store (like admin::Role), we *must*
@@ -238,6 +610,15 @@ mod v1 {
         }
     }
 
+    impl From<prev::Role> for Role {
+        fn from(value: prev::Role) -> Self {
+            match value {
+                prev::Role::Moderator => Self::Moderator,
+                prev::Role::Admin => Self::Admin,
+            }
+        }
+    }
+
     #[derive(Clone, Deserialize, Serialize)]
     /// NOTE: May not be present if performed from the command line or from a
     /// legacy file.
@@ -428,13 +809,13 @@ mod v1 {
         fn validate(
             &mut self,
             now: DateTime<Utc>,
-            uuid: Uuid,
+            target: BanTarget,
         ) -> Result<Version, <Final as EditableSetting>::Error> {
             let make_error = |current_entry: &BanRecord| {
                 let username = current_entry.username_when_performed.clone();
                 move |kind| BanError {
                     kind,
-                    uuid,
+                    target,
                     username,
                 }
             };
@@ -465,17 +846,151 @@ mod v1 {
         }
     }
 
-    #[derive(Clone, Deserialize, Serialize, Default)]
-    #[serde(transparent)]
-    pub struct Banlist(pub(super) HashMap<Uuid, BanEntry>);
+    /// A single IP address, or a range of them in CIDR notation like
+    /// `192.168.0.0/16`.  Stored as its string representation, so it can be
+    /// used as the key of a map in the settings file.
+    ///
+    /// The host bits of the address are always zero, so equal ranges compare
+    /// and hash equal no matter how they were written.
+    #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+    #[serde(try_from = "String", into = "String")]
+    pub struct IpRange {
+        addr: IpAddr,
+        prefix_len: u8,
+    }
 
-    impl Deref for Banlist {
-        type Target = HashMap<Uuid, BanEntry>;
+    impl IpRange {
+        /// Creates the range of addresses that share the first `prefix_len`
+        /// bits with `addr`.  Fails if `prefix_len` is larger than the
+        /// address.
+        pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self, String> {
+            let bits = match addr {
+                IpAddr::V4(_) => 32,
+                IpAddr::V6(_) => 128,
+            };
+            if prefix_len > bits {
+                return Err(format!(
+                    "prefix length {} is longer than the address ({} bits)",
+                    prefix_len, bits
+                ));
+            }
+            let addr = match addr {
+                IpAddr::V4(addr) => {
+                    let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+                    IpAddr::V4(Ipv4Addr::from(u32::from(addr) & mask))
+                },
+                IpAddr::V6(addr) => {
+                    let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+                    IpAddr::V6(Ipv6Addr::from(u128::from(addr) & mask))
+                },
+            };
+            Ok(Self { addr, prefix_len })
+        }
 
-        fn deref(&self) -> &Self::Target { &self.0 }
+        /// Range containing just `addr`.
+        pub fn single(addr: IpAddr) -> Self {
+            let addr = canonical(addr);
+            let prefix_len = match addr {
+                IpAddr::V4(_) => 32,
+                IpAddr::V6(_) => 128,
+            };
+            Self { addr, prefix_len }
+        }
+
+        pub fn addr(&self) -> IpAddr { self.addr }
+
+        pub fn prefix_len(&self) -> u8 { self.prefix_len }
+
+        /// Returns true if `ip` is part of this range.  IPv4 addresses mapped
+        /// into IPv6, as reported by dual stack sockets, are matched against
+        /// IPv4 ranges.
+        pub fn contains(&self, ip: IpAddr) -> bool {
+            match Self::new(canonical(ip), self.prefix_len) {
+                Ok(range) => range.addr == self.addr,
+                // `ip` is an IPv4 address and this range is IPv6.
+                Err(_) => false,
+            }
+        }
+    }
+
+    /// Turns `::ffff:a.b.c.d` into `a.b.c.d`.
+    fn canonical(ip: IpAddr) -> IpAddr {
+        match ip {
+            IpAddr::V6(v6) => match v6.octets() {
+                [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, a, b, c, d] => {
+                    IpAddr::V4(Ipv4Addr::new(a, b, c, d))
+                },
+                _ => ip,
+            },
+            IpAddr::V4(_) => ip,
+        }
+    }
+
+    impl FromStr for IpRange {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.split_once('/') {
+                Some((addr, prefix_len)) => {
+                    let addr = canonical(
+                        addr.parse()
+                            .map_err(|e| format!("Invalid address {}: {}", addr, e))?,
+                    );
+                    let prefix_len = prefix_len
+                        .parse()
+                        .map_err(|e| format!("Invalid prefix length {}: {}", prefix_len, e))?;
+                    Self::new(addr, prefix_len)
+                },
+                None => s
+                    .parse()
+                    .map(Self::single)
+                    .map_err(|e| format!("Invalid address {}: {}", s, e)),
+            }
+        }
+    }
+
+    impl fmt::Display for IpRange {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match (self.addr, self.prefix_len) {
+                (IpAddr::V4(_), 32) | (IpAddr::V6(_), 128) => write!(f, "{}", self.addr),
+                _ => write!(f, "{}/{}", self.addr, self.prefix_len),
+            }
+        }
+    }
+
+    impl TryFrom<String> for IpRange {
+        type Error = String;
+
+        fn try_from(value: String) -> Result<Self, Self::Error> { value.parse() }
+    }
+
+    impl From<IpRange> for String {
+        fn from(value: IpRange) -> Self { value.to_string() }
+    }
+
+    #[derive(Clone, Deserialize, Serialize, Default)]
+    pub struct Banlist {
+        pub(super) uuid_bans: HashMap<Uuid, BanEntry>,
+        /// Bans of addresses and address ranges.  They apply to every
+        /// connection from a matching address, no matter which account is
+        /// used.
+        pub(super) ip_bans: HashMap<IpRange, BanEntry>,
     }
 
     impl Banlist {
+        pub fn uuid_bans(&self) -> &HashMap<Uuid, BanEntry> { &self.uuid_bans }
+
+        pub fn ip_bans(&self) -> &HashMap<IpRange, BanEntry> { &self.ip_bans }
+
+        /// Returns the bans of all ranges containing `ip`, including expired
+        /// ones.
+        pub fn ip_bans_of(&self, ip: IpAddr) -> impl Iterator<Item = &Ban> {
+            self.ip_bans
+                .iter()
+                .filter(move |(range, _)| range.contains(ip))
+                .filter_map(|(_, entry)| entry.current.action.ban())
+        }
+
         /// Attempt to perform the ban action `action` for the user with UUID
         /// `uuid` and username `username`, starting from itme `now`
         /// (the information about the banning party will
@@ -521,6 +1036,53 @@ mod v1 {
             username_when_performed: String,
             action: BanAction,
             overwrite: bool,
+        ) -> Option<Result<(), Error<Final>>> {
+            self.edit_entry(
+                data_dir,
+                now,
+                |banlist| &mut banlist.uuid_bans,
+                uuid,
+                username_when_performed,
+                action,
+                overwrite,
+            )
+        }
+
+        /// Same as [`Banlist::ban_action`], but bans or unbans every
+        /// connection from an address in `range`.  `username_when_performed`
+        /// should be the name of the player whose address was banned, if the
+        /// ban was derived from one.
+        #[must_use]
+        pub fn ip_ban_action(
+            &mut self,
+            data_dir: &std::path::Path,
+            now: DateTime<Utc>,
+            range: IpRange,
+            username_when_performed: String,
+            action: BanAction,
+            overwrite: bool,
+        ) -> Option<Result<(), Error<Final>>> {
+            self.edit_entry(
+                data_dir,
+                now,
+                |banlist| &mut banlist.ip_bans,
+                range,
+                username_when_performed,
+                action,
+                overwrite,
+            )
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        fn edit_entry<K: Eq + Hash>(
+            &mut self,
+            data_dir: &std::path::Path,
+            now: DateTime<Utc>,
+            entries: fn(&mut Banlist) -> &mut HashMap<K, BanEntry>,
+            key: K,
+            username_when_performed: String,
+            action: BanAction,
+            overwrite: bool,
         ) -> Option<Result<(), Error<Final>>> {
             assert!(
                 matches!(
@@ -539,7 +1101,7 @@ mod v1 {
             // Perform an atomic edit.
             Some(
                 self.edit(data_dir.as_ref(), |banlist| {
-                    match banlist.0.entry(uuid) {
+                    match entries(banlist).entry(key) {
                         hash_map::Entry::Vacant(v) => {
                             // If this is an unban, it will have no effect, so return early.
                             if matches!(ban_record.action, BanAction::Unban(_)) {
@@ -586,48 +1148,63 @@ mod v1 {
         }
     }
 
+    impl From<prev::BanInfo> for BanInfo {
+        fn from(value: prev::BanInfo) -> Self {
+            BanInfo {
+                performed_by: value.performed_by,
+                performed_by_username: value.performed_by_username,
+                performed_by_role: value.performed_by_role.into(),
+            }
+        }
+    }
+
+    impl From<prev::BanAction> for BanAction {
+        fn from(value: prev::BanAction) -> Self {
+            match value {
+                prev::BanAction::Unban(unban) => BanAction::Unban(unban.into()),
+                prev::BanAction::Ban(prev::Ban {
+                    reason,
+                    info,
+                    end_date,
+                }) => BanAction::Ban(Ban {
+                    reason,
+                    info: info.map(Into::into),
+                    end_date,
+                }),
+            }
+        }
+    }
+
+    impl From<prev::BanRecord> for BanRecord {
+        fn from(value: prev::BanRecord) -> Self {
+            BanRecord {
+                username_when_performed: value.username_when_performed,
+                action: value.action.into(),
+                date: value.date,
+            }
+        }
+    }
+
     impl Banlist {
         /// One-off migration from the previous version.  This must be
         /// guaranteed to produce a valid settings file as long as it is
         /// called with a valid settings file from the previous version.
         pub(super) fn migrate(prev: prev::Banlist) -> Self {
-            // The ban start date for migrations from legacy is the current one; we could
-            // record that they actually have an unknown start date, but this
-            // would just complicate the format.
-            let date = Utc::now();
-            Banlist(
-                prev.0
+            Banlist {
+                uuid_bans: prev
+                    .0
                     .into_iter()
-                    .map(
-                        |(
-                            uid,
-                            prev::BanRecord {
-                                username_when_banned,
-                                reason,
-                            },
-                        )| {
-                            (uid, BanEntry {
-                                current: BanRecord {
-                                    username_when_performed: username_when_banned,
-                                    // We only recorded unbans pre-migration.
-                                    action: BanAction::Ban(Ban {
-                                        reason,
-                                        // We don't know who banned this user pre-migration.
-                                        info: None,
-                                        // All bans pre-migration are of unlimited duration.
-                                        end_date: None,
-                                    }),
-                                    date,
-                                },
-                                // Old bans never expire, so set the expiration hint to false.
-                                expired: false,
-                                // There is no known ban history yet.
-                                history: Vec::new(),
-                            })
-                        },
-                    )
+                    .map(|(uuid, entry)| {
+                        (uuid, BanEntry {
+                            current: entry.current.into(),
+                            history: entry.history.into_iter().map(Into::into).collect(),
+                            expired: entry.expired,
+                        })
+                    })
                     .collect(),
-            )
+                // There were no ip bans before this version.
+                ip_bans: HashMap::new(),
+            }
         }
 
         /// Perform any needed validation on this banlist that can't be done
@@ -639,8 +1216,14 @@ mod v1 {
         pub(super) fn validate(&mut self) -> Result<Version, <Final as EditableSetting>::Error> {
             let mut version = Version::Latest;
             let now = Utc::now();
-            for (&uuid, value) in self.0.iter_mut() {
-                if matches!(value.validate(now, uuid)?, Version::Old) {
+            for (&uuid, value) in self.uuid_bans.iter_mut() {
+                if matches!(value.validate(now, BanTarget::Uuid(uuid))?, Version::Old) {
+                    // Update detected.
+                    version = Version::Old;
+                }
+            }
+            for (&range, value) in self.ip_bans.iter_mut() {
+                if matches!(value.validate(now, BanTarget::Ip(range))?, Version::Old) {
                     // Update detected.
                     version = Version::Old;
                 }
@@ -660,4 +1243,53 @@ mod v1 {
             Ok(next::Banlist::migrate(value).try_into().expect(MIGRATION_UPGRADE_GUARANTEE))
         }
     } */
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn ip_range_parse() {
+            let range: IpRange = "192.168.17.3/16".parse().unwrap();
+            assert_eq!(range.to_string(), "192.168.0.0/16");
+            assert_eq!(range, "192.168.0.0/16".parse().unwrap());
+            assert_eq!(
+                "10.0.0.1".parse::<IpRange>().unwrap().to_string(),
+                "10.0.0.1"
+            );
+            assert_eq!(
+                "::ffff:10.0.0.1".parse::<IpRange>().unwrap().to_string(),
+                "10.0.0.1"
+            );
+            assert_eq!(
+                "2001:db8::1/32".parse::<IpRange>().unwrap().to_string(),
+                "2001:db8::/32"
+            );
+            assert_eq!(
+                "0.0.0.0/0".parse::<IpRange>().unwrap().to_string(),
+                "0.0.0.0/0"
+            );
+            assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+            assert!("10.0.0/8".parse::<IpRange>().is_err());
+            assert!("10.0.0.0/".parse::<IpRange>().is_err());
+        }
+
+        #[test]
+        fn ip_range_contains() {
+            let range: IpRange = "10.1.0.0/16".parse().unwrap();
+            assert!(range.contains("10.1.200.3".parse().unwrap()));
+            assert!(range.contains("::ffff:10.1.0.1".parse().unwrap()));
+            assert!(!range.contains("10.2.0.1".parse().unwrap()));
+            assert!(!range.contains("::1".parse().unwrap()));
+            let single = IpRange::single("2001:db8::1".parse().unwrap());
+            assert!(single.contains("2001:db8::1".parse().unwrap()));
+            assert!(!single.contains("2001:db8::2".parse().unwrap()));
+            assert!(
+                "0.0.0.0/0"
+                    .parse::<IpRange>()
+                    .unwrap()
+                    .contains("1.2.3.4".parse().unwrap())
+            );
+        }
+    }
 }
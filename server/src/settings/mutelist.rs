@@ -0,0 +1,181 @@
+//! Versioned mutelist settings files.
+
+use super::MUTELIST_FILENAME as FILENAME;
+use crate::settings::editable::{EditableSetting, Version};
+use core::convert::{Infallible, TryFrom};
+use serde::{Deserialize, Serialize};
+
+/// NOTE: Always replace this with the latest mutelist version. Then update the
+/// MutelistRaw, the TryFrom<MutelistRaw> for Mutelist, the previously most
+/// recent module, and add a new module for the latest version!  Please respect
+/// the migration upgrade guarantee found in the parent module with any upgrade.
+pub use self::v1::*;
+
+/// Versioned settings files, one per version.  The mutelist was added after
+/// settings files got versioned, so there is no v0 or legacy format.
+#[derive(Deserialize, Serialize)]
+pub enum MutelistRaw {
+    V1(v1::Mutelist),
+}
+
+impl From<Mutelist> for MutelistRaw {
+    fn from(value: Mutelist) -> Self {
+        // Replace variant with that of current latest version.
+        Self::V1(value)
+    }
+}
+
+impl TryFrom<MutelistRaw> for (Version, Mutelist) {
+    type Error = <Mutelist as EditableSetting>::Error;
+
+    fn try_from(value: MutelistRaw) -> Result<Self, <Mutelist as EditableSetting>::Error> {
+        use MutelistRaw::*;
+        Ok(match value {
+            // Latest version (move to old section using the pattern of other old version when it
+            // is no longer latest).
+            V1(mut value) => (value.validate()?, value),
+        })
+    }
+}
+
+type Final = Mutelist;
+
+impl EditableSetting for Mutelist {
+    type Error = Infallible;
+    /// There are no legacy mutelist files, an unversioned file is read like the
+    /// latest version.
+    type Legacy = Mutelist;
+    type Setting = MutelistRaw;
+
+    const FILENAME: &'static str = FILENAME;
+}
+
+mod v1 {
+    use super::Final;
+    use crate::settings::editable::{EditableSetting, Version};
+    use authc::Uuid;
+    use chrono::{prelude::*, Utc};
+    use common::comp::AdminRole;
+    use core::ops::{Deref, DerefMut};
+    use hashbrown::HashMap;
+    use serde::{Deserialize, Serialize};
+    /* use super::v2 as next; */
+
+    /// Important: even if the role we are storing here appears to be identical
+    /// to one used in another versioned store (like admin::Role), we *must*
+    /// have our own versioned copy!  This ensures that if there's an update
+    /// to the role somewhere else, the conversion function between them
+    /// will break, letting people make an intelligent decision.
+    ///
+    /// In particular, *never remove variants from this enum* (or any other enum
+    /// in a versioned settings file) without bumping the version and
+    /// writing a migration that understands how to properly deal with
+    /// existing instances of the old variant (you can delete From instances
+    /// for the old variants at this point).  Otherwise, we will lose
+    /// compatibility with old settings files, since we won't be able to
+    /// deserialize them!
+    #[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+    pub enum Role {
+        Moderator = 0,
+        Admin = 1,
+    }
+
+    impl From<AdminRole> for Role {
+        fn from(value: AdminRole) -> Self {
+            match value {
+                AdminRole::Moderator => Self::Moderator,
+                AdminRole::Admin => Self::Admin,
+            }
+        }
+    }
+
+    impl From<Role> for AdminRole {
+        fn from(value: Role) -> Self {
+            match value {
+                Role::Moderator => Self::Moderator,
+                Role::Admin => Self::Admin,
+            }
+        }
+    }
+
+    #[derive(Clone, Deserialize, Serialize)]
+    pub struct MuteInfo {
+        pub username_when_muted: String,
+        pub muted_by: Uuid,
+        /// NOTE: May not be up to date, if we allow username changes.
+        pub muted_by_username: String,
+        /// NOTE: Role of the muting user at the time of the mute.
+        pub muted_by_role: Role,
+    }
+
+    #[derive(Clone, Deserialize, Serialize)]
+    pub struct MuteRecord {
+        pub reason: String,
+        /// Date when the user was muted.
+        pub date: DateTime<Utc>,
+        /// NOTE: Should always be higher than date, if present!  The mute is
+        /// permanent if this is None.
+        pub end_date: Option<DateTime<Utc>>,
+        pub info: MuteInfo,
+    }
+
+    impl MuteRecord {
+        /// Returns true if the mute is expired, false otherwise.
+        pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+            self.end_date.map_or(false, |end_date| end_date <= now)
+        }
+    }
+
+    #[derive(Clone, Deserialize, Serialize, Default)]
+    #[serde(transparent)]
+    pub struct Mutelist(pub(super) HashMap<Uuid, MuteRecord>);
+
+    impl Deref for Mutelist {
+        type Target = HashMap<Uuid, MuteRecord>;
+
+        fn deref(&self) -> &Self::Target { &self.0 }
+    }
+
+    impl DerefMut for Mutelist {
+        fn deref_mut(&mut self) -> &mut Self::Target { &mut self.0 }
+    }
+
+    impl Mutelist {
+        /// Returns the mute of the user with UUID `uuid`, if they are muted at
+        /// time `now`.
+        pub fn active_mute(&self, uuid: &Uuid, now: DateTime<Utc>) -> Option<&MuteRecord> {
+            self.0.get(uuid).filter(|mute| !mute.is_expired(now))
+        }
+
+        /// Perform any needed validation on this mutelist that can't be done
+        /// using parsing.
+        ///
+        /// Expired mutes have no effect anymore, so they are removed.
+        ///
+        /// The returned version being "Old" indicates the loaded setting has
+        /// been modified during validation (this is why validate takes
+        /// `&mut self`).
+        pub(super) fn validate(&mut self) -> Result<Version, <Final as EditableSetting>::Error> {
+            let now = Utc::now();
+            let len = self.0.len();
+            self.0.retain(|_, mute| !mute.is_expired(now));
+            Ok(if self.0.len() == len {
+                Version::Latest
+            } else {
+                Version::Old
+            })
+        }
+    }
+
+    // NOTE: Whenever there is a version upgrade, copy this note as well as the
+    // commented-out code below to the next version, then uncomment the code
+    // for this version.
+    /* impl TryFrom<Mutelist> for Final {
+        type Error = <Final as EditableSetting>::Error;
+
+        fn try_from(mut value: Mutelist) -> Result<Final, Self::Error> {
+            value.validate()?;
+            Ok(next::Mutelist::migrate(value).try_into().expect(MIGRATION_UPGRADE_GUARANTEE))
+        }
+    } */
+}
@@ -1,12 +1,14 @@
 use crate::{
+    chat_filter::ChatFilter,
     client::Client,
     persistence::PersistedComponents,
     pet::restore_pet,
     presence::{Presence, RepositionOnChunkLoad},
-    settings::Settings,
+    settings::{EditableSettings, Settings},
     sys::sentinel::DeletedEntities,
     wiring, BattleModeBuffer, SpawnPoint,
 };
+use chrono::Utc;
 use common::{
     calendar::Calendar,
     character::CharacterId,
@@ -32,7 +34,7 @@ use specs::{
     saveload::MarkerAllocator, Builder, Entity as EcsEntity, EntityBuilder as EcsEntityBuilder,
     Join, WorldExt,
 };
-use std::time::Duration;
+use std::{borrow::Cow, time::Duration};
 use tracing::{trace, warn};
 use vek::*;
 
@@ -605,8 +607,53 @@ impl StateExt for State {
 
     /// Send the chat message to the proper players. Say and region are limited
     /// by location. Faction and group are limited by component.
-    fn send_chat(&self, msg: comp::UnresolvedChatMsg) {
+    fn send_chat(&self, mut msg: comp::UnresolvedChatMsg) {
         let ecs = self.ecs();
+
+        // Moderate messages sent by players
+        if let Some(sender) = msg.uid().and_then(|uid| ecs.entity_from_uid(uid.0)) {
+            if let Some(player) = ecs.read_storage::<comp::Player>().get(sender) {
+                let notify_sender = |text: String| {
+                    if let Some(client) = ecs.read_storage::<Client>().get(sender) {
+                        client.send_fallible(ServerGeneral::server_msg(
+                            comp::ChatType::CommandError,
+                            text,
+                        ));
+                    }
+                };
+
+                if let Some(mute) = ecs
+                    .read_resource::<EditableSettings>()
+                    .mutelist
+                    .active_mute(&player.uuid(), Utc::now())
+                {
+                    let duration = mute.end_date.map_or_else(
+                        || "permanently".to_string(),
+                        |end_date| format!("until {}", end_date.format("%Y-%m-%d %H:%M UTC")),
+                    );
+                    notify_sender(format!(
+                        "You are muted {}. Reason: {}",
+                        duration, mute.reason
+                    ));
+                    return;
+                }
+
+                let filtered = ecs
+                    .read_resource::<ChatFilter>()
+                    .filter(&msg.message)
+                    .map(Cow::into_owned);
+                match filtered {
+                    Some(message) => msg.message = message,
+                    None => {
+                        notify_sender(
+                            "Your message contains a banned word and was not sent".to_string(),
+                        );
+                        return;
+                    },
+                }
+            }
+        }
+
         let is_within =
             |target, a: &comp::Pos, b: &comp::Pos| a.0.distance_squared(b.0) < target * target;
 
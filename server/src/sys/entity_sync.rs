@@ -2,6 +2,7 @@ use super::sentinel::{DeletedEntities, ReadTrackers, TrackedComps};
 use crate::{
     client::Client,
     presence::{Presence, RegionSubscription},
+    settings::Settings,
     Tick,
 };
 use common::{
@@ -49,6 +50,7 @@ impl<'a> System<'a> for Sys {
         Write<'a, Vec<Outcome>>,
         Read<'a, PlayerPhysicsSettings>,
         ReadStorage<'a, Player>,
+        Read<'a, Settings>,
         TrackedComps<'a>,
         ReadTrackers<'a>,
     );
@@ -83,6 +85,7 @@ impl<'a> System<'a> for Sys {
             mut outcomes,
             player_physics_settings,
             players,
+            settings,
             tracked_comps,
             trackers,
         ): Self::SystemData,
@@ -207,6 +210,10 @@ impl<'a> System<'a> for Sys {
 
             for (client, _, client_entity, client_pos) in &mut subscribers {
                 let mut comp_sync_package = CompSyncPackage::new();
+                // With unreliable physics sync, regular updates go to the physics stream, which
+                // might drop them. Inserts and forced updates have to arrive.
+                let mut physics_sync_package = CompSyncPackage::new();
+                let mut forced_sync_package = CompSyncPackage::new();
 
                 for (_, entity, &uid, (&pos, last_pos), vel, ori, force_update, collider) in (
                     region.entities(),
@@ -257,17 +264,19 @@ impl<'a> System<'a> for Sys {
                         }
                     };
 
+                    let physics_sync_len = physics_sync_package.comp_updates.len();
+
                     if last_pos.is_none() {
                         comp_sync_package.comp_inserted(uid, pos);
                     } else if send_now {
-                        comp_sync_package.comp_modified(uid, pos);
+                        physics_sync_package.comp_modified(uid, pos);
                     }
 
                     if let Some((v, last_vel)) = vel {
                         if last_vel.is_none() {
                             comp_sync_package.comp_inserted(uid, *v);
                         } else if send_now {
-                            comp_sync_package.comp_modified(uid, *v);
+                            physics_sync_package.comp_modified(uid, *v);
                         }
                     }
 
@@ -275,12 +284,34 @@ impl<'a> System<'a> for Sys {
                         if last_ori.is_none() {
                             comp_sync_package.comp_inserted(uid, *o);
                         } else if send_now {
-                            comp_sync_package.comp_modified(uid, *o);
+                            physics_sync_package.comp_modified(uid, *o);
                         }
                     }
+
+                    if !settings.unreliable_physics_sync {
+                        comp_sync_package
+                            .comp_updates
+                            .extend(physics_sync_package.comp_updates.drain(physics_sync_len..));
+                    } else if force_update.is_some() {
+                        forced_sync_package
+                            .comp_updates
+                            .extend(physics_sync_package.comp_updates.drain(physics_sync_len..));
+                    }
                 }
 
                 client.send_fallible(ServerGeneral::CompSync(comp_sync_package));
+                if !forced_sync_package.comp_updates.is_empty() {
+                    client.send_fallible(ServerGeneral::ForcedPhysicsSync {
+                        tick,
+                        package: forced_sync_package,
+                    });
+                }
+                if !physics_sync_package.comp_updates.is_empty() {
+                    client.send_fallible(ServerGeneral::PhysicsSync {
+                        tick,
+                        package: physics_sync_package,
+                    });
+                }
             }
 
             // Update the last physics components for each entity
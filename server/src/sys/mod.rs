@@ -6,6 +6,7 @@ pub mod msg;
 pub mod object;
 pub mod persistence;
 pub mod pets;
+#[cfg(feature = "plugins")] pub mod plugin_timer;
 pub mod sentinel;
 pub mod subscription;
 pub mod terrain;
@@ -30,6 +31,8 @@ pub fn add_server_systems(dispatch_builder: &mut DispatcherBuilder) {
     dispatch::<terrain::Sys>(dispatch_builder, &[&msg::terrain::Sys::sys_name()]);
     dispatch::<waypoint::Sys>(dispatch_builder, &[]);
     dispatch::<invite_timeout::Sys>(dispatch_builder, &[]);
+    #[cfg(feature = "plugins")]
+    dispatch::<plugin_timer::Sys>(dispatch_builder, &[]);
     dispatch::<persistence::Sys>(dispatch_builder, &[]);
     dispatch::<object::Sys>(dispatch_builder, &[]);
     dispatch::<wiring::Sys>(dispatch_builder, &[]);
@@ -17,12 +17,47 @@ use common_state::{BlockChange, BuildAreas};
 use specs::{Entities, Join, Read, ReadExpect, ReadStorage, Write, WriteStorage};
 use tracing::{debug, trace, warn};
 use vek::*;
+#[cfg(feature = "plugins")]
+use {
+    common::{
+        comp::{Group, Inventory, Stats},
+        terrain::Block,
+        uid::{Uid, UidAllocator},
+        CachedSpatialGrid,
+    },
+    common_state::plugin::{memory_manager::EcsWorld, PluginMgr},
+    plugin_api::event::{BlockBreakEvent, BlockPlaceEvent, Player as PluginPlayer},
+    specs::{shred::ResourceId, SystemData, World},
+    tracing::error,
+};
 
 #[cfg(feature = "persistent_world")]
 pub type TerrainPersistenceData<'a> = Option<Write<'a, TerrainPersistence>>;
 #[cfg(not(feature = "persistent_world"))]
 pub type TerrainPersistenceData<'a> = ();
 
+#[cfg(feature = "plugins")]
+#[derive(SystemData)]
+pub struct PluginData<'a> {
+    plugin_mgr: Read<'a, PluginMgr>,
+    uids: ReadStorage<'a, Uid>,
+    uid_allocator: Read<'a, UidAllocator>,
+    inventories: ReadStorage<'a, Inventory>,
+    stats: ReadStorage<'a, Stats>,
+    groups: ReadStorage<'a, Group>,
+    spatial_grid: Read<'a, CachedSpatialGrid>,
+}
+#[cfg(not(feature = "plugins"))]
+pub type PluginData<'a> = ();
+
+/// A block changed by a player in build mode, reported to plugins once all the
+/// messages have been handled
+#[cfg(feature = "plugins")]
+pub enum BuildAction {
+    Break(specs::Entity, Vec3<i32>, Block),
+    Place(specs::Entity, Vec3<i32>, Block),
+}
+
 impl Sys {
     #[allow(clippy::too_many_arguments)]
     fn handle_client_in_game_msg(
@@ -46,6 +81,7 @@ impl Sys {
         _terrain_persistence: &mut TerrainPersistenceData<'_>,
         maybe_player: &Option<&Player>,
         maybe_admin: &Option<&Admin>,
+        #[cfg(feature = "plugins")] build_actions: &mut Vec<BuildAction>,
         msg: ClientGeneral,
     ) -> Result<(), crate::error::Error> {
         let presence = match maybe_presence {
@@ -215,6 +251,10 @@ impl Sys {
                                         terrain_persistence.set_block(pos, new_block);
                                     }
                                 }
+                                #[cfg(feature = "plugins")]
+                                if _was_set {
+                                    build_actions.push(BuildAction::Break(entity, pos, *old_block));
+                                }
                             }
                         }
                     }
@@ -240,6 +280,10 @@ impl Sys {
                                         terrain_persistence.set_block(pos, new_block);
                                     }
                                 }
+                                #[cfg(feature = "plugins")]
+                                if _was_set {
+                                    build_actions.push(BuildAction::Place(entity, pos, new_block));
+                                }
                             }
                         }
                     }
@@ -322,6 +366,7 @@ impl<'a> System<'a> for Sys {
         TerrainPersistenceData<'a>,
         ReadStorage<'a, Player>,
         ReadStorage<'a, Admin>,
+        PluginData<'a>,
     );
 
     const NAME: &'static str = "msg::in_game";
@@ -351,9 +396,14 @@ impl<'a> System<'a> for Sys {
             mut terrain_persistence,
             players,
             admins,
+            plugin_data,
         ): Self::SystemData,
     ) {
         let mut server_emitter = server_event_bus.emitter();
+        #[cfg(feature = "plugins")]
+        let mut build_actions = Vec::new();
+        #[cfg(not(feature = "plugins"))]
+        let _ = plugin_data;
 
         for (entity, client, mut maybe_presence, player, maybe_admin) in (
             &entities,
@@ -386,9 +436,62 @@ impl<'a> System<'a> for Sys {
                     &mut terrain_persistence,
                     &player,
                     &maybe_admin,
+                    #[cfg(feature = "plugins")]
+                    &mut build_actions,
                     msg,
                 )
             });
         }
+
+        #[cfg(feature = "plugins")]
+        if !build_actions.is_empty() {
+            let ecs_world = EcsWorld {
+                entities: &entities,
+                health: (&healths).into(),
+                uid: (&plugin_data.uids).into(),
+                player: (&players).into(),
+                pos: (&positions).into(),
+                vel: (&velocities).into(),
+                inventory: (&plugin_data.inventories).into(),
+                stats: (&plugin_data.stats).into(),
+                skill_set: (&skill_sets).into(),
+                group: (&plugin_data.groups).into(),
+                uid_allocator: &plugin_data.uid_allocator,
+                spatial_grid: &plugin_data.spatial_grid,
+                terrain: &terrain,
+            };
+            for action in build_actions {
+                let result = match action {
+                    BuildAction::Break(entity, pos, block) => {
+                        plugin_data.uids.get(entity).map(|uid| {
+                            plugin_data
+                                .plugin_mgr
+                                .execute_event(&ecs_world, &BlockBreakEvent {
+                                    player: PluginPlayer { id: *uid },
+                                    pos: pos.into_array(),
+                                    block: block.into(),
+                                })
+                        })
+                    },
+                    BuildAction::Place(entity, pos, block) => {
+                        plugin_data.uids.get(entity).map(|uid| {
+                            plugin_data
+                                .plugin_mgr
+                                .execute_event(&ecs_world, &BlockPlaceEvent {
+                                    player: PluginPlayer { id: *uid },
+                                    pos: pos.into_array(),
+                                    block: block.into(),
+                                })
+                        })
+                    },
+                };
+                if let Some(Err(e)) = result {
+                    error!(
+                        ?e,
+                        "Error occured while executing a build mode plugin event"
+                    );
+                }
+            }
+        }
     }
 }
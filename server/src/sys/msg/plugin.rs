@@ -0,0 +1,71 @@
+use crate::client::Client;
+use common::event::{EventBus, ServerEvent};
+use common_ecs::{Job, Origin, Phase, System};
+use common_net::msg::PluginMsg;
+use specs::{Entities, Join, Read, ReadStorage};
+use tracing::{debug, trace};
+
+#[cfg(feature = "plugins")]
+use common_state::plugin::PluginMgr;
+
+#[cfg(feature = "plugins")]
+type ReadPlugin<'a> = Read<'a, PluginMgr>;
+#[cfg(not(feature = "plugins"))]
+type ReadPlugin<'a> = Option<Read<'a, ()>>;
+
+impl Sys {
+    fn handle_plugin_msg(
+        client: &Client,
+        msg: PluginMsg,
+        _plugin_mgr: &ReadPlugin,
+    ) -> Result<(), crate::error::Error> {
+        match msg {
+            PluginMsg::Request(hashes) => {
+                for hash in hashes {
+                    #[cfg(feature = "plugins")]
+                    let tarball = _plugin_mgr.client_tarball(&hash).map(<[u8]>::to_vec);
+                    #[cfg(not(feature = "plugins"))]
+                    let tarball = None;
+                    client.send(PluginMsg::Tarball(hash, tarball))?;
+                }
+            },
+            PluginMsg::Tarball(..) => trace!("Ignoring plugin tarball sent by a client"),
+        }
+        Ok(())
+    }
+}
+
+/// This system answers the requests of clients downloading client-side plugins
+#[derive(Default)]
+pub struct Sys;
+impl<'a> System<'a> for Sys {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, EventBus<ServerEvent>>,
+        ReadStorage<'a, Client>,
+        ReadPlugin<'a>,
+    );
+
+    const NAME: &'static str = "msg::plugin";
+    const ORIGIN: Origin = Origin::Server;
+    const PHASE: Phase = Phase::Create;
+
+    fn run(
+        _job: &mut Job<Self>,
+        (entities, server_event_bus, clients, plugin_mgr): Self::SystemData,
+    ) {
+        let mut server_emitter = server_event_bus.emitter();
+
+        for (entity, client) in (&entities, &clients).join() {
+            if let Err(e) = super::try_recv_all(client, 6, |client, msg| {
+                Self::handle_plugin_msg(client, msg, &plugin_mgr)
+            }) {
+                debug!(?entity, ?e, "network error with client, disconnecting");
+                server_emitter.emit(ServerEvent::ClientDisconnect(
+                    entity,
+                    common::comp::DisconnectReason::NetworkError,
+                ));
+            }
+        }
+    }
+}
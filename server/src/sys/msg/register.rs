@@ -23,13 +23,36 @@ use specs::{
 use tracing::trace;
 
 #[cfg(feature = "plugins")]
-use {common_state::plugin::memory_manager::EcsWorld, common_state::plugin::PluginMgr};
+use {
+    common::{
+        comp::{Group, Inventory, Pos, SkillSet, Vel},
+        terrain::TerrainGrid,
+        CachedSpatialGrid,
+    },
+    common_state::plugin::memory_manager::EcsWorld,
+    common_state::plugin::PluginMgr,
+};
 
 #[cfg(feature = "plugins")]
 type ReadPlugin<'a> = Read<'a, PluginMgr>;
 #[cfg(not(feature = "plugins"))]
 type ReadPlugin<'a> = Option<Read<'a, ()>>;
 
+/// The components plugins can read while handling `on_join`
+#[cfg(feature = "plugins")]
+#[derive(SystemData)]
+pub struct PluginWorldData<'a> {
+    positions: ReadStorage<'a, Pos>,
+    velocities: ReadStorage<'a, Vel>,
+    inventories: ReadStorage<'a, Inventory>,
+    skill_sets: ReadStorage<'a, SkillSet>,
+    groups: ReadStorage<'a, Group>,
+    spatial_grid: Read<'a, CachedSpatialGrid>,
+    terrain: ReadExpect<'a, TerrainGrid>,
+}
+#[cfg(not(feature = "plugins"))]
+pub type PluginWorldData<'a> = ();
+
 #[derive(SystemData)]
 pub struct ReadData<'a> {
     entities: Entities<'a>,
@@ -43,6 +66,7 @@ pub struct ReadData<'a> {
     _healths: ReadStorage<'a, Health>, // used by plugin feature
     _plugin_mgr: ReadPlugin<'a>,       // used by plugin feature
     _uid_allocator: Read<'a, UidAllocator>, // used by plugin feature
+    _plugin_world: PluginWorldData<'a>, // used by plugin feature
 }
 
 /// This system will handle new messages from clients
@@ -116,7 +140,15 @@ impl<'a> System<'a> for Sys {
                     health: (&read_data._healths).into(),
                     uid: (&read_data.uids).into(),
                     player: (&players).into(),
+                    pos: (&read_data._plugin_world.positions).into(),
+                    vel: (&read_data._plugin_world.velocities).into(),
+                    inventory: (&read_data._plugin_world.inventories).into(),
+                    stats: (&read_data.stats).into(),
+                    skill_set: (&read_data._plugin_world.skill_sets).into(),
+                    group: (&read_data._plugin_world.groups).into(),
                     uid_allocator: &read_data._uid_allocator,
+                    spatial_grid: &read_data._plugin_world.spatial_grid,
+                    terrain: &read_data._plugin_world.terrain,
                 };
 
                 let (username, uuid) = match login_provider.login(
@@ -127,7 +159,12 @@ impl<'a> System<'a> for Sys {
                     &read_data._plugin_mgr,
                     &*read_data.editable_settings.admins,
                     &*read_data.editable_settings.whitelist,
-                    &*read_data.editable_settings.banlist,
+                    &read_data.editable_settings.banlist,
+                    client
+                        .participant
+                        .as_ref()
+                        .and_then(|participant| participant.peer_addr())
+                        .map(|addr| addr.ip()),
                 ) {
                     None => return Ok(()),
                     Some(r) => {
@@ -207,6 +244,12 @@ impl<'a> System<'a> for Sys {
                         player_list.clone(),
                     )))?;
 
+                    // Send the chat commands provided by plugins
+                    #[cfg(feature = "plugins")]
+                    client.send(ServerGeneral::ChatCommands(
+                        read_data._plugin_mgr.commands(),
+                    ))?;
+
                     // Add to list to notify all clients of the new player
                     new_players.push(entity);
                 }
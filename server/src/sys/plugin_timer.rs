@@ -0,0 +1,61 @@
+use common::{
+    comp::{Group, Health, Inventory, Player, Pos, SkillSet, Stats, Vel},
+    resources::Time,
+    terrain::TerrainGrid,
+    uid::{Uid, UidAllocator},
+    CachedSpatialGrid,
+};
+use common_ecs::{Job, Origin, Phase, System};
+use common_state::plugin::{memory_manager::EcsWorld, PluginMgr};
+use specs::{shred::ResourceId, Entities, Read, ReadExpect, ReadStorage, SystemData, World};
+
+#[derive(SystemData)]
+pub struct ReadData<'a> {
+    entities: Entities<'a>,
+    time: Read<'a, Time>,
+    plugin_mgr: Read<'a, PluginMgr>,
+    uid_allocator: Read<'a, UidAllocator>,
+    spatial_grid: Read<'a, CachedSpatialGrid>,
+    terrain: ReadExpect<'a, TerrainGrid>,
+    healths: ReadStorage<'a, Health>,
+    uids: ReadStorage<'a, Uid>,
+    players: ReadStorage<'a, Player>,
+    positions: ReadStorage<'a, Pos>,
+    velocities: ReadStorage<'a, Vel>,
+    inventories: ReadStorage<'a, Inventory>,
+    stats: ReadStorage<'a, Stats>,
+    skill_sets: ReadStorage<'a, SkillSet>,
+    groups: ReadStorage<'a, Group>,
+}
+
+/// This system fires the timers scheduled by plugins once they are due
+#[derive(Default)]
+pub struct Sys;
+impl<'a> System<'a> for Sys {
+    type SystemData = ReadData<'a>;
+
+    const NAME: &'static str = "plugin_timer";
+    const ORIGIN: Origin = Origin::Server;
+    const PHASE: Phase = Phase::Create;
+
+    fn run(_job: &mut Job<Self>, read_data: Self::SystemData) {
+        let ecs_world = EcsWorld {
+            entities: &read_data.entities,
+            health: (&read_data.healths).into(),
+            uid: (&read_data.uids).into(),
+            player: (&read_data.players).into(),
+            pos: (&read_data.positions).into(),
+            vel: (&read_data.velocities).into(),
+            inventory: (&read_data.inventories).into(),
+            stats: (&read_data.stats).into(),
+            skill_set: (&read_data.skill_sets).into(),
+            group: (&read_data.groups).into(),
+            uid_allocator: &read_data.uid_allocator,
+            spatial_grid: &read_data.spatial_grid,
+            terrain: &read_data.terrain,
+        };
+        read_data
+            .plugin_mgr
+            .fire_timers(&ecs_world, read_data.time.0);
+    }
+}
@@ -1,7 +1,7 @@
 use client::{
     addr::ConnectionArgs,
     error::{Error as ClientError, NetworkConnectError, NetworkError},
-    Client, ServerInfo,
+    Client, PublicIdentity, ServerInfo,
 };
 use crossbeam_channel::{unbounded, Receiver, Sender, TryRecvError};
 use std::{
@@ -28,6 +28,9 @@ pub enum Error {
 #[allow(clippy::large_enum_variant)] // TODO: Pending review in #587
 pub enum Msg {
     IsAuthTrusted(String),
+    /// The server wants the client to download and run these plugins
+    #[cfg(feature = "plugins")]
+    ArePluginsAccepted(Vec<String>),
     Done(Result<Client, Error>),
 }
 
@@ -39,17 +42,26 @@ pub struct AuthTrust(String, bool);
 pub struct ClientInit {
     rx: Receiver<Msg>,
     trust_tx: Sender<AuthTrust>,
+    #[cfg(feature = "plugins")]
+    plugin_consent_tx: Sender<bool>,
     cancel: Arc<AtomicBool>,
 }
 impl ClientInit {
     pub fn new(
         connection_args: ConnectionArgs,
+        pinned_identity: Option<PublicIdentity>,
         username: String,
         password: String,
         runtime: Arc<runtime::Runtime>,
     ) -> Self {
         let (tx, rx) = unbounded();
         let (trust_tx, trust_rx) = unbounded();
+        #[cfg(feature = "plugins")]
+        let (plugin_consent_tx, plugin_consent_rx) = unbounded();
+        #[cfg(feature = "plugins")]
+        let plugin_cache_dir = common_base::userdata_dir_workspace!()
+            .join("voxygen")
+            .join("plugin_cache");
         let cancel = Arc::new(AtomicBool::new(false));
         let cancel2 = Arc::clone(&cancel);
 
@@ -72,8 +84,9 @@ impl ClientInit {
                     break;
                 }
                 let mut mismatched_server_info = None;
-                match Client::new(
+                match Client::new_pinned(
                     connection_args.clone(),
+                    pinned_identity,
                     Arc::clone(&runtime2),
                     &mut mismatched_server_info,
                 )
@@ -87,6 +100,23 @@ impl ClientInit {
                             });
                             break 'tries;
                         }
+                        #[cfg(feature = "plugins")]
+                        {
+                            let consent_fn = |plugins: &[String]| {
+                                let _ = tx.send(Msg::ArePluginsAccepted(plugins.to_vec()));
+                                plugin_consent_rx.recv().unwrap_or(false)
+                            };
+                            if let Err(e) = client
+                                .load_server_plugins(&plugin_cache_dir, consent_fn)
+                                .await
+                            {
+                                last_err = Some(Error::ClientError {
+                                    error: e,
+                                    mismatched_server_info: None,
+                                });
+                                break 'tries;
+                            }
+                        }
                         let _ = tx.send(Msg::Done(Ok(client)));
                         tokio::task::block_in_place(move || drop(runtime2));
                         return;
@@ -120,6 +150,8 @@ impl ClientInit {
         ClientInit {
             rx,
             trust_tx,
+            #[cfg(feature = "plugins")]
+            plugin_consent_tx,
             cancel,
         }
     }
@@ -140,6 +172,10 @@ impl ClientInit {
         let _ = self.trust_tx.send(AuthTrust(auth_server, trusted));
     }
 
+    /// Report whether the user accepts to run the plugins of the server
+    #[cfg(feature = "plugins")]
+    pub fn plugin_consent(&self, accepted: bool) { let _ = self.plugin_consent_tx.send(accepted); }
+
     pub fn cancel(&mut self) { self.cancel.store(true, Ordering::Relaxed); }
 }
 
@@ -14,7 +14,7 @@ use crate::{
 use client::{
     addr::ConnectionArgs,
     error::{InitProtocolError, NetworkConnectError, NetworkError},
-    Client, ServerInfo,
+    Client, PublicIdentity, ServerInfo,
 };
 use client_init::{ClientInit, Error as InitError, Msg as InitMsg};
 use common::comp;
@@ -103,6 +103,7 @@ impl PlayState for MainMenuState {
                             "singleplayer".to_owned(),
                             "".to_owned(),
                             ConnectionArgs::Mpsc(14004),
+                            None,
                             &mut self.init,
                             &global_state.tokio_runtime,
                             &global_state.i18n,
@@ -166,6 +167,21 @@ impl PlayState for MainMenuState {
         // Poll client creation.
         match self.init.client().and_then(|init| init.poll()) {
             Some(InitMsg::Done(Ok(mut client))) => {
+                // Trust the server on first use, see `attempt_login`
+                if let Some(identity) = client.server_identity() {
+                    let net_settings = &mut global_state.settings.networking;
+                    if !net_settings
+                        .server_identities
+                        .contains_key(&net_settings.default_server)
+                    {
+                        net_settings
+                            .server_identities
+                            .insert(net_settings.default_server.clone(), identity.to_string());
+                        global_state
+                            .settings
+                            .save_to_file_warn(&global_state.config_dir);
+                    }
+                }
                 // Register voxygen components / resources
                 crate::ecs::init(client.state_mut().ecs_mut());
                 self.init = InitState::Pipeline(Box::new(client));
@@ -198,6 +214,10 @@ impl PlayState for MainMenuState {
                     self.main_menu_ui.auth_trust_prompt(auth_server);
                 }
             },
+            #[cfg(feature = "plugins")]
+            Some(InitMsg::ArePluginsAccepted(plugins)) => {
+                self.main_menu_ui.plugin_prompt(plugins);
+            },
             None => {},
         }
 
@@ -278,6 +298,12 @@ impl PlayState for MainMenuState {
                     if !net_settings.servers.contains(&server_address) {
                         net_settings.servers.push(server_address.clone());
                     }
+                    // QUIC servers are verified by their certificate
+                    let pinned_identity = net_settings
+                        .server_identities
+                        .get(&server_address)
+                        .filter(|_| !use_quic)
+                        .and_then(|identity| PublicIdentity::from_hex(identity));
                     global_state
                         .settings
                         .save_to_file_warn(&global_state.config_dir);
@@ -298,6 +324,7 @@ impl PlayState for MainMenuState {
                         username,
                         password,
                         connection_args,
+                        pinned_identity,
                         &mut self.init,
                         &global_state.tokio_runtime,
                         &global_state.i18n,
@@ -353,6 +380,12 @@ impl PlayState for MainMenuState {
                         .client()
                         .map(|init| init.auth_trust(auth_server, trust));
                 },
+                #[cfg(feature = "plugins")]
+                MainMenuEvent::PluginConsent(accepted) => {
+                    if let Some(init) = self.init.client() {
+                        init.plugin_consent(accepted);
+                    }
+                },
             }
         }
 
@@ -444,6 +477,9 @@ fn get_client_msg_error(
                     .to_owned(),
                 mismatched_server_info,
             ),
+            Error::NetworkErr(NetworkError::ConnectFailed(NetworkConnectError::Handshake(
+                InitProtocolError::WrongIdentity(_),
+            ))) => localization.get("main.login.wrong_server_identity").into(),
             Error::NetworkErr(e) => net_error(e.to_string(), mismatched_server_info),
             Error::ParticipantErr(e) => net_error(e.to_string(), mismatched_server_info),
             Error::StreamErr(e) => net_error(e.to_string(), mismatched_server_info),
@@ -488,6 +524,7 @@ fn attempt_login(
     username: String,
     password: String,
     connection_args: ConnectionArgs,
+    pinned_identity: Option<PublicIdentity>,
     init: &mut InitState,
     runtime: &Arc<runtime::Runtime>,
     localized_strings: &LocalizationHandle,
@@ -518,6 +555,7 @@ fn attempt_login(
     if let InitState::None = init {
         *init = InitState::Client(ClientInit::new(
             connection_args,
+            pinned_identity,
             username,
             password,
             Arc::clone(runtime),
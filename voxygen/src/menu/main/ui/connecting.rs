@@ -194,65 +194,82 @@ impl Screen {
                 ]
             },
             ConnectionState::AuthTrustPrompt { msg, .. } => {
-                let text = Text::new(msg).size(fonts.cyri.scale(25));
+                self.prompt(fonts, msg, i18n.get("common.add"), i18n, button_style)
+            },
+            #[cfg(feature = "plugins")]
+            ConnectionState::PluginPrompt { msg } => {
+                self.prompt(fonts, msg, i18n.get("common.accept"), i18n, button_style)
+            },
+        };
 
-                let cancel = neat_button(
-                    &mut self.cancel_button,
-                    i18n.get("common.cancel"),
-                    0.7,
-                    button_style,
-                    Some(Message::TrustPromptCancel),
-                );
-                let add = neat_button(
-                    &mut self.add_button,
-                    i18n.get("common.add"),
-                    0.7,
-                    button_style,
-                    Some(Message::TrustPromptAdd),
-                );
+        Column::with_children(children)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into()
+    }
 
-                let content = Column::with_children(vec![
-                    text.into(),
-                    Container::new(
-                        Row::with_children(vec![cancel, add])
-                            .spacing(20)
-                            .height(Length::Units(25)),
-                    )
-                    .align_x(Align::End)
-                    .width(Length::Fill)
-                    .into(),
-                ])
-                .spacing(4)
-                .max_width(520)
-                .width(Length::Fill)
-                .height(Length::Fill);
+    /// A message with a cancel and an accept button, answered with
+    /// `TrustPromptCancel` and `TrustPromptAdd`
+    fn prompt(
+        &mut self,
+        fonts: &Fonts,
+        msg: &str,
+        accept_label: &str,
+        i18n: &Localization,
+        button_style: style::button::Style,
+    ) -> Vec<Element<Message>> {
+        let text = Text::new(msg).size(fonts.cyri.scale(25));
 
-                let prompt_window = Container::new(content)
-                    .style(
-                        style::container::Style::color_with_double_cornerless_border(
-                            (22, 18, 16, 255).into(),
-                            (11, 11, 11, 255).into(),
-                            (54, 46, 38, 255).into(),
-                        ),
-                    )
-                    .padding(20);
+        let cancel = neat_button(
+            &mut self.cancel_button,
+            i18n.get("common.cancel"),
+            0.7,
+            button_style,
+            Some(Message::TrustPromptCancel),
+        );
+        let add = neat_button(
+            &mut self.add_button,
+            accept_label,
+            0.7,
+            button_style,
+            Some(Message::TrustPromptAdd),
+        );
 
-                let container = Container::new(prompt_window)
-                    .width(Length::Fill)
-                    .height(Length::Fill)
-                    .center_x()
-                    .center_y();
+        let content = Column::with_children(vec![
+            text.into(),
+            Container::new(
+                Row::with_children(vec![cancel, add])
+                    .spacing(20)
+                    .height(Length::Units(25)),
+            )
+            .align_x(Align::End)
+            .width(Length::Fill)
+            .into(),
+        ])
+        .spacing(4)
+        .max_width(520)
+        .width(Length::Fill)
+        .height(Length::Fill);
 
-                vec![
-                    container.into(),
-                    Space::new(Length::Fill, Length::Units(fonts.cyri.scale(15))).into(),
-                ]
-            },
-        };
+        let prompt_window = Container::new(content)
+            .style(
+                style::container::Style::color_with_double_cornerless_border(
+                    (22, 18, 16, 255).into(),
+                    (11, 11, 11, 255).into(),
+                    (54, 46, 38, 255).into(),
+                ),
+            )
+            .padding(20);
 
-        Column::with_children(children)
+        let container = Container::new(prompt_window)
             .width(Length::Fill)
             .height(Length::Fill)
-            .into()
+            .center_x()
+            .center_y();
+
+        vec![
+            container.into(),
+            Space::new(Length::Fill, Length::Units(fonts.cyri.scale(15))).into(),
+        ]
     }
 }
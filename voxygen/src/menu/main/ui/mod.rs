@@ -87,6 +87,8 @@ pub enum Event {
     // Note: Keeping in case we re-add the disclaimer
     //DisclaimerAccepted,
     AuthServerTrust(String, bool),
+    #[cfg(feature = "plugins")]
+    PluginConsent(bool),
 }
 
 pub struct LoginInfo {
@@ -97,7 +99,14 @@ pub struct LoginInfo {
 
 enum ConnectionState {
     InProgress,
-    AuthTrustPrompt { auth_server: String, msg: String },
+    AuthTrustPrompt {
+        auth_server: String,
+        msg: String,
+    },
+    #[cfg(feature = "plugins")]
+    PluginPrompt {
+        msg: String,
+    },
 }
 
 enum Screen {
@@ -409,12 +418,20 @@ impl Controls {
                     connection_state, ..
                 } = &mut self.screen
                 {
-                    if let ConnectionState::AuthTrustPrompt { auth_server, .. } = connection_state {
-                        let auth_server = std::mem::take(auth_server);
-                        let added = matches!(msg, Message::TrustPromptAdd);
-
-                        *connection_state = ConnectionState::InProgress;
-                        events.push(Event::AuthServerTrust(auth_server, added));
+                    let added = matches!(msg, Message::TrustPromptAdd);
+                    match connection_state {
+                        ConnectionState::AuthTrustPrompt { auth_server, .. } => {
+                            let auth_server = std::mem::take(auth_server);
+
+                            *connection_state = ConnectionState::InProgress;
+                            events.push(Event::AuthServerTrust(auth_server, added));
+                        },
+                        #[cfg(feature = "plugins")]
+                        ConnectionState::PluginPrompt { .. } => {
+                            *connection_state = ConnectionState::InProgress;
+                            events.push(Event::PluginConsent(added));
+                        },
+                        ConnectionState::InProgress => {},
                     }
                 }
             },
@@ -463,6 +480,23 @@ impl Controls {
         }
     }
 
+    #[cfg(feature = "plugins")]
+    fn plugin_prompt(&mut self, plugins: Vec<String>) {
+        if let Screen::Connecting {
+            connection_state, ..
+        } = &mut self.screen
+        {
+            let msg = format!(
+                "The server you are trying to connect to wants you to run these \
+                 plugins:\n\n{}\n\nPlugins run code provided by the server on your computer. Only \
+                 accept if you trust this server!",
+                plugins.join("\n")
+            );
+
+            *connection_state = ConnectionState::PluginPrompt { msg };
+        }
+    }
+
     fn connection_error(&mut self, error: String) {
         if matches!(&self.screen, Screen::Connecting { .. })
             || matches!(&self.screen, Screen::Login { .. })
@@ -550,6 +584,9 @@ impl MainMenuUi {
         self.controls.auth_trust_prompt(auth_server);
     }
 
+    #[cfg(feature = "plugins")]
+    pub fn plugin_prompt(&mut self, plugins: Vec<String>) { self.controls.plugin_prompt(plugins); }
+
     pub fn show_info(&mut self, msg: String) { self.controls.connection_error(msg); }
 
     pub fn connected(&mut self) { self.controls.exit_connect_screen(); }
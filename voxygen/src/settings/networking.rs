@@ -1,4 +1,4 @@
-use hashbrown::HashSet;
+use hashbrown::{HashMap, HashSet};
 use serde::{Deserialize, Serialize};
 
 /// `NetworkingSettings` stores server and networking settings.
@@ -9,6 +9,9 @@ pub struct NetworkingSettings {
     pub servers: Vec<String>,
     pub default_server: String,
     pub trusted_auth_servers: HashSet<String>,
+    /// Network identities of the servers, trusted on first use and required
+    /// from then on
+    pub server_identities: HashMap<String, String>,
     pub use_quic: bool,
 }
 
@@ -22,6 +25,7 @@ impl Default for NetworkingSettings {
                 .iter()
                 .map(|s| s.to_string())
                 .collect(),
+            server_identities: HashMap::new(),
             use_quic: false,
         }
     }
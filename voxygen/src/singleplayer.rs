@@ -190,6 +190,7 @@ fn run_server(mut server: Server, stop_server_r: Receiver<()>, paused: Arc<Atomi
                 Event::ClientConnected { .. } => info!("Client connected!"),
                 Event::ClientDisconnected { .. } => info!("Client disconnected!"),
                 Event::Chat { entity: _, msg } => info!("[Client] {}", msg),
+                Event::Shutdown { .. } => info!("Ignoring shutdown request in singleplayer"),
             }
         }
 